@@ -0,0 +1,155 @@
+use crate::client::Client;
+use crate::commands::{Command, Context};
+use crate::resp::RESP;
+use crate::server_result::ServerResult;
+use crate::storage::{Key, Storage, ValueType};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// What a command asks for when it cannot be served yet: the keys to wait on and for how long
+#[derive(Debug, PartialEq)]
+pub struct Block {
+    pub keys: Vec<Key>,
+    /// Type of the data waited for, keys holding another type leave the client blocked
+    pub value_type: ValueType,
+    /// `None` to wait forever
+    pub timeout: Option<Duration>,
+    /// Arguments to run the command with once data arrives, when they differ from the original
+    /// ones, as for XREAD which resolves `$` to the last entry when it blocks
+    pub args: Option<Vec<Vec<u8>>>,
+}
+
+/// A client waiting for data, with the command to run again once one of its keys receives some
+#[derive(Debug)]
+struct BlockedClient {
+    client: Client,
+    command: &'static Command,
+    args: Vec<Vec<u8>>,
+    keys: Vec<Key>,
+    value_type: ValueType,
+    sender: oneshot::Sender<ServerResult<RESP>>,
+}
+
+/// The clients blocked by commands such as BLPOP, by client id
+#[derive(Debug, Default)]
+pub struct BlockedClients {
+    clients: HashMap<u64, BlockedClient>,
+}
+
+/// Handle given to the connection of a blocked client, to receive the reply once it is served
+#[derive(Debug)]
+pub struct Blocked {
+    pub client_id: u64,
+    pub receiver: oneshot::Receiver<ServerResult<RESP>>,
+    pub timeout: Option<Duration>,
+}
+
+/// Park the client until one of the keys receives data
+pub fn block_client(
+    storage: &mut Storage,
+    client: &Client,
+    command: &'static Command,
+    args: &[&[u8]],
+    block: Block,
+) -> Blocked {
+    let (sender, receiver) = oneshot::channel();
+
+    for key in &block.keys {
+        storage.dbs[client.db].block_on(key, client.id);
+    }
+
+    storage.blocked_clients.clients.insert(
+        client.id,
+        BlockedClient {
+            client: client.clone(),
+            command,
+            args: block
+                .args
+                .unwrap_or_else(|| args.iter().map(|arg| arg.to_vec()).collect()),
+            keys: block.keys,
+            value_type: block.value_type,
+            sender,
+        },
+    );
+
+    Blocked {
+        client_id: client.id,
+        receiver,
+        timeout: block.timeout,
+    }
+}
+
+/// Stop waiting on behalf of a client, returning false if it has already been served
+pub fn unblock_client(storage: &mut Storage, client_id: u64) -> bool {
+    match storage.blocked_clients.clients.remove(&client_id) {
+        Some(blocked) => {
+            for key in &blocked.keys {
+                storage.dbs[blocked.client.db].unblock_from(key, client_id);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Run again the commands of the clients blocked on keys that received data. Clients are served
+/// in the order they blocked, for as long as their command finds data to consume.
+pub fn serve_blocked_clients(storage: &mut Storage) {
+    for db in 0..storage.dbs.len() {
+        serve_blocked_clients_of(storage, db);
+    }
+}
+
+/// Serve the clients blocked on the keys of a database, which they have selected
+fn serve_blocked_clients_of(storage: &mut Storage, db: usize) {
+    loop {
+        // Serving a client may signal other keys, as BLMOVE pushes to its destination
+        let keys = storage.dbs[db].take_ready_keys();
+        if keys.is_empty() {
+            return;
+        }
+
+        for key in keys {
+            for client_id in storage.dbs[db].blocked_on(&key) {
+                let mut blocked = match storage.blocked_clients.clients.remove(&client_id) {
+                    Some(blocked) => blocked,
+                    None => continue,
+                };
+
+                // The data would be lost if the connection has gone away
+                if blocked.sender.is_closed() {
+                    for key in &blocked.keys {
+                        storage.dbs[db].unblock_from(key, client_id);
+                    }
+                    continue;
+                }
+
+                // As in Redis, a key now holding another type keeps the client blocked rather
+                // than failing its command
+                if storage.dbs[db]
+                    .peek_entry(&key)
+                    .is_some_and(|entry| entry.value.value_type() != blocked.value_type)
+                {
+                    storage.blocked_clients.clients.insert(client_id, blocked);
+                    continue;
+                }
+
+                let args: Vec<&[u8]> = blocked.args.iter().map(Vec::as_slice).collect();
+                let mut ctx = Context::new(&mut blocked.client, storage);
+                let result = (blocked.command.handler)(&mut ctx, &args);
+
+                // The key has no more data for the clients behind this one
+                if ctx.block.is_some() {
+                    storage.blocked_clients.clients.insert(client_id, blocked);
+                    break;
+                }
+
+                for key in &blocked.keys {
+                    storage.dbs[db].unblock_from(key, client_id);
+                }
+                let _ = blocked.sender.send(result);
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::pubsub::MessageSender;
+use crate::resp::{PartialRequest, ProtocolVersion};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// State attached to a single client connection
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub id: u64,
+    pub name: Option<String>,
+    pub protocol: ProtocolVersion,
+    /// Index of the selected database
+    pub db: usize,
+    /// Progress parsing the request at the start of the connection buffer
+    pub partial_request: PartialRequest,
+    /// Where the messages published to the channels it subscribed to are sent
+    pub messages: Option<MessageSender>,
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            name: None,
+            protocol: ProtocolVersion::default(),
+            db: 0,
+            partial_request: PartialRequest::default(),
+            messages: None,
+        }
+    }
+}
@@ -0,0 +1,836 @@
+use crate::commands::string::normalise_range;
+use crate::commands::{Context, parse_integer};
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{MAX_STRING_LENGTH, Value};
+
+/// Number of bits in the largest string value
+const MAX_BITS: u64 = MAX_STRING_LENGTH as u64 * 8;
+
+/// Parse a bit offset, which must address a bit of the largest string value
+fn parse_offset(arg: &[u8]) -> ServerResult<u64> {
+    match parse_integer(arg) {
+        Ok(offset) if offset >= 0 && (offset as u64) < MAX_BITS => Ok(offset as u64),
+        _ => Err(ServerError::InvalidBitOffset),
+    }
+}
+
+/// The value of a bit, where bit 0 is the most significant bit of the first byte. Bits past the
+/// end of the string are clear.
+fn get_bit(data: &[u8], offset: u64) -> bool {
+    data.get((offset / 8) as usize)
+        .is_some_and(|byte| byte & (0x80 >> (offset % 8)) != 0)
+}
+
+/// Grow the string with zero bytes so that it holds the bit at the offset
+fn ensure_bit(data: &mut Vec<u8>, offset: u64) {
+    let length = (offset / 8) as usize + 1;
+    if data.len() < length {
+        data.resize(length, 0);
+    }
+}
+
+/// Whether ranges are given in bytes or in bits
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RangeUnit {
+    Byte,
+    Bit,
+}
+
+impl RangeUnit {
+    fn parse(arg: Option<&&[u8]>) -> ServerResult<Self> {
+        match arg {
+            None => Ok(Self::Byte),
+            Some(arg) if arg.eq_ignore_ascii_case(b"byte") => Ok(Self::Byte),
+            Some(arg) if arg.eq_ignore_ascii_case(b"bit") => Ok(Self::Bit),
+            Some(_) => Err(ServerError::Syntax),
+        }
+    }
+
+    /// The first and last bits of a range, inclusive, given a string of `length` bytes
+    fn bits(self, start: i64, end: i64, length: usize) -> Option<(u64, u64)> {
+        match self {
+            Self::Byte => normalise_range(start, end, length)
+                .map(|(start, end)| (start as u64 * 8, end as u64 * 8 + 7)),
+            Self::Bit => normalise_range(start, end, length * 8)
+                .map(|(start, end)| (start as u64, end as u64)),
+        }
+    }
+}
+
+/// Number of set bits between two bit offsets, inclusive
+fn count_bits(data: &[u8], first: u64, last: u64) -> u64 {
+    let (first_byte, last_byte) = ((first / 8) as usize, (last / 8) as usize);
+
+    let mut count: u64 = data[first_byte..=last_byte]
+        .iter()
+        .map(|byte| byte.count_ones() as u64)
+        .sum();
+
+    // Leave out the bits of the edge bytes that are not in the range
+    count -= (data[first_byte] & !(0xff >> (first % 8))).count_ones() as u64;
+    count -= (data[last_byte] & (0x7f >> (last % 8))).count_ones() as u64;
+    count
+}
+
+/// The offset of the first bit with the value between two bit offsets, inclusive
+fn find_bit(data: &[u8], bit: bool, first: u64, last: u64) -> Option<u64> {
+    // Bytes made only of the other value are skipped whole
+    let skipped = if bit { 0x00 } else { 0xff };
+
+    let mut offset = first;
+    while offset <= last {
+        if offset.is_multiple_of(8) && offset + 7 <= last && data[(offset / 8) as usize] == skipped
+        {
+            offset += 8;
+            continue;
+        }
+        if get_bit(data, offset) == bit {
+            return Some(offset);
+        }
+        offset += 1;
+    }
+
+    None
+}
+
+/// SETBIT key offset value
+pub fn setbit(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let offset = parse_offset(args[1])?;
+    let value = match args[2] {
+        b"0" => false,
+        b"1" => true,
+        _ => {
+            return Err(ServerError::InvalidArgument(String::from(
+                "bit is not an integer or out of range",
+            )));
+        }
+    };
+
+    let db = ctx.db();
+    if db.get_string(key)?.is_none() {
+        db.insert(key, Value::String(vec![]));
+    }
+    let data = db
+        .get_string_mut(key)?
+        .expect("the key has just been created");
+
+    ensure_bit(data, offset);
+    let previous = get_bit(data, offset);
+    let mask = 0x80 >> (offset % 8);
+    if value {
+        data[(offset / 8) as usize] |= mask;
+    } else {
+        data[(offset / 8) as usize] &= !mask;
+    }
+
+    Ok(RESP::Integer(previous as i64))
+}
+
+/// GETBIT key offset
+pub fn getbit(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let offset = parse_offset(args[1])?;
+    let bit = ctx
+        .db()
+        .get_string(args[0])?
+        .is_some_and(|data| get_bit(data, offset));
+
+    Ok(RESP::Integer(bit as i64))
+}
+
+/// BITCOUNT key [start end [BYTE | BIT]]
+pub fn bitcount(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let range = match args.len() {
+        1 => None,
+        3 | 4 => Some((
+            parse_integer(args[1])?,
+            parse_integer(args[2])?,
+            RangeUnit::parse(args.get(3))?,
+        )),
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let data = match ctx.db().get_string(args[0])? {
+        Some(data) => data,
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    let (start, end, unit) = range.unwrap_or((0, -1, RangeUnit::Byte));
+    let count = match unit.bits(start, end, data.len()) {
+        Some((first, last)) => count_bits(data, first, last),
+        None => 0,
+    };
+
+    Ok(RESP::Integer(count as i64))
+}
+
+/// BITPOS key bit [start [end [BYTE | BIT]]]
+pub fn bitpos(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let bit = match args[1] {
+        b"0" => false,
+        b"1" => true,
+        _ => {
+            return Err(ServerError::InvalidArgument(String::from(
+                "The bit argument must be 1 or 0.",
+            )));
+        }
+    };
+
+    if args.len() > 5 {
+        return Err(ServerError::Syntax);
+    }
+    let start = args.get(2).map(|arg| parse_integer(arg)).transpose()?;
+    let end = args.get(3).map(|arg| parse_integer(arg)).transpose()?;
+    let unit = RangeUnit::parse(args.get(4))?;
+
+    let data = match ctx.db().get_string(args[0])? {
+        Some(data) => data,
+        None => return Ok(RESP::Integer(if bit { -1 } else { 0 })),
+    };
+
+    let (first, last) = match unit.bits(start.unwrap_or(0), end.unwrap_or(-1), data.len()) {
+        Some(range) => range,
+        None => return Ok(RESP::Integer(-1)),
+    };
+
+    let position = match find_bit(data, bit, first, last) {
+        Some(position) => position as i64,
+        // Without an explicit end, the string is considered padded with clear bits
+        None if !bit && end.is_none() => last as i64 + 1,
+        None => -1,
+    };
+
+    Ok(RESP::Integer(position))
+}
+
+/// The operations of BITOP
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+    /// Bits set in the first key and in none of the others
+    Diff,
+}
+
+impl BitOperation {
+    fn parse(arg: &[u8]) -> ServerResult<Self> {
+        match arg.to_ascii_lowercase().as_slice() {
+            b"and" => Ok(Self::And),
+            b"or" => Ok(Self::Or),
+            b"xor" => Ok(Self::Xor),
+            b"not" => Ok(Self::Not),
+            b"diff" => Ok(Self::Diff),
+            _ => Err(ServerError::Syntax),
+        }
+    }
+
+    /// Combine the strings, the shorter ones being padded with zero bytes
+    fn apply(self, sources: &[&[u8]]) -> Vec<u8> {
+        let length = sources.iter().map(|data| data.len()).max().unwrap_or(0);
+        let byte = |data: &[u8], index: usize| data.get(index).copied().unwrap_or(0);
+
+        (0..length)
+            .map(|index| {
+                let mut bytes = sources.iter().map(|data| byte(data, index));
+                let first = bytes.next().unwrap_or(0);
+
+                match self {
+                    Self::And => bytes.fold(first, |result, byte| result & byte),
+                    Self::Or => bytes.fold(first, |result, byte| result | byte),
+                    Self::Xor => bytes.fold(first, |result, byte| result ^ byte),
+                    Self::Not => !first,
+                    Self::Diff => first & !bytes.fold(0, |result, byte| result | byte),
+                }
+            })
+            .collect()
+    }
+}
+
+/// BITOP AND | OR | XOR | NOT | DIFF destkey key [key ...]
+pub fn bitop(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let operation = BitOperation::parse(args[0])?;
+    let (destination, keys) = (args[1], &args[2..]);
+
+    match operation {
+        BitOperation::Not if keys.len() != 1 => {
+            return Err(ServerError::InvalidArgument(String::from(
+                "BITOP NOT must be called with a single source key.",
+            )));
+        }
+        BitOperation::Diff if keys.len() < 2 => {
+            return Err(ServerError::InvalidArgument(String::from(
+                "BITOP DIFF must be called with at least two source keys.",
+            )));
+        }
+        _ => {}
+    }
+
+    let db = ctx.db();
+    let sources = db
+        .get_values(keys)
+        .into_iter()
+        .map(|value| match value {
+            Some(Value::String(data)) => Ok(data.as_slice()),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok([].as_slice()),
+        })
+        .collect::<ServerResult<Vec<&[u8]>>>()?;
+
+    let result = operation.apply(&sources);
+    let length = result.len();
+
+    if result.is_empty() {
+        db.remove(destination);
+    } else {
+        db.insert(destination, Value::String(result));
+    }
+
+    Ok(RESP::Integer(length as i64))
+}
+
+/// The integer type of a BITFIELD field: signed up to 64 bits or unsigned up to 63 bits
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FieldType {
+    signed: bool,
+    bits: u32,
+}
+
+impl FieldType {
+    fn parse(arg: &[u8]) -> ServerResult<Self> {
+        let invalid = || {
+            ServerError::InvalidArgument(String::from(
+                "Invalid bitfield type. Use something like i16 u8. Note that u64 is not \
+                 supported but i64 is.",
+            ))
+        };
+
+        let (signed, bits) = match arg {
+            [b'i' | b'I', bits @ ..] => (true, bits),
+            [b'u' | b'U', bits @ ..] => (false, bits),
+            _ => return Err(invalid()),
+        };
+        let bits: u32 = std::str::from_utf8(bits)
+            .ok()
+            .and_then(|bits| bits.parse().ok())
+            .ok_or_else(invalid)?;
+
+        match (signed, bits) {
+            (true, 1..=64) | (false, 1..=63) => Ok(Self { signed, bits }),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Parse the offset of a field, `#N` standing for N times its width
+    fn parse_offset(self, arg: &[u8]) -> ServerResult<u64> {
+        let offset = match arg {
+            [b'#', index @ ..] => match parse_integer(index) {
+                Ok(index) if index >= 0 => (index as u64).checked_mul(self.bits as u64),
+                _ => None,
+            },
+            _ => match parse_integer(arg) {
+                Ok(offset) if offset >= 0 => Some(offset as u64),
+                _ => None,
+            },
+        };
+
+        offset
+            .filter(|offset| offset + self.bits as u64 <= MAX_BITS)
+            .ok_or(ServerError::InvalidBitOffset)
+    }
+
+    fn min(self) -> i128 {
+        match self.signed {
+            true => -(1 << (self.bits - 1)),
+            false => 0,
+        }
+    }
+
+    fn max(self) -> i128 {
+        match self.signed {
+            true => (1 << (self.bits - 1)) - 1,
+            false => (1 << self.bits) - 1,
+        }
+    }
+
+    /// Read the field, sign-extending signed values
+    fn get(self, data: &[u8], offset: u64) -> i64 {
+        let mut value: u64 = 0;
+        for bit in offset..offset + self.bits as u64 {
+            value = (value << 1) | get_bit(data, bit) as u64;
+        }
+
+        match self.signed && self.bits < 64 {
+            true => ((value << (64 - self.bits)) as i64) >> (64 - self.bits),
+            false => value as i64,
+        }
+    }
+
+    /// Write the low bits of the value to the field, the string being long enough
+    fn set(self, data: &mut [u8], offset: u64, value: i64) {
+        for index in 0..self.bits as u64 {
+            let bit = (value as u64 >> (self.bits as u64 - 1 - index)) & 1;
+            let position = offset + index;
+            let mask = 0x80 >> (position % 8);
+
+            if bit == 1 {
+                data[(position / 8) as usize] |= mask;
+            } else {
+                data[(position / 8) as usize] &= !mask;
+            }
+        }
+    }
+
+    /// Fit a value into the field, or `None` if it overflows with the FAIL behaviour
+    fn fit(self, value: i128, overflow: Overflow) -> Option<i64> {
+        if (self.min()..=self.max()).contains(&value) {
+            return Some(value as i64);
+        }
+
+        match overflow {
+            Overflow::Wrap => {
+                let wrapped = value.rem_euclid(1 << self.bits);
+                match self.signed && wrapped > self.max() {
+                    true => Some((wrapped - (1 << self.bits)) as i64),
+                    false => Some(wrapped as i64),
+                }
+            }
+            Overflow::Sat => Some(value.clamp(self.min(), self.max()) as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+/// How BITFIELD handles values that do not fit into their field
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldOperation {
+    Get,
+    Set(i64, Overflow),
+    IncrBy(i64, Overflow),
+}
+
+/// Shared implementation of BITFIELD and BITFIELD_RO
+fn bitfield_generic(ctx: &mut Context, args: &[&[u8]], read_only: bool) -> ServerResult<RESP> {
+    let key = args[0];
+    let mut operations = vec![];
+    let mut overflow = Overflow::Wrap;
+
+    let mut index = 1;
+    while index < args.len() {
+        let subcommand = args[index].to_ascii_lowercase();
+
+        if subcommand == b"overflow" {
+            let behaviour = args.get(index + 1).ok_or(ServerError::Syntax)?;
+            overflow = match behaviour.to_ascii_lowercase().as_slice() {
+                b"wrap" => Overflow::Wrap,
+                b"sat" => Overflow::Sat,
+                b"fail" => Overflow::Fail,
+                _ => {
+                    return Err(ServerError::InvalidArgument(String::from(
+                        "Invalid OVERFLOW type specified",
+                    )));
+                }
+            };
+            index += 2;
+            continue;
+        }
+
+        let arity = match subcommand.as_slice() {
+            b"get" => 3,
+            b"set" | b"incrby" => 4,
+            _ => return Err(ServerError::Syntax),
+        };
+        if index + arity > args.len() {
+            return Err(ServerError::Syntax);
+        }
+
+        let field_type = FieldType::parse(args[index + 1])?;
+        let offset = field_type.parse_offset(args[index + 2])?;
+        let operation = match subcommand.as_slice() {
+            b"get" => FieldOperation::Get,
+            _ if read_only => {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "BITFIELD_RO only supports the GET subcommand",
+                )));
+            }
+            b"set" => FieldOperation::Set(parse_integer(args[index + 3])?, overflow),
+            _ => FieldOperation::IncrBy(parse_integer(args[index + 3])?, overflow),
+        };
+
+        operations.push((field_type, offset, operation));
+        index += arity;
+    }
+
+    let db = ctx.db();
+
+    // Only GET operations leave a missing key missing
+    let highest_write = operations
+        .iter()
+        .filter(|(_, _, operation)| *operation != FieldOperation::Get)
+        .map(|(field_type, offset, _)| offset + field_type.bits as u64 - 1)
+        .max();
+
+    let highest_write = match highest_write {
+        Some(highest_write) => highest_write,
+        None => {
+            let data = db.get_string(key)?.map_or(&[][..], |data| data.as_slice());
+            let values = operations
+                .iter()
+                .map(|(field_type, offset, _)| RESP::Integer(field_type.get(data, *offset)))
+                .collect();
+
+            return Ok(RESP::Array(values));
+        }
+    };
+
+    if db.get_string(key)?.is_none() {
+        db.insert(key, Value::String(vec![]));
+    }
+    let data = db
+        .get_string_mut(key)?
+        .expect("the key has just been created");
+    ensure_bit(data, highest_write);
+
+    let mut replies = Vec::with_capacity(operations.len());
+    for (field_type, offset, operation) in operations {
+        let current = field_type.get(data, offset);
+
+        let reply = match operation {
+            FieldOperation::Get => Some(current),
+            FieldOperation::Set(value, overflow) => {
+                // Unsigned fields take the value as its two's complement, as in Redis
+                let value = match field_type.signed {
+                    true => value as i128,
+                    false => value as u64 as i128,
+                };
+                field_type.fit(value, overflow).map(|value| {
+                    field_type.set(data, offset, value);
+                    current
+                })
+            }
+            FieldOperation::IncrBy(increment, overflow) => field_type
+                .fit(current as i128 + increment as i128, overflow)
+                .inspect(|&value| field_type.set(data, offset, value)),
+        };
+
+        replies.push(reply.map_or(RESP::Null, RESP::Integer));
+    }
+
+    Ok(RESP::Array(replies))
+}
+
+/// BITFIELD key [GET encoding offset | [OVERFLOW WRAP | SAT | FAIL]
+///   SET encoding offset value | INCRBY encoding offset increment ...]
+pub fn bitfield(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    bitfield_generic(ctx, args, false)
+}
+
+/// BITFIELD_RO key [GET encoding offset [GET encoding offset ...]]
+pub fn bitfield_ro(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    bitfield_generic(ctx, args, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    #[test]
+    fn test_setbit_and_getbit() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["SETBIT", "key", "7", "1"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SETBIT", "key", "7", "0"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SETBIT", "key", "1", "1"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GET", "key"]),
+            Ok(RESP::BulkString(vec![0x40]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETBIT", "key", "1"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETBIT", "key", "1000"]),
+            Ok(RESP::Integer(0))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["SETBIT", "key", "20", "1"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["STRLEN", "key"]),
+            Ok(RESP::Integer(3))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["SETBIT", "key", "4294967296", "1"]),
+            Err(ServerError::InvalidBitOffset)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SETBIT", "key", "0", "2"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "bit is not an integer or out of range"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_bitcount() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "key", "foobar"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["BITCOUNT", "key"]),
+            Ok(RESP::Integer(26))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITCOUNT", "key", "0", "0"]),
+            Ok(RESP::Integer(4))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITCOUNT", "key", "1", "1"]),
+            Ok(RESP::Integer(6))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITCOUNT", "key", "-2", "-1"]),
+            Ok(RESP::Integer(7))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITCOUNT", "key", "5", "30", "BIT"]),
+            Ok(RESP::Integer(17))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITCOUNT", "key", "3", "1"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITCOUNT", "key", "0"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITCOUNT", "missing"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_bitpos() {
+        let mut storage = Storage::new();
+        for offset in 12..=15 {
+            execute(&mut storage, &["SETBIT", "key", &offset.to_string(), "1"]).unwrap();
+        }
+        execute(&mut storage, &["SETBIT", "key", "16", "1"]).unwrap();
+
+        // 00000000 00001111 10000000
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "key", "1"]),
+            Ok(RESP::Integer(12))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "key", "1", "2"]),
+            Ok(RESP::Integer(16))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "key", "0", "1"]),
+            Ok(RESP::Integer(8))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "key", "0", "12", "15", "BIT"]),
+            Ok(RESP::Integer(-1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "key", "1", "17", "-1", "BIT"]),
+            Ok(RESP::Integer(-1))
+        );
+
+        execute(&mut storage, &["SET", "ones", "\u{7f}"]).unwrap();
+        execute(&mut storage, &["SETBIT", "ones", "0", "1"]).unwrap();
+        // Without an end, the string is padded with clear bits
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "ones", "0"]),
+            Ok(RESP::Integer(8))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "ones", "0", "0", "-1"]),
+            Ok(RESP::Integer(-1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "missing", "0"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITPOS", "missing", "1"]),
+            Ok(RESP::Integer(-1))
+        );
+    }
+
+    #[test]
+    fn test_bitop() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "a", "abc"]).unwrap();
+        execute(&mut storage, &["SET", "b", "a"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["BITOP", "AND", "dest", "a", "b"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GET", "dest"]),
+            Ok(RESP::BulkString(vec![b'a', 0, 0]))
+        );
+        execute(&mut storage, &["BITOP", "XOR", "dest", "a", "b"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["GET", "dest"]),
+            Ok(RESP::BulkString(vec![0, b'b', b'c']))
+        );
+        execute(&mut storage, &["BITOP", "NOT", "dest", "b"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["GET", "dest"]),
+            Ok(RESP::BulkString(vec![!b'a']))
+        );
+        execute(
+            &mut storage,
+            &["BITOP", "DIFF", "dest", "a", "b", "missing"],
+        )
+        .unwrap();
+        assert_eq!(
+            execute(&mut storage, &["GET", "dest"]),
+            Ok(RESP::BulkString(vec![0, b'b', b'c']))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["BITOP", "OR", "dest", "missing"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "dest"]),
+            Ok(RESP::Integer(0))
+        );
+
+        assert!(matches!(
+            execute(&mut storage, &["BITOP", "NOT", "dest", "a", "b"]),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        execute(&mut storage, &["RPUSH", "list", "x"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["BITOP", "OR", "dest", "a", "list"]),
+            Err(ServerError::WrongType)
+        );
+    }
+
+    #[test]
+    fn test_bitfield() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "BITFIELD", "key", "SET", "i8", "0", "-100", "GET", "u4", "0", "GET", "i8", "0"
+                ]
+            ),
+            Ok(RESP::Array(vec![
+                RESP::Integer(0),
+                RESP::Integer(9),
+                RESP::Integer(-100),
+            ]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["BITFIELD", "key", "INCRBY", "i8", "0", "-50"]
+            ),
+            Ok(RESP::Array(vec![RESP::Integer(106)]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "BITFIELD", "key", "OVERFLOW", "SAT", "INCRBY", "i8", "0", "100", "OVERFLOW",
+                    "FAIL", "INCRBY", "i8", "0", "1",
+                ]
+            ),
+            Ok(RESP::Array(vec![RESP::Integer(127), RESP::Null]))
+        );
+
+        // `#1` addresses the second field of the type
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "BITFIELD", "counters", "INCRBY", "u2", "#1", "5", "GET", "u4", "0"
+                ]
+            ),
+            Ok(RESP::Array(vec![RESP::Integer(1), RESP::Integer(1)]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["BITFIELD", "counters", "SET", "u2", "#1", "-1"]
+            ),
+            Ok(RESP::Array(vec![RESP::Integer(1)]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["BITFIELD_RO", "counters", "GET", "u2", "#1"]
+            ),
+            Ok(RESP::Array(vec![RESP::Integer(3)]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "BITFIELD", "big", "SET", "i64", "0", "-1", "GET", "i64", "0"
+                ]
+            ),
+            Ok(RESP::Array(vec![RESP::Integer(0), RESP::Integer(-1)]))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["BITFIELD", "missing", "GET", "u8", "0"]),
+            Ok(RESP::Array(vec![RESP::Integer(0)]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "missing"]),
+            Ok(RESP::Integer(0))
+        );
+
+        assert!(matches!(
+            execute(&mut storage, &["BITFIELD", "key", "GET", "u64", "0"]),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            execute(&mut storage, &["BITFIELD_RO", "key", "SET", "u8", "0", "1"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "BITFIELD_RO only supports the GET subcommand"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BITFIELD", "key", "GET", "u8", "-1"]),
+            Err(ServerError::InvalidBitOffset)
+        );
+    }
+}
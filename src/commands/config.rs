@@ -0,0 +1,123 @@
+use crate::commands::Context;
+use crate::glob;
+use crate::hyperloglog::SPARSE_MAX_BYTES;
+use crate::resp::{MAX_BULK_LENGTH, RESP};
+use crate::server::MAX_QUERY_BUFFER_LENGTH;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::DATABASES;
+use crate::{BIND_ADDRESS, HZ, PORT};
+
+/// The configuration parameters and their values, which are fixed when the server is built
+fn parameters() -> Vec<(&'static str, String)> {
+    vec![
+        ("appendonly", String::from("no")),
+        ("bind", String::from(BIND_ADDRESS)),
+        (
+            "client-query-buffer-limit",
+            MAX_QUERY_BUFFER_LENGTH.to_string(),
+        ),
+        ("databases", DATABASES.to_string()),
+        ("hll-sparse-max-bytes", SPARSE_MAX_BYTES.to_string()),
+        ("hz", HZ.to_string()),
+        ("port", PORT.to_string()),
+        ("proto-max-bulk-len", MAX_BULK_LENGTH.to_string()),
+        ("save", String::new()),
+    ]
+}
+
+/// CONFIG GET parameter [parameter ...]
+pub fn config(_ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let subcommand = args[0].to_ascii_lowercase();
+    if subcommand != b"get" {
+        return Err(ServerError::InvalidArgument(format!(
+            "unknown subcommand '{}'. Try CONFIG HELP.",
+            String::from_utf8_lossy(args[0])
+        )));
+    }
+    if args.len() < 2 {
+        return Err(ServerError::WrongArity(String::from("config|get")));
+    }
+
+    // Parameter names are lowercase and matched regardless of case
+    let patterns: Vec<Vec<u8>> = args[1..]
+        .iter()
+        .map(|pattern| pattern.to_ascii_lowercase())
+        .collect();
+
+    // Each parameter is listed once, even when several patterns match it
+    Ok(RESP::Map(
+        parameters()
+            .into_iter()
+            .filter(|(name, _)| {
+                patterns
+                    .iter()
+                    .any(|pattern| glob::matches(pattern, name.as_bytes()))
+            })
+            .map(|(name, value)| {
+                (
+                    RESP::BulkString(name.as_bytes().to_vec()),
+                    RESP::BulkString(value.into_bytes()),
+                )
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    fn pair(name: &str, value: &str) -> (RESP, RESP) {
+        (
+            RESP::BulkString(name.as_bytes().to_vec()),
+            RESP::BulkString(value.as_bytes().to_vec()),
+        )
+    }
+
+    #[test]
+    fn test_config_get() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["CONFIG", "GET", "port"]),
+            Ok(RESP::Map(vec![pair("port", "6379")]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["config", "get", "DATA*", "d?tabases"]),
+            Ok(RESP::Map(vec![pair("databases", "16")]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["CONFIG", "GET", "h*", "[ps]*"]),
+            Ok(RESP::Map(vec![
+                pair("hll-sparse-max-bytes", "3000"),
+                pair("hz", "10"),
+                pair("port", "6379"),
+                pair("proto-max-bulk-len", "536870912"),
+                pair("save", ""),
+            ]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["CONFIG", "GET", "maxmemory"]),
+            Ok(RESP::Map(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["CONFIG", "SET", "port", "1"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "unknown subcommand 'SET'. Try CONFIG HELP."
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["CONFIG", "GET"]),
+            Err(ServerError::WrongArity(String::from("config|get")))
+        );
+    }
+}
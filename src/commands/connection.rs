@@ -0,0 +1,176 @@
+use crate::commands::{Context, parse_db_index, parse_integer};
+use crate::resp::{ProtocolVersion, RESP};
+use crate::server_result::{ServerError, ServerResult};
+
+pub fn ping(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    // RESP2 clients waiting for messages tell replies apart by their shape, like messages
+    if ctx.client.protocol == ProtocolVersion::RESP2
+        && ctx.storage.pubsub.subscription_count(ctx.client.id) > 0
+    {
+        return match args {
+            [] | [_] => Ok(RESP::Array(vec![
+                RESP::BulkString(b"pong".to_vec()),
+                RESP::BulkString(args.first().map_or(vec![], |message| message.to_vec())),
+            ])),
+            _ => Err(ServerError::WrongArity(String::from("ping"))),
+        };
+    }
+
+    match args {
+        [] => Ok(RESP::SimpleString(String::from("PONG"))),
+        [message] => Ok(RESP::BulkString(message.to_vec())),
+        _ => Err(ServerError::WrongArity(String::from("ping"))),
+    }
+}
+
+pub fn echo(_ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    Ok(RESP::BulkString(args[0].to_vec()))
+}
+
+/// SELECT index
+pub fn select(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    ctx.client.db = parse_db_index(args[0])?;
+
+    Ok(RESP::SimpleString(String::from("OK")))
+}
+
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+pub fn hello(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let client = &mut *ctx.client;
+    let mut protocol = client.protocol;
+    let mut name = None;
+
+    if let Some(version) = args.first() {
+        protocol = match parse_integer(version)? {
+            2 => ProtocolVersion::RESP2,
+            3 => ProtocolVersion::RESP3,
+            _ => return Err(ServerError::NoProto),
+        };
+    }
+
+    let mut index = 1;
+    while index < args.len() {
+        let remaining = args.len() - index - 1;
+
+        match args[index].to_ascii_lowercase().as_slice() {
+            b"auth" if remaining >= 2 => {
+                // There is no ACL support, so only the default user without password exists
+                if args[index + 1] != b"default" {
+                    return Err(ServerError::WrongPass);
+                }
+                index += 3;
+            }
+            b"setname" if remaining >= 1 => {
+                name = Some(String::from_utf8_lossy(args[index + 1]).into_owned());
+                index += 2;
+            }
+            _ => return Err(ServerError::Syntax),
+        }
+    }
+
+    client.protocol = protocol;
+    if name.is_some() {
+        client.name = name;
+    }
+
+    let proto = match protocol {
+        ProtocolVersion::RESP2 => 2,
+        ProtocolVersion::RESP3 => 3,
+    };
+
+    Ok(RESP::Map(vec![
+        (
+            RESP::BulkString(b"server".to_vec()),
+            RESP::BulkString(b"redis".to_vec()),
+        ),
+        (
+            RESP::BulkString(b"version".to_vec()),
+            RESP::BulkString(env!("CARGO_PKG_VERSION").as_bytes().to_vec()),
+        ),
+        (RESP::BulkString(b"proto".to_vec()), RESP::Integer(proto)),
+        (
+            RESP::BulkString(b"id".to_vec()),
+            RESP::Integer(client.id as i64),
+        ),
+        (
+            RESP::BulkString(b"mode".to_vec()),
+            RESP::BulkString(b"standalone".to_vec()),
+        ),
+        (
+            RESP::BulkString(b"role".to_vec()),
+            RESP::BulkString(b"master".to_vec()),
+        ),
+        (RESP::BulkString(b"modules".to_vec()), RESP::Array(vec![])),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Client;
+    use crate::storage::Storage;
+
+    fn run_hello(client: &mut Client, args: &[&[u8]]) -> ServerResult<RESP> {
+        let mut storage = Storage::new();
+        hello(&mut Context::new(client, &mut storage), args)
+    }
+
+    fn args<'a>(parts: &[&'a str]) -> Vec<&'a [u8]> {
+        parts.iter().map(|part| part.as_bytes()).collect()
+    }
+
+    #[test]
+    fn test_hello_switches_protocol() {
+        let mut client = Client::new();
+        let output = run_hello(&mut client, &args(&["3"])).unwrap();
+
+        assert_eq!(client.protocol, ProtocolVersion::RESP3);
+        match output {
+            RESP::Map(pairs) => {
+                assert!(pairs.contains(&(RESP::BulkString(b"proto".to_vec()), RESP::Integer(3))))
+            }
+            _ => panic!(),
+        }
+
+        run_hello(&mut client, &args(&["2"])).unwrap();
+        assert_eq!(client.protocol, ProtocolVersion::RESP2);
+    }
+
+    #[test]
+    fn test_hello_without_version_keeps_protocol() {
+        let mut client = Client::new();
+        run_hello(&mut client, &[]).unwrap();
+        assert_eq!(client.protocol, ProtocolVersion::RESP2);
+    }
+
+    #[test]
+    fn test_hello_unsupported_version() {
+        let mut client = Client::new();
+        let error = run_hello(&mut client, &args(&["4"])).unwrap_err();
+
+        assert_eq!(error, ServerError::NoProto);
+        assert_eq!(client.protocol, ProtocolVersion::RESP2);
+    }
+
+    #[test]
+    fn test_hello_setname_and_auth() {
+        let mut client = Client::new();
+        run_hello(
+            &mut client,
+            &args(&["3", "AUTH", "default", "secret", "SETNAME", "worker"]),
+        )
+        .unwrap();
+
+        assert_eq!(client.name, Some(String::from("worker")));
+        assert_eq!(client.protocol, ProtocolVersion::RESP3);
+    }
+
+    #[test]
+    fn test_hello_syntax_error() {
+        let mut client = Client::new();
+        let error = run_hello(&mut client, &args(&["3", "SETNAME"])).unwrap_err();
+
+        assert_eq!(error, ServerError::Syntax);
+        assert_eq!(client.protocol, ProtocolVersion::RESP2);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::commands::{Context, parse_integer};
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::current_time_ms;
+
+/// How the expiration time given to the EXPIRE family is expressed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TimeUnit {
+    Seconds,
+    Milliseconds,
+}
+
+/// NX, XX, GT and LT options of the EXPIRE family
+#[derive(Debug, Default)]
+pub struct ExpireConditions {
+    if_no_expiry: bool,
+    if_expiry: bool,
+    if_greater: bool,
+    if_less: bool,
+}
+
+impl ExpireConditions {
+    pub fn parse(args: &[&[u8]]) -> ServerResult<Self> {
+        let mut conditions = Self::default();
+
+        for arg in args {
+            match arg.to_ascii_lowercase().as_slice() {
+                b"nx" => conditions.if_no_expiry = true,
+                b"xx" => conditions.if_expiry = true,
+                b"gt" => conditions.if_greater = true,
+                b"lt" => conditions.if_less = true,
+                _ => {
+                    return Err(ServerError::UnsupportedOption(
+                        String::from_utf8_lossy(arg).into_owned(),
+                    ));
+                }
+            }
+        }
+
+        if conditions.if_no_expiry
+            && (conditions.if_expiry || conditions.if_greater || conditions.if_less)
+        {
+            return Err(ServerError::IncompatibleOptions(String::from(
+                "NX and XX, GT or LT",
+            )));
+        }
+        if conditions.if_greater && conditions.if_less {
+            return Err(ServerError::IncompatibleOptions(String::from("GT and LT")));
+        }
+
+        Ok(conditions)
+    }
+
+    /// Whether a key expiring at `current` may be given the expiration time `new`.
+    /// Keys without a time to live are considered to never expire.
+    pub fn allow(&self, current: Option<u64>, new: i64) -> bool {
+        match current {
+            None => !self.if_expiry && !self.if_greater,
+            Some(current) => {
+                let current = current as i64;
+                !self.if_no_expiry
+                    && (!self.if_greater || new > current)
+                    && (!self.if_less || new < current)
+            }
+        }
+    }
+}
+
+/// Convert the time given to the EXPIRE family into an absolute time in milliseconds, which may
+/// be in the past
+pub fn absolute_expire_time(
+    amount: &[u8],
+    unit: TimeUnit,
+    relative: bool,
+    command: &str,
+) -> ServerResult<i64> {
+    let amount = parse_integer(amount)?;
+    let invalid = || ServerError::InvalidExpireTime(String::from(command));
+
+    let milliseconds = match unit {
+        TimeUnit::Seconds => amount.checked_mul(1000).ok_or_else(invalid)?,
+        TimeUnit::Milliseconds => amount,
+    };
+
+    if relative {
+        milliseconds
+            .checked_add(current_time_ms() as i64)
+            .ok_or_else(invalid)
+    } else {
+        Ok(milliseconds)
+    }
+}
+
+/// Shared implementation of EXPIRE, PEXPIRE, EXPIREAT and PEXPIREAT
+fn expire_generic(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    unit: TimeUnit,
+    relative: bool,
+    command: &str,
+) -> ServerResult<RESP> {
+    let key = args[0];
+    let expires_at = absolute_expire_time(args[1], unit, relative, command)?;
+    let conditions = ExpireConditions::parse(&args[2..])?;
+
+    let db = ctx.db();
+    let current = match db.get_entry(key) {
+        Some(entry) => entry.expires_at,
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    if !conditions.allow(current, expires_at) {
+        return Ok(RESP::Integer(0));
+    }
+
+    // Times in the past delete the key right away
+    db.set_expiry(key, Some(expires_at.max(0) as u64));
+    Ok(RESP::Integer(1))
+}
+
+/// EXPIRE key seconds [NX | XX | GT | LT]
+pub fn expire(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    expire_generic(ctx, args, TimeUnit::Seconds, true, "expire")
+}
+
+/// PEXPIRE key milliseconds [NX | XX | GT | LT]
+pub fn pexpire(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    expire_generic(ctx, args, TimeUnit::Milliseconds, true, "pexpire")
+}
+
+/// EXPIREAT key unix-time-seconds [NX | XX | GT | LT]
+pub fn expireat(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    expire_generic(ctx, args, TimeUnit::Seconds, false, "expireat")
+}
+
+/// PEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT]
+pub fn pexpireat(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    expire_generic(ctx, args, TimeUnit::Milliseconds, false, "pexpireat")
+}
+
+/// Express an expiration time as the remaining time to live, or as is when `absolute`
+pub fn ttl_value(expires_at: u64, unit: TimeUnit, absolute: bool) -> i64 {
+    let milliseconds = if absolute {
+        expires_at
+    } else {
+        expires_at.saturating_sub(current_time_ms())
+    };
+
+    match unit {
+        TimeUnit::Seconds => ((milliseconds + 500) / 1000) as i64,
+        TimeUnit::Milliseconds => milliseconds as i64,
+    }
+}
+
+/// Shared implementation of TTL, PTTL, EXPIRETIME and PEXPIRETIME: -2 for missing keys and
+/// -1 for keys without a time to live
+fn ttl_generic(ctx: &mut Context, key: &[u8], unit: TimeUnit, absolute: bool) -> RESP {
+    let expires_at = match ctx.db().get_entry(key) {
+        Some(entry) => entry.expires_at,
+        None => return RESP::Integer(-2),
+    };
+
+    match expires_at {
+        Some(expires_at) => RESP::Integer(ttl_value(expires_at, unit, absolute)),
+        None => RESP::Integer(-1),
+    }
+}
+
+/// TTL key
+pub fn ttl(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    Ok(ttl_generic(ctx, args[0], TimeUnit::Seconds, false))
+}
+
+/// PTTL key
+pub fn pttl(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    Ok(ttl_generic(ctx, args[0], TimeUnit::Milliseconds, false))
+}
+
+/// EXPIRETIME key
+pub fn expiretime(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    Ok(ttl_generic(ctx, args[0], TimeUnit::Seconds, true))
+}
+
+/// PEXPIRETIME key
+pub fn pexpiretime(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    Ok(ttl_generic(ctx, args[0], TimeUnit::Milliseconds, true))
+}
+
+/// PERSIST key
+pub fn persist(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+
+    match db.get_entry(args[0]) {
+        Some(entry) if entry.expires_at.is_some() => {
+            db.set_expiry(args[0], None);
+            Ok(RESP::Integer(1))
+        }
+        _ => Ok(RESP::Integer(0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::{Storage, current_time_ms};
+
+    fn integer(storage: &mut Storage, parts: &[&str]) -> i64 {
+        match execute(storage, parts) {
+            Ok(RESP::Integer(value)) => value,
+            other => panic!("unexpected reply {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_expire_and_ttl() {
+        let mut storage = Storage::new();
+
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "100"]), 0);
+        assert_eq!(integer(&mut storage, &["TTL", "key"]), -2);
+
+        execute(&mut storage, &["SET", "key", "value"]).unwrap();
+        assert_eq!(integer(&mut storage, &["TTL", "key"]), -1);
+        assert_eq!(integer(&mut storage, &["PTTL", "key"]), -1);
+
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "100"]), 1);
+        assert_eq!(integer(&mut storage, &["TTL", "key"]), 100);
+        let pttl = integer(&mut storage, &["PTTL", "key"]);
+        assert!(pttl > 99_000 && pttl <= 100_000);
+
+        assert_eq!(integer(&mut storage, &["PEXPIRE", "key", "1700"]), 1);
+        assert_eq!(integer(&mut storage, &["TTL", "key"]), 2);
+    }
+
+    #[test]
+    fn test_expire_in_the_past_deletes_the_key() {
+        let mut storage = Storage::new();
+
+        execute(&mut storage, &["SET", "key", "value"]).unwrap();
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "-1"]), 1);
+        assert_eq!(execute(&mut storage, &["GET", "key"]), Ok(RESP::Null));
+
+        execute(&mut storage, &["SET", "key", "value"]).unwrap();
+        assert_eq!(integer(&mut storage, &["PEXPIREAT", "key", "1000"]), 1);
+        assert_eq!(integer(&mut storage, &["EXISTS", "key"]), 0);
+    }
+
+    #[test]
+    fn test_expire_in_the_past_deletes_the_field_expiries() {
+        let mut storage = Storage::new();
+
+        execute(&mut storage, &["HSET", "hash", "field", "value"]).unwrap();
+        execute(
+            &mut storage,
+            &["HEXPIRE", "hash", "100", "FIELDS", "1", "field"],
+        )
+        .unwrap();
+        assert_eq!(integer(&mut storage, &["EXPIRE", "hash", "-1"]), 1);
+        assert_eq!(integer(&mut storage, &["EXISTS", "hash"]), 0);
+
+        // Nothing is left for the active expiry to reclaim
+        let later = current_time_ms() + 1_000_000;
+        assert_eq!(storage.dbs[0].remove_expired(later, 10), 0);
+    }
+
+    #[test]
+    fn test_expireat_and_expiretime() {
+        let mut storage = Storage::new();
+        let at = current_time_ms() / 1000 + 1000;
+
+        execute(&mut storage, &["SET", "key", "value"]).unwrap();
+        assert_eq!(integer(&mut storage, &["EXPIRETIME", "key"]), -1);
+        assert_eq!(integer(&mut storage, &["EXPIRETIME", "missing"]), -2);
+
+        assert_eq!(
+            integer(&mut storage, &["EXPIREAT", "key", &at.to_string()]),
+            1
+        );
+        assert_eq!(integer(&mut storage, &["EXPIRETIME", "key"]), at as i64);
+        assert_eq!(
+            integer(&mut storage, &["PEXPIRETIME", "key"]),
+            at as i64 * 1000
+        );
+    }
+
+    #[test]
+    fn test_expire_conditions() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "key", "value"]).unwrap();
+
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "100", "XX"]), 0);
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "100", "GT"]), 0);
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "100", "NX"]), 1);
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "200", "NX"]), 0);
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "50", "GT"]), 0);
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "200", "GT"]), 1);
+        assert_eq!(integer(&mut storage, &["EXPIRE", "key", "300", "LT"]), 0);
+        assert_eq!(
+            integer(&mut storage, &["EXPIRE", "key", "150", "xx", "lt"]),
+            1
+        );
+        assert_eq!(integer(&mut storage, &["TTL", "key"]), 150);
+
+        execute(&mut storage, &["SET", "other", "value"]).unwrap();
+        assert_eq!(integer(&mut storage, &["EXPIRE", "other", "100", "LT"]), 1);
+    }
+
+    #[test]
+    fn test_expire_option_errors() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "key", "value"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["EXPIRE", "key", "100", "NX", "XX"]),
+            Err(ServerError::IncompatibleOptions(String::from(
+                "NX and XX, GT or LT"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXPIRE", "key", "100", "GT", "LT"]),
+            Err(ServerError::IncompatibleOptions(String::from("GT and LT")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXPIRE", "key", "100", "FOO"]),
+            Err(ServerError::UnsupportedOption(String::from("FOO")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXPIRE", "key", "abc"]),
+            Err(ServerError::NotAnInteger)
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXPIRE", "key", "9223372036854775807"]),
+            Err(ServerError::InvalidExpireTime(String::from("expire")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PEXPIRE", "key", "9223372036854775807"]),
+            Err(ServerError::InvalidExpireTime(String::from("pexpire")))
+        );
+    }
+
+    #[test]
+    fn test_persist() {
+        let mut storage = Storage::new();
+
+        assert_eq!(integer(&mut storage, &["PERSIST", "key"]), 0);
+
+        execute(&mut storage, &["SET", "key", "value", "EX", "100"]).unwrap();
+        assert_eq!(integer(&mut storage, &["PERSIST", "key"]), 1);
+        assert_eq!(integer(&mut storage, &["TTL", "key"]), -1);
+        assert_eq!(integer(&mut storage, &["PERSIST", "key"]), 0);
+    }
+}
@@ -0,0 +1,767 @@
+use crate::commands::sorted_set::{self, store};
+use crate::commands::{Context, parse_float, parse_integer};
+use crate::geohash::{self, Search, Shape};
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+use crate::sorted_set::SortedSet;
+
+/// Parse a distance unit into its length in meters
+fn parse_unit(arg: &[u8]) -> ServerResult<f64> {
+    match arg.to_ascii_lowercase().as_slice() {
+        b"m" => Ok(1.0),
+        b"km" => Ok(1000.0),
+        b"ft" => Ok(0.3048),
+        b"mi" => Ok(1609.34),
+        _ => Err(ServerError::InvalidArgument(String::from(
+            "unsupported unit provided. please use M, KM, FT, MI",
+        ))),
+    }
+}
+
+/// Parse a longitude and a latitude that can be indexed
+fn parse_coordinates(longitude: &[u8], latitude: &[u8]) -> ServerResult<(f64, f64)> {
+    let longitude = parse_float(longitude)?;
+    let latitude = parse_float(latitude)?;
+
+    if !geohash::is_valid(longitude, latitude) {
+        return Err(ServerError::InvalidArgument(format!(
+            "invalid longitude,latitude pair {:.6},{:.6}",
+            longitude, latitude
+        )));
+    }
+
+    Ok((longitude, latitude))
+}
+
+/// Parse a non-negative distance, the error naming what it measures
+fn parse_distance(arg: &[u8], name: &str) -> ServerResult<f64> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|value| !value.is_nan())
+        .ok_or_else(|| ServerError::InvalidArgument(format!("need numeric {}", name)))
+}
+
+/// Distances are replied with a precision of 0.1 mm, as strings in both protocols
+fn distance_reply(distance: f64) -> RESP {
+    RESP::BulkString(format!("{:.4}", distance).into_bytes())
+}
+
+fn coordinates_reply(score: f64) -> RESP {
+    let (longitude, latitude) = geohash::decode(score);
+    RESP::Array(vec![RESP::Double(longitude), RESP::Double(latitude)])
+}
+
+/// GEOADD key [NX | XX] [CH] longitude latitude member [longitude latitude member ...]
+pub fn geoadd(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let mut options = vec![];
+    let mut index = 1;
+    while index < args.len() {
+        match args[index].to_ascii_lowercase().as_slice() {
+            b"nx" | b"xx" | b"ch" => options.push(args[index].to_vec()),
+            _ => break,
+        }
+        index += 1;
+    }
+
+    let triples = &args[index..];
+    if triples.is_empty() || !triples.len().is_multiple_of(3) {
+        return Err(ServerError::Syntax);
+    }
+
+    // The members are added with ZADD, their coordinates encoded as score
+    let mut zadd_args = vec![args[0].to_vec()];
+    zadd_args.extend(options);
+    for triple in triples.chunks(3) {
+        let (longitude, latitude) = parse_coordinates(triple[0], triple[1])?;
+        let score = geohash::score(longitude, latitude).expect("the coordinates are valid");
+
+        zadd_args.push(score.to_string().into_bytes());
+        zadd_args.push(triple[2].to_vec());
+    }
+
+    let zadd_args: Vec<&[u8]> = zadd_args.iter().map(Vec::as_slice).collect();
+    sorted_set::zadd(ctx, &zadd_args)
+}
+
+/// GEOPOS key [member [member ...]]
+pub fn geopos(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let zset = ctx.db().get_sorted_set(args[0])?;
+
+    let positions = args[1..]
+        .iter()
+        .map(|member| match zset.and_then(|zset| zset.score(member)) {
+            Some(score) => coordinates_reply(score),
+            None => RESP::NullArray,
+        })
+        .collect();
+
+    Ok(RESP::Array(positions))
+}
+
+/// GEODIST key member1 member2 [M | KM | FT | MI]
+pub fn geodist(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let unit = match args.len() {
+        3 => 1.0,
+        4 => parse_unit(args[3])?,
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let zset = match ctx.db().get_sorted_set(args[0])? {
+        Some(zset) => zset,
+        None => return Ok(RESP::Null),
+    };
+
+    match (zset.score(args[1]), zset.score(args[2])) {
+        (Some(score1), Some(score2)) => {
+            let (longitude1, latitude1) = geohash::decode(score1);
+            let (longitude2, latitude2) = geohash::decode(score2);
+            let distance = geohash::distance(longitude1, latitude1, longitude2, latitude2);
+
+            Ok(distance_reply(distance / unit))
+        }
+        _ => Ok(RESP::Null),
+    }
+}
+
+/// GEOHASH key [member [member ...]]
+pub fn geohash(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let zset = ctx.db().get_sorted_set(args[0])?;
+
+    let hashes = args[1..]
+        .iter()
+        .map(|member| match zset.and_then(|zset| zset.score(member)) {
+            Some(score) => RESP::BulkString(geohash::geohash_string(score).into_bytes()),
+            None => RESP::Null,
+        })
+        .collect();
+
+    Ok(RESP::Array(hashes))
+}
+
+/// Where a search is centered
+enum Origin<'a> {
+    Member(&'a [u8]),
+    Coordinates(f64, f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Order {
+    Unsorted,
+    Ascending,
+    Descending,
+}
+
+/// The options of GEOSEARCH and GEOSEARCHSTORE
+struct SearchOptions<'a> {
+    origin: Option<Origin<'a>>,
+    shape: Option<Shape>,
+    /// Length of the unit of the shape in meters, distances are replied in the same unit
+    unit: f64,
+    order: Order,
+    count: Option<usize>,
+    any: bool,
+    with_coordinates: bool,
+    with_distance: bool,
+    with_hash: bool,
+    store_distance: bool,
+}
+
+impl<'a> SearchOptions<'a> {
+    fn parse(args: &[&'a [u8]], command: &str, store: bool) -> ServerResult<Self> {
+        let mut options = SearchOptions {
+            origin: None,
+            shape: None,
+            unit: 1.0,
+            order: Order::Unsorted,
+            count: None,
+            any: false,
+            with_coordinates: false,
+            with_distance: false,
+            with_hash: false,
+            store_distance: false,
+        };
+        let mut from_member = false;
+        let mut from_coordinates = false;
+        let mut by_radius = false;
+        let mut by_box = false;
+
+        let mut index = 0;
+        while index < args.len() {
+            let remaining = args.len() - index - 1;
+
+            match args[index].to_ascii_lowercase().as_slice() {
+                b"frommember" if remaining >= 1 && !from_member => {
+                    options.origin = Some(Origin::Member(args[index + 1]));
+                    from_member = true;
+                    index += 1;
+                }
+                b"fromlonlat" if remaining >= 2 && !from_coordinates => {
+                    let (longitude, latitude) =
+                        parse_coordinates(args[index + 1], args[index + 2])?;
+                    options.origin = Some(Origin::Coordinates(longitude, latitude));
+                    from_coordinates = true;
+                    index += 2;
+                }
+                b"byradius" if remaining >= 2 && !by_radius => {
+                    let radius = parse_distance(args[index + 1], "radius")?;
+                    if radius < 0.0 {
+                        return Err(ServerError::InvalidArgument(String::from(
+                            "radius cannot be negative",
+                        )));
+                    }
+                    options.unit = parse_unit(args[index + 2])?;
+                    options.shape = Some(Shape::Radius(radius * options.unit));
+                    by_radius = true;
+                    index += 2;
+                }
+                b"bybox" if remaining >= 3 && !by_box => {
+                    let width = parse_distance(args[index + 1], "width")?;
+                    let height = parse_distance(args[index + 2], "height")?;
+                    if width < 0.0 || height < 0.0 {
+                        return Err(ServerError::InvalidArgument(String::from(
+                            "height or width cannot be negative",
+                        )));
+                    }
+                    options.unit = parse_unit(args[index + 3])?;
+                    options.shape = Some(Shape::Box(width * options.unit, height * options.unit));
+                    by_box = true;
+                    index += 3;
+                }
+                b"asc" => options.order = Order::Ascending,
+                b"desc" => options.order = Order::Descending,
+                b"count" if remaining >= 1 => {
+                    let count = parse_integer(args[index + 1])?;
+                    if count <= 0 {
+                        return Err(ServerError::InvalidArgument(String::from(
+                            "COUNT must be > 0",
+                        )));
+                    }
+                    options.count = Some(count as usize);
+                    index += 1;
+
+                    if remaining >= 2 && args[index + 1].eq_ignore_ascii_case(b"any") {
+                        options.any = true;
+                        index += 1;
+                    }
+                }
+                b"withcoord" => options.with_coordinates = true,
+                b"withdist" => options.with_distance = true,
+                b"withhash" => options.with_hash = true,
+                b"storedist" if store => options.store_distance = true,
+                _ => return Err(ServerError::Syntax),
+            }
+            index += 1;
+        }
+
+        if from_member == from_coordinates {
+            return Err(ServerError::InvalidArgument(format!(
+                "exactly one of FROMMEMBER or FROMLONLAT can be specified for {}",
+                command
+            )));
+        }
+        if by_radius == by_box {
+            return Err(ServerError::InvalidArgument(format!(
+                "exactly one of BYRADIUS and BYBOX can be specified for {}",
+                command
+            )));
+        }
+        if store && (options.with_coordinates || options.with_distance || options.with_hash) {
+            return Err(ServerError::InvalidArgument(format!(
+                "{} is not compatible with WITHDIST, WITHHASH and WITHCOORD options",
+                command
+            )));
+        }
+
+        // Without ANY, the closest members are returned
+        if options.count.is_some() && !options.any && options.order == Order::Unsorted {
+            options.order = Order::Ascending;
+        }
+
+        Ok(options)
+    }
+}
+
+/// A member found by a search, with its score and its distance from the center in meters
+struct Found {
+    member: Vec<u8>,
+    score: f64,
+    distance: f64,
+}
+
+/// Find the members of the sorted set in the searched area, in the order of the options
+fn search(zset: &SortedSet, options: &SearchOptions) -> ServerResult<Vec<Found>> {
+    let (longitude, latitude) = match options.origin {
+        Some(Origin::Member(member)) => {
+            let score = zset.score(member).ok_or_else(|| {
+                ServerError::InvalidArgument(String::from("could not decode requested zset member"))
+            })?;
+            geohash::decode(score)
+        }
+        Some(Origin::Coordinates(longitude, latitude)) => (longitude, latitude),
+        None => unreachable!("the origin is required"),
+    };
+    let search = Search {
+        longitude,
+        latitude,
+        shape: options.shape.expect("the shape is required"),
+    };
+
+    // Unsorted searches with ANY stop as soon as enough members are found
+    let limit = options.count.filter(|_| options.any);
+    let mut found = vec![];
+
+    for (min, max) in search.score_ranges() {
+        if let Some(limit) = limit
+            && found.len() >= limit
+        {
+            break;
+        }
+
+        let start = zset.count_while(|score, _| score < min as f64);
+        let end = zset.count_while(|score, _| score < max as f64);
+        for (member, score) in zset.range(start, end, false) {
+            let (member_longitude, member_latitude) = geohash::decode(score);
+            if let Some(distance) = search.distance_if_within(member_longitude, member_latitude) {
+                found.push(Found {
+                    member: member.to_vec(),
+                    score,
+                    distance,
+                });
+                if limit.is_some_and(|limit| found.len() >= limit) {
+                    break;
+                }
+            }
+        }
+    }
+
+    match options.order {
+        Order::Unsorted => {}
+        Order::Ascending => found.sort_by(|a, b| a.distance.total_cmp(&b.distance)),
+        Order::Descending => found.sort_by(|a, b| b.distance.total_cmp(&a.distance)),
+    }
+    if let Some(count) = options.count {
+        found.truncate(count);
+    }
+
+    Ok(found)
+}
+
+/// GEOSEARCH key FROMMEMBER member | FROMLONLAT longitude latitude
+///     BYRADIUS radius M | KM | FT | MI | BYBOX width height M | KM | FT | MI
+///     [ASC | DESC] [COUNT count [ANY]] [WITHCOORD] [WITHDIST] [WITHHASH]
+pub fn geosearch(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let options = SearchOptions::parse(&args[1..], "GEOSEARCH", false)?;
+
+    let found = match ctx.db().get_sorted_set(args[0])? {
+        Some(zset) => search(zset, &options)?,
+        None => vec![],
+    };
+
+    let with_details = options.with_coordinates || options.with_distance || options.with_hash;
+    let reply = found
+        .into_iter()
+        .map(|found| {
+            let member = RESP::BulkString(found.member);
+            if !with_details {
+                return member;
+            }
+
+            let mut details = vec![member];
+            if options.with_distance {
+                details.push(distance_reply(found.distance / options.unit));
+            }
+            if options.with_hash {
+                details.push(RESP::Integer(found.score as i64));
+            }
+            if options.with_coordinates {
+                details.push(coordinates_reply(found.score));
+            }
+            RESP::Array(details)
+        })
+        .collect();
+
+    Ok(RESP::Array(reply))
+}
+
+/// GEOSEARCHSTORE destination source FROMMEMBER member | FROMLONLAT longitude latitude
+///     BYRADIUS radius M | KM | FT | MI | BYBOX width height M | KM | FT | MI
+///     [ASC | DESC] [COUNT count [ANY]] [STOREDIST]
+pub fn geosearchstore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let options = SearchOptions::parse(&args[2..], "GEOSEARCHSTORE", true)?;
+
+    let db = ctx.db();
+    let found = match db.get_sorted_set(args[1])? {
+        Some(zset) => search(zset, &options)?,
+        None => vec![],
+    };
+
+    // The members keep their geohash as score, or get their distance with STOREDIST
+    let mut result = SortedSet::default();
+    for found in found {
+        let score = match options.store_distance {
+            true => found.distance / options.unit,
+            false => found.score,
+        };
+        result.insert(&found.member, score);
+    }
+
+    Ok(store(db, args[0], result))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    fn bulk(data: &str) -> RESP {
+        RESP::BulkString(data.as_bytes().to_vec())
+    }
+
+    fn sicily() -> Storage {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &[
+                "GEOADD",
+                "Sicily",
+                "13.361389",
+                "38.115556",
+                "Palermo",
+                "15.087269",
+                "37.502669",
+                "Catania",
+            ],
+        )
+        .unwrap();
+        storage
+    }
+
+    #[test]
+    fn test_geoadd() {
+        let mut storage = sicily();
+
+        assert_eq!(
+            execute(&mut storage, &["ZSCORE", "Sicily", "Palermo"]),
+            Ok(RESP::Double(3479099956230698.0))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOADD",
+                    "Sicily",
+                    "CH",
+                    "13.361389",
+                    "38.115556",
+                    "Palermo"
+                ]
+            ),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOADD", "Sicily", "XX", "CH", "13.5", "38.1", "Palermo", "14", "37", "New"
+                ]
+            ),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZCARD", "Sicily"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GEOADD", "Sicily", "13", "38", "a", "14"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["GEOADD", "Sicily", "181", "38", "Nowhere"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "invalid longitude,latitude pair 181.000000,38.000000"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_geopos_geodist_and_geohash() {
+        let mut storage = sicily();
+
+        let position = match execute(&mut storage, &["GEOPOS", "Sicily", "Palermo", "Nowhere"]) {
+            Ok(RESP::Array(mut positions)) => {
+                assert_eq!(positions.pop(), Some(RESP::NullArray));
+                positions.pop().unwrap()
+            }
+            other => panic!("unexpected reply {:?}", other),
+        };
+        match position {
+            RESP::Array(coordinates) => match coordinates.as_slice() {
+                [RESP::Double(longitude), RESP::Double(latitude)] => {
+                    assert!((longitude - 13.361389).abs() < 1e-5);
+                    assert!((latitude - 38.115556).abs() < 1e-5);
+                }
+                other => panic!("unexpected coordinates {:?}", other),
+            },
+            other => panic!("unexpected position {:?}", other),
+        }
+
+        assert_eq!(
+            execute(&mut storage, &["GEODIST", "Sicily", "Palermo", "Catania"]),
+            Ok(bulk("166274.1516"))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["GEODIST", "Sicily", "Palermo", "Catania", "km"]
+            ),
+            Ok(bulk("166.2742"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GEODIST", "Sicily", "Palermo", "Nowhere"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["GEODIST", "Sicily", "Palermo", "Catania", "yd"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "unsupported unit provided. please use M, KM, FT, MI"
+            )))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["GEOHASH", "Sicily", "Palermo", "Nowhere"]),
+            Ok(RESP::Array(vec![bulk("sqc8b49rny0"), RESP::Null]))
+        );
+    }
+
+    #[test]
+    fn test_geosearch() {
+        let mut storage = sicily();
+        execute(
+            &mut storage,
+            &[
+                "GEOADD",
+                "Sicily",
+                "12.758489",
+                "38.788135",
+                "edge1",
+                "17.241510",
+                "38.788135",
+                "edge2",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "200",
+                    "km",
+                    "ASC"
+                ]
+            ),
+            Ok(RESP::Array(vec![bulk("Catania"), bulk("Palermo")]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYBOX",
+                    "400",
+                    "400",
+                    "km",
+                    "DESC",
+                    "WITHDIST"
+                ]
+            ),
+            Ok(RESP::Array(vec![
+                RESP::Array(vec![bulk("edge1"), bulk("279.7405")]),
+                RESP::Array(vec![bulk("edge2"), bulk("279.7403")]),
+                RESP::Array(vec![bulk("Palermo"), bulk("190.4424")]),
+                RESP::Array(vec![bulk("Catania"), bulk("56.4413")]),
+            ]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOSEARCH",
+                    "Sicily",
+                    "FROMMEMBER",
+                    "Palermo",
+                    "BYRADIUS",
+                    "200",
+                    "km",
+                    "COUNT",
+                    "1",
+                    "WITHHASH"
+                ]
+            ),
+            Ok(RESP::Array(vec![RESP::Array(vec![
+                bulk("Palermo"),
+                RESP::Integer(3479099956230698)
+            ])]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOSEARCH",
+                    "missing",
+                    "FROMMEMBER",
+                    "Palermo",
+                    "BYRADIUS",
+                    "200",
+                    "km"
+                ]
+            ),
+            Ok(RESP::Array(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_geosearch_errors() {
+        let mut storage = sicily();
+        let mut search = |args: &[&str]| {
+            let mut command = vec!["GEOSEARCH", "Sicily"];
+            command.extend(args);
+            execute(&mut storage, &command)
+        };
+        let error = |message: &str| Err(ServerError::InvalidArgument(String::from(message)));
+
+        assert_eq!(
+            search(&["FROMMEMBER", "Nowhere", "BYRADIUS", "1", "km"]),
+            error("could not decode requested zset member")
+        );
+        assert_eq!(
+            search(&["FROMLONLAT", "15", "37", "BYRADIUS", "-1", "km"]),
+            error("radius cannot be negative")
+        );
+        assert_eq!(
+            search(&[
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYRADIUS",
+                "1",
+                "km",
+                "BYBOX",
+                "1",
+                "1",
+                "km"
+            ]),
+            error("exactly one of BYRADIUS and BYBOX can be specified for GEOSEARCH")
+        );
+        assert_eq!(
+            search(&["BYRADIUS", "1", "km", "ASC", "WITHDIST"]),
+            error("exactly one of FROMMEMBER or FROMLONLAT can be specified for GEOSEARCH")
+        );
+        assert_eq!(
+            search(&[
+                "FROMLONLAT",
+                "15",
+                "37",
+                "BYRADIUS",
+                "1",
+                "km",
+                "COUNT",
+                "0"
+            ]),
+            error("COUNT must be > 0")
+        );
+        assert_eq!(
+            search(&["FROMLONLAT", "15", "37", "BYRADIUS", "1", "km", "ANY"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOSEARCHSTORE",
+                    "dest",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "1",
+                    "km",
+                    "WITHDIST"
+                ]
+            ),
+            error("GEOSEARCHSTORE is not compatible with WITHDIST, WITHHASH and WITHCOORD options")
+        );
+    }
+
+    #[test]
+    fn test_geosearchstore() {
+        let mut storage = sicily();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOSEARCHSTORE",
+                    "dest",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "15",
+                    "37",
+                    "BYRADIUS",
+                    "200",
+                    "km",
+                    "COUNT",
+                    "1",
+                    "STOREDIST"
+                ]
+            ),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "dest", "0", "-1", "WITHSCORES"]),
+            Ok(RESP::Array(vec![
+                bulk("Catania"),
+                RESP::Double(56.4412578701582)
+            ]))
+        );
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "GEOSEARCHSTORE",
+                    "dest",
+                    "Sicily",
+                    "FROMLONLAT",
+                    "0",
+                    "0",
+                    "BYRADIUS",
+                    "1",
+                    "km"
+                ]
+            ),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "dest"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+}
@@ -0,0 +1,1242 @@
+use crate::commands::expire::{ExpireConditions, TimeUnit, absolute_expire_time, ttl_value};
+use crate::commands::scan::{ScanOptions, Scanned, parse_cursor, scan_reply};
+use crate::commands::string::ExpireOption;
+use crate::commands::{
+    Context, parse_float, parse_integer, parse_random_count, pick_distinct, write_random_picks,
+};
+use crate::resp::{ProtocolVersion, RESP};
+use crate::resp_writer::RESPWriter;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{Db, Hash, Value, current_time_ms};
+
+/// Return the hash stored at the key, creating an empty one if the key is missing
+fn get_or_create_hash<'a>(db: &'a mut Db, key: &[u8]) -> ServerResult<&'a mut Hash> {
+    if db.get_hash(key)?.is_none() {
+        db.insert(key, Value::Hash(Hash::default()));
+    }
+
+    Ok(db
+        .get_hash_mut(key)?
+        .expect("the key has just been created"))
+}
+
+fn bulk_or_null(value: Option<&Vec<u8>>) -> RESP {
+    match value {
+        Some(data) => RESP::BulkString(data.clone()),
+        None => RESP::Null,
+    }
+}
+
+/// HSET key field value [field value ...]
+pub fn hset(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    if args.len() % 2 != 1 {
+        return Err(ServerError::WrongArity(String::from("hset")));
+    }
+
+    let hash = get_or_create_hash(ctx.db(), args[0])?;
+    let added = args[1..]
+        .chunks(2)
+        .filter(|pair| hash.insert(pair[0], pair[1].to_vec()))
+        .count();
+
+    Ok(RESP::Integer(added as i64))
+}
+
+/// HSETNX key field value
+pub fn hsetnx(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let hash = get_or_create_hash(ctx.db(), args[0])?;
+
+    if hash.contains_key(args[1]) {
+        return Ok(RESP::Integer(0));
+    }
+
+    hash.insert(args[1], args[2].to_vec());
+    Ok(RESP::Integer(1))
+}
+
+/// HGET key field
+pub fn hget(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let value = ctx
+        .db()
+        .get_hash(args[0])?
+        .and_then(|hash| hash.get(args[1]));
+    Ok(bulk_or_null(value))
+}
+
+/// HMGET key field [field ...]
+pub fn hmget(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let hash = ctx.db().get_hash(args[0])?;
+
+    let values = args[1..]
+        .iter()
+        .map(|field| bulk_or_null(hash.and_then(|hash| hash.get(field))))
+        .collect();
+
+    Ok(RESP::Array(values))
+}
+
+/// HDEL key field [field ...]
+pub fn hdel(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let db = ctx.db();
+
+    let hash = match db.get_hash_mut(key)? {
+        Some(hash) => hash,
+        None => return Ok(RESP::Integer(0)),
+    };
+    let deleted = args[1..]
+        .iter()
+        .filter(|field| hash.remove(field).is_some())
+        .count();
+
+    db.remove_if_empty(key);
+    Ok(RESP::Integer(deleted as i64))
+}
+
+/// HEXISTS key field
+pub fn hexists(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let exists = ctx
+        .db()
+        .get_hash(args[0])?
+        .is_some_and(|hash| hash.contains_key(args[1]));
+
+    Ok(RESP::Integer(exists as i64))
+}
+
+/// HLEN key
+pub fn hlen(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let length = ctx.db().get_hash(args[0])?.map_or(0, |hash| hash.len());
+    Ok(RESP::Integer(length as i64))
+}
+
+/// HSTRLEN key field
+pub fn hstrlen(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let length = ctx
+        .db()
+        .get_hash(args[0])?
+        .and_then(|hash| hash.get(args[1]))
+        .map_or(0, |value| value.len());
+
+    Ok(RESP::Integer(length as i64))
+}
+
+/// HKEYS key
+pub fn hkeys(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let fields = ctx.db().get_hash(args[0])?.map_or_else(Vec::new, |hash| {
+        hash.keys()
+            .map(|field| RESP::BulkString(field.clone()))
+            .collect()
+    });
+
+    Ok(RESP::Array(fields))
+}
+
+/// HVALS key
+pub fn hvals(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let values = ctx.db().get_hash(args[0])?.map_or_else(Vec::new, |hash| {
+        hash.values()
+            .map(|value| RESP::BulkString(value.clone()))
+            .collect()
+    });
+
+    Ok(RESP::Array(values))
+}
+
+/// HGETALL key
+pub fn hgetall(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    // Maps are sent as flat arrays of fields and values to RESP2 clients
+    let pairs = ctx.db().get_hash(args[0])?.map_or_else(Vec::new, |hash| {
+        hash.iter()
+            .map(|(field, value)| {
+                (
+                    RESP::BulkString(field.clone()),
+                    RESP::BulkString(value.clone()),
+                )
+            })
+            .collect()
+    });
+
+    Ok(RESP::Map(pairs))
+}
+
+/// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+pub fn hscan(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let cursor = parse_cursor(args[1])?;
+    let options = ScanOptions::parse(&args[2..], Scanned::Hash)?;
+
+    let hash = match ctx.db().get_hash(args[0])? {
+        Some(hash) => hash,
+        None => return Ok(scan_reply(0, vec![])),
+    };
+
+    let (cursor, pairs) = options.collect(cursor, |cursor, pairs| {
+        hash.scan(cursor, |field, value| {
+            pairs.push((field.clone(), value.clone()))
+        })
+    });
+
+    // Fields and values are flattened, as with HGETALL for RESP2 clients
+    let mut elements = vec![];
+    for (field, value) in pairs {
+        if !options.matches(&field) {
+            continue;
+        }
+        elements.push(RESP::BulkString(field));
+        if !options.no_values {
+            elements.push(RESP::BulkString(value));
+        }
+    }
+
+    Ok(scan_reply(cursor, elements))
+}
+
+/// HINCRBY key field increment
+pub fn hincrby(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let increment = parse_integer(args[2])?;
+    let hash = get_or_create_hash(ctx.db(), args[0])?;
+
+    let current = match hash.get(args[1]) {
+        Some(value) => parse_integer(value).map_err(|_| ServerError::HashValueNotAnInteger)?,
+        None => 0,
+    };
+    let value = current
+        .checked_add(increment)
+        .ok_or(ServerError::IncrementOverflow)?;
+
+    hash.insert_keep_ttl(args[1], value.to_string().into_bytes());
+    Ok(RESP::Integer(value))
+}
+
+/// HINCRBYFLOAT key field increment
+pub fn hincrbyfloat(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let increment = parse_float(args[2])?;
+    let hash = get_or_create_hash(ctx.db(), args[0])?;
+
+    let current = match hash.get(args[1]) {
+        Some(value) => parse_float(value).map_err(|_| ServerError::HashValueNotAFloat)?,
+        None => 0.0,
+    };
+
+    let value = current + increment;
+    if !value.is_finite() {
+        return Err(ServerError::IncrementNaN);
+    }
+
+    let data = value.to_string().into_bytes();
+    hash.insert_keep_ttl(args[1], data.clone());
+    Ok(RESP::BulkString(data))
+}
+
+/// HRANDFIELD key [count [WITHVALUES]]
+pub fn hrandfield(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (count, with_values) = match args[1..] {
+        [] => (None, false),
+        [count] => (Some(parse_random_count(count, i64::MAX)?), false),
+        // As in Redis, the count is halved since the reply holds two entries per field
+        [count, option] if option.eq_ignore_ascii_case(b"withvalues") => {
+            (Some(parse_random_count(count, i64::MAX / 2)?), true)
+        }
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let count = match count {
+        Some(count) => count,
+        None => {
+            let hash = ctx.db().get_hash(args[0])?;
+            let field = hash.and_then(Hash::random_entry).map(|(field, _)| field);
+            return Ok(bulk_or_null(field));
+        }
+    };
+
+    // RESP3 clients get each field with its value in an array of two
+    let protocol = ctx.client.protocol;
+    let (elements_per_field, nested) = match (with_values, protocol) {
+        (false, _) => (1, false),
+        (true, ProtocolVersion::RESP2) => (2, false),
+        (true, ProtocolVersion::RESP3) => (1, true),
+    };
+    let write_field = |writer: &mut RESPWriter, (field, value): (&Vec<u8>, &Vec<u8>)| {
+        if nested {
+            writer.array_header(2);
+        }
+        writer.bulk_string(field);
+        if with_values {
+            writer.bulk_string(value);
+        }
+    };
+
+    ctx.write_reply(|db, writer| {
+        let hash = match db.get_hash(args[0])? {
+            Some(hash) => hash,
+            None => {
+                writer.array_header(0);
+                return Ok(());
+            }
+        };
+        let live = hash.live_len();
+
+        // A positive count asks for distinct fields, a negative one allows repetitions
+        if count >= 0 {
+            let picked = pick_distinct(count as usize, live, hash.iter(), || hash.random_entry());
+            writer.array_header(picked.len() * elements_per_field);
+            picked
+                .into_iter()
+                .for_each(|entry| write_field(writer, entry));
+            return Ok(());
+        }
+
+        let picks = if live == 0 {
+            0
+        } else {
+            count.unsigned_abs() as usize
+        };
+        write_random_picks(writer, picks, elements_per_field, |writer| {
+            let entry = hash.random_entry().expect("the hash has live fields");
+            write_field(writer, entry);
+        })
+    })
+}
+
+/// Parse the `FIELDS numfields field [field ...]` arguments ending the commands that act on
+/// specific fields. Each field is followed by `values_per_field - 1` values.
+fn parse_fields<'a, 'b>(
+    args: &'a [&'b [u8]],
+    values_per_field: usize,
+) -> ServerResult<&'a [&'b [u8]]> {
+    match args {
+        [keyword, count, fields @ ..] if keyword.eq_ignore_ascii_case(b"fields") => {
+            let count = parse_integer(count)?;
+            if count <= 0 {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "Parameter `numFields` should be greater than 0",
+                )));
+            }
+            if fields.len() % values_per_field != 0
+                || count as usize != fields.len() / values_per_field
+            {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "The `numfields` parameter must match the number of arguments",
+                )));
+            }
+            Ok(fields)
+        }
+        _ => Err(ServerError::InvalidArgument(String::from(
+            "Mandatory argument FIELDS is missing or not at the right position",
+        ))),
+    }
+}
+
+fn is_fields_keyword(arg: Option<&&[u8]>) -> bool {
+    arg.is_some_and(|arg| arg.eq_ignore_ascii_case(b"fields"))
+}
+
+/// Shared implementation of HEXPIRE, HPEXPIRE, HEXPIREAT and HPEXPIREAT, replying for each
+/// field with -2 if it does not exist, 0 if the condition is not met, 1 if the time was set and
+/// 2 if the field was deleted because the time is in the past
+fn hexpire_generic(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    unit: TimeUnit,
+    relative: bool,
+    command: &str,
+) -> ServerResult<RESP> {
+    let key = args[0];
+    let expires_at = absolute_expire_time(args[1], unit, relative, command)?;
+
+    // At most one of NX, XX, GT and LT comes before the fields
+    let fields_index = if is_fields_keyword(args.get(2)) { 2 } else { 3 };
+    let conditions = ExpireConditions::parse(&args[2..fields_index])?;
+    let fields = parse_fields(&args[fields_index..], 1)?;
+
+    let now = current_time_ms() as i64;
+    let db = ctx.db();
+    let mut replies = Vec::with_capacity(fields.len());
+
+    for field in fields {
+        let current = match db.get_hash(key)? {
+            Some(hash) if hash.contains_key(field) => hash.expires_at(field),
+            _ => {
+                replies.push(RESP::Integer(-2));
+                continue;
+            }
+        };
+
+        if !conditions.allow(current, expires_at) {
+            replies.push(RESP::Integer(0));
+            continue;
+        }
+
+        db.set_field_expiry(key, field, Some(expires_at.max(0) as u64));
+        replies.push(RESP::Integer(if expires_at <= now { 2 } else { 1 }));
+    }
+
+    Ok(RESP::Array(replies))
+}
+
+/// HEXPIRE key seconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub fn hexpire(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    hexpire_generic(ctx, args, TimeUnit::Seconds, true, "hexpire")
+}
+
+/// HPEXPIRE key milliseconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub fn hpexpire(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    hexpire_generic(ctx, args, TimeUnit::Milliseconds, true, "hpexpire")
+}
+
+/// HEXPIREAT key unix-time-seconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub fn hexpireat(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    hexpire_generic(ctx, args, TimeUnit::Seconds, false, "hexpireat")
+}
+
+/// HPEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub fn hpexpireat(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    hexpire_generic(ctx, args, TimeUnit::Milliseconds, false, "hpexpireat")
+}
+
+/// Shared implementation of HTTL, HPTTL, HEXPIRETIME and HPEXPIRETIME, replying for each field
+/// with -2 if it does not exist and -1 if it has no time to live
+fn httl_generic(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    unit: TimeUnit,
+    absolute: bool,
+) -> ServerResult<RESP> {
+    let fields = parse_fields(&args[1..], 1)?;
+    let hash = ctx.db().get_hash(args[0])?;
+
+    let replies = fields
+        .iter()
+        .map(|field| match hash {
+            Some(hash) if hash.contains_key(field) => match hash.expires_at(field) {
+                Some(expires_at) => RESP::Integer(ttl_value(expires_at, unit, absolute)),
+                None => RESP::Integer(-1),
+            },
+            _ => RESP::Integer(-2),
+        })
+        .collect();
+
+    Ok(RESP::Array(replies))
+}
+
+/// HTTL key FIELDS numfields field [field ...]
+pub fn httl(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    httl_generic(ctx, args, TimeUnit::Seconds, false)
+}
+
+/// HPTTL key FIELDS numfields field [field ...]
+pub fn hpttl(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    httl_generic(ctx, args, TimeUnit::Milliseconds, false)
+}
+
+/// HEXPIRETIME key FIELDS numfields field [field ...]
+pub fn hexpiretime(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    httl_generic(ctx, args, TimeUnit::Seconds, true)
+}
+
+/// HPEXPIRETIME key FIELDS numfields field [field ...]
+pub fn hpexpiretime(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    httl_generic(ctx, args, TimeUnit::Milliseconds, true)
+}
+
+/// HPERSIST key FIELDS numfields field [field ...]
+pub fn hpersist(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let fields = parse_fields(&args[1..], 1)?;
+
+    let db = ctx.db();
+    let mut replies = Vec::with_capacity(fields.len());
+
+    for field in fields {
+        let reply = match db.get_hash(key)? {
+            Some(hash) if hash.contains_key(field) => match hash.expires_at(field) {
+                Some(_) => {
+                    db.set_field_expiry(key, field, None);
+                    1
+                }
+                None => -1,
+            },
+            _ => -2,
+        };
+        replies.push(RESP::Integer(reply));
+    }
+
+    Ok(RESP::Array(replies))
+}
+
+/// HGETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+/// PXAT unix-time-milliseconds | PERSIST] FIELDS numfields field [field ...]
+pub fn hgetex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+
+    // `Some(None)` removes the time to live of the fields
+    let mut expire: Option<Option<u64>> = None;
+    let mut index = 1;
+    while !is_fields_keyword(args.get(index)) && index < args.len() {
+        let option = args[index].to_ascii_lowercase();
+
+        match ExpireOption::parse(&option) {
+            _ if expire.is_some() => return Err(ServerError::Syntax),
+            _ if option == b"persist" => {
+                expire = Some(None);
+                index += 1;
+            }
+            Some(unit) if index + 1 < args.len() => {
+                expire = Some(Some(unit.to_absolute_ms(args[index + 1], "hgetex")?));
+                index += 2;
+            }
+            _ => return Err(ServerError::Syntax),
+        }
+    }
+
+    let fields = parse_fields(&args[index..], 1)?;
+
+    let db = ctx.db();
+    let hash = db.get_hash(key)?;
+    let values = fields
+        .iter()
+        .map(|field| bulk_or_null(hash.and_then(|hash| hash.get(field))))
+        .collect();
+
+    if let Some(expires_at) = expire {
+        for field in fields {
+            db.set_field_expiry(key, field, expires_at);
+        }
+    }
+
+    Ok(RESP::Array(values))
+}
+
+/// HSETEX key [FNX | FXX] [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+/// PXAT unix-time-milliseconds | KEEPTTL] FIELDS numfields field value [field value ...]
+pub fn hsetex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let mut only_if_missing = false;
+    let mut only_if_exists = false;
+    let mut keep_ttl = false;
+    let mut expires_at = None;
+
+    let mut index = 1;
+    while !is_fields_keyword(args.get(index)) && index < args.len() {
+        let option = args[index].to_ascii_lowercase();
+        let has_expire = keep_ttl || expires_at.is_some();
+
+        match option.as_slice() {
+            b"fnx" if !only_if_exists => only_if_missing = true,
+            b"fxx" if !only_if_missing => only_if_exists = true,
+            b"keepttl" if !has_expire => keep_ttl = true,
+            _ => match ExpireOption::parse(&option) {
+                Some(unit) if !has_expire && index + 1 < args.len() => {
+                    index += 1;
+                    expires_at = Some(unit.to_absolute_ms(args[index], "hsetex")?);
+                }
+                _ => return Err(ServerError::Syntax),
+            },
+        }
+
+        index += 1;
+    }
+
+    let pairs = parse_fields(&args[index..], 2)?;
+
+    let db = ctx.db();
+    if only_if_missing || only_if_exists {
+        let hash = db.get_hash(key)?;
+        let exists = |field: &[u8]| hash.is_some_and(|hash| hash.contains_key(field));
+
+        let allowed = pairs.chunks(2).all(|pair| {
+            if only_if_missing {
+                !exists(pair[0])
+            } else {
+                exists(pair[0])
+            }
+        });
+        if !allowed {
+            return Ok(RESP::Integer(0));
+        }
+    }
+
+    let hash = get_or_create_hash(db, key)?;
+    for pair in pairs.chunks(2) {
+        if keep_ttl {
+            hash.insert_keep_ttl(pair[0], pair[1].to_vec());
+        } else {
+            hash.insert(pair[0], pair[1].to_vec());
+        }
+    }
+
+    if let Some(expires_at) = expires_at {
+        for pair in pairs.chunks(2) {
+            db.set_field_expiry(key, pair[0], Some(expires_at));
+        }
+    }
+
+    Ok(RESP::Integer(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::{ProtocolVersion, RESP};
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    fn bulk(data: &str) -> RESP {
+        RESP::BulkString(data.as_bytes().to_vec())
+    }
+
+    /// Sort the elements of an array reply, as hashes have no order
+    fn sorted(reply: RESP) -> Vec<String> {
+        match reply {
+            RESP::Array(elements) => {
+                let mut elements: Vec<String> = elements
+                    .into_iter()
+                    .map(|element| match element {
+                        RESP::BulkString(data) => String::from_utf8(data).unwrap(),
+                        other => panic!("unexpected element {:?}", other),
+                    })
+                    .collect();
+                elements.sort();
+                elements
+            }
+            other => panic!("unexpected reply {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_hset_and_hget() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HSET", "hash", "a", "3", "c", "4"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(execute(&mut storage, &["HGET", "hash", "a"]), Ok(bulk("3")));
+        assert_eq!(
+            execute(&mut storage, &["HGET", "hash", "z"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["HGET", "none", "a"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["HMGET", "hash", "a", "z", "b"]),
+            Ok(RESP::Array(vec![bulk("3"), RESP::Null, bulk("2")]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HLEN", "hash"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HSET", "hash", "a"]),
+            Err(ServerError::WrongArity(String::from("hset")))
+        );
+    }
+
+    #[test]
+    fn test_hsetnx_hexists_hstrlen() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["HSETNX", "hash", "a", "hello"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HSETNX", "hash", "a", "other"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HEXISTS", "hash", "a"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HEXISTS", "hash", "b"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HSTRLEN", "hash", "a"]),
+            Ok(RESP::Integer(5))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HSTRLEN", "hash", "b"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_hdel() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["HDEL", "hash", "a", "z", "a"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HDEL", "hash", "b"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "hash"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HDEL", "hash", "b"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_hkeys_hvals_hgetall() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+
+        assert_eq!(
+            sorted(execute(&mut storage, &["HKEYS", "hash"]).unwrap()),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["HVALS", "hash"]).unwrap()),
+            vec!["1", "2"]
+        );
+
+        let mut pairs = match execute(&mut storage, &["HGETALL", "hash"]) {
+            Ok(RESP::Map(pairs)) => pairs,
+            other => panic!("unexpected reply {:?}", other),
+        };
+        pairs.sort_by_key(|(field, _)| field.to_string());
+        assert_eq!(pairs, vec![(bulk("a"), bulk("1")), (bulk("b"), bulk("2"))]);
+
+        assert_eq!(
+            execute(&mut storage, &["HGETALL", "none"]),
+            Ok(RESP::Map(vec![]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HKEYS", "none"]),
+            Ok(RESP::Array(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_hgetall_encoding() {
+        let reply = RESP::Map(vec![(bulk("a"), bulk("1"))]);
+
+        assert_eq!(
+            reply.encode(ProtocolVersion::RESP2),
+            b"*2\r\n$1\r\na\r\n$1\r\n1\r\n"
+        );
+        assert_eq!(
+            reply.encode(ProtocolVersion::RESP3),
+            b"%1\r\n$1\r\na\r\n$1\r\n1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_hincrby() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["HINCRBY", "hash", "n", "5"]),
+            Ok(RESP::Integer(5))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HINCRBY", "hash", "n", "-7"]),
+            Ok(RESP::Integer(-2))
+        );
+
+        execute(&mut storage, &["HSET", "hash", "s", "abc"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["HINCRBY", "hash", "s", "1"]),
+            Err(ServerError::HashValueNotAnInteger)
+        );
+        assert_eq!(
+            execute(&mut storage, &["HINCRBY", "hash", "n", "x"]),
+            Err(ServerError::NotAnInteger)
+        );
+
+        execute(
+            &mut storage,
+            &["HSET", "hash", "max", "9223372036854775807"],
+        )
+        .unwrap();
+        assert_eq!(
+            execute(&mut storage, &["HINCRBY", "hash", "max", "1"]),
+            Err(ServerError::IncrementOverflow)
+        );
+    }
+
+    #[test]
+    fn test_hincrbyfloat() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["HINCRBYFLOAT", "hash", "f", "10.5"]),
+            Ok(bulk("10.5"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HINCRBYFLOAT", "hash", "f", "0.1"]),
+            Ok(bulk("10.6"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HGET", "hash", "f"]),
+            Ok(bulk("10.6"))
+        );
+
+        execute(&mut storage, &["HSET", "hash", "s", "abc"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["HINCRBYFLOAT", "hash", "s", "1"]),
+            Err(ServerError::HashValueNotAFloat)
+        );
+    }
+
+    #[test]
+    fn test_hrandfield() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["HRANDFIELD", "hash"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["HRANDFIELD", "hash", "3"]),
+            Ok(RESP::Array(vec![]))
+        );
+
+        execute(
+            &mut storage,
+            &["HSET", "hash", "a", "1", "b", "2", "c", "3"],
+        )
+        .unwrap();
+
+        let field = execute(&mut storage, &["HRANDFIELD", "hash"]).unwrap();
+        assert!([bulk("a"), bulk("b"), bulk("c")].contains(&field));
+
+        assert_eq!(
+            sorted(execute(&mut storage, &["HRANDFIELD", "hash", "10"]).unwrap()),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["HRANDFIELD", "hash", "2"]).unwrap()).len(),
+            2
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["HRANDFIELD", "hash", "-5"]).unwrap()).len(),
+            5
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["HRANDFIELD", "hash", "-2", "WITHVALUES"]).unwrap())
+                .len(),
+            4
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HRANDFIELD", "hash", "-9223372036854775808"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "value is out of range"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HRANDFIELD", "hash", "-1000000000", "WITHVALUES"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "value is out of range"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HRANDFIELD", "hash", "4611686018427387904", "WITHVALUES"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "value is out of range"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HRANDFIELD", "hash", "1000000000"])
+                .map(sorted)
+                .unwrap(),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            execute(&mut storage, &["HRANDFIELD", "hash", "1", "WITHSCORES"]),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+        execute(&mut storage, &["HSET", "hash", "a", "1"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["HGET", "string", "a"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["HSET", "string", "a", "1"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPUSH", "hash", "a"]),
+            Err(ServerError::WrongType)
+        );
+    }
+
+    fn integers(values: &[i64]) -> RESP {
+        RESP::Array(values.iter().map(|value| RESP::Integer(*value)).collect())
+    }
+
+    #[test]
+    fn test_hexpire() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HEXPIRE", "hash", "100", "FIELDS", "2", "a", "missing"]
+            ),
+            Ok(integers(&[1, -2]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HEXPIRE", "hash", "200", "NX", "FIELDS", "2", "a", "b"]
+            ),
+            Ok(integers(&[0, 1]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HEXPIRE", "hash", "50", "GT", "FIELDS", "1", "a"]
+            ),
+            Ok(integers(&[0]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HTTL", "hash", "FIELDS", "3", "a", "b", "c"]
+            ),
+            Ok(integers(&[100, 200, -2]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HEXPIRE", "missing", "100", "FIELDS", "1", "a"]
+            ),
+            Ok(integers(&[-2]))
+        );
+
+        // A time in the past deletes the field, and the key with its last field
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HPEXPIREAT", "hash", "1", "FIELDS", "1", "a"]
+            ),
+            Ok(integers(&[2]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HLEN", "hash"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HEXPIRE", "hash", "0", "FIELDS", "1", "b"]),
+            Ok(integers(&[2]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "hash"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_hexpire_arguments() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1"]).unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HEXPIRE", "hash", "100", "NX", "XX", "FIELDS", "1", "a"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "Mandatory argument FIELDS is missing or not at the right position"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HEXPIRE", "hash", "100", "FIELDS", "0", "a"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "Parameter `numFields` should be greater than 0"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HEXPIRE", "hash", "100", "FIELDS", "2", "a"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "The `numfields` parameter must match the number of arguments"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HEXPIRE", "hash", "abc", "FIELDS", "1", "a"]
+            ),
+            Err(ServerError::NotAnInteger)
+        );
+    }
+
+    #[test]
+    fn test_hpersist_and_hexpiretime() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+        execute(
+            &mut storage,
+            &["HPEXPIREAT", "hash", "99999999999999", "FIELDS", "1", "a"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HPEXPIRETIME", "hash", "FIELDS", "2", "a", "b"]
+            ),
+            Ok(integers(&[99999999999999, -1]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HPERSIST", "hash", "FIELDS", "3", "a", "b", "c"]
+            ),
+            Ok(integers(&[1, -1, -2]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HPTTL", "hash", "FIELDS", "1", "a"]),
+            Ok(integers(&[-1]))
+        );
+    }
+
+    #[test]
+    fn test_hset_clears_field_ttl() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1"]).unwrap();
+        execute(
+            &mut storage,
+            &["HEXPIRE", "hash", "100", "FIELDS", "1", "a"],
+        )
+        .unwrap();
+
+        execute(&mut storage, &["HINCRBY", "hash", "a", "1"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["HTTL", "hash", "FIELDS", "1", "a"]),
+            Ok(integers(&[100]))
+        );
+
+        execute(&mut storage, &["HSET", "hash", "a", "1"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["HTTL", "hash", "FIELDS", "1", "a"]),
+            Ok(integers(&[-1]))
+        );
+    }
+
+    #[test]
+    fn test_hgetex() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HGETEX", "hash", "EX", "100", "FIELDS", "2", "a", "c"]
+            ),
+            Ok(RESP::Array(vec![bulk("1"), RESP::Null]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HTTL", "hash", "FIELDS", "3", "a", "b", "c"]
+            ),
+            Ok(integers(&[100, -1, -2]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HGETEX", "hash", "PERSIST", "FIELDS", "1", "a"]
+            ),
+            Ok(RESP::Array(vec![bulk("1")]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HTTL", "hash", "FIELDS", "1", "a"]),
+            Ok(integers(&[-1]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HGETEX", "hash", "PXAT", "1", "FIELDS", "1", "a"]
+            ),
+            Ok(RESP::Array(vec![bulk("1")]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HEXISTS", "hash", "a"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HGETEX", "hash", "EX", "1", "PX", "1", "FIELDS", "1", "b"]
+            ),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_hsetex() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HSETEX", "hash", "FXX", "FIELDS", "1", "a", "1"]
+            ),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "hash"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "HSETEX", "hash", "FNX", "EX", "100", "FIELDS", "2", "a", "1", "b", "2"
+                ]
+            ),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HSETEX", "hash", "FNX", "FIELDS", "2", "a", "3", "c", "4"]
+            ),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HLEN", "hash"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HSETEX", "hash", "FXX", "KEEPTTL", "FIELDS", "1", "a", "5"]
+            ),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HTTL", "hash", "FIELDS", "1", "a"]),
+            Ok(integers(&[100]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HSETEX", "hash", "FIELDS", "1", "b", "6"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HTTL", "hash", "FIELDS", "1", "b"]),
+            Ok(integers(&[-1]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HSETEX", "hash", "FIELDS", "2", "a", "1"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "The `numfields` parameter must match the number of arguments"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["HSETEX", "hash", "FNX", "FXX", "FIELDS", "1", "a", "1"]
+            ),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_field_active_expiry() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+        execute(
+            &mut storage,
+            &["HPEXPIRE", "hash", "10", "FIELDS", "2", "a", "b"],
+        )
+        .unwrap();
+        execute(&mut storage, &["HSET", "other", "a", "1"]).unwrap();
+        execute(
+            &mut storage,
+            &["HPEXPIRE", "other", "10", "FIELDS", "1", "a"],
+        )
+        .unwrap();
+        execute(&mut storage, &["HPERSIST", "other", "FIELDS", "1", "a"]).unwrap();
+
+        let removed = storage.dbs[0].remove_expired(crate::storage::current_time_ms() + 100, 100);
+
+        assert_eq!(removed, 2);
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "hash"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HLEN", "other"]),
+            Ok(RESP::Integer(1))
+        );
+    }
+
+    #[test]
+    fn test_expired_fields_are_hidden_until_removed() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+        execute(&mut storage, &["HPEXPIRE", "hash", "1", "FIELDS", "1", "a"]).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        assert_eq!(
+            execute(&mut storage, &["HGET", "hash", "a"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["HEXISTS", "hash", "a"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HGETALL", "hash"]),
+            Ok(RESP::Map(vec![(bulk("b"), bulk("2"))]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HTTL", "hash", "FIELDS", "1", "a"]),
+            Ok(integers(&[-2]))
+        );
+        // As in Redis, the length counts the expired fields not removed yet
+        assert_eq!(
+            execute(&mut storage, &["HLEN", "hash"]),
+            Ok(RESP::Integer(2))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["HSET", "hash", "a", "3"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["HTTL", "hash", "FIELDS", "1", "a"]),
+            Ok(integers(&[-1]))
+        );
+    }
+}
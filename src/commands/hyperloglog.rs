@@ -0,0 +1,370 @@
+use crate::commands::Context;
+use crate::hyperloglog::{self, Opcode, REGISTERS};
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{Db, Value};
+
+/// The HyperLogLog stored at the key, which must be a string in the HyperLogLog format
+fn get_hyperloglog_mut<'a>(db: &'a mut Db, key: &[u8]) -> ServerResult<Option<&'a mut Vec<u8>>> {
+    match db.get_string_mut(key)? {
+        Some(data) if hyperloglog::is_hyperloglog(data) => Ok(Some(data)),
+        Some(_) => Err(ServerError::NotHyperLogLog),
+        None => Ok(None),
+    }
+}
+
+/// Merge the registers of the HyperLogLogs at the keys, missing keys counting as empty. Returns
+/// whether one of them is in the dense encoding.
+fn merge_keys(db: &mut Db, keys: &[&[u8]]) -> ServerResult<([u8; REGISTERS], bool)> {
+    let mut registers = [0; REGISTERS];
+    let mut dense = false;
+
+    for value in db.get_values(keys) {
+        let data = match value {
+            Some(Value::String(data)) if hyperloglog::is_hyperloglog(data) => data,
+            Some(Value::String(_)) => return Err(ServerError::NotHyperLogLog),
+            Some(_) => return Err(ServerError::WrongType),
+            None => continue,
+        };
+
+        dense |= !hyperloglog::is_sparse(data);
+        hyperloglog::merge_into(&mut registers, data)?;
+    }
+
+    Ok((registers, dense))
+}
+
+/// PFADD key [element [element ...]]
+pub fn pfadd(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let db = ctx.db();
+
+    let mut updated = false;
+    if get_hyperloglog_mut(db, key)?.is_none() {
+        db.insert(key, Value::String(hyperloglog::new()));
+        updated = true;
+    }
+    let data = get_hyperloglog_mut(db, key)?.expect("the key has just been created");
+
+    for element in &args[1..] {
+        updated |= hyperloglog::add(data, element)?;
+    }
+
+    Ok(RESP::Integer(updated as i64))
+}
+
+/// PFCOUNT key [key ...]
+pub fn pfcount(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+
+    if args.len() > 1 {
+        let (registers, _) = merge_keys(db, args)?;
+        return Ok(RESP::Integer(
+            hyperloglog::count_registers(&registers) as i64
+        ));
+    }
+
+    let data = match get_hyperloglog_mut(db, args[0])? {
+        Some(data) => data,
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    // The estimate is cached in the value until the next change
+    let count = match hyperloglog::cached_count(data) {
+        Some(count) => count,
+        None => {
+            let count = hyperloglog::count(data)?;
+            hyperloglog::set_cached_count(data, count);
+            count
+        }
+    };
+
+    Ok(RESP::Integer(count as i64))
+}
+
+/// PFMERGE destkey [sourcekey [sourcekey ...]]
+pub fn pfmerge(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let destination = args[0];
+    let db = ctx.db();
+
+    // The destination is merged with the sources
+    let (registers, dense) = merge_keys(db, args)?;
+
+    if get_hyperloglog_mut(db, destination)?.is_none() {
+        db.insert(destination, Value::String(hyperloglog::new()));
+    }
+    let data = get_hyperloglog_mut(db, destination)?.expect("the key has just been created");
+
+    if dense {
+        hyperloglog::to_dense(data)?;
+    }
+    for (index, register) in registers.into_iter().enumerate() {
+        if register > 0 {
+            hyperloglog::raise_register(data, index, register)?;
+        }
+    }
+    hyperloglog::invalidate_cache(data);
+
+    Ok(RESP::SimpleString(String::from("OK")))
+}
+
+/// PFDEBUG GETREG | DECODE | ENCODING | TODENSE key
+pub fn pfdebug(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let subcommand = String::from_utf8_lossy(args[0]).to_string();
+    let data = get_hyperloglog_mut(ctx.db(), args[1])?.ok_or_else(|| {
+        ServerError::InvalidArgument(String::from("The specified key does not exist"))
+    })?;
+
+    match subcommand.to_ascii_lowercase().as_str() {
+        "getreg" => {
+            hyperloglog::to_dense(data)?;
+            let registers = (0..REGISTERS)
+                .map(|index| {
+                    let register =
+                        hyperloglog::dense_register(&data[hyperloglog::HEADER_SIZE..], index);
+                    RESP::Integer(register as i64)
+                })
+                .collect();
+
+            Ok(RESP::Array(registers))
+        }
+        "decode" => {
+            if !hyperloglog::is_sparse(data) {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "HLL encoding is not sparse",
+                )));
+            }
+
+            let decoded: Vec<String> = hyperloglog::opcodes(data)
+                .map(|opcode| match opcode {
+                    Opcode::Zero(len) => format!("z:{}", len),
+                    Opcode::XZero(len) => format!("Z:{}", len),
+                    Opcode::Val(value, len) => format!("v:{},{}", value, len),
+                })
+                .collect();
+
+            Ok(RESP::BulkString(decoded.join(" ").into_bytes()))
+        }
+        "encoding" => Ok(RESP::SimpleString(String::from(
+            match hyperloglog::is_sparse(data) {
+                true => "sparse",
+                false => "dense",
+            },
+        ))),
+        "todense" => Ok(RESP::Integer(hyperloglog::to_dense(data)? as i64)),
+        _ => Err(ServerError::InvalidArgument(format!(
+            "Unknown PFDEBUG subcommand '{}'",
+            subcommand
+        ))),
+    }
+}
+
+/// PFSELFTEST
+pub fn pfselftest(_ctx: &mut Context, _args: &[&[u8]]) -> ServerResult<RESP> {
+    hyperloglog::self_test().map_err(ServerError::TestFailed)?;
+
+    Ok(RESP::SimpleString(String::from("OK")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    fn bulk(data: &str) -> RESP {
+        RESP::BulkString(data.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_pfadd_and_pfcount() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["PFADD", "hll", "a", "b", "c", "d", "e", "f", "g"]
+            ),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFADD", "hll", "a", "b"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFCOUNT", "hll"]),
+            Ok(RESP::Integer(7))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFADD", "empty"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFCOUNT", "empty"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFCOUNT", "missing"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFDEBUG", "ENCODING", "hll"]),
+            Ok(RESP::SimpleString(String::from("sparse")))
+        );
+
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["PFADD", "string", "a"]),
+            Err(ServerError::NotHyperLogLog)
+        );
+        execute(&mut storage, &["RPUSH", "list", "a"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["PFCOUNT", "hll", "list"]),
+            Err(ServerError::WrongType)
+        );
+    }
+
+    #[test]
+    fn test_estimates_with_promotion() {
+        let mut storage = Storage::new();
+
+        for batch in 0..10 {
+            let elements: Vec<String> = (0..1000)
+                .map(|element| format!("element:{}", batch * 1000 + element))
+                .collect();
+            let mut args = vec!["PFADD", "hll"];
+            args.extend(elements.iter().map(String::as_str));
+            execute(&mut storage, &args).unwrap();
+        }
+
+        assert_eq!(
+            execute(&mut storage, &["PFDEBUG", "ENCODING", "hll"]),
+            Ok(RESP::SimpleString(String::from("dense")))
+        );
+        let count = match execute(&mut storage, &["PFCOUNT", "hll"]) {
+            Ok(RESP::Integer(count)) => count,
+            other => panic!("unexpected reply {:?}", other),
+        };
+        assert!((9800..=10200).contains(&count), "{}", count);
+    }
+
+    #[test]
+    fn test_pfmerge() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["PFADD", "a", "1", "2", "3"]).unwrap();
+        execute(&mut storage, &["PFADD", "b", "3", "4"]).unwrap();
+        execute(&mut storage, &["PFADD", "dest", "5"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["PFCOUNT", "a", "b", "missing"]),
+            Ok(RESP::Integer(4))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFMERGE", "dest", "a", "b"]),
+            Ok(RESP::SimpleString(String::from("OK")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFCOUNT", "dest"]),
+            Ok(RESP::Integer(5))
+        );
+
+        // Merging into a sparse value gives the same bytes as adding the elements to it
+        execute(&mut storage, &["PFADD", "all", "1", "2", "3", "4", "5"]).unwrap();
+        execute(&mut storage, &["PFCOUNT", "all"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["GET", "dest"]),
+            execute(&mut storage, &["GET", "all"])
+        );
+
+        execute(&mut storage, &["PFDEBUG", "TODENSE", "b"]).unwrap();
+        execute(&mut storage, &["PFMERGE", "merged", "a", "b"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["PFDEBUG", "ENCODING", "merged"]),
+            Ok(RESP::SimpleString(String::from("dense")))
+        );
+    }
+
+    #[test]
+    fn test_encodings_match_the_reference() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["PFADD", "hll", "a", "b", "c", "foo", "bar"]).unwrap();
+
+        // Expected bytes computed independently, with the reference MurmurHash64A and the
+        // encodings described in the hyperloglog.c of Redis. The registers set are 7348 to 5,
+        // 8436 to 1, 10007 to 1, 12711 to 2 and 15780 to 1.
+        let header = |encoding: u8| {
+            let mut header = b"HYLL".to_vec();
+            header.extend([encoding, 0, 0, 0]);
+            // The cached cardinality is invalidated by the additions
+            header.extend([0, 0, 0, 0, 0, 0, 0, 0x80]);
+            header
+        };
+
+        let mut sparse = header(1);
+        sparse.extend([
+            0x5c, 0xb3, 0x90, 0x44, 0x3e, 0x80, 0x46, 0x21, 0x80, 0x4a, 0x8e, 0x84, 0x4b, 0xfb,
+            0x80, 0x42, 0x5a,
+        ]);
+        assert_eq!(
+            execute(&mut storage, &["GET", "hll"]),
+            Ok(RESP::BulkString(sparse))
+        );
+
+        execute(&mut storage, &["PFDEBUG", "TODENSE", "hll"]).unwrap();
+        let mut dense = header(0);
+        dense.resize(12304, 0);
+        for (index, byte) in [
+            (5511, 0x05),
+            (6327, 0x01),
+            (7505, 0x04),
+            (9533, 0x08),
+            (11835, 0x01),
+        ] {
+            dense[16 + index] = byte;
+        }
+        assert_eq!(
+            execute(&mut storage, &["GET", "hll"]),
+            Ok(RESP::BulkString(dense))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFCOUNT", "hll"]),
+            Ok(RESP::Integer(5))
+        );
+    }
+
+    #[test]
+    fn test_pfdebug() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["PFADD", "hll"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["PFDEBUG", "DECODE", "hll"]),
+            Ok(bulk("Z:16384"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFDEBUG", "TODENSE", "hll"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFDEBUG", "TODENSE", "hll"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFDEBUG", "DECODE", "hll"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "HLL encoding is not sparse"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["STRLEN", "hll"]),
+            Ok(RESP::Integer(12304))
+        );
+        assert_eq!(
+            execute(&mut storage, &["PFDEBUG", "GETREG", "missing"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "The specified key does not exist"
+            )))
+        );
+    }
+}
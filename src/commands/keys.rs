@@ -0,0 +1,654 @@
+use crate::commands::scan::{ScanOptions, Scanned, parse_cursor, scan_reply};
+use crate::commands::{Context, parse_db_index, parse_integer};
+use crate::glob;
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{Value, current_time_ms};
+
+/// Limits of the compact encodings, with the defaults of the Redis configuration
+const MAX_LISTPACK_ENTRIES: usize = 128;
+const MAX_LISTPACK_VALUE: usize = 64;
+const MAX_LIST_LISTPACK_SIZE: usize = 8 * 1024;
+const MAX_INTSET_ENTRIES: usize = 512;
+/// Longest string stored along with its object header
+const MAX_EMBSTR_LENGTH: usize = 44;
+/// Integers from 0 up to this value excluded are shared by all keys in Redis
+const SHARED_INTEGERS: i64 = 10000;
+/// Reference count of shared objects, which are never freed
+const SHARED_REFCOUNT: i64 = i32::MAX as i64;
+
+/// DEL key [key ...]
+pub fn del(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+    let deleted = args.iter().filter(|key| db.remove(key).is_some()).count();
+
+    Ok(RESP::Integer(deleted as i64))
+}
+
+/// EXISTS key [key ...]
+pub fn exists(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    // Keys mentioned several times are counted several times
+    let db = ctx.db();
+    let found = args.iter().filter(|key| db.contains_key(key)).count();
+
+    Ok(RESP::Integer(found as i64))
+}
+
+/// UNLINK key [key ...]
+pub fn unlink(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    // Values are dropped right away, there is no background thread to free them
+    del(ctx, args)
+}
+
+/// TOUCH key [key ...]
+pub fn touch(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+    let touched = args.iter().filter(|key| db.contains_key(key)).count();
+
+    Ok(RESP::Integer(touched as i64))
+}
+
+/// The name of the type of a value, as replied by TYPE
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => "string",
+        Value::List(_) => "list",
+        Value::Hash(_) => "hash",
+        Value::Set(_) => "set",
+        Value::SortedSet(_) => "zset",
+        Value::Stream(_) => "stream",
+    }
+}
+
+/// TYPE key
+pub fn type_(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let name = match ctx.db().peek_entry(args[0]) {
+        Some(entry) => type_name(&entry.value),
+        None => "none",
+    };
+
+    Ok(RESP::SimpleString(String::from(name)))
+}
+
+/// Shared implementation of RENAME and RENAMENX
+fn rename_generic(ctx: &mut Context, args: &[&[u8]], only_new: bool) -> ServerResult<bool> {
+    let (source, destination) = (args[0], args[1]);
+    let db = ctx.db();
+
+    if !db.contains_key(source) {
+        return Err(ServerError::NoSuchKey);
+    }
+    if source == destination {
+        return Ok(!only_new);
+    }
+    if only_new && db.contains_key(destination) {
+        return Ok(false);
+    }
+
+    let (value, expires_at) = db.take(source).expect("the key exists");
+    db.insert(destination, value);
+    db.set_expiry(destination, expires_at);
+
+    Ok(true)
+}
+
+/// RENAME key newkey
+pub fn rename(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    rename_generic(ctx, args, false)?;
+
+    Ok(RESP::SimpleString(String::from("OK")))
+}
+
+/// RENAMENX key newkey
+pub fn renamenx(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let renamed = rename_generic(ctx, args, true)?;
+
+    Ok(RESP::Integer(renamed as i64))
+}
+
+/// COPY source destination [DB destination-db] [REPLACE]
+pub fn copy(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (source, destination) = (args[0], args[1]);
+    let mut destination_db = ctx.client.db;
+    let mut replace = false;
+
+    let mut index = 2;
+    while index < args.len() {
+        match args[index].to_ascii_lowercase().as_slice() {
+            b"db" if index + 1 < args.len() => {
+                destination_db = parse_db_index(args[index + 1])?;
+                index += 1;
+            }
+            b"replace" => replace = true,
+            _ => return Err(ServerError::Syntax),
+        }
+        index += 1;
+    }
+
+    if source == destination && destination_db == ctx.client.db {
+        return Err(ServerError::InvalidArgument(String::from(
+            "source and destination objects are the same",
+        )));
+    }
+
+    let (value, expires_at) = match ctx.db().get_entry(source) {
+        Some(entry) => (entry.value.clone(), entry.expires_at),
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    let db = &mut ctx.storage.dbs[destination_db];
+    if !replace && db.contains_key(destination) {
+        return Ok(RESP::Integer(0));
+    }
+    db.insert(destination, value);
+    db.set_expiry(destination, expires_at);
+
+    Ok(RESP::Integer(1))
+}
+
+/// MOVE key db
+pub fn move_(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let destination_db = parse_db_index(args[1])?;
+
+    if destination_db == ctx.client.db {
+        return Err(ServerError::InvalidArgument(String::from(
+            "source and destination objects are the same",
+        )));
+    }
+
+    if !ctx.db().contains_key(key) || ctx.storage.dbs[destination_db].contains_key(key) {
+        return Ok(RESP::Integer(0));
+    }
+
+    let (value, expires_at) = ctx.db().take(key).expect("the key exists");
+    let db = &mut ctx.storage.dbs[destination_db];
+    db.insert(key, value);
+    db.set_expiry(key, expires_at);
+
+    Ok(RESP::Integer(1))
+}
+
+/// RANDOMKEY
+pub fn randomkey(ctx: &mut Context, _args: &[&[u8]]) -> ServerResult<RESP> {
+    Ok(match ctx.db().random_key() {
+        Some(key) => RESP::BulkString(key),
+        None => RESP::Null,
+    })
+}
+
+/// KEYS pattern
+pub fn keys(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    ctx.write_reply(|db, writer| {
+        writer.deferred_array(|writer| {
+            let mut count = 0;
+            for key in db.keys().filter(|key| glob::matches(args[0], key)) {
+                writer.bulk_string(key);
+                count += 1;
+            }
+            count
+        });
+        Ok(())
+    })
+}
+
+/// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+pub fn scan(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let cursor = parse_cursor(args[0])?;
+    let options = ScanOptions::parse(&args[1..], Scanned::Keys)?;
+
+    let db = ctx.db();
+    let (cursor, keys) = options.collect(cursor, |cursor, keys| {
+        db.scan(cursor, |key| keys.push(key.clone()))
+    });
+
+    // Expired keys are removed rather than returned
+    let keys = keys
+        .into_iter()
+        .filter(|key| options.matches(key))
+        .filter(|key| match db.peek_entry(key) {
+            Some(entry) => options
+                .type_name
+                .as_ref()
+                .is_none_or(|name| name == type_name(&entry.value)),
+            None => false,
+        })
+        .map(RESP::BulkString)
+        .collect();
+
+    Ok(scan_reply(cursor, keys))
+}
+
+/// DBSIZE
+pub fn dbsize(ctx: &mut Context, _args: &[&[u8]]) -> ServerResult<RESP> {
+    Ok(RESP::Integer(ctx.db().len() as i64))
+}
+
+fn is_small(len: usize, mut elements: impl Iterator<Item = usize>) -> bool {
+    len <= MAX_LISTPACK_ENTRIES && elements.all(|length| length <= MAX_LISTPACK_VALUE)
+}
+
+/// The encoding Redis would use for the value, as replied by OBJECT ENCODING. Unlike Redis,
+/// where values are converted once they grow, it only depends on the current content.
+fn encoding(value: &Value) -> &'static str {
+    match value {
+        Value::String(data) => {
+            if data.len() <= 20 && parse_integer(data).is_ok() {
+                "int"
+            } else if data.len() <= MAX_EMBSTR_LENGTH {
+                "embstr"
+            } else {
+                "raw"
+            }
+        }
+        Value::List(list) => {
+            let size: usize = list.iter().map(Vec::len).sum();
+            match size <= MAX_LIST_LISTPACK_SIZE {
+                true => "listpack",
+                false => "quicklist",
+            }
+        }
+        Value::Hash(hash) => {
+            let lengths = hash
+                .iter()
+                .flat_map(|(field, value)| [field.len(), value.len()]);
+            let with_expiry = hash.keys().any(|field| hash.expires_at(field).is_some());
+
+            match (is_small(hash.len(), lengths), with_expiry) {
+                (true, false) => "listpack",
+                (true, true) => "listpackex",
+                (false, _) => "hashtable",
+            }
+        }
+        Value::Set(set) => {
+            if set.len() <= MAX_INTSET_ENTRIES
+                && set.keys().all(|member| parse_integer(member).is_ok())
+            {
+                "intset"
+            } else if is_small(set.len(), set.keys().map(Vec::len)) {
+                "listpack"
+            } else {
+                "hashtable"
+            }
+        }
+        Value::SortedSet(zset) => {
+            match is_small(zset.len(), zset.iter().map(|(member, _)| member.len())) {
+                true => "listpack",
+                false => "skiplist",
+            }
+        }
+        Value::Stream(_) => "stream",
+    }
+}
+
+/// OBJECT ENCODING | FREQ | IDLETIME | REFCOUNT key
+pub fn object(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let subcommand = args[0].to_ascii_lowercase();
+    if !matches!(
+        subcommand.as_slice(),
+        b"encoding" | b"freq" | b"idletime" | b"refcount"
+    ) {
+        return Err(ServerError::InvalidArgument(format!(
+            "unknown subcommand '{}'. Try OBJECT HELP.",
+            String::from_utf8_lossy(args[0])
+        )));
+    }
+    if args.len() != 2 {
+        return Err(ServerError::WrongArity(format!(
+            "object|{}",
+            String::from_utf8_lossy(&subcommand)
+        )));
+    }
+
+    // Introspection does not count as an access to the key
+    let entry = match ctx.db().peek_entry(args[1]) {
+        Some(entry) => entry,
+        None => return Ok(RESP::Null),
+    };
+    let now = current_time_ms();
+
+    Ok(match subcommand.as_slice() {
+        b"encoding" => RESP::BulkString(encoding(&entry.value).as_bytes().to_vec()),
+        b"freq" => RESP::Integer(entry.frequency(now) as i64),
+        b"idletime" => RESP::Integer((now.saturating_sub(entry.accessed_at) / 1000) as i64),
+        _ => match &entry.value {
+            Value::String(data)
+                if parse_integer(data).is_ok_and(|value| (0..SHARED_INTEGERS).contains(&value)) =>
+            {
+                RESP::Integer(SHARED_REFCOUNT)
+            }
+            _ => RESP::Integer(1),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::{Storage, Value};
+
+    fn bulk(data: &str) -> RESP {
+        RESP::BulkString(data.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_del() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["MSET", "a", "1", "b", "2"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["DEL", "a", "b", "c", "a"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(execute(&mut storage, &["GET", "a"]), Ok(RESP::Null));
+    }
+
+    #[test]
+    fn test_exists() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "a", "1"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "a", "b", "a"]),
+            Ok(RESP::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &["MSET", "one", "1", "two", "2", "three", "3"],
+        )
+        .unwrap();
+
+        let RESP::Array(mut keys) = execute(&mut storage, &["KEYS", "t*"]).unwrap() else {
+            panic!("KEYS replies with an array");
+        };
+        keys.sort_by_key(|key| format!("{key:?}"));
+        assert_eq!(keys, vec![bulk("three"), bulk("two")]);
+
+        assert_eq!(
+            execute(&mut storage, &["KEYS", "?n[a-f]"]),
+            Ok(RESP::Array(vec![bulk("one")]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["KEYS", "x*"]),
+            Ok(RESP::Array(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_type() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "string", "1"]).unwrap();
+        execute(&mut storage, &["RPUSH", "list", "a"]).unwrap();
+        execute(&mut storage, &["HSET", "hash", "a", "1"]).unwrap();
+        execute(&mut storage, &["SADD", "set", "a"]).unwrap();
+        execute(&mut storage, &["ZADD", "zset", "1", "a"]).unwrap();
+        execute(&mut storage, &["XADD", "stream", "*", "a", "1"]).unwrap();
+
+        for (key, name) in [
+            ("string", "string"),
+            ("list", "list"),
+            ("hash", "hash"),
+            ("set", "set"),
+            ("zset", "zset"),
+            ("stream", "stream"),
+            ("missing", "none"),
+        ] {
+            assert_eq!(
+                execute(&mut storage, &["TYPE", key]),
+                Ok(RESP::SimpleString(String::from(name)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "a", "1", "EX", "100"]).unwrap();
+        execute(&mut storage, &["SET", "b", "2"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["RENAMENX", "a", "b"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["RENAME", "a", "b"]),
+            Ok(RESP::SimpleString(String::from("OK")))
+        );
+        assert_eq!(execute(&mut storage, &["GET", "b"]), Ok(bulk("1")));
+        assert_eq!(execute(&mut storage, &["TTL", "b"]), Ok(RESP::Integer(100)));
+        assert_eq!(
+            execute(&mut storage, &["RENAMENX", "b", "c"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["RENAMENX", "c", "c"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["RENAME", "c", "c"]),
+            Ok(RESP::SimpleString(String::from("OK")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["RENAME", "missing", "d"]),
+            Err(ServerError::NoSuchKey)
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "a", "b"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_rename_keeps_field_expiry() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+        execute(
+            &mut storage,
+            &["HPEXPIRE", "hash", "10", "FIELDS", "1", "a"],
+        )
+        .unwrap();
+        execute(&mut storage, &["RENAME", "hash", "renamed"]).unwrap();
+
+        let removed = storage.dbs[0].remove_expired(crate::storage::current_time_ms() + 100, 100);
+        assert_eq!(removed, 1);
+        assert_eq!(
+            execute(&mut storage, &["HLEN", "renamed"]),
+            Ok(RESP::Integer(1))
+        );
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["RPUSH", "list", "a", "b"]).unwrap();
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["COPY", "list", "copy"]),
+            Ok(RESP::Integer(1))
+        );
+        execute(&mut storage, &["RPUSH", "copy", "c"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["LLEN", "list"]),
+            Ok(RESP::Integer(2))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["COPY", "string", "copy"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["COPY", "string", "copy", "REPLACE"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(execute(&mut storage, &["GET", "copy"]), Ok(bulk("value")));
+        assert_eq!(
+            execute(&mut storage, &["COPY", "missing", "copy"]),
+            Ok(RESP::Integer(0))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["COPY", "string", "string", "DB", "3"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            storage.dbs[3].get(b"string"),
+            Some(&Value::String(b"value".to_vec()))
+        );
+        assert_eq!(
+            execute(&mut storage, &["COPY", "string", "string"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "source and destination objects are the same"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["COPY", "string", "other", "DB", "16"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "DB index is out of range"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["COPY", "string", "other", "DB"]),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_move() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "a", "1", "EX", "100"]).unwrap();
+        execute(&mut storage, &["SET", "b", "2"]).unwrap();
+        storage.dbs[1].insert(b"b", Value::String(b"other".to_vec()));
+
+        assert_eq!(
+            execute(&mut storage, &["MOVE", "a", "1"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "a"]),
+            Ok(RESP::Integer(0))
+        );
+        assert!(storage.dbs[1].get_entry(b"a").unwrap().expires_at.is_some());
+
+        assert_eq!(
+            execute(&mut storage, &["MOVE", "b", "1"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["MOVE", "missing", "1"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["MOVE", "b", "0"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "source and destination objects are the same"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["MOVE", "b", "one"]),
+            Err(ServerError::NotAnInteger)
+        );
+    }
+
+    #[test]
+    fn test_randomkey_dbsize_touch_and_unlink() {
+        let mut storage = Storage::new();
+        assert_eq!(execute(&mut storage, &["RANDOMKEY"]), Ok(RESP::Null));
+        assert_eq!(execute(&mut storage, &["DBSIZE"]), Ok(RESP::Integer(0)));
+
+        execute(&mut storage, &["MSET", "a", "1", "b", "2"]).unwrap();
+        assert_eq!(execute(&mut storage, &["DBSIZE"]), Ok(RESP::Integer(2)));
+        match execute(&mut storage, &["RANDOMKEY"]) {
+            Ok(RESP::BulkString(key)) => assert!(key == b"a" || key == b"b"),
+            other => panic!("unexpected reply {:?}", other),
+        }
+
+        assert_eq!(
+            execute(&mut storage, &["TOUCH", "a", "b", "c"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["UNLINK", "a", "c"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(execute(&mut storage, &["RANDOMKEY"]), Ok(bulk("b")));
+    }
+
+    #[test]
+    fn test_object() {
+        let mut storage = Storage::new();
+        let long = "x".repeat(100);
+        execute(&mut storage, &["SET", "int", "12"]).unwrap();
+        execute(&mut storage, &["SET", "embstr", "value"]).unwrap();
+        execute(&mut storage, &["SET", "raw", &long]).unwrap();
+        execute(&mut storage, &["RPUSH", "list", "a"]).unwrap();
+        execute(&mut storage, &["HSET", "hash", "a", "1"]).unwrap();
+        execute(&mut storage, &["HSET", "big", "a", &long]).unwrap();
+        execute(&mut storage, &["SADD", "intset", "1", "2"]).unwrap();
+        execute(&mut storage, &["SADD", "set", "a"]).unwrap();
+        execute(&mut storage, &["ZADD", "zset", "1", &long]).unwrap();
+
+        for (key, encoding) in [
+            ("int", "int"),
+            ("embstr", "embstr"),
+            ("raw", "raw"),
+            ("list", "listpack"),
+            ("hash", "listpack"),
+            ("big", "hashtable"),
+            ("intset", "intset"),
+            ("set", "listpack"),
+            ("zset", "skiplist"),
+        ] {
+            assert_eq!(
+                execute(&mut storage, &["OBJECT", "ENCODING", key]),
+                Ok(bulk(encoding))
+            );
+        }
+
+        assert_eq!(
+            execute(&mut storage, &["OBJECT", "REFCOUNT", "int"]),
+            Ok(RESP::Integer(2147483647))
+        );
+        assert_eq!(
+            execute(&mut storage, &["OBJECT", "REFCOUNT", "list"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["OBJECT", "IDLETIME", "list"]),
+            Ok(RESP::Integer(0))
+        );
+
+        // The first access always increments the access frequency of new keys
+        storage.dbs[0].insert(b"new", Value::String(b"value".to_vec()));
+        assert_eq!(
+            execute(&mut storage, &["OBJECT", "FREQ", "new"]),
+            Ok(RESP::Integer(5))
+        );
+        execute(&mut storage, &["GET", "new"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["OBJECT", "FREQ", "new"]),
+            Ok(RESP::Integer(6))
+        );
+        assert_eq!(
+            execute(&mut storage, &["OBJECT", "ENCODING", "missing"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["OBJECT", "ENCODING"]),
+            Err(ServerError::WrongArity(String::from("object|encoding")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["OBJECT", "SIZE", "list"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "unknown subcommand 'SIZE'. Try OBJECT HELP."
+            )))
+        );
+    }
+}
@@ -0,0 +1,989 @@
+use crate::commands::{Context, parse_integer, parse_timeout};
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{Db, Value, ValueType};
+use std::collections::VecDeque;
+
+/// End of a list that elements are pushed to or popped from
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum End {
+    Left,
+    Right,
+}
+
+impl End {
+    fn parse(arg: &[u8]) -> ServerResult<Self> {
+        match arg.to_ascii_lowercase().as_slice() {
+            b"left" => Ok(Self::Left),
+            b"right" => Ok(Self::Right),
+            _ => Err(ServerError::Syntax),
+        }
+    }
+}
+
+fn push_element(list: &mut VecDeque<Vec<u8>>, end: End, element: Vec<u8>) {
+    match end {
+        End::Left => list.push_front(element),
+        End::Right => list.push_back(element),
+    }
+}
+
+fn pop_element(list: &mut VecDeque<Vec<u8>>, end: End) -> Option<Vec<u8>> {
+    match end {
+        End::Left => list.pop_front(),
+        End::Right => list.pop_back(),
+    }
+}
+
+fn pop_elements(list: &mut VecDeque<Vec<u8>>, end: End, count: usize) -> Vec<Vec<u8>> {
+    let count = count.min(list.len());
+
+    match end {
+        End::Left => list.drain(..count).collect(),
+        End::Right => list.drain(list.len() - count..).rev().collect(),
+    }
+}
+
+fn bulk_array(elements: Vec<Vec<u8>>) -> RESP {
+    RESP::Array(elements.into_iter().map(RESP::BulkString).collect())
+}
+
+/// Return the list stored at the key, creating an empty one if the key is missing
+fn get_or_create_list<'a>(db: &'a mut Db, key: &[u8]) -> ServerResult<&'a mut VecDeque<Vec<u8>>> {
+    if db.get_list(key)?.is_none() {
+        db.insert(key, Value::List(VecDeque::new()));
+    }
+
+    Ok(db
+        .get_list_mut(key)?
+        .expect("the key has just been created"))
+}
+
+/// Resolve a possibly negative range against a list of the given length, as LRANGE does
+fn list_range(start: i64, end: i64, length: usize) -> Option<(usize, usize)> {
+    let length = length as i64;
+
+    let start = if start < 0 {
+        (length + start).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 {
+        length + end
+    } else {
+        end.min(length - 1)
+    };
+
+    if start > end || start >= length {
+        return None;
+    }
+
+    Some((start as usize, end as usize))
+}
+
+/// Resolve a possibly negative index against a list of the given length
+fn list_index(index: i64, length: usize) -> Option<usize> {
+    let index = if index < 0 {
+        length as i64 + index
+    } else {
+        index
+    };
+
+    (0..length as i64)
+        .contains(&index)
+        .then_some(index as usize)
+}
+
+/// Shared implementation of LPUSH, RPUSH, LPUSHX and RPUSHX
+fn push_generic(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    end: End,
+    only_if_exists: bool,
+) -> ServerResult<RESP> {
+    let key = args[0];
+    let db = ctx.db();
+
+    if only_if_exists && db.get_list(key)?.is_none() {
+        return Ok(RESP::Integer(0));
+    }
+
+    let list = get_or_create_list(db, key)?;
+    for element in &args[1..] {
+        push_element(list, end, element.to_vec());
+    }
+    let length = list.len();
+
+    db.signal_ready(key);
+    Ok(RESP::Integer(length as i64))
+}
+
+/// LPUSH key element [element ...]
+pub fn lpush(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    push_generic(ctx, args, End::Left, false)
+}
+
+/// RPUSH key element [element ...]
+pub fn rpush(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    push_generic(ctx, args, End::Right, false)
+}
+
+/// LPUSHX key element [element ...]
+pub fn lpushx(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    push_generic(ctx, args, End::Left, true)
+}
+
+/// RPUSHX key element [element ...]
+pub fn rpushx(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    push_generic(ctx, args, End::Right, true)
+}
+
+/// Shared implementation of LPOP and RPOP
+fn pop_generic(ctx: &mut Context, args: &[&[u8]], end: End, command: &str) -> ServerResult<RESP> {
+    let key = args[0];
+
+    let count = match args[1..] {
+        [] => None,
+        [count] => {
+            let count = parse_integer(count)?;
+            if count < 0 {
+                return Err(ServerError::NotPositive);
+            }
+            Some(count as usize)
+        }
+        _ => return Err(ServerError::WrongArity(String::from(command))),
+    };
+
+    let db = ctx.db();
+    let list = match db.get_list_mut(key)? {
+        Some(list) => list,
+        None if count.is_some() => return Ok(RESP::NullArray),
+        None => return Ok(RESP::Null),
+    };
+
+    let reply = match count {
+        Some(count) => bulk_array(pop_elements(list, end, count)),
+        None => RESP::BulkString(pop_element(list, end).expect("lists are never empty")),
+    };
+
+    db.remove_if_empty(key);
+    Ok(reply)
+}
+
+/// LPOP key [count]
+pub fn lpop(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    pop_generic(ctx, args, End::Left, "lpop")
+}
+
+/// RPOP key [count]
+pub fn rpop(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    pop_generic(ctx, args, End::Right, "rpop")
+}
+
+/// LLEN key
+pub fn llen(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let length = ctx.db().get_list(args[0])?.map_or(0, |list| list.len());
+    Ok(RESP::Integer(length as i64))
+}
+
+/// LRANGE key start stop
+pub fn lrange(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let start = parse_integer(args[1])?;
+    let end = parse_integer(args[2])?;
+
+    ctx.write_reply(|db, writer| {
+        let range = db.get_list(args[0])?.and_then(|list| {
+            list_range(start, end, list.len()).map(|(start, end)| list.range(start..=end))
+        });
+
+        match range {
+            Some(elements) => {
+                writer.array_header(elements.len());
+                elements.for_each(|element| writer.bulk_string(element));
+            }
+            None => writer.array_header(0),
+        }
+        Ok(())
+    })
+}
+
+/// LINDEX key index
+pub fn lindex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let index = parse_integer(args[1])?;
+
+    let element = ctx
+        .db()
+        .get_list(args[0])?
+        .and_then(|list| list_index(index, list.len()).map(|index| &list[index]));
+
+    match element {
+        Some(element) => Ok(RESP::BulkString(element.clone())),
+        None => Ok(RESP::Null),
+    }
+}
+
+/// LSET key index element
+pub fn lset(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let index = parse_integer(args[1])?;
+
+    let list = ctx
+        .db()
+        .get_list_mut(args[0])?
+        .ok_or(ServerError::NoSuchKey)?;
+    let index = list_index(index, list.len()).ok_or(ServerError::IndexOutOfRange)?;
+    list[index] = args[2].to_vec();
+
+    Ok(RESP::SimpleString(String::from("OK")))
+}
+
+/// LINSERT key BEFORE | AFTER pivot element
+pub fn linsert(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (key, pivot, element) = (args[0], args[2], args[3]);
+
+    let after = match args[1].to_ascii_lowercase().as_slice() {
+        b"before" => false,
+        b"after" => true,
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let list = match ctx.db().get_list_mut(key)? {
+        Some(list) => list,
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    match list.iter().position(|current| current == pivot) {
+        Some(position) => {
+            let position = if after { position + 1 } else { position };
+            list.insert(position, element.to_vec());
+            Ok(RESP::Integer(list.len() as i64))
+        }
+        None => Ok(RESP::Integer(-1)),
+    }
+}
+
+/// LREM key count element
+pub fn lrem(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (key, element) = (args[0], args[2]);
+    let count = parse_integer(args[1])?;
+
+    let db = ctx.db();
+    let list = match db.get_list_mut(key)? {
+        Some(list) => list,
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    // A positive count removes from the head, a negative one from the tail, zero removes all
+    let limit = if count == 0 {
+        usize::MAX
+    } else {
+        count.unsigned_abs() as usize
+    };
+    let matches = list.iter().filter(|current| *current == element).count();
+    let removed = matches.min(limit);
+
+    // Removing from the tail keeps the first matches
+    let mut skipped = if count < 0 { matches - removed } else { 0 };
+    let mut remaining = removed;
+    list.retain(|current| {
+        if remaining == 0 || current != element {
+            return true;
+        }
+        if skipped > 0 {
+            skipped -= 1;
+            return true;
+        }
+        remaining -= 1;
+        false
+    });
+
+    db.remove_if_empty(key);
+    Ok(RESP::Integer(removed as i64))
+}
+
+/// LTRIM key start stop
+pub fn ltrim(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let start = parse_integer(args[1])?;
+    let end = parse_integer(args[2])?;
+
+    let db = ctx.db();
+    if let Some(list) = db.get_list_mut(key)? {
+        match list_range(start, end, list.len()) {
+            Some((start, end)) => {
+                list.truncate(end + 1);
+                list.drain(..start);
+            }
+            None => list.clear(),
+        }
+        db.remove_if_empty(key);
+    }
+
+    Ok(RESP::SimpleString(String::from("OK")))
+}
+
+/// LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
+pub fn lpos(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (key, element) = (args[0], args[1]);
+    let mut rank: i64 = 1;
+    let mut count = None;
+    let mut max_length = 0;
+
+    let mut index = 2;
+    while index < args.len() {
+        let value = match args.get(index + 1) {
+            Some(value) => parse_integer(value)?,
+            None => return Err(ServerError::Syntax),
+        };
+
+        match args[index].to_ascii_lowercase().as_slice() {
+            b"rank" if value == 0 => {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list",
+                )));
+            }
+            b"rank" if value == i64::MIN => return Err(ServerError::NotAnInteger),
+            b"rank" => rank = value,
+            b"count" if value < 0 => {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "COUNT can't be negative",
+                )));
+            }
+            b"count" => count = Some(value as usize),
+            b"maxlen" if value < 0 => {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "MAXLEN can't be negative",
+                )));
+            }
+            b"maxlen" => max_length = value as usize,
+            _ => return Err(ServerError::Syntax),
+        }
+
+        index += 2;
+    }
+
+    let mut matches = vec![];
+    if let Some(list) = ctx.db().get_list(key)? {
+        let max_length = if max_length == 0 {
+            list.len()
+        } else {
+            max_length.min(list.len())
+        };
+        // COUNT 0 returns every match
+        let wanted = match count {
+            Some(0) => usize::MAX,
+            Some(count) => count,
+            None => 1,
+        };
+
+        let positions: Box<dyn Iterator<Item = usize>> = if rank > 0 {
+            Box::new(0..max_length)
+        } else {
+            Box::new((list.len() - max_length..list.len()).rev())
+        };
+
+        matches = positions
+            .filter(|&position| list[position] == element)
+            .skip(rank.unsigned_abs() as usize - 1)
+            .take(wanted)
+            .map(|position| RESP::Integer(position as i64))
+            .collect();
+    }
+
+    match count {
+        Some(_) => Ok(RESP::Array(matches)),
+        None => Ok(matches.pop().unwrap_or(RESP::Null)),
+    }
+}
+
+/// Pop an element from the source list and push it to the destination list, which may be the
+/// same. Nothing is changed when the source list is missing.
+fn move_element(
+    db: &mut Db,
+    source: &[u8],
+    destination: &[u8],
+    from: End,
+    to: End,
+) -> ServerResult<Option<Vec<u8>>> {
+    let list = match db.get_list_mut(source)? {
+        Some(list) => list,
+        None => return Ok(None),
+    };
+    let element = pop_element(list, from).expect("lists are never empty");
+
+    // Checked before anything is modified, so that a type error loses no element
+    if db.get_list(destination).is_err() {
+        let list = db.get_list_mut(source)?.expect("the list was found above");
+        push_element(list, from, element);
+        return Err(ServerError::WrongType);
+    }
+
+    // Pushed first, so that a list rotated onto itself is never deleted and keeps its time to live
+    push_element(get_or_create_list(db, destination)?, to, element.clone());
+    db.remove_if_empty(source);
+    db.signal_ready(destination);
+
+    Ok(Some(element))
+}
+
+/// LMOVE source destination LEFT | RIGHT LEFT | RIGHT
+pub fn lmove(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let from = End::parse(args[2])?;
+    let to = End::parse(args[3])?;
+
+    match move_element(ctx.db(), args[0], args[1], from, to)? {
+        Some(element) => Ok(RESP::BulkString(element)),
+        None => Ok(RESP::Null),
+    }
+}
+
+/// Parse the `numkeys key [key ...] LEFT | RIGHT [COUNT count]` arguments of LMPOP
+fn parse_mpop<'a, 'b>(args: &'a [&'b [u8]]) -> ServerResult<(&'a [&'b [u8]], End, usize)> {
+    let num_keys = parse_integer(args[0])?;
+    if num_keys <= 0 {
+        return Err(ServerError::InvalidArgument(String::from(
+            "numkeys should be greater than 0",
+        )));
+    }
+
+    let num_keys = num_keys as usize;
+    if num_keys >= args.len() - 1 {
+        return Err(ServerError::Syntax);
+    }
+
+    let keys = &args[1..=num_keys];
+    let end = End::parse(args[num_keys + 1])?;
+
+    let count = match &args[num_keys + 2..] {
+        [] => 1,
+        [option, count] if option.eq_ignore_ascii_case(b"count") => {
+            let count = parse_integer(count)?;
+            if count <= 0 {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "count should be greater than 0",
+                )));
+            }
+            count as usize
+        }
+        _ => return Err(ServerError::Syntax),
+    };
+
+    Ok((keys, end, count))
+}
+
+/// Pop up to `count` elements from the first non-empty list among the keys
+fn mpop(db: &mut Db, keys: &[&[u8]], end: End, count: usize) -> ServerResult<RESP> {
+    for key in keys {
+        if let Some(list) = db.get_list_mut(key)? {
+            let elements = pop_elements(list, end, count);
+            db.remove_if_empty(key);
+
+            return Ok(RESP::Array(vec![
+                RESP::BulkString(key.to_vec()),
+                bulk_array(elements),
+            ]));
+        }
+    }
+
+    Ok(RESP::NullArray)
+}
+
+/// LMPOP numkeys key [key ...] LEFT | RIGHT [COUNT count]
+pub fn lmpop(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (keys, end, count) = parse_mpop(args)?;
+    mpop(ctx.db(), keys, end, count)
+}
+
+/// Shared implementation of BLPOP and BRPOP
+fn blocking_pop(ctx: &mut Context, args: &[&[u8]], end: End) -> ServerResult<RESP> {
+    let (keys, timeout) = args.split_at(args.len() - 1);
+    let timeout = parse_timeout(timeout[0])?;
+
+    let db = ctx.db();
+    for key in keys {
+        if let Some(list) = db.get_list_mut(key)? {
+            let element = pop_element(list, end).expect("lists are never empty");
+            db.remove_if_empty(key);
+
+            return Ok(RESP::Array(vec![
+                RESP::BulkString(key.to_vec()),
+                RESP::BulkString(element),
+            ]));
+        }
+    }
+
+    ctx.block_on(keys, ValueType::List, timeout);
+    Ok(RESP::NullArray)
+}
+
+/// BLPOP key [key ...] timeout
+pub fn blpop(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    blocking_pop(ctx, args, End::Left)
+}
+
+/// BRPOP key [key ...] timeout
+pub fn brpop(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    blocking_pop(ctx, args, End::Right)
+}
+
+/// BLMOVE source destination LEFT | RIGHT LEFT | RIGHT timeout
+pub fn blmove(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let from = End::parse(args[2])?;
+    let to = End::parse(args[3])?;
+    let timeout = parse_timeout(args[4])?;
+
+    match move_element(ctx.db(), args[0], args[1], from, to)? {
+        Some(element) => Ok(RESP::BulkString(element)),
+        None => {
+            ctx.block_on(&args[..1], ValueType::List, timeout);
+            Ok(RESP::Null)
+        }
+    }
+}
+
+/// BLMPOP timeout numkeys key [key ...] LEFT | RIGHT [COUNT count]
+pub fn blmpop(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let timeout = parse_timeout(args[0])?;
+    let (keys, end, count) = parse_mpop(&args[1..])?;
+
+    match mpop(ctx.db(), keys, end, count)? {
+        RESP::NullArray => {
+            ctx.block_on(keys, ValueType::List, timeout);
+            Ok(RESP::NullArray)
+        }
+        reply => Ok(reply),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    fn bulk(data: &str) -> RESP {
+        RESP::BulkString(data.as_bytes().to_vec())
+    }
+
+    fn bulks(elements: &[&str]) -> RESP {
+        RESP::Array(elements.iter().map(|element| bulk(element)).collect())
+    }
+
+    fn lrange(storage: &mut Storage, key: &str) -> RESP {
+        execute(storage, &["LRANGE", key, "0", "-1"]).unwrap()
+    }
+
+    #[test]
+    fn test_push_and_range() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["RPUSH", "list", "b", "c"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPUSH", "list", "a", "z"]),
+            Ok(RESP::Integer(4))
+        );
+        assert_eq!(lrange(&mut storage, "list"), bulks(&["z", "a", "b", "c"]));
+        assert_eq!(
+            execute(&mut storage, &["LRANGE", "list", "1", "2"]),
+            Ok(bulks(&["a", "b"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LRANGE", "list", "-2", "100"]),
+            Ok(bulks(&["b", "c"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LRANGE", "list", "0", "-5"]),
+            Ok(bulks(&[]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LRANGE", "list", "3", "1"]),
+            Ok(bulks(&[]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LLEN", "list"]),
+            Ok(RESP::Integer(4))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LLEN", "none"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_pushx() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["LPUSHX", "list", "a"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "list"]),
+            Ok(RESP::Integer(0))
+        );
+
+        execute(&mut storage, &["RPUSH", "list", "a"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["RPUSHX", "list", "b", "c"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPUSHX", "list", "z"]),
+            Ok(RESP::Integer(4))
+        );
+        assert_eq!(lrange(&mut storage, "list"), bulks(&["z", "a", "b", "c"]));
+    }
+
+    #[test]
+    fn test_pop() {
+        let mut storage = Storage::new();
+
+        assert_eq!(execute(&mut storage, &["LPOP", "list"]), Ok(RESP::Null));
+        assert_eq!(
+            execute(&mut storage, &["LPOP", "list", "2"]),
+            Ok(RESP::NullArray)
+        );
+
+        execute(&mut storage, &["RPUSH", "list", "a", "b", "c", "d", "e"]).unwrap();
+        assert_eq!(execute(&mut storage, &["LPOP", "list"]), Ok(bulk("a")));
+        assert_eq!(execute(&mut storage, &["RPOP", "list"]), Ok(bulk("e")));
+        assert_eq!(
+            execute(&mut storage, &["RPOP", "list", "2"]),
+            Ok(bulks(&["d", "c"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOP", "list", "0"]),
+            Ok(bulks(&[]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOP", "list", "-1"]),
+            Err(ServerError::NotPositive)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOP", "list", "10"]),
+            Ok(bulks(&["b"]))
+        );
+
+        // Empty lists are removed from the keyspace
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "list"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOP", "list", "1", "2"]),
+            Err(ServerError::WrongArity(String::from("lpop")))
+        );
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+        execute(&mut storage, &["RPUSH", "list", "a"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["LPUSH", "string", "a"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LRANGE", "string", "0", "-1"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["GET", "list"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LMOVE", "list", "string", "LEFT", "LEFT"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(lrange(&mut storage, "list"), bulks(&["a"]));
+
+        // SET replaces values of any type
+        assert_eq!(
+            execute(&mut storage, &["SET", "list", "value"]),
+            Ok(RESP::SimpleString(String::from("OK")))
+        );
+    }
+
+    #[test]
+    fn test_lindex_and_lset() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["RPUSH", "list", "a", "b", "c"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["LINDEX", "list", "0"]),
+            Ok(bulk("a"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LINDEX", "list", "-1"]),
+            Ok(bulk("c"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LINDEX", "list", "3"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LINDEX", "none", "0"]),
+            Ok(RESP::Null)
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["LSET", "list", "-2", "x"]),
+            Ok(RESP::SimpleString(String::from("OK")))
+        );
+        assert_eq!(lrange(&mut storage, "list"), bulks(&["a", "x", "c"]));
+        assert_eq!(
+            execute(&mut storage, &["LSET", "list", "3", "x"]),
+            Err(ServerError::IndexOutOfRange)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LSET", "none", "0", "x"]),
+            Err(ServerError::NoSuchKey)
+        );
+    }
+
+    #[test]
+    fn test_linsert() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["LINSERT", "list", "BEFORE", "a", "x"]),
+            Ok(RESP::Integer(0))
+        );
+
+        execute(&mut storage, &["RPUSH", "list", "a", "b"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["LINSERT", "list", "BEFORE", "a", "x"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LINSERT", "list", "after", "b", "y"]),
+            Ok(RESP::Integer(4))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LINSERT", "list", "AFTER", "z", "y"]),
+            Ok(RESP::Integer(-1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LINSERT", "list", "MIDDLE", "a", "y"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(lrange(&mut storage, "list"), bulks(&["x", "a", "b", "y"]));
+    }
+
+    #[test]
+    fn test_lrem() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["RPUSH", "list", "a", "b", "a", "c", "a"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["LREM", "list", "-2", "a"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(lrange(&mut storage, "list"), bulks(&["a", "b", "c"]));
+        assert_eq!(
+            execute(&mut storage, &["LREM", "list", "1", "b"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LREM", "list", "0", "z"]),
+            Ok(RESP::Integer(0))
+        );
+
+        execute(&mut storage, &["RPUSH", "list", "a"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["LREM", "list", "0", "a"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(lrange(&mut storage, "list"), bulks(&["c"]));
+    }
+
+    #[test]
+    fn test_ltrim() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["RPUSH", "list", "a", "b", "c", "d"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["LTRIM", "list", "1", "-2"]),
+            Ok(RESP::SimpleString(String::from("OK")))
+        );
+        assert_eq!(lrange(&mut storage, "list"), bulks(&["b", "c"]));
+
+        execute(&mut storage, &["LTRIM", "list", "5", "10"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "list"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_lpos() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &["RPUSH", "list", "a", "b", "c", "1", "2", "3", "c", "c"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["LPOS", "list", "c"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOS", "list", "c", "RANK", "2"]),
+            Ok(RESP::Integer(6))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOS", "list", "c", "RANK", "-1"]),
+            Ok(RESP::Integer(7))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOS", "list", "c", "COUNT", "0"]),
+            Ok(RESP::Array(vec![
+                RESP::Integer(2),
+                RESP::Integer(6),
+                RESP::Integer(7)
+            ]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["LPOS", "list", "c", "RANK", "-1", "COUNT", "2"]
+            ),
+            Ok(RESP::Array(vec![RESP::Integer(7), RESP::Integer(6)]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["LPOS", "list", "c", "COUNT", "0", "MAXLEN", "3"]
+            ),
+            Ok(RESP::Array(vec![RESP::Integer(2)]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOS", "list", "z"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LPOS", "none", "z", "COUNT", "1"]),
+            Ok(RESP::Array(vec![]))
+        );
+
+        assert!(matches!(
+            execute(&mut storage, &["LPOS", "list", "c", "RANK", "0"]),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            execute(&mut storage, &["LPOS", "list", "c", "COUNT", "-1"]),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            execute(&mut storage, &["LPOS", "list", "c", "RANK"]),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_lmove_rotates_a_single_element_list_onto_itself() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["RPUSH", "list", "a"]).unwrap();
+        execute(&mut storage, &["EXPIRE", "list", "100"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["LMOVE", "list", "list", "LEFT", "RIGHT"]),
+            Ok(RESP::BulkString(b"a".to_vec()))
+        );
+        assert_eq!(
+            execute(&mut storage, &["TTL", "list"]),
+            Ok(RESP::Integer(100))
+        );
+    }
+
+    #[test]
+    fn test_lmove() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["RPUSH", "source", "a", "b", "c"]).unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["LMOVE", "source", "target", "LEFT", "RIGHT"]
+            ),
+            Ok(bulk("a"))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["LMOVE", "source", "target", "RIGHT", "LEFT"]
+            ),
+            Ok(bulk("c"))
+        );
+        assert_eq!(lrange(&mut storage, "target"), bulks(&["c", "a"]));
+
+        // Rotation of a single list
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["LMOVE", "target", "target", "LEFT", "RIGHT"]
+            ),
+            Ok(bulk("c"))
+        );
+        assert_eq!(lrange(&mut storage, "target"), bulks(&["a", "c"]));
+
+        execute(&mut storage, &["LMOVE", "source", "target", "LEFT", "LEFT"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "source"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LMOVE", "source", "target", "LEFT", "LEFT"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LMOVE", "target", "source", "UP", "LEFT"]),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_lmpop() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["RPUSH", "second", "a", "b", "c"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["LMPOP", "2", "first", "second", "LEFT"]),
+            Ok(RESP::Array(vec![bulk("second"), bulks(&["a"])]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["LMPOP", "2", "first", "second", "RIGHT", "COUNT", "5"]
+            ),
+            Ok(RESP::Array(vec![bulk("second"), bulks(&["c", "b"])]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["LMPOP", "1", "second", "LEFT"]),
+            Ok(RESP::NullArray)
+        );
+
+        assert!(matches!(
+            execute(&mut storage, &["LMPOP", "0", "first", "LEFT"]),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            execute(&mut storage, &["LMPOP", "1", "first", "LEFT", "COUNT", "0"]),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            execute(&mut storage, &["LMPOP", "2", "first", "LEFT"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["LMPOP", "1", "first", "LEFT", "COUNT"]),
+            Err(ServerError::Syntax)
+        );
+    }
+}
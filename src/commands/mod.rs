@@ -0,0 +1,1279 @@
+mod bitmap;
+mod config;
+mod connection;
+mod expire;
+mod geo;
+mod hash;
+mod hyperloglog;
+mod keys;
+mod list;
+mod pubsub;
+mod scan;
+mod set;
+mod sorted_set;
+mod stream;
+mod string;
+
+use crate::blocking::Block;
+use crate::client::Client;
+use crate::resp::{ProtocolVersion, RESP, bytes_to_resp};
+use crate::resp_writer::RESPWriter;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{DATABASES, Db, Storage, ValueType};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Everything a command can act upon: the client that sent it and the server data
+pub struct Context<'a> {
+    pub client: &'a mut Client,
+    pub storage: &'a mut Storage,
+    /// Set by blocking commands that found no data, the reply they return is then discarded
+    pub block: Option<Block>,
+    /// Output of the connection, where large replies are written as they are produced, see
+    /// [`Context::write_reply`]
+    pub output: Option<&'a mut Vec<u8>>,
+    /// Set once the reply was written to the output, the reply returned is then discarded
+    pub streamed: bool,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(client: &'a mut Client, storage: &'a mut Storage) -> Self {
+        Self {
+            client,
+            storage,
+            block: None,
+            output: None,
+            streamed: false,
+        }
+    }
+
+    pub fn db(&mut self) -> &mut Db {
+        &mut self.storage.dbs[self.client.db]
+    }
+
+    /// Write the reply straight into the output of the connection, so that large aggregates are
+    /// not built in memory first. This must be the last thing a command does, what `write`
+    /// wrote is dropped if it fails. Without an output, as when a blocked client is served, the
+    /// reply is decoded from what `write` wrote and returned, only the first one when several
+    /// were written like the confirmations of SUBSCRIBE.
+    pub fn write_reply(
+        &mut self,
+        write: impl FnOnce(&mut Db, &mut RESPWriter) -> ServerResult<()>,
+    ) -> ServerResult<RESP> {
+        let db = &mut self.storage.dbs[self.client.db];
+
+        match self.output.as_deref_mut() {
+            Some(output) => {
+                let start = output.len();
+                if let Err(error) = write(db, &mut RESPWriter::new(output, self.client.protocol)) {
+                    output.truncate(start);
+                    return Err(error);
+                }
+
+                self.streamed = true;
+                Ok(RESP::Null)
+            }
+            None => {
+                // RESP3 keeps the types of the reply, which is downgraded when sent if needed
+                let mut buffer = Vec::new();
+                write(
+                    db,
+                    &mut RESPWriter::new(&mut buffer, ProtocolVersion::RESP3),
+                )?;
+
+                Ok(bytes_to_resp(&buffer, &mut 0).expect("the reply was just encoded"))
+            }
+        }
+    }
+
+    /// Wait for one of the keys to receive data of the given type, the command is then executed
+    /// again
+    pub fn block_on(&mut self, keys: &[&[u8]], value_type: ValueType, timeout: Option<Duration>) {
+        self.block = Some(Block {
+            keys: keys.iter().map(|key| key.to_vec()).collect(),
+            value_type,
+            timeout,
+            args: None,
+        });
+    }
+
+    /// Like [`Context::block_on`], executing the command with other arguments once data arrives
+    pub fn block_on_with_args(
+        &mut self,
+        keys: &[&[u8]],
+        value_type: ValueType,
+        timeout: Option<Duration>,
+        args: Vec<Vec<u8>>,
+    ) {
+        self.block_on(keys, value_type, timeout);
+        if let Some(block) = &mut self.block {
+            block.args = Some(args);
+        }
+    }
+}
+
+pub type CommandHandler = fn(&mut Context, &[&[u8]]) -> ServerResult<RESP>;
+
+#[derive(Debug)]
+pub struct Command {
+    pub name: &'static str,
+    /// Number of arguments including the command name, negative values mean "at least"
+    pub arity: i32,
+    pub handler: CommandHandler,
+}
+
+impl Command {
+    pub fn check_arity(&self, argc: usize) -> bool {
+        if self.arity < 0 {
+            argc >= self.arity.unsigned_abs() as usize
+        } else {
+            argc == self.arity as usize
+        }
+    }
+}
+
+static COMMANDS: &[Command] = &[
+    Command {
+        name: "append",
+        arity: 3,
+        handler: string::append,
+    },
+    Command {
+        name: "bitcount",
+        arity: -2,
+        handler: bitmap::bitcount,
+    },
+    Command {
+        name: "bitfield",
+        arity: -2,
+        handler: bitmap::bitfield,
+    },
+    Command {
+        name: "bitfield_ro",
+        arity: -2,
+        handler: bitmap::bitfield_ro,
+    },
+    Command {
+        name: "bitop",
+        arity: -4,
+        handler: bitmap::bitop,
+    },
+    Command {
+        name: "bitpos",
+        arity: -3,
+        handler: bitmap::bitpos,
+    },
+    Command {
+        name: "blmove",
+        arity: 6,
+        handler: list::blmove,
+    },
+    Command {
+        name: "blmpop",
+        arity: -5,
+        handler: list::blmpop,
+    },
+    Command {
+        name: "blpop",
+        arity: -3,
+        handler: list::blpop,
+    },
+    Command {
+        name: "brpop",
+        arity: -3,
+        handler: list::brpop,
+    },
+    Command {
+        name: "bzpopmax",
+        arity: -3,
+        handler: sorted_set::bzpopmax,
+    },
+    Command {
+        name: "bzpopmin",
+        arity: -3,
+        handler: sorted_set::bzpopmin,
+    },
+    Command {
+        name: "config",
+        arity: -2,
+        handler: config::config,
+    },
+    Command {
+        name: "copy",
+        arity: -3,
+        handler: keys::copy,
+    },
+    Command {
+        name: "dbsize",
+        arity: 1,
+        handler: keys::dbsize,
+    },
+    Command {
+        name: "decr",
+        arity: 2,
+        handler: string::decr,
+    },
+    Command {
+        name: "decrby",
+        arity: 3,
+        handler: string::decrby,
+    },
+    Command {
+        name: "del",
+        arity: -2,
+        handler: keys::del,
+    },
+    Command {
+        name: "echo",
+        arity: 2,
+        handler: connection::echo,
+    },
+    Command {
+        name: "exists",
+        arity: -2,
+        handler: keys::exists,
+    },
+    Command {
+        name: "expire",
+        arity: -3,
+        handler: expire::expire,
+    },
+    Command {
+        name: "expireat",
+        arity: -3,
+        handler: expire::expireat,
+    },
+    Command {
+        name: "expiretime",
+        arity: 2,
+        handler: expire::expiretime,
+    },
+    Command {
+        name: "geoadd",
+        arity: -5,
+        handler: geo::geoadd,
+    },
+    Command {
+        name: "geodist",
+        arity: -4,
+        handler: geo::geodist,
+    },
+    Command {
+        name: "geohash",
+        arity: -2,
+        handler: geo::geohash,
+    },
+    Command {
+        name: "geopos",
+        arity: -2,
+        handler: geo::geopos,
+    },
+    Command {
+        name: "geosearch",
+        arity: -7,
+        handler: geo::geosearch,
+    },
+    Command {
+        name: "geosearchstore",
+        arity: -8,
+        handler: geo::geosearchstore,
+    },
+    Command {
+        name: "get",
+        arity: 2,
+        handler: string::get,
+    },
+    Command {
+        name: "getbit",
+        arity: 3,
+        handler: bitmap::getbit,
+    },
+    Command {
+        name: "getdel",
+        arity: 2,
+        handler: string::getdel,
+    },
+    Command {
+        name: "getex",
+        arity: -2,
+        handler: string::getex,
+    },
+    Command {
+        name: "getrange",
+        arity: 4,
+        handler: string::getrange,
+    },
+    Command {
+        name: "getset",
+        arity: 3,
+        handler: string::getset,
+    },
+    Command {
+        name: "hdel",
+        arity: -3,
+        handler: hash::hdel,
+    },
+    Command {
+        name: "hello",
+        arity: -1,
+        handler: connection::hello,
+    },
+    Command {
+        name: "hexists",
+        arity: 3,
+        handler: hash::hexists,
+    },
+    Command {
+        name: "hexpire",
+        arity: -6,
+        handler: hash::hexpire,
+    },
+    Command {
+        name: "hexpireat",
+        arity: -6,
+        handler: hash::hexpireat,
+    },
+    Command {
+        name: "hexpiretime",
+        arity: -5,
+        handler: hash::hexpiretime,
+    },
+    Command {
+        name: "hget",
+        arity: 3,
+        handler: hash::hget,
+    },
+    Command {
+        name: "hgetall",
+        arity: 2,
+        handler: hash::hgetall,
+    },
+    Command {
+        name: "hgetex",
+        arity: -5,
+        handler: hash::hgetex,
+    },
+    Command {
+        name: "hincrby",
+        arity: 4,
+        handler: hash::hincrby,
+    },
+    Command {
+        name: "hincrbyfloat",
+        arity: 4,
+        handler: hash::hincrbyfloat,
+    },
+    Command {
+        name: "hkeys",
+        arity: 2,
+        handler: hash::hkeys,
+    },
+    Command {
+        name: "hlen",
+        arity: 2,
+        handler: hash::hlen,
+    },
+    Command {
+        name: "hmget",
+        arity: -3,
+        handler: hash::hmget,
+    },
+    Command {
+        name: "hpersist",
+        arity: -5,
+        handler: hash::hpersist,
+    },
+    Command {
+        name: "hpexpire",
+        arity: -6,
+        handler: hash::hpexpire,
+    },
+    Command {
+        name: "hpexpireat",
+        arity: -6,
+        handler: hash::hpexpireat,
+    },
+    Command {
+        name: "hpexpiretime",
+        arity: -5,
+        handler: hash::hpexpiretime,
+    },
+    Command {
+        name: "hpttl",
+        arity: -5,
+        handler: hash::hpttl,
+    },
+    Command {
+        name: "hrandfield",
+        arity: -2,
+        handler: hash::hrandfield,
+    },
+    Command {
+        name: "hscan",
+        arity: -3,
+        handler: hash::hscan,
+    },
+    Command {
+        name: "hset",
+        arity: -4,
+        handler: hash::hset,
+    },
+    Command {
+        name: "hsetex",
+        arity: -6,
+        handler: hash::hsetex,
+    },
+    Command {
+        name: "hsetnx",
+        arity: 4,
+        handler: hash::hsetnx,
+    },
+    Command {
+        name: "hstrlen",
+        arity: 3,
+        handler: hash::hstrlen,
+    },
+    Command {
+        name: "httl",
+        arity: -5,
+        handler: hash::httl,
+    },
+    Command {
+        name: "hvals",
+        arity: 2,
+        handler: hash::hvals,
+    },
+    Command {
+        name: "incr",
+        arity: 2,
+        handler: string::incr,
+    },
+    Command {
+        name: "incrby",
+        arity: 3,
+        handler: string::incrby,
+    },
+    Command {
+        name: "incrbyfloat",
+        arity: 3,
+        handler: string::incrbyfloat,
+    },
+    Command {
+        name: "keys",
+        arity: 2,
+        handler: keys::keys,
+    },
+    Command {
+        name: "lindex",
+        arity: 3,
+        handler: list::lindex,
+    },
+    Command {
+        name: "linsert",
+        arity: 5,
+        handler: list::linsert,
+    },
+    Command {
+        name: "llen",
+        arity: 2,
+        handler: list::llen,
+    },
+    Command {
+        name: "lmove",
+        arity: 5,
+        handler: list::lmove,
+    },
+    Command {
+        name: "lmpop",
+        arity: -4,
+        handler: list::lmpop,
+    },
+    Command {
+        name: "lpop",
+        arity: -2,
+        handler: list::lpop,
+    },
+    Command {
+        name: "lpos",
+        arity: -3,
+        handler: list::lpos,
+    },
+    Command {
+        name: "lpush",
+        arity: -3,
+        handler: list::lpush,
+    },
+    Command {
+        name: "lpushx",
+        arity: -3,
+        handler: list::lpushx,
+    },
+    Command {
+        name: "lrange",
+        arity: 4,
+        handler: list::lrange,
+    },
+    Command {
+        name: "lrem",
+        arity: 4,
+        handler: list::lrem,
+    },
+    Command {
+        name: "lset",
+        arity: 4,
+        handler: list::lset,
+    },
+    Command {
+        name: "ltrim",
+        arity: 4,
+        handler: list::ltrim,
+    },
+    Command {
+        name: "mget",
+        arity: -2,
+        handler: string::mget,
+    },
+    Command {
+        name: "move",
+        arity: 3,
+        handler: keys::move_,
+    },
+    Command {
+        name: "mset",
+        arity: -3,
+        handler: string::mset,
+    },
+    Command {
+        name: "msetnx",
+        arity: -3,
+        handler: string::msetnx,
+    },
+    Command {
+        name: "object",
+        arity: -2,
+        handler: keys::object,
+    },
+    Command {
+        name: "persist",
+        arity: 2,
+        handler: expire::persist,
+    },
+    Command {
+        name: "pexpire",
+        arity: -3,
+        handler: expire::pexpire,
+    },
+    Command {
+        name: "pexpireat",
+        arity: -3,
+        handler: expire::pexpireat,
+    },
+    Command {
+        name: "pexpiretime",
+        arity: 2,
+        handler: expire::pexpiretime,
+    },
+    Command {
+        name: "pfadd",
+        arity: -2,
+        handler: hyperloglog::pfadd,
+    },
+    Command {
+        name: "pfcount",
+        arity: -2,
+        handler: hyperloglog::pfcount,
+    },
+    Command {
+        name: "pfdebug",
+        arity: 3,
+        handler: hyperloglog::pfdebug,
+    },
+    Command {
+        name: "pfmerge",
+        arity: -2,
+        handler: hyperloglog::pfmerge,
+    },
+    Command {
+        name: "pfselftest",
+        arity: 1,
+        handler: hyperloglog::pfselftest,
+    },
+    Command {
+        name: "ping",
+        arity: -1,
+        handler: connection::ping,
+    },
+    Command {
+        name: "psetex",
+        arity: 4,
+        handler: string::psetex,
+    },
+    Command {
+        name: "psubscribe",
+        arity: -2,
+        handler: pubsub::psubscribe,
+    },
+    Command {
+        name: "pttl",
+        arity: 2,
+        handler: expire::pttl,
+    },
+    Command {
+        name: "publish",
+        arity: 3,
+        handler: pubsub::publish,
+    },
+    Command {
+        name: "punsubscribe",
+        arity: -1,
+        handler: pubsub::punsubscribe,
+    },
+    Command {
+        name: "randomkey",
+        arity: 1,
+        handler: keys::randomkey,
+    },
+    Command {
+        name: "rename",
+        arity: 3,
+        handler: keys::rename,
+    },
+    Command {
+        name: "renamenx",
+        arity: 3,
+        handler: keys::renamenx,
+    },
+    Command {
+        name: "rpop",
+        arity: -2,
+        handler: list::rpop,
+    },
+    Command {
+        name: "rpush",
+        arity: -3,
+        handler: list::rpush,
+    },
+    Command {
+        name: "rpushx",
+        arity: -3,
+        handler: list::rpushx,
+    },
+    Command {
+        name: "sadd",
+        arity: -3,
+        handler: set::sadd,
+    },
+    Command {
+        name: "scan",
+        arity: -2,
+        handler: keys::scan,
+    },
+    Command {
+        name: "scard",
+        arity: 2,
+        handler: set::scard,
+    },
+    Command {
+        name: "sdiff",
+        arity: -2,
+        handler: set::sdiff,
+    },
+    Command {
+        name: "sdiffstore",
+        arity: -3,
+        handler: set::sdiffstore,
+    },
+    Command {
+        name: "select",
+        arity: 2,
+        handler: connection::select,
+    },
+    Command {
+        name: "set",
+        arity: -3,
+        handler: string::set,
+    },
+    Command {
+        name: "setbit",
+        arity: 4,
+        handler: bitmap::setbit,
+    },
+    Command {
+        name: "setex",
+        arity: 4,
+        handler: string::setex,
+    },
+    Command {
+        name: "setnx",
+        arity: 3,
+        handler: string::setnx,
+    },
+    Command {
+        name: "setrange",
+        arity: 4,
+        handler: string::setrange,
+    },
+    Command {
+        name: "sinter",
+        arity: -2,
+        handler: set::sinter,
+    },
+    Command {
+        name: "sintercard",
+        arity: -3,
+        handler: set::sintercard,
+    },
+    Command {
+        name: "sinterstore",
+        arity: -3,
+        handler: set::sinterstore,
+    },
+    Command {
+        name: "sismember",
+        arity: 3,
+        handler: set::sismember,
+    },
+    Command {
+        name: "smembers",
+        arity: 2,
+        handler: set::smembers,
+    },
+    Command {
+        name: "smismember",
+        arity: -3,
+        handler: set::smismember,
+    },
+    Command {
+        name: "smove",
+        arity: 4,
+        handler: set::smove,
+    },
+    Command {
+        name: "spop",
+        arity: -2,
+        handler: set::spop,
+    },
+    Command {
+        name: "srandmember",
+        arity: -2,
+        handler: set::srandmember,
+    },
+    Command {
+        name: "srem",
+        arity: -3,
+        handler: set::srem,
+    },
+    Command {
+        name: "sscan",
+        arity: -3,
+        handler: set::sscan,
+    },
+    Command {
+        name: "strlen",
+        arity: 2,
+        handler: string::strlen,
+    },
+    Command {
+        name: "subscribe",
+        arity: -2,
+        handler: pubsub::subscribe,
+    },
+    Command {
+        name: "sunion",
+        arity: -2,
+        handler: set::sunion,
+    },
+    Command {
+        name: "sunionstore",
+        arity: -3,
+        handler: set::sunionstore,
+    },
+    Command {
+        name: "touch",
+        arity: -2,
+        handler: keys::touch,
+    },
+    Command {
+        name: "ttl",
+        arity: 2,
+        handler: expire::ttl,
+    },
+    Command {
+        name: "type",
+        arity: 2,
+        handler: keys::type_,
+    },
+    Command {
+        name: "unlink",
+        arity: -2,
+        handler: keys::unlink,
+    },
+    Command {
+        name: "unsubscribe",
+        arity: -1,
+        handler: pubsub::unsubscribe,
+    },
+    Command {
+        name: "xack",
+        arity: -4,
+        handler: stream::xack,
+    },
+    Command {
+        name: "xadd",
+        arity: -5,
+        handler: stream::xadd,
+    },
+    Command {
+        name: "xautoclaim",
+        arity: -6,
+        handler: stream::xautoclaim,
+    },
+    Command {
+        name: "xclaim",
+        arity: -6,
+        handler: stream::xclaim,
+    },
+    Command {
+        name: "xdel",
+        arity: -3,
+        handler: stream::xdel,
+    },
+    Command {
+        name: "xgroup",
+        arity: -2,
+        handler: stream::xgroup,
+    },
+    Command {
+        name: "xinfo",
+        arity: -2,
+        handler: stream::xinfo,
+    },
+    Command {
+        name: "xlen",
+        arity: 2,
+        handler: stream::xlen,
+    },
+    Command {
+        name: "xpending",
+        arity: -3,
+        handler: stream::xpending,
+    },
+    Command {
+        name: "xrange",
+        arity: -4,
+        handler: stream::xrange,
+    },
+    Command {
+        name: "xread",
+        arity: -4,
+        handler: stream::xread,
+    },
+    Command {
+        name: "xreadgroup",
+        arity: -7,
+        handler: stream::xreadgroup,
+    },
+    Command {
+        name: "xrevrange",
+        arity: -4,
+        handler: stream::xrevrange,
+    },
+    Command {
+        name: "xtrim",
+        arity: -4,
+        handler: stream::xtrim,
+    },
+    Command {
+        name: "zadd",
+        arity: -4,
+        handler: sorted_set::zadd,
+    },
+    Command {
+        name: "zcard",
+        arity: 2,
+        handler: sorted_set::zcard,
+    },
+    Command {
+        name: "zcount",
+        arity: 4,
+        handler: sorted_set::zcount,
+    },
+    Command {
+        name: "zdiffstore",
+        arity: -4,
+        handler: sorted_set::zdiffstore,
+    },
+    Command {
+        name: "zincrby",
+        arity: 4,
+        handler: sorted_set::zincrby,
+    },
+    Command {
+        name: "zinterstore",
+        arity: -4,
+        handler: sorted_set::zinterstore,
+    },
+    Command {
+        name: "zlexcount",
+        arity: 4,
+        handler: sorted_set::zlexcount,
+    },
+    Command {
+        name: "zmscore",
+        arity: -3,
+        handler: sorted_set::zmscore,
+    },
+    Command {
+        name: "zpopmax",
+        arity: -2,
+        handler: sorted_set::zpopmax,
+    },
+    Command {
+        name: "zpopmin",
+        arity: -2,
+        handler: sorted_set::zpopmin,
+    },
+    Command {
+        name: "zrange",
+        arity: -4,
+        handler: sorted_set::zrange,
+    },
+    Command {
+        name: "zrangebylex",
+        arity: -4,
+        handler: sorted_set::zrangebylex,
+    },
+    Command {
+        name: "zrangebyscore",
+        arity: -4,
+        handler: sorted_set::zrangebyscore,
+    },
+    Command {
+        name: "zrangestore",
+        arity: -5,
+        handler: sorted_set::zrangestore,
+    },
+    Command {
+        name: "zrank",
+        arity: -3,
+        handler: sorted_set::zrank,
+    },
+    Command {
+        name: "zrem",
+        arity: -3,
+        handler: sorted_set::zrem,
+    },
+    Command {
+        name: "zremrangebylex",
+        arity: 4,
+        handler: sorted_set::zremrangebylex,
+    },
+    Command {
+        name: "zremrangebyrank",
+        arity: 4,
+        handler: sorted_set::zremrangebyrank,
+    },
+    Command {
+        name: "zremrangebyscore",
+        arity: 4,
+        handler: sorted_set::zremrangebyscore,
+    },
+    Command {
+        name: "zrevrange",
+        arity: -4,
+        handler: sorted_set::zrevrange,
+    },
+    Command {
+        name: "zrevrangebylex",
+        arity: -4,
+        handler: sorted_set::zrevrangebylex,
+    },
+    Command {
+        name: "zrevrangebyscore",
+        arity: -4,
+        handler: sorted_set::zrevrangebyscore,
+    },
+    Command {
+        name: "zrevrank",
+        arity: -3,
+        handler: sorted_set::zrevrank,
+    },
+    Command {
+        name: "zscan",
+        arity: -3,
+        handler: sorted_set::zscan,
+    },
+    Command {
+        name: "zscore",
+        arity: 3,
+        handler: sorted_set::zscore,
+    },
+    Command {
+        name: "zunionstore",
+        arity: -4,
+        handler: sorted_set::zunionstore,
+    },
+];
+
+static COMMAND_TABLE: LazyLock<HashMap<&'static str, &'static Command>> = LazyLock::new(|| {
+    COMMANDS
+        .iter()
+        .map(|command| (command.name, command))
+        .collect()
+});
+
+/// Find a command by name, ignoring case
+pub fn lookup_command(name: &[u8]) -> Option<&'static Command> {
+    let name = std::str::from_utf8(name).ok()?.to_ascii_lowercase();
+    COMMAND_TABLE.get(name.as_str()).copied()
+}
+
+/// Parse a command argument as a signed 64 bits integer. Like Redis, only the canonical
+/// representation is accepted: no sign other than '-', no leading zeros and no spaces.
+pub fn parse_integer(arg: &[u8]) -> ServerResult<i64> {
+    let digits = arg.strip_prefix(b"-").unwrap_or(arg);
+    let canonical = match digits {
+        [b'0'] => arg.len() == 1,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    };
+
+    std::str::from_utf8(arg)
+        .ok()
+        .filter(|_| canonical)
+        .and_then(|value| value.parse::<i64>().ok())
+        .ok_or(ServerError::NotAnInteger)
+}
+
+/// Largest reply of picks with repetitions, past which their count is out of range. Redis only
+/// bounds them by the memory available, so that counts close to `i64::MAX` exhaust it.
+const MAX_RANDOM_REPLY_LENGTH: usize = 512 * 1024 * 1024;
+
+/// Length of the shortest element of a reply, an empty bulk string
+const MIN_ELEMENT_LENGTH: usize = b"$0\r\n\r\n".len();
+
+fn random_count_out_of_range() -> ServerError {
+    ServerError::InvalidArgument(String::from("value is out of range"))
+}
+
+/// Parse the count of HRANDFIELD and SRANDMEMBER, between `-max` and `max`. A negative count
+/// asks for that many picks with repetitions.
+pub fn parse_random_count(arg: &[u8], max: i64) -> ServerResult<i64> {
+    let count = parse_integer(arg)?;
+    if !(-max..=max).contains(&count) {
+        return Err(random_count_out_of_range());
+    }
+
+    Ok(count)
+}
+
+/// Write an array of `picks` entries picked at random with repetitions, `write` writing one of
+/// them as `elements_per_pick` elements. The count is out of range once the reply grows too
+/// large.
+pub fn write_random_picks(
+    writer: &mut RESPWriter,
+    picks: usize,
+    elements_per_pick: usize,
+    mut write: impl FnMut(&mut RESPWriter),
+) -> ServerResult<()> {
+    let elements = picks.saturating_mul(elements_per_pick);
+    if elements.saturating_mul(MIN_ELEMENT_LENGTH) > MAX_RANDOM_REPLY_LENGTH {
+        return Err(random_count_out_of_range());
+    }
+
+    writer.array_header(elements);
+    let start = writer.position();
+    for _ in 0..picks {
+        write(writer);
+        if writer.position() - start > MAX_RANDOM_REPLY_LENGTH {
+            return Err(random_count_out_of_range());
+        }
+    }
+
+    Ok(())
+}
+
+/// Pick `count` distinct entries at random among the `length` entries of a collection, as
+/// Redis does: all of them when the count covers the collection, a sample of all of them when
+/// the count is a large part of it, and otherwise random entries until enough distinct ones
+/// were picked, which would take many attempts in the other cases.
+pub fn pick_distinct<T: Copy + Eq + std::hash::Hash>(
+    count: usize,
+    length: usize,
+    entries: impl Iterator<Item = T>,
+    mut random_entry: impl FnMut() -> Option<T>,
+) -> Vec<T> {
+    if count >= length {
+        return entries.collect();
+    }
+
+    if count.saturating_mul(3) > length {
+        let entries: Vec<T> = entries.collect();
+        return rand::seq::index::sample(&mut rand::rng(), entries.len(), count)
+            .into_iter()
+            .map(|index| entries[index])
+            .collect();
+    }
+
+    let mut picked = std::collections::HashSet::with_capacity(count);
+    while picked.len() < count {
+        match random_entry() {
+            Some(entry) => picked.insert(entry),
+            None => break,
+        };
+    }
+    picked.into_iter().collect()
+}
+
+/// Parse a command argument as the index of a database
+pub fn parse_db_index(arg: &[u8]) -> ServerResult<usize> {
+    let index = parse_integer(arg)?;
+    if !(0..DATABASES as i64).contains(&index) {
+        return Err(ServerError::InvalidArgument(String::from(
+            "DB index is out of range",
+        )));
+    }
+
+    Ok(index as usize)
+}
+
+/// Parse a command argument as a double, NaN is rejected
+pub fn parse_float(arg: &[u8]) -> ServerResult<f64> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|value| !value.is_nan())
+        .ok_or(ServerError::NotAFloat)
+}
+
+/// Parse the timeout of blocking commands, in seconds with decimals. Zero means forever.
+pub fn parse_timeout(arg: &[u8]) -> ServerResult<Option<Duration>> {
+    let seconds = std::str::from_utf8(arg)
+        .ok()
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|value| value.is_finite())
+        .ok_or_else(|| {
+            ServerError::InvalidArgument(String::from("timeout is not a float or out of range"))
+        })?;
+
+    if seconds < 0.0 {
+        return Err(ServerError::InvalidArgument(String::from(
+            "timeout is negative",
+        )));
+    }
+
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(timeout) if timeout.is_zero() => Ok(None),
+        Ok(timeout) => Ok(Some(timeout)),
+        Err(_) => Err(ServerError::InvalidArgument(String::from(
+            "timeout is out of range",
+        ))),
+    }
+}
+
+/// Run a command the way the server would, for the tests of the command implementations
+#[cfg(test)]
+pub fn execute(storage: &mut Storage, parts: &[&str]) -> ServerResult<RESP> {
+    let name = parts[0].as_bytes();
+    let args: Vec<&[u8]> = parts[1..].iter().map(|part| part.as_bytes()).collect();
+
+    let command = lookup_command(name).expect("unknown command");
+    if !command.check_arity(parts.len()) {
+        return Err(ServerError::WrongArity(String::from(command.name)));
+    }
+
+    let mut client = Client::new();
+    let mut ctx = Context::new(&mut client, storage);
+
+    (command.handler)(&mut ctx, &args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngExt;
+
+    #[test]
+    fn test_lookup_command_ignores_case() {
+        let command = lookup_command(b"PiNg").unwrap();
+        assert_eq!(command.name, "ping");
+    }
+
+    #[test]
+    fn test_lookup_command_unknown() {
+        assert!(lookup_command(b"nosuchcommand").is_none());
+    }
+
+    #[test]
+    fn test_parse_integer() {
+        assert_eq!(parse_integer(b"-12"), Ok(-12));
+        assert_eq!(parse_integer(b"12a"), Err(ServerError::NotAnInteger));
+        assert_eq!(parse_integer(b"+12"), Err(ServerError::NotAnInteger));
+        assert_eq!(parse_integer(b"\xff"), Err(ServerError::NotAnInteger));
+        assert_eq!(parse_integer(b"0"), Ok(0));
+        assert_eq!(parse_integer(b"007"), Err(ServerError::NotAnInteger));
+        assert_eq!(parse_integer(b"-0"), Err(ServerError::NotAnInteger));
+        assert_eq!(parse_integer(b" 1"), Err(ServerError::NotAnInteger));
+        assert_eq!(parse_integer(b""), Err(ServerError::NotAnInteger));
+        assert_eq!(
+            parse_integer(b"9223372036854775808"),
+            Err(ServerError::NotAnInteger)
+        );
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(parse_float(b"1.5"), Ok(1.5));
+        assert_eq!(parse_float(b"-3"), Ok(-3.0));
+        assert_eq!(parse_float(b"5e3"), Ok(5000.0));
+        assert_eq!(parse_float(b"nan"), Err(ServerError::NotAFloat));
+        assert_eq!(parse_float(b" 1.5"), Err(ServerError::NotAFloat));
+        assert_eq!(parse_float(b"abc"), Err(ServerError::NotAFloat));
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout(b"0"), Ok(None));
+        assert_eq!(parse_timeout(b"1.5"), Ok(Some(Duration::from_millis(1500))));
+        assert!(matches!(
+            parse_timeout(b"-1"),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            parse_timeout(b"abc"),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            parse_timeout(b"inf"),
+            Err(ServerError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_pick_distinct() {
+        let entries: Vec<u32> = (0..100).collect();
+        let random = || Some(entries[rand::rng().random_range(0..entries.len())]);
+
+        // Random picks, then a sample, then everything
+        for count in [10, 50, 200] {
+            let mut picked = pick_distinct(count, entries.len(), entries.iter().copied(), random);
+            picked.sort();
+            picked.dedup();
+            assert_eq!(picked.len(), count.min(100));
+        }
+    }
+
+    #[test]
+    fn test_check_arity() {
+        let echo = lookup_command(b"echo").unwrap();
+        assert!(echo.check_arity(2));
+        assert!(!echo.check_arity(1));
+        assert!(!echo.check_arity(3));
+
+        let ping = lookup_command(b"ping").unwrap();
+        assert!(ping.check_arity(1));
+        assert!(ping.check_arity(2));
+        assert!(!ping.check_arity(0));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::commands::Context;
+use crate::pubsub::Subscription;
+use crate::resp::RESP;
+use crate::server_result::ServerResult;
+
+/// Reply with one push message per channel or pattern, `[kind, name, count]` where count is the
+/// number of subscriptions of the client afterwards. A missing name is sent as a null.
+fn confirm(
+    ctx: &mut Context,
+    kind: &str,
+    confirmations: Vec<(Option<&[u8]>, usize)>,
+) -> ServerResult<RESP> {
+    ctx.write_reply(|_, writer| {
+        for (name, count) in confirmations {
+            writer.push_message_header(3);
+            writer.bulk_string(kind.as_bytes());
+            match name {
+                Some(name) => writer.bulk_string(name),
+                None => writer.null(),
+            }
+            writer.integer(count as i64);
+        }
+        Ok(())
+    })
+}
+
+fn subscribe_to(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    subscription: Subscription,
+    kind: &str,
+) -> ServerResult<RESP> {
+    let pubsub = &mut ctx.storage.pubsub;
+    let confirmations = args
+        .iter()
+        .map(|&name| {
+            pubsub.subscribe(ctx.client, subscription, name);
+            (Some(name), pubsub.subscription_count(ctx.client.id))
+        })
+        .collect();
+
+    confirm(ctx, kind, confirmations)
+}
+
+/// Unsubscribe from the given channels or patterns, or from all of them if none is given
+fn unsubscribe_from(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    subscription: Subscription,
+    kind: &str,
+) -> ServerResult<RESP> {
+    let client_id = ctx.client.id;
+    let pubsub = &mut ctx.storage.pubsub;
+    let subscribed = pubsub.subscriptions(client_id, subscription);
+    let names: Vec<&[u8]> = if args.is_empty() {
+        subscribed.iter().map(Vec::as_slice).collect()
+    } else {
+        args.to_vec()
+    };
+
+    let confirmations = if names.is_empty() {
+        vec![(None, pubsub.subscription_count(client_id))]
+    } else {
+        names
+            .into_iter()
+            .map(|name| {
+                pubsub.unsubscribe(client_id, subscription, name);
+                (Some(name), pubsub.subscription_count(client_id))
+            })
+            .collect()
+    };
+
+    confirm(ctx, kind, confirmations)
+}
+
+/// SUBSCRIBE channel [channel ...]
+pub fn subscribe(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    subscribe_to(ctx, args, Subscription::Channel, "subscribe")
+}
+
+/// UNSUBSCRIBE [channel [channel ...]]
+pub fn unsubscribe(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    unsubscribe_from(ctx, args, Subscription::Channel, "unsubscribe")
+}
+
+/// PSUBSCRIBE pattern [pattern ...]
+pub fn psubscribe(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    subscribe_to(ctx, args, Subscription::Pattern, "psubscribe")
+}
+
+/// PUNSUBSCRIBE [pattern [pattern ...]]
+pub fn punsubscribe(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    unsubscribe_from(ctx, args, Subscription::Pattern, "punsubscribe")
+}
+
+/// PUBLISH channel message
+pub fn publish(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let receivers = ctx.storage.pubsub.publish(args[0], args[1]);
+
+    Ok(RESP::Integer(receivers as i64))
+}
@@ -0,0 +1,317 @@
+use crate::commands::parse_integer;
+use crate::glob;
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+
+/// Number of elements a scan call looks for, unless COUNT is given
+const DEFAULT_COUNT: usize = 10;
+/// Buckets visited by a scan call for each element asked for, to bound the work on sparse tables
+const BUCKETS_PER_ELEMENT: usize = 10;
+
+/// What is scanned, which decides the options allowed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scanned {
+    Keys,
+    Set,
+    Hash,
+    SortedSet,
+}
+
+/// The options of SCAN, SSCAN, HSCAN and ZSCAN
+#[derive(Debug)]
+pub struct ScanOptions<'a> {
+    pattern: Option<&'a [u8]>,
+    pub count: usize,
+    /// Type of the keys returned by SCAN, as named by TYPE
+    pub type_name: Option<String>,
+    /// Fields returned by HSCAN without their values
+    pub no_values: bool,
+}
+
+/// Parse the cursor of a scan command, an unsigned integer returned by the previous call
+pub fn parse_cursor(arg: &[u8]) -> ServerResult<u64> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .ok_or_else(|| ServerError::InvalidArgument(String::from("invalid cursor")))
+}
+
+impl<'a> ScanOptions<'a> {
+    pub fn parse(args: &[&'a [u8]], scanned: Scanned) -> ServerResult<Self> {
+        let mut options = ScanOptions {
+            pattern: None,
+            count: DEFAULT_COUNT,
+            type_name: None,
+            no_values: false,
+        };
+
+        let mut index = 0;
+        while index < args.len() {
+            let has_value = index + 1 < args.len();
+
+            match args[index].to_ascii_lowercase().as_slice() {
+                b"count" if has_value => {
+                    let count = parse_integer(args[index + 1])?;
+                    if count < 1 {
+                        return Err(ServerError::Syntax);
+                    }
+                    options.count = count as usize;
+                    index += 1;
+                }
+                // Matching everything is the same as not matching
+                b"match" if has_value => {
+                    options.pattern = Some(args[index + 1]).filter(|pattern| *pattern != b"*");
+                    index += 1;
+                }
+                b"type" if has_value && scanned == Scanned::Keys => {
+                    let name = String::from_utf8_lossy(args[index + 1]).to_ascii_lowercase();
+                    if !["string", "list", "set", "zset", "hash", "stream"].contains(&name.as_str())
+                    {
+                        return Err(ServerError::InvalidArgument(format!(
+                            "unknown type name '{}'",
+                            String::from_utf8_lossy(args[index + 1])
+                        )));
+                    }
+                    options.type_name = Some(name);
+                    index += 1;
+                }
+                b"novalues" => {
+                    if scanned != Scanned::Hash {
+                        return Err(ServerError::InvalidArgument(String::from(
+                            "NOVALUES option can only be used in HSCAN",
+                        )));
+                    }
+                    options.no_values = true;
+                }
+                _ => return Err(ServerError::Syntax),
+            }
+            index += 1;
+        }
+
+        Ok(options)
+    }
+
+    /// Whether the key, member or field matches the pattern given with MATCH
+    pub fn matches(&self, element: &[u8]) -> bool {
+        self.pattern
+            .is_none_or(|pattern| glob::matches(pattern, element))
+    }
+
+    /// Call the scan function from the cursor until about as many elements as asked for were
+    /// found, or too many buckets were visited. Returns the cursor to continue from and the
+    /// elements, before they are filtered.
+    pub fn collect<T>(
+        &self,
+        mut cursor: u64,
+        mut scan: impl FnMut(u64, &mut Vec<T>) -> u64,
+    ) -> (u64, Vec<T>) {
+        let mut found = vec![];
+        let mut visits = self.count.saturating_mul(BUCKETS_PER_ELEMENT);
+
+        loop {
+            cursor = scan(cursor, &mut found);
+            visits -= 1;
+
+            if cursor == 0 || visits == 0 || found.len() >= self.count {
+                return (cursor, found);
+            }
+        }
+    }
+}
+
+/// The reply of scan commands: the cursor to continue from, as a string, and the elements
+pub fn scan_reply(cursor: u64, elements: Vec<RESP>) -> RESP {
+    RESP::Array(vec![
+        RESP::BulkString(cursor.to_string().into_bytes()),
+        RESP::Array(elements),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    /// Iterate with the scan command until the cursor is back to 0, and return the elements
+    fn scan_all(storage: &mut Storage, command: &[&str]) -> Vec<String> {
+        let mut cursor = String::from("0");
+        let mut elements = vec![];
+
+        loop {
+            let mut args = command.to_vec();
+            let position = if command[0] == "SCAN" { 1 } else { 2 };
+            args.insert(position, &cursor);
+
+            let RESP::Array(reply) = execute(storage, &args).unwrap() else {
+                panic!("scan replies are arrays");
+            };
+            let [RESP::BulkString(next), RESP::Array(found)] = reply.as_slice() else {
+                panic!("scan replies are a cursor and elements");
+            };
+            elements.extend(found.iter().map(|element| match element {
+                RESP::BulkString(data) => String::from_utf8(data.clone()).unwrap(),
+                _ => panic!("scanned elements are strings"),
+            }));
+
+            cursor = String::from_utf8(next.clone()).unwrap();
+            if cursor == "0" {
+                return elements;
+            }
+        }
+    }
+
+    fn sorted(mut elements: Vec<String>) -> Vec<String> {
+        elements.sort();
+        elements
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut storage = Storage::new();
+        for index in 0..100 {
+            execute(&mut storage, &["SET", &format!("key:{index}"), "1"]).unwrap();
+        }
+        execute(&mut storage, &["RPUSH", "list", "a"]).unwrap();
+
+        let keys = scan_all(&mut storage, &["SCAN", "COUNT", "7"]);
+        assert_eq!(keys.len(), 101);
+
+        assert_eq!(
+            sorted(scan_all(&mut storage, &["SCAN", "MATCH", "key:1?"])),
+            (10..20)
+                .map(|index| format!("key:{index}"))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            scan_all(&mut storage, &["SCAN", "TYPE", "list"]),
+            vec!["list"]
+        );
+        assert_eq!(
+            scan_all(&mut storage, &["SCAN", "TYPE", "zset"]),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_scan_is_complete_across_resizes() {
+        let mut storage = Storage::new();
+        for index in 0..200 {
+            execute(&mut storage, &["SET", &format!("{index}"), "1"]).unwrap();
+        }
+
+        // Keys present for the whole iteration are returned even if the table grows meanwhile
+        let mut cursor = String::from("0");
+        let mut keys = vec![];
+        let mut added = 200;
+        loop {
+            let RESP::Array(reply) =
+                execute(&mut storage, &["SCAN", &cursor, "COUNT", "5"]).unwrap()
+            else {
+                panic!("scan replies are arrays");
+            };
+            let [RESP::BulkString(next), RESP::Array(found)] = reply.as_slice() else {
+                panic!("scan replies are a cursor and elements");
+            };
+            keys.extend(found.iter().map(|key| match key {
+                RESP::BulkString(data) => String::from_utf8(data.clone()).unwrap(),
+                _ => panic!("scanned keys are strings"),
+            }));
+            cursor = String::from_utf8(next.clone()).unwrap();
+            if cursor == "0" {
+                break;
+            }
+
+            for _ in 0..20 {
+                execute(&mut storage, &["SET", &format!("{added}"), "1"]).unwrap();
+                added += 1;
+            }
+        }
+
+        for index in 0..200 {
+            assert!(keys.contains(&format!("{index}")));
+        }
+    }
+
+    #[test]
+    fn test_sscan() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SADD", "set", "a", "b", "c", "ab"]).unwrap();
+
+        assert_eq!(
+            sorted(scan_all(&mut storage, &["SSCAN", "set"])),
+            vec!["a", "ab", "b", "c"]
+        );
+        assert_eq!(
+            sorted(scan_all(&mut storage, &["SSCAN", "set", "MATCH", "a*"])),
+            vec!["a", "ab"]
+        );
+        assert_eq!(
+            scan_all(&mut storage, &["SSCAN", "missing"]),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_hscan() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["HSET", "hash", "a", "1", "b", "2"]).unwrap();
+
+        let mut pairs = scan_all(&mut storage, &["HSCAN", "hash", "MATCH", "a"]);
+        assert_eq!(pairs, vec!["a", "1"]);
+
+        pairs = scan_all(&mut storage, &["HSCAN", "hash", "NOVALUES"]);
+        assert_eq!(sorted(pairs), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_zscan() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["ZADD", "zset", "1.5", "a", "2", "b"]).unwrap();
+
+        assert_eq!(
+            scan_all(&mut storage, &["ZSCAN", "zset", "MATCH", "a"]),
+            vec!["a", "1.5"]
+        );
+        assert_eq!(
+            scan_all(&mut storage, &["ZSCAN", "zset", "MATCH", "b"]),
+            vec!["b", "2"]
+        );
+    }
+
+    #[test]
+    fn test_scan_errors() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["SCAN", "x"]),
+            Err(ServerError::InvalidArgument(String::from("invalid cursor")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SCAN", "0", "COUNT", "0"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SCAN", "0", "MATCH"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SCAN", "0", "TYPE", "foo"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "unknown type name 'foo'"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SSCAN", "set", "0", "NOVALUES"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "NOVALUES option can only be used in HSCAN"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SSCAN", "string", "0"]),
+            Err(ServerError::WrongType)
+        );
+    }
+}
@@ -0,0 +1,721 @@
+use crate::commands::scan::{ScanOptions, Scanned, parse_cursor, scan_reply};
+use crate::commands::{
+    Context, parse_integer, parse_random_count, pick_distinct, write_random_picks,
+};
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{Db, Set, Value};
+
+/// Return the set stored at the key, creating an empty one if the key is missing
+fn get_or_create_set<'a>(db: &'a mut Db, key: &[u8]) -> ServerResult<&'a mut Set> {
+    if db.get_set(key)?.is_none() {
+        db.insert(key, Value::Set(Set::new()));
+    }
+
+    Ok(db.get_set_mut(key)?.expect("the key has just been created"))
+}
+
+fn set_reply<'a>(members: impl IntoIterator<Item = &'a Vec<u8>>) -> RESP {
+    RESP::Set(
+        members
+            .into_iter()
+            .map(|member| RESP::BulkString(member.clone()))
+            .collect(),
+    )
+}
+
+/// SADD key member [member ...]
+pub fn sadd(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let set = get_or_create_set(ctx.db(), args[0])?;
+    let added = args[1..]
+        .iter()
+        .filter(|member| set.insert(member.to_vec(), ()).is_none())
+        .count();
+
+    Ok(RESP::Integer(added as i64))
+}
+
+/// SREM key member [member ...]
+pub fn srem(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+    let set = match db.get_set_mut(args[0])? {
+        Some(set) => set,
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    let removed = args[1..]
+        .iter()
+        .filter(|member| set.remove(**member).is_some())
+        .count();
+    db.remove_if_empty(args[0]);
+
+    Ok(RESP::Integer(removed as i64))
+}
+
+/// SISMEMBER key member
+pub fn sismember(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let set = ctx.db().get_set(args[0])?;
+    let found = set.is_some_and(|set| set.contains_key(args[1]));
+
+    Ok(RESP::Integer(found as i64))
+}
+
+/// SMISMEMBER key member [member ...]
+pub fn smismember(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let set = ctx.db().get_set(args[0])?;
+    let replies = args[1..]
+        .iter()
+        .map(|member| RESP::Integer(set.is_some_and(|set| set.contains_key(*member)) as i64))
+        .collect();
+
+    Ok(RESP::Array(replies))
+}
+
+/// SMEMBERS key
+pub fn smembers(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    ctx.write_reply(|db, writer| {
+        match db.get_set(args[0])? {
+            Some(set) => {
+                writer.set_header(set.len());
+                set.keys().for_each(|member| writer.bulk_string(member));
+            }
+            None => writer.set_header(0),
+        }
+        Ok(())
+    })
+}
+
+/// SCARD key
+pub fn scard(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let set = ctx.db().get_set(args[0])?;
+
+    Ok(RESP::Integer(set.map_or(0, |set| set.len()) as i64))
+}
+
+/// Pick a member at random
+fn random_member(set: &Set) -> Option<&Vec<u8>> {
+    set.random_entry().map(|(member, _)| member)
+}
+
+/// SPOP key [count]
+pub fn spop(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let count = match args[1..] {
+        [] => None,
+        [count] => match parse_integer(count)? {
+            count if count < 0 => return Err(ServerError::NotPositive),
+            count => Some(count as usize),
+        },
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let db = ctx.db();
+    let set = match db.get_set_mut(key)? {
+        Some(set) => set,
+        None if count.is_some() => return Ok(RESP::Set(vec![])),
+        None => return Ok(RESP::Null),
+    };
+
+    let count = match count {
+        Some(count) => count,
+        None => {
+            let member = random_member(set).expect("sets are never empty").clone();
+            set.remove(&member);
+            db.remove_if_empty(key);
+            return Ok(RESP::BulkString(member));
+        }
+    };
+
+    // Popping every member is the same as deleting the key
+    if count >= set.len() {
+        let set = match db.remove(key) {
+            Some(Value::Set(set)) => set,
+            _ => unreachable!("the key holds a set"),
+        };
+        return Ok(set_reply(set.keys()));
+    }
+
+    let members: Vec<&Vec<u8>> = set.keys().collect();
+    let picked: Vec<Vec<u8>> = rand::seq::index::sample(&mut rand::rng(), members.len(), count)
+        .into_iter()
+        .map(|index| members[index].clone())
+        .collect();
+
+    for member in &picked {
+        set.remove(member);
+    }
+
+    Ok(set_reply(&picked))
+}
+
+/// SRANDMEMBER key [count]
+pub fn srandmember(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let count = match args[1..] {
+        [] => None,
+        [count] => Some(parse_random_count(count, i64::MAX)?),
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let count = match count {
+        Some(count) => count,
+        None => {
+            let member = ctx.db().get_set(args[0])?.and_then(random_member);
+            return Ok(member.map_or(RESP::Null, |member| RESP::BulkString(member.clone())));
+        }
+    };
+
+    ctx.write_reply(|db, writer| {
+        let set = match db.get_set(args[0])? {
+            Some(set) => set,
+            None => {
+                writer.array_header(0);
+                return Ok(());
+            }
+        };
+
+        // A positive count asks for distinct members, a negative one allows repetitions
+        if count >= 0 {
+            let picked =
+                pick_distinct(count as usize, set.len(), set.keys(), || random_member(set));
+            writer.array_header(picked.len());
+            picked
+                .into_iter()
+                .for_each(|member| writer.bulk_string(member));
+            return Ok(());
+        }
+
+        write_random_picks(writer, count.unsigned_abs() as usize, 1, |writer| {
+            let member = random_member(set).expect("sets in the keyspace are not empty");
+            writer.bulk_string(member);
+        })
+    })
+}
+
+/// SMOVE source destination member
+pub fn smove(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (source, destination, member) = (args[0], args[1], args[2]);
+    let db = ctx.db();
+
+    let found = db
+        .get_set(source)?
+        .is_some_and(|set| set.contains_key(member));
+    // The destination must hold a set even when there is nothing to move
+    db.get_set(destination)?;
+
+    if !found {
+        return Ok(RESP::Integer(0));
+    }
+    if source == destination {
+        return Ok(RESP::Integer(1));
+    }
+
+    if let Some(set) = db.get_set_mut(source)? {
+        set.remove(member);
+    }
+    db.remove_if_empty(source);
+    get_or_create_set(db, destination)?.insert(member.to_vec(), ());
+
+    Ok(RESP::Integer(1))
+}
+
+/// SSCAN key cursor [MATCH pattern] [COUNT count]
+pub fn sscan(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let cursor = parse_cursor(args[1])?;
+    let options = ScanOptions::parse(&args[2..], Scanned::Set)?;
+
+    let set = match ctx.db().get_set(args[0])? {
+        Some(set) => set,
+        None => return Ok(scan_reply(0, vec![])),
+    };
+
+    let (cursor, members) = options.collect(cursor, |cursor, members| {
+        set.scan(cursor, |member, _| members.push(member.clone()))
+    });
+    let members = members
+        .into_iter()
+        .filter(|member| options.matches(member))
+        .map(RESP::BulkString)
+        .collect();
+
+    Ok(scan_reply(cursor, members))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SetOperation {
+    Intersection,
+    Union,
+    Difference,
+}
+
+/// The members common to all the sets, a missing set making the intersection empty
+fn intersection(sets: Vec<Option<&Set>>) -> impl Iterator<Item = &Vec<u8>> {
+    let mut sets: Vec<&Set> = sets.into_iter().collect::<Option<_>>().unwrap_or_default();
+
+    // Checking the members of the smallest set against the others is the least work
+    sets.sort_by_key(|set| set.len());
+    let others = sets.split_off(sets.len().min(1));
+
+    sets.into_iter()
+        .flat_map(|set| set.keys())
+        .filter(move |member| others.iter().all(|set| set.contains_key(*member)))
+}
+
+/// Apply the operation to the sets stored at the keys, missing keys counting as empty sets
+fn combine(db: &mut Db, keys: &[&[u8]], operation: SetOperation) -> ServerResult<Set> {
+    let sets = db.get_sets(keys)?;
+
+    let result = match operation {
+        SetOperation::Intersection => intersection(sets)
+            .map(|member| (member.clone(), ()))
+            .collect(),
+        SetOperation::Union => sets
+            .into_iter()
+            .flatten()
+            .flat_map(Set::keys)
+            .map(|member| (member.clone(), ()))
+            .collect(),
+        SetOperation::Difference => match sets.split_first() {
+            Some((Some(first), others)) => first
+                .keys()
+                .filter(|member| !others.iter().flatten().any(|set| set.contains_key(*member)))
+                .map(|member| (member.clone(), ()))
+                .collect(),
+            _ => Set::new(),
+        },
+    };
+
+    Ok(result)
+}
+
+fn combine_generic(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    operation: SetOperation,
+) -> ServerResult<RESP> {
+    let result = combine(ctx.db(), args, operation)?;
+
+    Ok(set_reply(result.keys()))
+}
+
+/// Shared implementation of SINTERSTORE, SUNIONSTORE and SDIFFSTORE, which overwrite the
+/// destination whatever its type
+fn combine_store_generic(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    operation: SetOperation,
+) -> ServerResult<RESP> {
+    let db = ctx.db();
+    let result = combine(db, &args[1..], operation)?;
+    let cardinality = result.len();
+
+    if result.is_empty() {
+        db.remove(args[0]);
+    } else {
+        db.insert(args[0], Value::Set(result));
+    }
+
+    Ok(RESP::Integer(cardinality as i64))
+}
+
+/// SINTER key [key ...]
+pub fn sinter(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_generic(ctx, args, SetOperation::Intersection)
+}
+
+/// SUNION key [key ...]
+pub fn sunion(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_generic(ctx, args, SetOperation::Union)
+}
+
+/// SDIFF key [key ...]
+pub fn sdiff(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_generic(ctx, args, SetOperation::Difference)
+}
+
+/// SINTERSTORE destination key [key ...]
+pub fn sinterstore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_store_generic(ctx, args, SetOperation::Intersection)
+}
+
+/// SUNIONSTORE destination key [key ...]
+pub fn sunionstore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_store_generic(ctx, args, SetOperation::Union)
+}
+
+/// SDIFFSTORE destination key [key ...]
+pub fn sdiffstore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_store_generic(ctx, args, SetOperation::Difference)
+}
+
+/// SINTERCARD numkeys key [key ...] [LIMIT limit]
+pub fn sintercard(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let num_keys = parse_integer(args[0])?;
+    if num_keys <= 0 {
+        return Err(ServerError::InvalidArgument(String::from(
+            "numkeys should be greater than 0",
+        )));
+    }
+
+    let num_keys = num_keys as usize;
+    if num_keys > args.len() - 1 {
+        return Err(ServerError::InvalidArgument(String::from(
+            "Number of keys can't be greater than number of args",
+        )));
+    }
+
+    // A limit of 0 means no limit
+    let limit = match &args[num_keys + 1..] {
+        [] => usize::MAX,
+        [option, limit] if option.eq_ignore_ascii_case(b"limit") => match parse_integer(limit)? {
+            limit if limit < 0 => {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "LIMIT can't be negative",
+                )));
+            }
+            0 => usize::MAX,
+            limit => limit as usize,
+        },
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let sets = ctx.db().get_sets(&args[1..=num_keys])?;
+    let cardinality = intersection(sets).take(limit).count();
+
+    Ok(RESP::Integer(cardinality as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    /// Sort the members of a set or array reply, as sets have no order
+    fn sorted(reply: RESP) -> Vec<String> {
+        let members = match reply {
+            RESP::Set(members) | RESP::Array(members) => members,
+            other => panic!("unexpected reply {:?}", other),
+        };
+
+        let mut members: Vec<String> = members
+            .into_iter()
+            .map(|member| match member {
+                RESP::BulkString(data) => String::from_utf8(data).unwrap(),
+                other => panic!("unexpected member {:?}", other),
+            })
+            .collect();
+        members.sort();
+        members
+    }
+
+    fn members(storage: &mut Storage, key: &str) -> Vec<String> {
+        sorted(execute(storage, &["SMEMBERS", key]).unwrap())
+    }
+
+    #[test]
+    fn test_sadd_and_srem() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["SADD", "set", "a", "b", "a"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SADD", "set", "b", "c"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(members(&mut storage, "set"), vec!["a", "b", "c"]);
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["SRANDMEMBER", "set", "-9223372036854775808"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "value is out of range"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SRANDMEMBER", "set", "-1000000000"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "value is out of range"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SCARD", "set"]),
+            Ok(RESP::Integer(3))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["SREM", "set", "a", "x"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SREM", "set", "b", "c"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "set"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SMEMBERS", "set"]),
+            Ok(RESP::Set(vec![]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SCARD", "set"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_sismember_and_smismember() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SADD", "set", "a", "b"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["SISMEMBER", "set", "a"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SISMEMBER", "missing", "a"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SMISMEMBER", "set", "b", "c", "a"]),
+            Ok(RESP::Array(vec![
+                RESP::Integer(1),
+                RESP::Integer(0),
+                RESP::Integer(1)
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_spop() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SADD", "set", "a", "b", "c", "d"]).unwrap();
+
+        let popped = match execute(&mut storage, &["SPOP", "set"]) {
+            Ok(RESP::BulkString(data)) => String::from_utf8(data).unwrap(),
+            other => panic!("unexpected reply {:?}", other),
+        };
+        assert_eq!(
+            execute(&mut storage, &["SISMEMBER", "set", &popped]),
+            Ok(RESP::Integer(0))
+        );
+
+        let popped = sorted(execute(&mut storage, &["SPOP", "set", "2"]).unwrap());
+        assert_eq!(popped.len(), 2);
+        assert_eq!(
+            execute(&mut storage, &["SCARD", "set"]),
+            Ok(RESP::Integer(1))
+        );
+
+        assert_eq!(
+            sorted(execute(&mut storage, &["SPOP", "set", "5"]).unwrap()).len(),
+            1
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "set"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(execute(&mut storage, &["SPOP", "set"]), Ok(RESP::Null));
+        assert_eq!(
+            execute(&mut storage, &["SPOP", "set", "1"]),
+            Ok(RESP::Set(vec![]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SPOP", "set", "-1"]),
+            Err(ServerError::NotPositive)
+        );
+    }
+
+    #[test]
+    fn test_srandmember() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SADD", "set", "a", "b", "c"]).unwrap();
+
+        assert!(matches!(
+            execute(&mut storage, &["SRANDMEMBER", "set"]),
+            Ok(RESP::BulkString(_))
+        ));
+        assert_eq!(
+            sorted(execute(&mut storage, &["SRANDMEMBER", "set", "5"]).unwrap()),
+            vec!["a", "b", "c"]
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["SRANDMEMBER", "set", "-5"]).unwrap()).len(),
+            5
+        );
+        assert_eq!(
+            execute(&mut storage, &["SCARD", "set"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SRANDMEMBER", "missing"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SRANDMEMBER", "missing", "2"]),
+            Ok(RESP::Array(vec![]))
+        );
+    }
+
+    #[test]
+    fn test_smove() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SADD", "source", "a", "b"]).unwrap();
+        execute(&mut storage, &["SADD", "destination", "c"]).unwrap();
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["SMOVE", "source", "destination", "a"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(members(&mut storage, "destination"), vec!["a", "c"]);
+        assert_eq!(
+            execute(&mut storage, &["SMOVE", "source", "destination", "x"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SMOVE", "source", "source", "b"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SMOVE", "source", "string", "b"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SMOVE", "source", "new", "b"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "source"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(members(&mut storage, "new"), vec!["b"]);
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SADD", "a", "1", "2", "3", "4"]).unwrap();
+        execute(&mut storage, &["SADD", "b", "2", "3", "5"]).unwrap();
+        execute(&mut storage, &["SADD", "c", "3", "4", "5"]).unwrap();
+
+        assert_eq!(
+            sorted(execute(&mut storage, &["SINTER", "a", "b", "c"]).unwrap()),
+            vec!["3"]
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["SINTER", "a", "missing"]).unwrap()),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["SUNION", "a", "b", "missing"]).unwrap()),
+            vec!["1", "2", "3", "4", "5"]
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["SDIFF", "a", "b", "missing"]).unwrap()),
+            vec!["1", "4"]
+        );
+        assert_eq!(
+            sorted(execute(&mut storage, &["SDIFF", "missing", "a"]).unwrap()),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_set_algebra_store() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SADD", "a", "1", "2", "3"]).unwrap();
+        execute(&mut storage, &["SADD", "b", "2", "3", "4"]).unwrap();
+        execute(&mut storage, &["SET", "destination", "value"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["SINTERSTORE", "destination", "a", "b"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(members(&mut storage, "destination"), vec!["2", "3"]);
+        assert_eq!(
+            execute(&mut storage, &["SUNIONSTORE", "a", "a", "b"]),
+            Ok(RESP::Integer(4))
+        );
+        assert_eq!(members(&mut storage, "a"), vec!["1", "2", "3", "4"]);
+        assert_eq!(
+            execute(&mut storage, &["SDIFFSTORE", "destination", "b", "a"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "destination"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_sintercard() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SADD", "a", "1", "2", "3", "4"]).unwrap();
+        execute(&mut storage, &["SADD", "b", "1", "2", "3", "5"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["SINTERCARD", "2", "a", "b"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SINTERCARD", "2", "a", "b", "LIMIT", "2"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SINTERCARD", "2", "a", "b", "LIMIT", "0"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SINTERCARD", "1", "missing"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SINTERCARD", "0", "a"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "numkeys should be greater than 0"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SINTERCARD", "3", "a", "b"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "Number of keys can't be greater than number of args"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SINTERCARD", "2", "a", "b", "LIMIT", "-1"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "LIMIT can't be negative"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SINTERCARD", "1", "a", "b"]),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+        execute(&mut storage, &["SADD", "set", "a"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["SADD", "string", "a"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SINTER", "set", "string"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["GET", "set"]),
+            Err(ServerError::WrongType)
+        );
+    }
+}
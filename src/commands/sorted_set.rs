@@ -0,0 +1,1640 @@
+use crate::commands::scan::{ScanOptions, Scanned, parse_cursor, scan_reply};
+use crate::commands::set::SetOperation;
+use crate::commands::{Context, parse_float, parse_integer, parse_timeout};
+use crate::resp::{ProtocolVersion, RESP};
+use crate::resp_writer::write_double;
+use crate::server_result::{ServerError, ServerResult};
+use crate::sorted_set::SortedSet;
+use crate::storage::{Db, Set, Value, ValueType};
+use std::collections::HashMap;
+
+/// Return the sorted set stored at the key, creating an empty one if the key is missing
+fn get_or_create_sorted_set<'a>(db: &'a mut Db, key: &[u8]) -> ServerResult<&'a mut SortedSet> {
+    if db.get_sorted_set(key)?.is_none() {
+        db.insert(key, Value::SortedSet(SortedSet::default()));
+    }
+
+    Ok(db
+        .get_sorted_set_mut(key)?
+        .expect("the key has just been created"))
+}
+
+/// Store the sorted set under the key, or delete the key if the sorted set is empty
+pub fn store(db: &mut Db, key: &[u8], zset: SortedSet) -> RESP {
+    let cardinality = zset.len();
+
+    if zset.is_empty() {
+        db.remove(key);
+    } else {
+        db.insert(key, Value::SortedSet(zset));
+    }
+
+    RESP::Integer(cardinality as i64)
+}
+
+/// Members alone, or members with their score as pairs in RESP3 and flattened in RESP2
+fn elements_reply<'a>(
+    elements: impl Iterator<Item = (&'a [u8], f64)>,
+    with_scores: bool,
+    protocol: ProtocolVersion,
+) -> RESP {
+    let reply = elements
+        .flat_map(|(member, score)| {
+            let member = RESP::BulkString(member.to_vec());
+
+            match (with_scores, protocol) {
+                (false, _) => vec![member],
+                (true, ProtocolVersion::RESP2) => vec![member, RESP::Double(score)],
+                (true, ProtocolVersion::RESP3) => {
+                    vec![RESP::Array(vec![member, RESP::Double(score)])]
+                }
+            }
+        })
+        .collect();
+
+    RESP::Array(reply)
+}
+
+/// The options of ZADD, which are all flags
+#[derive(Debug, Default)]
+struct AddOptions {
+    only_new: bool,
+    only_existing: bool,
+    only_greater: bool,
+    only_less: bool,
+    changed: bool,
+    increment: bool,
+}
+
+/// What adding a member did to the sorted set, with the resulting score
+#[derive(Debug, PartialEq)]
+enum Added {
+    New(f64),
+    Updated(f64),
+    Unchanged(f64),
+    /// The options prevented the change
+    Skipped,
+}
+
+/// Add a member or update its score, following the ZADD options
+fn add_member(
+    zset: &mut SortedSet,
+    member: &[u8],
+    mut score: f64,
+    options: &AddOptions,
+) -> ServerResult<Added> {
+    let current = match zset.score(member) {
+        Some(current) => current,
+        None if options.only_existing => return Ok(Added::Skipped),
+        None => {
+            zset.insert(member, score);
+            return Ok(Added::New(score));
+        }
+    };
+
+    if options.only_new {
+        return Ok(Added::Skipped);
+    }
+
+    if options.increment {
+        score += current;
+        if score.is_nan() {
+            return Err(ServerError::InvalidArgument(String::from(
+                "resulting score is not a number (NaN)",
+            )));
+        }
+    }
+
+    if (options.only_greater && score <= current) || (options.only_less && score >= current) {
+        return Ok(Added::Skipped);
+    }
+
+    if score == current {
+        return Ok(Added::Unchanged(score));
+    }
+
+    zset.insert(member, score);
+    Ok(Added::Updated(score))
+}
+
+/// ZADD key [NX | XX] [GT | LT] [CH] [INCR] score member [score member ...]
+pub fn zadd(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let mut options = AddOptions::default();
+
+    let mut index = 1;
+    while index < args.len() {
+        match args[index].to_ascii_lowercase().as_slice() {
+            b"nx" => options.only_new = true,
+            b"xx" => options.only_existing = true,
+            b"gt" => options.only_greater = true,
+            b"lt" => options.only_less = true,
+            b"ch" => options.changed = true,
+            b"incr" => options.increment = true,
+            _ => break,
+        }
+        index += 1;
+    }
+
+    let pairs = &args[index..];
+    if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+        return Err(ServerError::Syntax);
+    }
+    if options.only_new && options.only_existing {
+        return Err(ServerError::IncompatibleOptions(String::from("XX and NX")));
+    }
+    let exclusive = [options.only_new, options.only_greater, options.only_less];
+    if exclusive.iter().filter(|option| **option).count() > 1 {
+        return Err(ServerError::IncompatibleOptions(String::from(
+            "GT, LT, and/or NX",
+        )));
+    }
+    if options.increment && pairs.len() > 2 {
+        return Err(ServerError::InvalidArgument(String::from(
+            "INCR option supports a single increment-element pair",
+        )));
+    }
+
+    // Nothing is changed unless all the scores are valid
+    let scores = pairs
+        .chunks(2)
+        .map(|pair| parse_float(pair[0]))
+        .collect::<ServerResult<Vec<f64>>>()?;
+
+    let db = ctx.db();
+    if options.only_existing && db.get_sorted_set(key)?.is_none() {
+        return Ok(if options.increment {
+            RESP::Null
+        } else {
+            RESP::Integer(0)
+        });
+    }
+
+    let zset = get_or_create_sorted_set(db, key)?;
+    let mut count = 0;
+    let mut last = Added::Skipped;
+
+    for (pair, score) in pairs.chunks(2).zip(scores) {
+        last = add_member(zset, pair[1], score, &options)?;
+
+        match last {
+            Added::New(_) => count += 1,
+            Added::Updated(_) if options.changed => count += 1,
+            _ => {}
+        }
+    }
+
+    if options.increment {
+        return Ok(match last {
+            Added::New(score) | Added::Updated(score) | Added::Unchanged(score) => {
+                RESP::Double(score)
+            }
+            Added::Skipped => RESP::Null,
+        });
+    }
+
+    Ok(RESP::Integer(count))
+}
+
+/// ZINCRBY key increment member
+pub fn zincrby(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let increment = parse_float(args[1])?;
+    let options = AddOptions {
+        increment: true,
+        ..AddOptions::default()
+    };
+
+    let zset = get_or_create_sorted_set(ctx.db(), args[0])?;
+    match add_member(zset, args[2], increment, &options)? {
+        Added::New(score) | Added::Updated(score) | Added::Unchanged(score) => {
+            Ok(RESP::Double(score))
+        }
+        Added::Skipped => unreachable!("no option prevents the increment"),
+    }
+}
+
+/// ZREM key member [member ...]
+pub fn zrem(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+    let zset = match db.get_sorted_set_mut(args[0])? {
+        Some(zset) => zset,
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    let removed = args[1..]
+        .iter()
+        .filter(|member| zset.remove(member))
+        .count();
+    db.remove_if_empty(args[0]);
+
+    Ok(RESP::Integer(removed as i64))
+}
+
+fn score_or_null(zset: Option<&SortedSet>, member: &[u8]) -> RESP {
+    match zset.and_then(|zset| zset.score(member)) {
+        Some(score) => RESP::Double(score),
+        None => RESP::Null,
+    }
+}
+
+/// ZSCORE key member
+pub fn zscore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let zset = ctx.db().get_sorted_set(args[0])?;
+
+    Ok(score_or_null(zset, args[1]))
+}
+
+/// ZSCAN key cursor [MATCH pattern] [COUNT count]
+pub fn zscan(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let cursor = parse_cursor(args[1])?;
+    let options = ScanOptions::parse(&args[2..], Scanned::SortedSet)?;
+
+    let zset = match ctx.db().get_sorted_set(args[0])? {
+        Some(zset) => zset,
+        None => return Ok(scan_reply(0, vec![])),
+    };
+
+    let (cursor, members) = options.collect(cursor, |cursor, members| {
+        zset.scan(cursor, |member, score| {
+            members.push((member.to_vec(), score))
+        })
+    });
+
+    // Scores are sent as strings, whatever the protocol
+    let mut elements = vec![];
+    for (member, score) in members {
+        if !options.matches(&member) {
+            continue;
+        }
+        let mut formatted = vec![];
+        write_double(&mut formatted, score);
+        elements.push(RESP::BulkString(member));
+        elements.push(RESP::BulkString(formatted));
+    }
+
+    Ok(scan_reply(cursor, elements))
+}
+
+/// ZMSCORE key member [member ...]
+pub fn zmscore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let zset = ctx.db().get_sorted_set(args[0])?;
+    let scores = args[1..]
+        .iter()
+        .map(|member| score_or_null(zset, member))
+        .collect();
+
+    Ok(RESP::Array(scores))
+}
+
+/// ZCARD key
+pub fn zcard(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let zset = ctx.db().get_sorted_set(args[0])?;
+
+    Ok(RESP::Integer(zset.map_or(0, |zset| zset.len()) as i64))
+}
+
+/// A score range bound, `(` making it exclusive
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScoreBound {
+    value: f64,
+    exclusive: bool,
+}
+
+impl ScoreBound {
+    fn parse(arg: &[u8]) -> ServerResult<Self> {
+        let (value, exclusive) = match arg.strip_prefix(b"(") {
+            Some(value) => (value, true),
+            None => (arg, false),
+        };
+
+        let value = parse_float(value)
+            .map_err(|_| ServerError::InvalidArgument(String::from("min or max is not a float")))?;
+
+        Ok(Self { value, exclusive })
+    }
+
+    /// Whether the score is below the range starting at this bound
+    fn is_above(&self, score: f64) -> bool {
+        score < self.value || (self.exclusive && score == self.value)
+    }
+
+    /// Whether the score is within the range ending at this bound
+    fn is_below_or_at(&self, score: f64) -> bool {
+        score < self.value || (!self.exclusive && score == self.value)
+    }
+}
+
+/// A lexicographical range bound: `-` and `+` for the lowest and highest members, or a member
+/// prefixed by `[` or `(` to include or exclude it
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LexBound<'a> {
+    Lowest,
+    Highest,
+    Inclusive(&'a [u8]),
+    Exclusive(&'a [u8]),
+}
+
+impl<'a> LexBound<'a> {
+    fn parse(arg: &'a [u8]) -> ServerResult<Self> {
+        match arg {
+            b"-" => Ok(Self::Lowest),
+            b"+" => Ok(Self::Highest),
+            [b'[', member @ ..] => Ok(Self::Inclusive(member)),
+            [b'(', member @ ..] => Ok(Self::Exclusive(member)),
+            _ => Err(ServerError::InvalidArgument(String::from(
+                "min or max not valid string range item",
+            ))),
+        }
+    }
+
+    /// Whether the member is below the range starting at this bound
+    fn is_above(&self, member: &[u8]) -> bool {
+        match self {
+            Self::Lowest => false,
+            Self::Highest => true,
+            Self::Inclusive(bound) => member < *bound,
+            Self::Exclusive(bound) => member <= *bound,
+        }
+    }
+
+    /// Whether the member is within the range ending at this bound
+    fn is_below_or_at(&self, member: &[u8]) -> bool {
+        match self {
+            Self::Lowest => false,
+            Self::Highest => true,
+            Self::Inclusive(bound) => member <= *bound,
+            Self::Exclusive(bound) => member < *bound,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RangeKind {
+    Rank,
+    Score,
+    Lex,
+}
+
+/// The elements selected by a range, from the lowest to the highest
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Range<'a> {
+    /// Indexes from the lowest element, or from the highest if negative
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    Lex(LexBound<'a>, LexBound<'a>),
+}
+
+impl<'a> Range<'a> {
+    fn parse(kind: RangeKind, min: &'a [u8], max: &'a [u8]) -> ServerResult<Self> {
+        match kind {
+            RangeKind::Rank => Ok(Self::Rank(parse_integer(min)?, parse_integer(max)?)),
+            RangeKind::Score => Ok(Self::Score(
+                ScoreBound::parse(min)?,
+                ScoreBound::parse(max)?,
+            )),
+            RangeKind::Lex => Ok(Self::Lex(LexBound::parse(min)?, LexBound::parse(max)?)),
+        }
+    }
+
+    /// The ranks of the selected elements, from `start` to `end` excluded. Rank indexes are
+    /// counted from the highest element when `reverse`.
+    fn ranks(&self, zset: &SortedSet, reverse: bool) -> (usize, usize) {
+        let (start, end) = match self {
+            Self::Rank(start, stop) => {
+                let len = zset.len() as i64;
+                let start = if *start < 0 { start + len } else { *start }.max(0);
+                let stop = if *stop < 0 { stop + len } else { *stop }.min(len - 1);
+
+                if start > stop || start >= len {
+                    return (0, 0);
+                }
+                if reverse {
+                    ((len - 1 - stop) as usize, (len - start) as usize)
+                } else {
+                    (start as usize, stop as usize + 1)
+                }
+            }
+            Self::Score(min, max) => (
+                zset.count_while(|score, _| min.is_above(score)),
+                zset.count_while(|score, _| max.is_below_or_at(score)),
+            ),
+            Self::Lex(min, max) => (
+                zset.count_while(|_, member| min.is_above(member)),
+                zset.count_while(|_, member| max.is_below_or_at(member)),
+            ),
+        };
+
+        (start, end.max(start))
+    }
+}
+
+/// The arguments of ZRANGE and of the commands it supersedes
+#[derive(Debug)]
+struct RangeOptions<'a> {
+    range: Range<'a>,
+    reverse: bool,
+    /// Offset and count, in the order of the reply
+    limit: Option<(i64, i64)>,
+    with_scores: bool,
+}
+
+impl<'a> RangeOptions<'a> {
+    /// Parse `min max [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]`. The older
+    /// commands give their kind and direction, which cannot be chosen through options then.
+    fn parse(
+        args: &[&'a [u8]],
+        fixed: Option<(RangeKind, bool)>,
+        allow_scores: bool,
+    ) -> ServerResult<Self> {
+        let (mut kind, mut reverse) = fixed.unwrap_or((RangeKind::Rank, false));
+        let mut limit = None;
+        let mut with_scores = false;
+
+        let mut index = 2;
+        while index < args.len() {
+            match args[index].to_ascii_lowercase().as_slice() {
+                b"withscores" if allow_scores => with_scores = true,
+                b"limit" if index + 2 < args.len() => {
+                    limit = Some((
+                        parse_integer(args[index + 1])?,
+                        parse_integer(args[index + 2])?,
+                    ));
+                    index += 2;
+                }
+                b"rev" if fixed.is_none() => reverse = true,
+                b"byscore" if fixed.is_none() && kind != RangeKind::Lex => kind = RangeKind::Score,
+                b"bylex" if fixed.is_none() && kind != RangeKind::Score => kind = RangeKind::Lex,
+                _ => return Err(ServerError::Syntax),
+            }
+            index += 1;
+        }
+
+        if limit.is_some() && kind == RangeKind::Rank {
+            return Err(ServerError::InvalidArgument(String::from(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX",
+            )));
+        }
+        if with_scores && kind == RangeKind::Lex {
+            return Err(ServerError::InvalidArgument(String::from(
+                "syntax error, WITHSCORES not supported in combination with BYLEX",
+            )));
+        }
+
+        // Score and lexicographical ranges are given from the highest bound when reversed
+        let (min, max) = if reverse && kind != RangeKind::Rank {
+            (args[1], args[0])
+        } else {
+            (args[0], args[1])
+        };
+
+        Ok(Self {
+            range: Range::parse(kind, min, max)?,
+            reverse,
+            limit,
+            with_scores,
+        })
+    }
+
+    /// The ranks of the elements to reply with, from `start` to `end` excluded
+    fn ranks(&self, zset: &SortedSet) -> (usize, usize) {
+        let (start, end) = self.range.ranks(zset, self.reverse);
+
+        let (offset, count) = match self.limit {
+            None => return (start, end),
+            Some((offset, _)) if offset < 0 => return (0, 0),
+            Some((offset, count)) => (offset as usize, count),
+        };
+
+        let available = end - start;
+        let offset = offset.min(available);
+        let count = match count {
+            count if count < 0 => available - offset,
+            count => (count as usize).min(available - offset),
+        };
+
+        if self.reverse {
+            (end - offset - count, end - offset)
+        } else {
+            (start + offset, start + offset + count)
+        }
+    }
+}
+
+/// Shared implementation of ZRANGE and of the commands it supersedes
+fn range_generic(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    fixed: Option<(RangeKind, bool)>,
+) -> ServerResult<RESP> {
+    let options = RangeOptions::parse(&args[1..], fixed, true)?;
+    let protocol = ctx.client.protocol;
+
+    let zset = match ctx.db().get_sorted_set(args[0])? {
+        Some(zset) => zset,
+        None => return Ok(RESP::Array(vec![])),
+    };
+
+    let (start, end) = options.ranks(zset);
+    let elements = zset.range(start, end, options.reverse);
+
+    Ok(elements_reply(elements, options.with_scores, protocol))
+}
+
+/// ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count] [WITHSCORES]
+pub fn zrange(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    range_generic(ctx, args, None)
+}
+
+/// ZREVRANGE key start stop [WITHSCORES]
+pub fn zrevrange(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    range_generic(ctx, args, Some((RangeKind::Rank, true)))
+}
+
+/// ZRANGEBYSCORE key min max [WITHSCORES] [LIMIT offset count]
+pub fn zrangebyscore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    range_generic(ctx, args, Some((RangeKind::Score, false)))
+}
+
+/// ZREVRANGEBYSCORE key max min [WITHSCORES] [LIMIT offset count]
+pub fn zrevrangebyscore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    range_generic(ctx, args, Some((RangeKind::Score, true)))
+}
+
+/// ZRANGEBYLEX key min max [LIMIT offset count]
+pub fn zrangebylex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    range_generic(ctx, args, Some((RangeKind::Lex, false)))
+}
+
+/// ZREVRANGEBYLEX key max min [LIMIT offset count]
+pub fn zrevrangebylex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    range_generic(ctx, args, Some((RangeKind::Lex, true)))
+}
+
+/// ZRANGESTORE dst src min max [BYSCORE | BYLEX] [REV] [LIMIT offset count]
+pub fn zrangestore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let options = RangeOptions::parse(&args[2..], None, false)?;
+
+    let db = ctx.db();
+    let mut result = SortedSet::default();
+    if let Some(zset) = db.get_sorted_set(args[1])? {
+        let (start, end) = options.ranks(zset);
+        for (member, score) in zset.range(start, end, false) {
+            result.insert(member, score);
+        }
+    }
+
+    Ok(store(db, args[0], result))
+}
+
+/// Shared implementation of ZCOUNT and ZLEXCOUNT
+fn count_generic(ctx: &mut Context, args: &[&[u8]], kind: RangeKind) -> ServerResult<RESP> {
+    let range = Range::parse(kind, args[1], args[2])?;
+
+    let count = match ctx.db().get_sorted_set(args[0])? {
+        Some(zset) => {
+            let (start, end) = range.ranks(zset, false);
+            end - start
+        }
+        None => 0,
+    };
+
+    Ok(RESP::Integer(count as i64))
+}
+
+/// ZCOUNT key min max
+pub fn zcount(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    count_generic(ctx, args, RangeKind::Score)
+}
+
+/// ZLEXCOUNT key min max
+pub fn zlexcount(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    count_generic(ctx, args, RangeKind::Lex)
+}
+
+/// Shared implementation of ZREMRANGEBYRANK, ZREMRANGEBYSCORE and ZREMRANGEBYLEX
+fn remove_range_generic(ctx: &mut Context, args: &[&[u8]], kind: RangeKind) -> ServerResult<RESP> {
+    let range = Range::parse(kind, args[1], args[2])?;
+
+    let db = ctx.db();
+    let zset = match db.get_sorted_set_mut(args[0])? {
+        Some(zset) => zset,
+        None => return Ok(RESP::Integer(0)),
+    };
+
+    let (start, end) = range.ranks(zset, false);
+    let members: Vec<Vec<u8>> = zset
+        .range(start, end, false)
+        .map(|(member, _)| member.to_vec())
+        .collect();
+    for member in &members {
+        zset.remove(member);
+    }
+    db.remove_if_empty(args[0]);
+
+    Ok(RESP::Integer(members.len() as i64))
+}
+
+/// ZREMRANGEBYRANK key start stop
+pub fn zremrangebyrank(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    remove_range_generic(ctx, args, RangeKind::Rank)
+}
+
+/// ZREMRANGEBYSCORE key min max
+pub fn zremrangebyscore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    remove_range_generic(ctx, args, RangeKind::Score)
+}
+
+/// ZREMRANGEBYLEX key min max
+pub fn zremrangebylex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    remove_range_generic(ctx, args, RangeKind::Lex)
+}
+
+/// Shared implementation of ZRANK and ZREVRANK
+fn rank_generic(ctx: &mut Context, args: &[&[u8]], reverse: bool) -> ServerResult<RESP> {
+    let with_score = match args[2..] {
+        [] => false,
+        [option] if option.eq_ignore_ascii_case(b"withscore") => true,
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let zset = match ctx.db().get_sorted_set(args[0])? {
+        Some(zset) => zset,
+        None if with_score => return Ok(RESP::NullArray),
+        None => return Ok(RESP::Null),
+    };
+
+    let rank = match zset.rank(args[1]) {
+        Some(rank) if reverse => zset.len() - 1 - rank,
+        Some(rank) => rank,
+        None if with_score => return Ok(RESP::NullArray),
+        None => return Ok(RESP::Null),
+    };
+
+    if with_score {
+        let score = zset.score(args[1]).expect("the member has a rank");
+        return Ok(RESP::Array(vec![
+            RESP::Integer(rank as i64),
+            RESP::Double(score),
+        ]));
+    }
+
+    Ok(RESP::Integer(rank as i64))
+}
+
+/// ZRANK key member [WITHSCORE]
+pub fn zrank(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    rank_generic(ctx, args, false)
+}
+
+/// ZREVRANK key member [WITHSCORE]
+pub fn zrevrank(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    rank_generic(ctx, args, true)
+}
+
+/// Remove up to `count` of the lowest elements, or of the highest if `max`
+fn pop_elements(zset: &mut SortedSet, count: usize, max: bool) -> Vec<(Vec<u8>, f64)> {
+    let len = zset.len();
+    let (start, end) = if max {
+        (len.saturating_sub(count), len)
+    } else {
+        (0, count.min(len))
+    };
+
+    let elements: Vec<(Vec<u8>, f64)> = zset
+        .range(start, end, max)
+        .map(|(member, score)| (member.to_vec(), score))
+        .collect();
+    for (member, _) in &elements {
+        zset.remove(member);
+    }
+
+    elements
+}
+
+/// Shared implementation of ZPOPMIN and ZPOPMAX
+fn pop_generic(ctx: &mut Context, args: &[&[u8]], max: bool) -> ServerResult<RESP> {
+    let count = match args[1..] {
+        [] => None,
+        [count] => match parse_integer(count)? {
+            count if count < 0 => return Err(ServerError::NotPositive),
+            count => Some(count as usize),
+        },
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let protocol = ctx.client.protocol;
+    let db = ctx.db();
+    let zset = match db.get_sorted_set_mut(args[0])? {
+        Some(zset) => zset,
+        None => return Ok(RESP::Array(vec![])),
+    };
+
+    let elements = pop_elements(zset, count.unwrap_or(1), max);
+    db.remove_if_empty(args[0]);
+
+    let elements = elements
+        .iter()
+        .map(|(member, score)| (member.as_slice(), *score));
+
+    // A single element is a flat pair whatever the protocol
+    match count {
+        Some(_) => Ok(elements_reply(elements, true, protocol)),
+        None => Ok(elements_reply(elements, true, ProtocolVersion::RESP2)),
+    }
+}
+
+/// ZPOPMIN key [count]
+pub fn zpopmin(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    pop_generic(ctx, args, false)
+}
+
+/// ZPOPMAX key [count]
+pub fn zpopmax(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    pop_generic(ctx, args, true)
+}
+
+/// Shared implementation of BZPOPMIN and BZPOPMAX
+fn blocking_pop(ctx: &mut Context, args: &[&[u8]], max: bool) -> ServerResult<RESP> {
+    let (keys, timeout) = args.split_at(args.len() - 1);
+    let timeout = parse_timeout(timeout[0])?;
+
+    let db = ctx.db();
+    for key in keys {
+        if let Some(zset) = db.get_sorted_set_mut(key)? {
+            let (member, score) = pop_elements(zset, 1, max)
+                .pop()
+                .expect("sorted sets are never empty");
+            db.remove_if_empty(key);
+
+            return Ok(RESP::Array(vec![
+                RESP::BulkString(key.to_vec()),
+                RESP::BulkString(member),
+                RESP::Double(score),
+            ]));
+        }
+    }
+
+    ctx.block_on(keys, ValueType::SortedSet, timeout);
+    Ok(RESP::NullArray)
+}
+
+/// BZPOPMIN key [key ...] timeout
+pub fn bzpopmin(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    blocking_pop(ctx, args, false)
+}
+
+/// BZPOPMAX key [key ...] timeout
+pub fn bzpopmax(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    blocking_pop(ctx, args, true)
+}
+
+/// How the scores of a member found in several inputs are combined
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            // The sum of opposite infinities is taken as zero, as in Redis
+            Aggregate::Sum => Some(a + b).filter(|sum| !sum.is_nan()).unwrap_or(0.0),
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+/// An input of ZUNIONSTORE and its siblings, where the members of plain sets score 1
+enum Input<'a> {
+    Set(&'a Set),
+    SortedSet(&'a SortedSet),
+}
+
+impl<'a> Input<'a> {
+    fn len(&self) -> usize {
+        match self {
+            Input::Set(set) => set.len(),
+            Input::SortedSet(zset) => zset.len(),
+        }
+    }
+
+    fn score(&self, member: &[u8]) -> Option<f64> {
+        match self {
+            Input::Set(set) => set.contains_key(member).then_some(1.0),
+            Input::SortedSet(zset) => zset.score(member),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&'a [u8], f64)> + 'a> {
+        match self {
+            Input::Set(set) => Box::new(set.keys().map(|member| (member.as_slice(), 1.0))),
+            Input::SortedSet(zset) => Box::new(zset.iter()),
+        }
+    }
+}
+
+fn weighted(score: f64, weight: f64) -> f64 {
+    Some(score * weight)
+        .filter(|score| !score.is_nan())
+        .unwrap_or(0.0)
+}
+
+/// Shared implementation of ZUNIONSTORE, ZINTERSTORE and ZDIFFSTORE
+fn combine_store_generic(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    operation: SetOperation,
+    command: &str,
+) -> ServerResult<RESP> {
+    let num_keys = parse_integer(args[1])?;
+    if num_keys < 1 {
+        return Err(ServerError::InvalidArgument(format!(
+            "at least 1 input key is needed for '{}' command",
+            command
+        )));
+    }
+
+    let num_keys = num_keys as usize;
+    if num_keys > args.len() - 2 {
+        return Err(ServerError::Syntax);
+    }
+    let keys = &args[2..2 + num_keys];
+
+    let mut weights = vec![1.0; num_keys];
+    let mut aggregate = Aggregate::Sum;
+    let accepts_options = !matches!(operation, SetOperation::Difference);
+
+    let mut index = 2 + num_keys;
+    while index < args.len() {
+        let remaining = args.len() - index - 1;
+
+        match args[index].to_ascii_lowercase().as_slice() {
+            b"weights" if accepts_options && remaining >= num_keys => {
+                for (weight, arg) in weights.iter_mut().zip(&args[index + 1..]) {
+                    *weight = parse_float(arg).map_err(|_| {
+                        ServerError::InvalidArgument(String::from("weight value is not a float"))
+                    })?;
+                }
+                index += num_keys;
+            }
+            b"aggregate" if accepts_options && remaining >= 1 => {
+                aggregate = match args[index + 1].to_ascii_lowercase().as_slice() {
+                    b"sum" => Aggregate::Sum,
+                    b"min" => Aggregate::Min,
+                    b"max" => Aggregate::Max,
+                    _ => return Err(ServerError::Syntax),
+                };
+                index += 1;
+            }
+            _ => return Err(ServerError::Syntax),
+        }
+        index += 1;
+    }
+
+    let db = ctx.db();
+    let inputs = db
+        .get_values(keys)
+        .into_iter()
+        .map(|value| match value {
+            Some(Value::Set(set)) => Ok(Some(Input::Set(set))),
+            Some(Value::SortedSet(zset)) => Ok(Some(Input::SortedSet(zset))),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        })
+        .collect::<ServerResult<Vec<Option<Input>>>>()?;
+
+    let mut scores: HashMap<&[u8], f64> = HashMap::new();
+    match operation {
+        SetOperation::Union => {
+            for (input, weight) in inputs.iter().zip(&weights) {
+                for (member, score) in input.iter().flat_map(|input| input.iter()) {
+                    let score = weighted(score, *weight);
+                    scores
+                        .entry(member)
+                        .and_modify(|current| *current = aggregate.apply(*current, score))
+                        .or_insert(score);
+                }
+            }
+        }
+        SetOperation::Intersection => {
+            // A missing input makes the intersection empty
+            let mut inputs: Vec<(Input, f64)> = match inputs.into_iter().collect::<Option<Vec<_>>>()
+            {
+                Some(inputs) => inputs.into_iter().zip(weights).collect(),
+                None => vec![],
+            };
+
+            // Looking up the members of the smallest input in the others is the least work
+            inputs.sort_by_key(|(input, _)| input.len());
+            if let Some(((smallest, weight), others)) = inputs.split_first() {
+                'members: for (member, score) in smallest.iter() {
+                    let mut score = weighted(score, *weight);
+                    for (other, weight) in others {
+                        match other.score(member) {
+                            Some(other) => score = aggregate.apply(score, weighted(other, *weight)),
+                            None => continue 'members,
+                        }
+                    }
+                    scores.insert(member, score);
+                }
+            }
+        }
+        SetOperation::Difference => {
+            if let Some((Some(first), others)) = inputs.split_first() {
+                for (member, score) in first.iter() {
+                    if !others
+                        .iter()
+                        .flatten()
+                        .any(|other| other.score(member).is_some())
+                    {
+                        scores.insert(member, score);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut result = SortedSet::default();
+    for (member, score) in scores {
+        result.insert(member, score);
+    }
+
+    Ok(store(db, args[0], result))
+}
+
+/// ZUNIONSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]]
+/// [AGGREGATE SUM | MIN | MAX]
+pub fn zunionstore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_store_generic(ctx, args, SetOperation::Union, "zunionstore")
+}
+
+/// ZINTERSTORE destination numkeys key [key ...] [WEIGHTS weight [weight ...]]
+/// [AGGREGATE SUM | MIN | MAX]
+pub fn zinterstore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_store_generic(ctx, args, SetOperation::Intersection, "zinterstore")
+}
+
+/// ZDIFFSTORE destination numkeys key [key ...]
+pub fn zdiffstore(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    combine_store_generic(ctx, args, SetOperation::Difference, "zdiffstore")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    fn bulk(data: &str) -> RESP {
+        RESP::BulkString(data.as_bytes().to_vec())
+    }
+
+    fn bulks(elements: &[&str]) -> RESP {
+        RESP::Array(elements.iter().map(|element| bulk(element)).collect())
+    }
+
+    /// Members with their scores, flattened as in RESP2
+    fn with_scores(elements: &[(&str, f64)]) -> RESP {
+        RESP::Array(
+            elements
+                .iter()
+                .flat_map(|(member, score)| [bulk(member), RESP::Double(*score)])
+                .collect(),
+        )
+    }
+
+    fn zrange(storage: &mut Storage, key: &str) -> RESP {
+        execute(storage, &["ZRANGE", key, "0", "-1", "WITHSCORES"]).unwrap()
+    }
+
+    #[test]
+    fn test_zadd_and_zscore() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "1", "a", "2", "b"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "3", "a", "1.5", "c"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            zrange(&mut storage, "zset"),
+            with_scores(&[("c", 1.5), ("b", 2.0), ("a", 3.0)])
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZSCORE", "zset", "a"]),
+            Ok(RESP::Double(3.0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZSCORE", "zset", "x"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZMSCORE", "zset", "b", "x"]),
+            Ok(RESP::Array(vec![RESP::Double(2.0), RESP::Null]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZCARD", "zset"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "-inf", "d"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZSCORE", "zset", "d"]),
+            Ok(RESP::Double(f64::NEG_INFINITY))
+        );
+    }
+
+    #[test]
+    fn test_zadd_options() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["ZADD", "zset", "5", "a"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "NX", "1", "a", "1", "b"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZADD", "zset", "XX", "CH", "2", "a", "2", "c"]
+            ),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZADD", "zset", "GT", "CH", "1", "a", "3", "b"]
+            ),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZADD", "zset", "LT", "CH", "1", "a", "4", "b"]
+            ),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            zrange(&mut storage, "zset"),
+            with_scores(&[("a", 1.0), ("b", 3.0)])
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "INCR", "2.5", "a"]),
+            Ok(RESP::Double(3.5))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "NX", "INCR", "1", "a"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "missing", "XX", "1", "a"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "missing"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_zadd_errors() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "NX", "XX", "1", "a"]),
+            Err(ServerError::IncompatibleOptions(String::from("XX and NX")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "GT", "LT", "1", "a"]),
+            Err(ServerError::IncompatibleOptions(String::from(
+                "GT, LT, and/or NX"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "INCR", "1", "a", "2", "b"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "INCR option supports a single increment-element pair"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "1", "a", "2"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "zset", "1", "a", "nan", "b"]),
+            Err(ServerError::NotAFloat)
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "zset"]),
+            Ok(RESP::Integer(0))
+        );
+
+        execute(&mut storage, &["ZADD", "zset", "inf", "a"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["ZINCRBY", "zset", "-inf", "a"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "resulting score is not a number (NaN)"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_zincrby_and_zrem() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["ZINCRBY", "zset", "2", "a"]),
+            Ok(RESP::Double(2.0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZINCRBY", "zset", "-0.5", "a"]),
+            Ok(RESP::Double(1.5))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZINCRBY", "zset", "x", "a"]),
+            Err(ServerError::NotAFloat)
+        );
+
+        execute(&mut storage, &["ZADD", "zset", "1", "b"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["ZREM", "zset", "a", "x"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZREM", "zset", "b"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "zset"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_zrank() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &["ZADD", "zset", "1", "a", "2", "b", "3", "c"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZRANK", "zset", "b"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZREVRANK", "zset", "a"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANK", "zset", "c", "WITHSCORE"]),
+            Ok(RESP::Array(vec![RESP::Integer(2), RESP::Double(3.0)]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANK", "zset", "x"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANK", "missing", "x", "WITHSCORE"]),
+            Ok(RESP::NullArray)
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANK", "zset", "a", "WITHSCORES"]),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_zrange_by_rank() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &["ZADD", "zset", "1", "a", "2", "b", "3", "c", "4", "d"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "zset", "1", "2"]),
+            Ok(bulks(&["b", "c"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "zset", "-2", "100"]),
+            Ok(bulks(&["c", "d"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "zset", "0", "1", "REV"]),
+            Ok(bulks(&["d", "c"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZREVRANGE", "zset", "1", "-1"]),
+            Ok(bulks(&["c", "b", "a"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "zset", "3", "1"]),
+            Ok(bulks(&[]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "missing", "0", "-1"]),
+            Ok(bulks(&[]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZRANGE", "zset", "0", "1", "LIMIT", "0", "1"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_zrange_by_score() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &["ZADD", "zset", "1", "a", "2", "b", "3", "c", "4", "d"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "zset", "(1", "3", "BYSCORE"]),
+            Ok(bulks(&["b", "c"]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZRANGE", "zset", "+inf", "(2", "BYSCORE", "REV"]
+            ),
+            Ok(bulks(&["d", "c"]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "ZRANGE",
+                    "zset",
+                    "-inf",
+                    "+inf",
+                    "BYSCORE",
+                    "LIMIT",
+                    "1",
+                    "2",
+                    "WITHSCORES"
+                ]
+            ),
+            Ok(with_scores(&[("b", 2.0), ("c", 3.0)]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZREVRANGEBYSCORE", "zset", "4", "1", "LIMIT", "1", "-1"]
+            ),
+            Ok(bulks(&["c", "b", "a"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGEBYSCORE", "zset", "3", "2"]),
+            Ok(bulks(&[]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGEBYSCORE", "zset", "x", "2"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "min or max is not a float"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGEBYSCORE", "zset", "0", "2", "REV"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZCOUNT", "zset", "(1", "+inf"]),
+            Ok(RESP::Integer(3))
+        );
+    }
+
+    #[test]
+    fn test_zrange_by_lex() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &["ZADD", "zset", "0", "a", "0", "b", "0", "c", "0", "d"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "zset", "[b", "(d", "BYLEX"]),
+            Ok(bulks(&["b", "c"]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "ZRANGE", "zset", "+", "-", "BYLEX", "REV", "LIMIT", "0", "2"
+                ]
+            ),
+            Ok(bulks(&["d", "c"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGEBYLEX", "zset", "(a", "+"]),
+            Ok(bulks(&["b", "c", "d"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZREVRANGEBYLEX", "zset", "[c", "-"]),
+            Ok(bulks(&["c", "b", "a"]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZLEXCOUNT", "zset", "-", "[b"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGEBYLEX", "zset", "a", "+"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "min or max not valid string range item"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZRANGE", "zset", "-", "+", "BYLEX", "WITHSCORES"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "syntax error, WITHSCORES not supported in combination with BYLEX"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_zrangestore() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &["ZADD", "zset", "1", "a", "2", "b", "3", "c"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZRANGESTORE", "dst", "zset", "2", "+inf", "BYSCORE"]
+            ),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            zrange(&mut storage, "dst"),
+            with_scores(&[("b", 2.0), ("c", 3.0)])
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGESTORE", "dst", "zset", "5", "10"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "dst"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZRANGESTORE", "dst", "zset", "0", "1", "WITHSCORES"]
+            ),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_zremrangeby() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &[
+                "ZADD", "zset", "1", "a", "2", "b", "3", "c", "4", "d", "5", "e",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZREMRANGEBYRANK", "zset", "0", "0"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZREMRANGEBYSCORE", "zset", "(4", "+inf"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZREMRANGEBYLEX", "zset", "[c", "[c"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            zrange(&mut storage, "zset"),
+            with_scores(&[("b", 2.0), ("d", 4.0)])
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZREMRANGEBYRANK", "zset", "0", "-1"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "zset"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_zpopmin_and_zpopmax() {
+        let mut storage = Storage::new();
+        execute(
+            &mut storage,
+            &["ZADD", "zset", "1", "a", "2", "b", "3", "c"],
+        )
+        .unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZPOPMIN", "zset"]),
+            Ok(with_scores(&[("a", 1.0)]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZPOPMAX", "zset", "5"]),
+            Ok(with_scores(&[("c", 3.0), ("b", 2.0)]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "zset"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZPOPMIN", "zset"]),
+            Ok(RESP::Array(vec![]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZPOPMIN", "zset", "-1"]),
+            Err(ServerError::NotPositive)
+        );
+    }
+
+    #[test]
+    fn test_bzpopmin_immediate() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["ZADD", "b", "1", "x", "2", "y"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["BZPOPMAX", "a", "b", "0"]),
+            Ok(RESP::Array(vec![bulk("b"), bulk("y"), RESP::Double(2.0)]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["BZPOPMIN", "a", "-1"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "timeout is negative"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_zunionstore_and_zinterstore() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["ZADD", "a", "1", "x", "2", "y"]).unwrap();
+        execute(&mut storage, &["ZADD", "b", "10", "y", "20", "z"]).unwrap();
+        execute(&mut storage, &["SADD", "set", "x", "y"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZUNIONSTORE", "dst", "2", "a", "b"]),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            zrange(&mut storage, "dst"),
+            with_scores(&[("x", 1.0), ("y", 12.0), ("z", 20.0)])
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "ZUNIONSTORE",
+                    "dst",
+                    "2",
+                    "a",
+                    "b",
+                    "WEIGHTS",
+                    "2",
+                    "1",
+                    "AGGREGATE",
+                    "MAX"
+                ]
+            ),
+            Ok(RESP::Integer(3))
+        );
+        assert_eq!(
+            zrange(&mut storage, "dst"),
+            with_scores(&[("x", 2.0), ("y", 10.0), ("z", 20.0)])
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["ZINTERSTORE", "dst", "3", "a", "b", "set"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(zrange(&mut storage, "dst"), with_scores(&[("y", 13.0)]));
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZINTERSTORE", "dst", "2", "a", "set", "AGGREGATE", "MIN"]
+            ),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            zrange(&mut storage, "dst"),
+            with_scores(&[("x", 1.0), ("y", 1.0)])
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZINTERSTORE", "dst", "2", "a", "missing"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "dst"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_zdiffstore() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["ZADD", "a", "1", "x", "2", "y", "3", "z"]).unwrap();
+        execute(&mut storage, &["ZADD", "b", "10", "y"]).unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZDIFFSTORE", "dst", "3", "a", "b", "missing"]
+            ),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            zrange(&mut storage, "dst"),
+            with_scores(&[("x", 1.0), ("z", 3.0)])
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZDIFFSTORE", "dst", "1", "a", "WEIGHTS", "1"]
+            ),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_combine_store_errors() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZUNIONSTORE", "dst", "0", "a"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "at least 1 input key is needed for 'zunionstore' command"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZUNIONSTORE", "dst", "3", "a", "b"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZUNIONSTORE", "dst", "2", "a", "b", "WEIGHTS", "1", "x"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "weight value is not a float"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["ZUNIONSTORE", "dst", "1", "a", "AGGREGATE", "AVG"]
+            ),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZINTERSTORE", "dst", "1", "string"]),
+            Err(ServerError::WrongType)
+        );
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "string", "value"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["ZADD", "string", "1", "a"]),
+            Err(ServerError::WrongType)
+        );
+        assert_eq!(
+            execute(&mut storage, &["ZRANGE", "string", "0", "-1"]),
+            Err(ServerError::WrongType)
+        );
+    }
+}
@@ -0,0 +1,1707 @@
+use crate::commands::{Context, parse_integer};
+use crate::resp::{ProtocolVersion, RESP};
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{Value, ValueType, current_time_ms};
+use crate::stream::{ConsumerGroup, Fields, Stream, StreamId, TrimStrategy};
+use std::ops::Bound;
+use std::time::Duration;
+
+/// Entries removed at most by approximate trimming without LIMIT, as in Redis
+const DEFAULT_TRIM_LIMIT: usize = 10_000;
+/// Entries examined by XAUTOCLAIM for each entry it may claim
+const AUTOCLAIM_ATTEMPTS_FACTOR: usize = 10;
+
+fn parse_id(arg: &[u8]) -> ServerResult<StreamId> {
+    StreamId::parse(arg, 0).ok_or(ServerError::InvalidStreamId)
+}
+
+/// Parse the start of a range: `-`, an identifier, or one prefixed by `(` to exclude it
+fn parse_range_start(arg: &[u8]) -> ServerResult<StreamId> {
+    match arg {
+        b"-" => Ok(StreamId::MIN),
+        [b'(', id @ ..] => StreamId::parse(id, 0)
+            .ok_or(ServerError::InvalidStreamId)?
+            .next()
+            .ok_or_else(|| {
+                ServerError::InvalidArgument(String::from("invalid start ID for the interval"))
+            }),
+        _ => StreamId::parse(arg, 0).ok_or(ServerError::InvalidStreamId),
+    }
+}
+
+/// Parse the end of a range: `+`, an identifier, or one prefixed by `(` to exclude it
+fn parse_range_end(arg: &[u8]) -> ServerResult<StreamId> {
+    match arg {
+        b"+" => Ok(StreamId::MAX),
+        [b'(', id @ ..] => StreamId::parse(id, u64::MAX)
+            .ok_or(ServerError::InvalidStreamId)?
+            .previous()
+            .ok_or_else(|| {
+                ServerError::InvalidArgument(String::from("invalid end ID for the interval"))
+            }),
+        _ => StreamId::parse(arg, u64::MAX).ok_or(ServerError::InvalidStreamId),
+    }
+}
+
+fn id_reply(id: StreamId) -> RESP {
+    RESP::BulkString(id.to_string().into_bytes())
+}
+
+fn entry_reply(id: StreamId, fields: &Fields) -> RESP {
+    let fields = fields
+        .iter()
+        .flat_map(|(field, value)| {
+            [
+                RESP::BulkString(field.clone()),
+                RESP::BulkString(value.clone()),
+            ]
+        })
+        .collect();
+
+    RESP::Array(vec![id_reply(id), RESP::Array(fields)])
+}
+
+fn entries_reply<'a>(entries: impl Iterator<Item = (&'a StreamId, &'a Fields)>) -> RESP {
+    RESP::Array(
+        entries
+            .map(|(id, fields)| entry_reply(*id, fields))
+            .collect(),
+    )
+}
+
+/// The entries read from each stream, as a map in RESP3 and as pairs in RESP2
+fn streams_reply(streams: Vec<(&[u8], RESP)>, protocol: ProtocolVersion) -> RESP {
+    let streams = streams
+        .into_iter()
+        .map(|(key, entries)| (RESP::BulkString(key.to_vec()), entries));
+
+    match protocol {
+        ProtocolVersion::RESP2 => RESP::Array(
+            streams
+                .map(|(key, entries)| RESP::Array(vec![key, entries]))
+                .collect(),
+        ),
+        ProtocolVersion::RESP3 => RESP::Map(streams.collect()),
+    }
+}
+
+fn no_such_key_or_group(key: &[u8], group: &[u8]) -> ServerError {
+    ServerError::NoGroup(format!(
+        "No such key '{}' or consumer group '{}'",
+        String::from_utf8_lossy(key),
+        String::from_utf8_lossy(group)
+    ))
+}
+
+fn no_such_group(key: &[u8], group: &[u8]) -> ServerError {
+    ServerError::NoGroup(format!(
+        "No such consumer group '{}' for key name '{}'",
+        String::from_utf8_lossy(group),
+        String::from_utf8_lossy(key)
+    ))
+}
+
+/// The trimming arguments of XADD and XTRIM
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TrimOptions {
+    strategy: TrimStrategy,
+    limit: Option<usize>,
+}
+
+impl TrimOptions {
+    /// Parse `MAXLEN | MINID [= | ~] threshold [LIMIT count]`, returning the number of arguments
+    /// used along with the options
+    fn parse(args: &[&[u8]]) -> ServerResult<(Self, usize)> {
+        let by_length = args[0].eq_ignore_ascii_case(b"maxlen");
+
+        let mut index = 1;
+        let approximate = match args.get(index).copied() {
+            Some(b"~") => true,
+            Some(b"=") => false,
+            _ => {
+                index -= 1;
+                false
+            }
+        };
+        index += 1;
+
+        let threshold = args.get(index).ok_or(ServerError::Syntax)?;
+        let strategy = if by_length {
+            match parse_integer(threshold)? {
+                max_len if max_len < 0 => {
+                    return Err(ServerError::InvalidArgument(String::from(
+                        "The MAXLEN argument must be >= 0.",
+                    )));
+                }
+                max_len => TrimStrategy::MaxLen(max_len as u64),
+            }
+        } else {
+            TrimStrategy::MinId(parse_id(threshold)?)
+        };
+        index += 1;
+
+        // Approximate trimming is bounded so that a single command does not stall the server.
+        // Entries are removed one by one here, so the trimming is otherwise exact.
+        let mut limit = approximate.then_some(DEFAULT_TRIM_LIMIT);
+        if args
+            .get(index)
+            .is_some_and(|arg| arg.eq_ignore_ascii_case(b"limit"))
+        {
+            let count = parse_integer(args.get(index + 1).ok_or(ServerError::Syntax)?)?;
+            if count < 0 {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "The LIMIT argument must be >= 0.",
+                )));
+            }
+            if !approximate {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "syntax error, LIMIT cannot be used without the special ~ option",
+                )));
+            }
+            limit = (count > 0).then_some(count as usize);
+            index += 2;
+        }
+
+        Ok((Self { strategy, limit }, index))
+    }
+}
+
+/// How XADD chooses the identifier of the new entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NewId {
+    /// `*`, from the current time
+    Auto,
+    /// `ms-*`, with the next sequence number for the time
+    AutoSequence(u64),
+    Explicit(StreamId),
+}
+
+impl NewId {
+    fn parse(arg: &[u8]) -> ServerResult<Self> {
+        match arg {
+            b"*" => Ok(Self::Auto),
+            _ => match arg.strip_suffix(b"-*") {
+                Some(ms) => match StreamId::parse(ms, 0) {
+                    Some(id) if !ms.contains(&b'-') => Ok(Self::AutoSequence(id.ms)),
+                    _ => Err(ServerError::InvalidStreamId),
+                },
+                None => Ok(Self::Explicit(parse_id(arg)?)),
+            },
+        }
+    }
+
+    /// The identifier of the entry added after the last one
+    fn resolve(self, last_id: StreamId) -> ServerResult<StreamId> {
+        let too_small = || {
+            ServerError::InvalidArgument(String::from(
+                "The ID specified in XADD is equal or smaller than the target stream top item",
+            ))
+        };
+
+        match self {
+            Self::Auto => {
+                let now = current_time_ms();
+                if now > last_id.ms {
+                    return Ok(StreamId::new(now, 0));
+                }
+                last_id.next().ok_or_else(|| {
+                    ServerError::InvalidArgument(String::from(
+                        "The stream has exhausted the last possible ID, unable to add more items",
+                    ))
+                })
+            }
+            Self::AutoSequence(ms) if ms > last_id.ms => Ok(StreamId::new(ms, 0)),
+            Self::AutoSequence(ms) if ms == last_id.ms => match last_id.seq.checked_add(1) {
+                Some(seq) => Ok(StreamId::new(ms, seq)),
+                None => Err(too_small()),
+            },
+            Self::AutoSequence(_) => Err(too_small()),
+            Self::Explicit(StreamId::MIN) => Err(ServerError::InvalidArgument(String::from(
+                "The ID specified in XADD must be greater than 0-0",
+            ))),
+            Self::Explicit(id) if id <= last_id => Err(too_small()),
+            Self::Explicit(id) => Ok(id),
+        }
+    }
+}
+
+/// XADD key [NOMKSTREAM] [MAXLEN | MINID [= | ~] threshold [LIMIT count]] * | id field value
+/// [field value ...]
+pub fn xadd(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let mut no_create = false;
+    let mut trim = None;
+
+    let mut index = 1;
+    while index < args.len() {
+        match args[index].to_ascii_lowercase().as_slice() {
+            b"nomkstream" => {
+                no_create = true;
+                index += 1;
+            }
+            b"maxlen" | b"minid" => {
+                let (options, used) = TrimOptions::parse(&args[index..])?;
+                trim = Some(options);
+                index += used;
+            }
+            _ => break,
+        }
+    }
+
+    let rest = &args[index..];
+    if rest.len() < 3 || rest.len().is_multiple_of(2) {
+        return Err(ServerError::WrongArity(String::from("xadd")));
+    }
+    let new_id = NewId::parse(rest[0])?;
+
+    let db = ctx.db();
+    let last_id = match db.get_stream(key)? {
+        Some(stream) => stream.last_id,
+        None if no_create => return Ok(RESP::Null),
+        None => StreamId::MIN,
+    };
+    let id = new_id.resolve(last_id)?;
+
+    if db.get_stream(key)?.is_none() {
+        db.insert(key, Value::Stream(Stream::default()));
+    }
+    let stream = db
+        .get_stream_mut(key)?
+        .expect("the key has just been created");
+
+    let fields = rest[1..]
+        .chunks(2)
+        .map(|pair| (pair[0].to_vec(), pair[1].to_vec()))
+        .collect();
+    stream.append(id, fields);
+
+    if let Some(trim) = trim {
+        stream.trim(trim.strategy, trim.limit);
+    }
+    db.signal_ready(key);
+
+    Ok(id_reply(id))
+}
+
+/// Shared implementation of XRANGE and XREVRANGE
+fn range_generic(ctx: &mut Context, args: &[&[u8]], reverse: bool) -> ServerResult<RESP> {
+    let (start, end) = if reverse {
+        (args[2], args[1])
+    } else {
+        (args[1], args[2])
+    };
+    let start = parse_range_start(start)?;
+    let end = parse_range_end(end)?;
+
+    let count = match args[3..] {
+        [] => usize::MAX,
+        [option, count] if option.eq_ignore_ascii_case(b"count") => match parse_integer(count)? {
+            count if count <= 0 => return Ok(RESP::NullArray),
+            count => count as usize,
+        },
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let stream = match ctx.db().get_stream(args[0])? {
+        Some(stream) if start <= end => stream,
+        _ => return Ok(RESP::Array(vec![])),
+    };
+
+    let entries = stream.entries.range(start..=end);
+    if reverse {
+        Ok(entries_reply(entries.rev().take(count)))
+    } else {
+        Ok(entries_reply(entries.take(count)))
+    }
+}
+
+/// XRANGE key start end [COUNT count]
+pub fn xrange(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    range_generic(ctx, args, false)
+}
+
+/// XREVRANGE key end start [COUNT count]
+pub fn xrevrange(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    range_generic(ctx, args, true)
+}
+
+/// XLEN key
+pub fn xlen(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let stream = ctx.db().get_stream(args[0])?;
+
+    Ok(RESP::Integer(stream.map_or(0, |stream| stream.len()) as i64))
+}
+
+/// XDEL key id [id ...]
+pub fn xdel(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let ids = args[1..]
+        .iter()
+        .map(|arg| parse_id(arg))
+        .collect::<ServerResult<Vec<StreamId>>>()?;
+
+    let deleted = match ctx.db().get_stream_mut(args[0])? {
+        Some(stream) => ids.into_iter().filter(|id| stream.delete(*id)).count(),
+        None => 0,
+    };
+
+    Ok(RESP::Integer(deleted as i64))
+}
+
+/// XTRIM key MAXLEN | MINID [= | ~] threshold [LIMIT count]
+pub fn xtrim(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    if !args[1].eq_ignore_ascii_case(b"maxlen") && !args[1].eq_ignore_ascii_case(b"minid") {
+        return Err(ServerError::Syntax);
+    }
+
+    let (options, used) = TrimOptions::parse(&args[1..])?;
+    if used != args.len() - 1 {
+        return Err(ServerError::Syntax);
+    }
+
+    let removed = match ctx.db().get_stream_mut(args[0])? {
+        Some(stream) => stream.trim(options.strategy, options.limit),
+        None => 0,
+    };
+
+    Ok(RESP::Integer(removed as i64))
+}
+
+/// Parse the BLOCK timeout of XREAD and XREADGROUP, in milliseconds. Zero means forever.
+fn parse_block_timeout(arg: &[u8]) -> ServerResult<Option<Duration>> {
+    let milliseconds = parse_integer(arg).map_err(|_| {
+        ServerError::InvalidArgument(String::from("timeout is not an integer or out of range"))
+    })?;
+
+    match milliseconds {
+        milliseconds if milliseconds < 0 => Err(ServerError::InvalidArgument(String::from(
+            "timeout is negative",
+        ))),
+        0 => Ok(None),
+        milliseconds => Ok(Some(Duration::from_millis(milliseconds as u64))),
+    }
+}
+
+/// The options of XREAD and XREADGROUP, followed by the streams to read
+#[derive(Debug, Default)]
+struct ReadOptions<'a, 'b> {
+    count: Option<usize>,
+    block: Option<Option<Duration>>,
+    no_ack: bool,
+    keys: &'a [&'b [u8]],
+    ids: &'a [&'b [u8]],
+}
+
+impl<'a, 'b> ReadOptions<'a, 'b> {
+    /// Parse `[COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key [key ...] id [id ...]`
+    fn parse(args: &'a [&'b [u8]], group: bool, command: &str) -> ServerResult<Self> {
+        let mut options = Self::default();
+
+        let mut index = 0;
+        loop {
+            let option = args.get(index).ok_or(ServerError::Syntax)?;
+            let value = args.get(index + 1);
+
+            match option.to_ascii_lowercase().as_slice() {
+                b"count" if value.is_some() => {
+                    // A count of zero or less means no limit
+                    let count = parse_integer(value.unwrap())?;
+                    options.count = (count > 0).then_some(count as usize);
+                    index += 1;
+                }
+                b"block" if value.is_some() => {
+                    options.block = Some(parse_block_timeout(value.unwrap())?);
+                    index += 1;
+                }
+                b"noack" if group => options.no_ack = true,
+                b"streams" => break,
+                _ => return Err(ServerError::Syntax),
+            }
+            index += 1;
+        }
+
+        let streams = &args[index + 1..];
+        if streams.is_empty() || !streams.len().is_multiple_of(2) {
+            return Err(ServerError::InvalidArgument(format!(
+                "Unbalanced '{}' list of streams: for each stream key an ID or '{}' must be \
+                 specified.",
+                command,
+                if group { ">" } else { "$" }
+            )));
+        }
+
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+        options.keys = keys;
+        options.ids = ids;
+        Ok(options)
+    }
+}
+
+/// XREAD [COUNT count] [BLOCK milliseconds] STREAMS key [key ...] id [id ...]
+pub fn xread(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let options = ReadOptions::parse(args, false, "xread")?;
+    let protocol = ctx.client.protocol;
+    let db = ctx.db();
+
+    // `$` stands for the last entry of the stream when the command is received
+    let mut ids = Vec::with_capacity(options.ids.len());
+    for (key, id) in options.keys.iter().zip(options.ids) {
+        let last_id = db.get_stream(key)?.map(|stream| stream.last_id);
+        ids.push(match *id {
+            b"$" => last_id.unwrap_or(StreamId::MIN),
+            _ => parse_id(id)?,
+        });
+    }
+
+    let mut streams = vec![];
+    for (key, id) in options.keys.iter().zip(&ids) {
+        if let Some(stream) = db.get_stream(key)? {
+            let mut entries = stream
+                .entries
+                .range((Bound::Excluded(*id), Bound::Unbounded))
+                .take(options.count.unwrap_or(usize::MAX))
+                .peekable();
+
+            if entries.peek().is_some() {
+                streams.push((*key, entries_reply(entries)));
+            }
+        }
+    }
+
+    if !streams.is_empty() {
+        return Ok(streams_reply(streams, protocol));
+    }
+
+    if let Some(timeout) = options.block {
+        let mut args: Vec<Vec<u8>> = args[..args.len() - ids.len()]
+            .iter()
+            .map(|arg| arg.to_vec())
+            .collect();
+        args.extend(ids.iter().map(|id| id.to_string().into_bytes()));
+
+        ctx.block_on_with_args(options.keys, ValueType::Stream, timeout, args);
+    }
+
+    Ok(RESP::NullArray)
+}
+
+/// XREADGROUP GROUP group consumer [COUNT count] [BLOCK milliseconds] [NOACK] STREAMS key
+/// [key ...] id [id ...]
+pub fn xreadgroup(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    if !args[0].eq_ignore_ascii_case(b"group") {
+        return Err(ServerError::Syntax);
+    }
+    let (group_name, consumer_name) = (args[1], args[2]);
+    let options = ReadOptions::parse(&args[3..], true, "xreadgroup")?;
+
+    // `None` asks for new entries, an identifier for the history of the consumer
+    let mut ids = Vec::with_capacity(options.ids.len());
+    for id in options.ids {
+        ids.push(match *id {
+            b">" => None,
+            b"$" => {
+                return Err(ServerError::InvalidArgument(String::from(
+                    "The $ ID is meaningless in the context of XREADGROUP: you want to read the \
+                     history of this consumer by specifying a proper ID, or use the > ID to get \
+                     new messages. The $ ID would just return an empty result set.",
+                )));
+            }
+            _ => Some(parse_id(id)?),
+        });
+    }
+
+    let protocol = ctx.client.protocol;
+    let db = ctx.db();
+    for key in options.keys {
+        match db.get_stream(key)? {
+            Some(stream) if stream.groups.contains_key(group_name) => {}
+            _ => {
+                return Err(ServerError::NoGroup(format!(
+                    "No such key '{}' or consumer group '{}' in XREADGROUP with GROUP option",
+                    String::from_utf8_lossy(key),
+                    String::from_utf8_lossy(group_name)
+                )));
+            }
+        }
+    }
+
+    let now = current_time_ms();
+    let count = options.count.unwrap_or(usize::MAX);
+    let mut streams = vec![];
+
+    for (key, id) in options.keys.iter().zip(ids) {
+        let stream = db.get_stream_mut(key)?.expect("the stream was checked");
+        let group = stream
+            .groups
+            .get_mut(group_name)
+            .expect("the group was checked");
+        group.consumer(consumer_name, now);
+
+        let entries = match id {
+            Some(start) => {
+                // Pending entries that were deleted since are given without their fields
+                let consumer = &group.consumers[consumer_name];
+                let entries = consumer
+                    .pending
+                    .range((Bound::Excluded(start), Bound::Unbounded))
+                    .take(count)
+                    .map(|id| match stream.entries.get(id) {
+                        Some(fields) => entry_reply(*id, fields),
+                        None => RESP::Array(vec![id_reply(*id), RESP::NullArray]),
+                    })
+                    .collect();
+
+                streams.push((*key, RESP::Array(entries)));
+                continue;
+            }
+            None => stream
+                .entries
+                .range((Bound::Excluded(group.last_delivered_id), Bound::Unbounded))
+                .take(count)
+                .map(|(id, fields)| (*id, fields.clone()))
+                .collect::<Vec<(StreamId, Fields)>>(),
+        };
+
+        if entries.is_empty() {
+            continue;
+        }
+
+        for (id, _) in &entries {
+            stream.advance_group(group_name, *id);
+        }
+
+        let group = stream
+            .groups
+            .get_mut(group_name)
+            .expect("the group was checked");
+        if !options.no_ack {
+            for (id, _) in &entries {
+                group.assign(*id, consumer_name, now, 1);
+            }
+        }
+        group.consumer(consumer_name, now).active_time = Some(now);
+
+        let entries = entries.iter().map(|(id, fields)| (id, fields));
+        streams.push((*key, entries_reply(entries)));
+    }
+
+    if !streams.is_empty() {
+        return Ok(streams_reply(streams, protocol));
+    }
+
+    if let Some(timeout) = options.block {
+        ctx.block_on(options.keys, ValueType::Stream, timeout);
+    }
+
+    Ok(RESP::NullArray)
+}
+
+/// XACK key group id [id ...]
+pub fn xack(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let ids = args[2..]
+        .iter()
+        .map(|arg| parse_id(arg))
+        .collect::<ServerResult<Vec<StreamId>>>()?;
+
+    let group = ctx
+        .db()
+        .get_stream_mut(args[0])?
+        .and_then(|stream| stream.groups.get_mut(args[1]));
+
+    let acknowledged = match group {
+        Some(group) => ids.into_iter().filter(|id| group.acknowledge(*id)).count(),
+        None => 0,
+    };
+
+    Ok(RESP::Integer(acknowledged as i64))
+}
+
+/// XPENDING key group [[IDLE min-idle-time] start end count [consumer]]
+pub fn xpending(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (key, group_name) = (args[0], args[1]);
+
+    let mut rest = &args[2..];
+    let mut min_idle = 0;
+    if rest
+        .first()
+        .is_some_and(|arg| arg.eq_ignore_ascii_case(b"idle"))
+    {
+        min_idle = parse_integer(rest.get(1).ok_or(ServerError::Syntax)?)?.max(0) as u64;
+        rest = &rest[2..];
+    }
+
+    let range = match rest {
+        [] if args.len() == 2 => None,
+        [start, end, count] | [start, end, count, _] => Some((
+            parse_range_start(start)?,
+            parse_range_end(end)?,
+            parse_integer(count)?.max(0) as usize,
+            rest.get(3).copied(),
+        )),
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let group = ctx
+        .db()
+        .get_stream(key)?
+        .and_then(|stream| stream.groups.get(group_name))
+        .ok_or_else(|| no_such_key_or_group(key, group_name))?;
+
+    let (start, end, count, consumer) = match range {
+        Some(range) => range,
+        None => return Ok(pending_summary(group)),
+    };
+    if start > end {
+        return Ok(RESP::Array(vec![]));
+    }
+
+    let now = current_time_ms();
+    let entries = group
+        .pending
+        .range(start..=end)
+        .filter(|(_, entry)| consumer.is_none_or(|consumer| entry.consumer == consumer))
+        .filter(|(_, entry)| now.saturating_sub(entry.delivery_time) >= min_idle)
+        .take(count)
+        .map(|(id, entry)| {
+            RESP::Array(vec![
+                id_reply(*id),
+                RESP::BulkString(entry.consumer.clone()),
+                RESP::Integer(now.saturating_sub(entry.delivery_time) as i64),
+                RESP::Integer(entry.delivery_count as i64),
+            ])
+        })
+        .collect();
+
+    Ok(RESP::Array(entries))
+}
+
+/// The number of pending entries of a group, their smallest and greatest identifiers, and the
+/// number of entries pending for each consumer
+fn pending_summary(group: &ConsumerGroup) -> RESP {
+    let (first, last) = match (group.pending.keys().next(), group.pending.keys().last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => {
+            return RESP::Array(vec![
+                RESP::Integer(0),
+                RESP::Null,
+                RESP::Null,
+                RESP::NullArray,
+            ]);
+        }
+    };
+
+    let consumers = group
+        .consumers
+        .iter()
+        .filter(|(_, consumer)| !consumer.pending.is_empty())
+        .map(|(name, consumer)| {
+            RESP::Array(vec![
+                RESP::BulkString(name.clone()),
+                RESP::BulkString(consumer.pending.len().to_string().into_bytes()),
+            ])
+        })
+        .collect();
+
+    RESP::Array(vec![
+        RESP::Integer(group.pending.len() as i64),
+        id_reply(first),
+        id_reply(last),
+        RESP::Array(consumers),
+    ])
+}
+
+/// XCLAIM key group consumer min-idle-time id [id ...] [IDLE ms] [TIME unix-time-milliseconds]
+/// [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID lastid]
+pub fn xclaim(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (key, group_name, consumer_name) = (args[0], args[1], args[2]);
+    let min_idle = parse_integer(args[3])
+        .map_err(|_| {
+            ServerError::InvalidArgument(String::from("Invalid min-idle-time argument for XCLAIM"))
+        })?
+        .max(0) as u64;
+
+    // The identifiers end at the first argument that is not one
+    let ids: Vec<StreamId> = args[4..]
+        .iter()
+        .map_while(|arg| StreamId::parse(arg, 0))
+        .collect();
+    if ids.is_empty() {
+        return Err(ServerError::InvalidStreamId);
+    }
+
+    let now = current_time_ms();
+    let mut delivery_time = now;
+    let mut retry_count = None;
+    let mut force = false;
+    let mut just_id = false;
+    let mut last_id = None;
+
+    let mut index = 4 + ids.len();
+    while index < args.len() {
+        let option = args[index].to_ascii_lowercase();
+        let value = args.get(index + 1);
+
+        match option.as_slice() {
+            b"force" => force = true,
+            b"justid" => just_id = true,
+            b"idle" if value.is_some() => {
+                let idle = parse_integer(value.unwrap())?.max(0) as u64;
+                delivery_time = now.saturating_sub(idle);
+                index += 1;
+            }
+            b"time" if value.is_some() => {
+                delivery_time = parse_integer(value.unwrap())?.max(0) as u64;
+                index += 1;
+            }
+            b"retrycount" if value.is_some() => {
+                retry_count = Some(parse_integer(value.unwrap())?.max(0) as u64);
+                index += 1;
+            }
+            b"lastid" if value.is_some() => {
+                last_id = Some(parse_id(value.unwrap())?);
+                index += 1;
+            }
+            _ => {
+                return Err(ServerError::InvalidArgument(format!(
+                    "Unrecognized XCLAIM option '{}'",
+                    String::from_utf8_lossy(args[index])
+                )));
+            }
+        }
+        index += 1;
+    }
+    let delivery_time = delivery_time.min(now);
+
+    let stream = ctx
+        .db()
+        .get_stream_mut(key)?
+        .filter(|stream| stream.groups.contains_key(group_name))
+        .ok_or_else(|| no_such_key_or_group(key, group_name))?;
+    let group = stream
+        .groups
+        .get_mut(group_name)
+        .expect("the group was checked");
+
+    if let Some(last_id) = last_id
+        && last_id > group.last_delivered_id
+    {
+        group.last_delivered_id = last_id;
+    }
+    group.consumer(consumer_name, now);
+
+    let mut claimed = vec![];
+    for id in ids {
+        let exists = stream.entries.contains_key(&id);
+        let pending = match group.pending.get(&id) {
+            Some(entry) => Some(entry),
+            None if force && exists => None,
+            None => continue,
+        };
+
+        // Entries deleted from the stream are not pending anymore
+        if !exists {
+            group.acknowledge(id);
+            continue;
+        }
+
+        if let Some(entry) = pending
+            && now.saturating_sub(entry.delivery_time) < min_idle
+        {
+            continue;
+        }
+
+        let previous_count = pending.map_or(0, |entry| entry.delivery_count);
+        let delivery_count = match retry_count {
+            Some(count) => count,
+            None if just_id => previous_count,
+            None => previous_count + 1,
+        };
+
+        group.assign(id, consumer_name, delivery_time, delivery_count);
+        claimed.push(id);
+    }
+
+    if !claimed.is_empty() {
+        group.consumer(consumer_name, now).active_time = Some(now);
+    }
+
+    let reply = claimed
+        .into_iter()
+        .map(|id| match just_id {
+            true => id_reply(id),
+            false => entry_reply(id, &stream.entries[&id]),
+        })
+        .collect();
+
+    Ok(RESP::Array(reply))
+}
+
+/// XAUTOCLAIM key group consumer min-idle-time start [COUNT count] [JUSTID]
+pub fn xautoclaim(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (key, group_name, consumer_name) = (args[0], args[1], args[2]);
+    let min_idle = parse_integer(args[3])
+        .map_err(|_| {
+            ServerError::InvalidArgument(String::from(
+                "Invalid min-idle-time argument for XAUTOCLAIM",
+            ))
+        })?
+        .max(0) as u64;
+    let start = parse_range_start(args[4])?;
+
+    let mut count = 100;
+    let mut just_id = false;
+
+    let mut index = 5;
+    while index < args.len() {
+        match args[index].to_ascii_lowercase().as_slice() {
+            b"justid" => just_id = true,
+            b"count" if index + 1 < args.len() => {
+                let max_count = i64::MAX / AUTOCLAIM_ATTEMPTS_FACTOR as i64;
+                count = match parse_integer(args[index + 1])? {
+                    count if count < 1 || count > max_count => {
+                        return Err(ServerError::InvalidArgument(String::from(
+                            "COUNT must be > 0",
+                        )));
+                    }
+                    count => count as usize,
+                };
+                index += 1;
+            }
+            _ => return Err(ServerError::Syntax),
+        }
+        index += 1;
+    }
+
+    let stream = ctx
+        .db()
+        .get_stream_mut(key)?
+        .filter(|stream| stream.groups.contains_key(group_name))
+        .ok_or_else(|| no_such_key_or_group(key, group_name))?;
+    let group = stream
+        .groups
+        .get_mut(group_name)
+        .expect("the group was checked");
+
+    let now = current_time_ms();
+    group.consumer(consumer_name, now);
+
+    // One more candidate than examined gives the cursor of the next call
+    let attempts = count * AUTOCLAIM_ATTEMPTS_FACTOR;
+    let candidates: Vec<StreamId> = group
+        .pending
+        .range(start..)
+        .take(attempts + 1)
+        .map(|(id, _)| *id)
+        .collect();
+
+    let mut claimed = vec![];
+    let mut deleted = vec![];
+    let mut next = StreamId::MIN;
+
+    for (examined, id) in candidates.into_iter().enumerate() {
+        if examined == attempts || claimed.len() == count {
+            next = id;
+            break;
+        }
+
+        if !stream.entries.contains_key(&id) {
+            group.acknowledge(id);
+            deleted.push(id);
+            continue;
+        }
+
+        let entry = &group.pending[&id];
+        if now.saturating_sub(entry.delivery_time) < min_idle {
+            continue;
+        }
+
+        let delivery_count = entry.delivery_count + if just_id { 0 } else { 1 };
+        group.assign(id, consumer_name, now, delivery_count);
+        claimed.push(id);
+    }
+
+    if !claimed.is_empty() {
+        group.consumer(consumer_name, now).active_time = Some(now);
+    }
+
+    let claimed = claimed
+        .into_iter()
+        .map(|id| match just_id {
+            true => id_reply(id),
+            false => entry_reply(id, &stream.entries[&id]),
+        })
+        .collect();
+
+    Ok(RESP::Array(vec![
+        id_reply(next),
+        RESP::Array(claimed),
+        RESP::Array(deleted.into_iter().map(id_reply).collect()),
+    ]))
+}
+
+/// Parse the `ENTRIESREAD entries-read` option of XGROUP CREATE and XGROUP SETID
+fn parse_entries_read(args: &[&[u8]]) -> ServerResult<Option<Option<u64>>> {
+    match args {
+        [] => Ok(None),
+        [option, value] if option.eq_ignore_ascii_case(b"entriesread") => {
+            match parse_integer(value)? {
+                -1 => Ok(Some(None)),
+                value if value < 0 => Err(ServerError::InvalidArgument(String::from(
+                    "value for ENTRIESREAD must be positive or -1",
+                ))),
+                value => Ok(Some(Some(value as u64))),
+            }
+        }
+        _ => Err(ServerError::Syntax),
+    }
+}
+
+/// XGROUP CREATE key group id | $ [MKSTREAM] [ENTRIESREAD entries-read]
+/// XGROUP SETID key group id | $ [ENTRIESREAD entries-read]
+/// XGROUP DESTROY key group
+/// XGROUP CREATECONSUMER key group consumer
+/// XGROUP DELCONSUMER key group consumer
+pub fn xgroup(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let subcommand = args[0].to_ascii_lowercase();
+    let arity_ok = match subcommand.as_slice() {
+        b"create" => (4..=7).contains(&args.len()),
+        b"setid" => (4..=6).contains(&args.len()),
+        b"destroy" => args.len() == 3,
+        b"createconsumer" | b"delconsumer" => args.len() == 4,
+        _ => {
+            return Err(ServerError::InvalidArgument(format!(
+                "unknown subcommand '{}'. Try XGROUP HELP.",
+                String::from_utf8_lossy(args[0])
+            )));
+        }
+    };
+    if !arity_ok {
+        return Err(ServerError::WrongArity(format!(
+            "xgroup|{}",
+            String::from_utf8_lossy(&subcommand)
+        )));
+    }
+
+    let (key, group_name) = (args[1], args[2]);
+    let mut options = &args[args.len().min(4)..];
+    let create_stream = subcommand == b"create"
+        && options
+            .first()
+            .is_some_and(|option| option.eq_ignore_ascii_case(b"mkstream"));
+    if create_stream {
+        options = &options[1..];
+    }
+
+    let db = ctx.db();
+    if db.get_stream(key)?.is_none() {
+        if !create_stream {
+            return Err(ServerError::InvalidArgument(String::from(
+                "The XGROUP subcommand requires the key to exist. Note that for CREATE you may \
+                 want to use the MKSTREAM option to create an empty stream automatically.",
+            )));
+        }
+        db.insert(key, Value::Stream(Stream::default()));
+    }
+    let stream = db.get_stream_mut(key)?.expect("the stream exists");
+    let now = current_time_ms();
+
+    match subcommand.as_slice() {
+        b"create" | b"setid" => {
+            let id = match args[3] {
+                b"$" => stream.last_id,
+                id => parse_id(id)?,
+            };
+            let entries_read = match parse_entries_read(options)? {
+                Some(entries_read) => entries_read,
+                None => stream.entries_read_until(id),
+            };
+
+            if subcommand == b"create" {
+                if stream.groups.contains_key(group_name) {
+                    return Err(ServerError::BusyGroup);
+                }
+                stream
+                    .groups
+                    .insert(group_name.to_vec(), ConsumerGroup::new(id, entries_read));
+            } else {
+                let group = stream
+                    .groups
+                    .get_mut(group_name)
+                    .ok_or_else(|| no_such_group(key, group_name))?;
+                group.last_delivered_id = id;
+                group.entries_read = entries_read;
+            }
+
+            Ok(RESP::SimpleString(String::from("OK")))
+        }
+        b"destroy" => Ok(RESP::Integer(
+            stream.groups.remove(group_name).is_some() as i64
+        )),
+        b"createconsumer" => {
+            let group = stream
+                .groups
+                .get_mut(group_name)
+                .ok_or_else(|| no_such_group(key, group_name))?;
+            let created = !group.consumers.contains_key(args[3]);
+            group.consumer(args[3], now);
+
+            Ok(RESP::Integer(created as i64))
+        }
+        _ => {
+            let group = stream
+                .groups
+                .get_mut(group_name)
+                .ok_or_else(|| no_such_group(key, group_name))?;
+            let pending = match group.consumers.remove(args[3]) {
+                Some(consumer) => consumer.pending,
+                None => return Ok(RESP::Integer(0)),
+            };
+
+            for id in &pending {
+                group.pending.remove(id);
+            }
+
+            Ok(RESP::Integer(pending.len() as i64))
+        }
+    }
+}
+
+fn field(name: &str, value: RESP) -> (RESP, RESP) {
+    (RESP::BulkString(name.as_bytes().to_vec()), value)
+}
+
+fn optional_integer(value: Option<u64>) -> RESP {
+    value.map_or(RESP::Null, |value| RESP::Integer(value as i64))
+}
+
+fn group_info(stream: &Stream, name: &[u8], group: &ConsumerGroup) -> Vec<(RESP, RESP)> {
+    vec![
+        field("name", RESP::BulkString(name.to_vec())),
+        field("consumers", RESP::Integer(group.consumers.len() as i64)),
+        field("pending", RESP::Integer(group.pending.len() as i64)),
+        field("last-delivered-id", id_reply(group.last_delivered_id)),
+        field("entries-read", optional_integer(group.entries_read)),
+        field("lag", optional_integer(stream.lag(group))),
+    ]
+}
+
+/// The details of a group given by XINFO STREAM FULL, with all its pending entries
+fn full_group_info(stream: &Stream, name: &[u8], group: &ConsumerGroup) -> RESP {
+    let pending = group
+        .pending
+        .iter()
+        .map(|(id, entry)| {
+            RESP::Array(vec![
+                id_reply(*id),
+                RESP::BulkString(entry.consumer.clone()),
+                RESP::Integer(entry.delivery_time as i64),
+                RESP::Integer(entry.delivery_count as i64),
+            ])
+        })
+        .collect();
+
+    let consumers = group
+        .consumers
+        .iter()
+        .map(|(name, consumer)| {
+            let pending = consumer
+                .pending
+                .iter()
+                .map(|id| {
+                    let entry = &group.pending[id];
+                    RESP::Array(vec![
+                        id_reply(*id),
+                        RESP::Integer(entry.delivery_time as i64),
+                        RESP::Integer(entry.delivery_count as i64),
+                    ])
+                })
+                .collect();
+
+            RESP::Map(vec![
+                field("name", RESP::BulkString(name.clone())),
+                field("seen-time", RESP::Integer(consumer.seen_time as i64)),
+                field(
+                    "active-time",
+                    RESP::Integer(consumer.active_time.map_or(-1, |time| time as i64)),
+                ),
+                field("pel-count", RESP::Integer(consumer.pending.len() as i64)),
+                field("pending", RESP::Array(pending)),
+            ])
+        })
+        .collect();
+
+    RESP::Map(vec![
+        field("name", RESP::BulkString(name.to_vec())),
+        field("last-delivered-id", id_reply(group.last_delivered_id)),
+        field("entries-read", optional_integer(group.entries_read)),
+        field("lag", optional_integer(stream.lag(group))),
+        field("pel-count", RESP::Integer(group.pending.len() as i64)),
+        field("pending", RESP::Array(pending)),
+        field("consumers", RESP::Array(consumers)),
+    ])
+}
+
+/// XINFO STREAM key [FULL [COUNT count]]
+fn stream_info(stream: &Stream, args: &[&[u8]]) -> ServerResult<RESP> {
+    let full = match args {
+        [] => None,
+        [option] if option.eq_ignore_ascii_case(b"full") => Some(10),
+        [option, count_option, count]
+            if option.eq_ignore_ascii_case(b"full")
+                && count_option.eq_ignore_ascii_case(b"count") =>
+        {
+            // A count of zero gives all the entries
+            match parse_integer(count)? {
+                count if count <= 0 => Some(usize::MAX),
+                count => Some(count as usize),
+            }
+        }
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let mut info = vec![
+        field("length", RESP::Integer(stream.len() as i64)),
+        field("last-generated-id", id_reply(stream.last_id)),
+        field("max-deleted-entry-id", id_reply(stream.max_deleted_id)),
+        field("entries-added", RESP::Integer(stream.entries_added as i64)),
+        field(
+            "recorded-first-entry-id",
+            id_reply(stream.first_id().unwrap_or(StreamId::MIN)),
+        ),
+    ];
+
+    match full {
+        None => {
+            let entry = |entry: Option<(&StreamId, &Fields)>| {
+                entry.map_or(RESP::Null, |(id, fields)| entry_reply(*id, fields))
+            };
+
+            info.push(field("groups", RESP::Integer(stream.groups.len() as i64)));
+            info.push(field(
+                "first-entry",
+                entry(stream.entries.first_key_value()),
+            ));
+            info.push(field("last-entry", entry(stream.entries.last_key_value())));
+        }
+        Some(count) => {
+            let groups = stream
+                .groups
+                .iter()
+                .map(|(name, group)| full_group_info(stream, name, group))
+                .collect();
+
+            info.push(field(
+                "entries",
+                entries_reply(stream.entries.iter().take(count)),
+            ));
+            info.push(field("groups", RESP::Array(groups)));
+        }
+    }
+
+    Ok(RESP::Map(info))
+}
+
+/// XINFO STREAM key [FULL [COUNT count]]
+/// XINFO GROUPS key
+/// XINFO CONSUMERS key group
+pub fn xinfo(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let subcommand = args[0].to_ascii_lowercase();
+    let arity_ok = match subcommand.as_slice() {
+        b"stream" => (2..=5).contains(&args.len()),
+        b"groups" => args.len() == 2,
+        b"consumers" => args.len() == 3,
+        _ => {
+            return Err(ServerError::InvalidArgument(format!(
+                "unknown subcommand '{}'. Try XINFO HELP.",
+                String::from_utf8_lossy(args[0])
+            )));
+        }
+    };
+    if !arity_ok {
+        return Err(ServerError::WrongArity(format!(
+            "xinfo|{}",
+            String::from_utf8_lossy(&subcommand)
+        )));
+    }
+
+    let key = args[1];
+    let stream = ctx.db().get_stream(key)?.ok_or(ServerError::NoSuchKey)?;
+
+    match subcommand.as_slice() {
+        b"stream" => stream_info(stream, &args[2..]),
+        b"groups" => Ok(RESP::Array(
+            stream
+                .groups
+                .iter()
+                .map(|(name, group)| RESP::Map(group_info(stream, name, group)))
+                .collect(),
+        )),
+        _ => {
+            let group = stream
+                .groups
+                .get(args[2])
+                .ok_or_else(|| no_such_group(key, args[2]))?;
+            let now = current_time_ms();
+
+            let consumers = group
+                .consumers
+                .iter()
+                .map(|(name, consumer)| {
+                    let inactive = consumer
+                        .active_time
+                        .map_or(-1, |time| now.saturating_sub(time) as i64);
+
+                    RESP::Map(vec![
+                        field("name", RESP::BulkString(name.clone())),
+                        field("pending", RESP::Integer(consumer.pending.len() as i64)),
+                        field(
+                            "idle",
+                            RESP::Integer(now.saturating_sub(consumer.seen_time) as i64),
+                        ),
+                        field("inactive", RESP::Integer(inactive)),
+                    ])
+                })
+                .collect();
+
+            Ok(RESP::Array(consumers))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::Storage;
+
+    fn bulk(data: &str) -> RESP {
+        RESP::BulkString(data.as_bytes().to_vec())
+    }
+
+    fn entry(id: &str, fields: &[&str]) -> RESP {
+        RESP::Array(vec![
+            bulk(id),
+            RESP::Array(fields.iter().map(|field| bulk(field)).collect()),
+        ])
+    }
+
+    #[test]
+    fn test_xadd_identifiers() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["XADD", "s", "1-1", "f", "v"]),
+            Ok(bulk("1-1"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XADD", "s", "1-*", "f", "v"]),
+            Ok(bulk("1-2"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XADD", "s", "1", "f", "v"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "The ID specified in XADD is equal or smaller than the target stream top item"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XADD", "other", "0-0", "f", "v"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "The ID specified in XADD must be greater than 0-0"
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XADD", "s", "1-2", "f"]),
+            Err(ServerError::WrongArity(String::from("xadd")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XADD", "none", "NOMKSTREAM", "*", "f", "v"]),
+            Ok(RESP::Null)
+        );
+        assert!(matches!(
+            execute(&mut storage, &["XADD", "s", "*", "f", "v"]),
+            Ok(RESP::BulkString(_))
+        ));
+        assert_eq!(execute(&mut storage, &["XLEN", "s"]), Ok(RESP::Integer(3)));
+        assert_eq!(
+            execute(&mut storage, &["EXISTS", "other", "none"]),
+            Ok(RESP::Integer(0))
+        );
+    }
+
+    #[test]
+    fn test_xadd_and_xtrim_trimming() {
+        let mut storage = Storage::new();
+        for ms in 1..=5 {
+            let id = format!("{}-0", ms);
+            execute(&mut storage, &["XADD", "s", "MAXLEN", "3", &id, "f", "v"]).unwrap();
+        }
+
+        assert_eq!(
+            execute(&mut storage, &["XRANGE", "s", "-", "+"]),
+            Ok(RESP::Array(vec![
+                entry("3-0", &["f", "v"]),
+                entry("4-0", &["f", "v"]),
+                entry("5-0", &["f", "v"]),
+            ]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XTRIM", "s", "MINID", "=", "5"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XTRIM", "s", "MAXLEN", "0", "LIMIT", "1"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "syntax error, LIMIT cannot be used without the special ~ option"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XTRIM", "s", "MAXLEN", "~", "0", "LIMIT", "1"]
+            ),
+            Ok(RESP::Integer(1))
+        );
+
+        // The empty stream is kept, with its last identifier
+        assert_eq!(execute(&mut storage, &["XLEN", "s"]), Ok(RESP::Integer(0)));
+        assert_eq!(
+            execute(&mut storage, &["XADD", "s", "5-0", "f", "v"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "The ID specified in XADD is equal or smaller than the target stream top item"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_xrange_and_xrevrange() {
+        let mut storage = Storage::new();
+        for id in ["1-0", "1-1", "2-0", "3-0"] {
+            execute(&mut storage, &["XADD", "s", id, "id", id]).unwrap();
+        }
+
+        assert_eq!(
+            execute(&mut storage, &["XRANGE", "s", "1", "2"]),
+            Ok(RESP::Array(vec![
+                entry("1-0", &["id", "1-0"]),
+                entry("1-1", &["id", "1-1"]),
+                entry("2-0", &["id", "2-0"]),
+            ]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XRANGE", "s", "(1-0", "+", "COUNT", "1"]),
+            Ok(RESP::Array(vec![entry("1-1", &["id", "1-1"])]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XREVRANGE", "s", "+", "(1-1", "COUNT", "2"]),
+            Ok(RESP::Array(vec![
+                entry("3-0", &["id", "3-0"]),
+                entry("2-0", &["id", "2-0"]),
+            ]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XRANGE", "s", "-", "+", "COUNT", "0"]),
+            Ok(RESP::NullArray)
+        );
+        assert_eq!(
+            execute(&mut storage, &["XRANGE", "s", "x", "+"]),
+            Err(ServerError::InvalidStreamId)
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["XDEL", "s", "1-1", "9-9"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XRANGE", "s", "1", "1"]),
+            Ok(RESP::Array(vec![entry("1-0", &["id", "1-0"])]))
+        );
+    }
+
+    #[test]
+    fn test_xread() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["XADD", "a", "1-0", "f", "a1"]).unwrap();
+        execute(&mut storage, &["XADD", "a", "2-0", "f", "a2"]).unwrap();
+        execute(&mut storage, &["XADD", "b", "1-0", "f", "b1"]).unwrap();
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "XREAD", "COUNT", "1", "STREAMS", "a", "b", "c", "0", "1", "0"
+                ]
+            ),
+            Ok(RESP::Array(vec![RESP::Array(vec![
+                bulk("a"),
+                RESP::Array(vec![entry("1-0", &["f", "a1"])]),
+            ])]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XREAD", "STREAMS", "a", "$"]),
+            Ok(RESP::NullArray)
+        );
+        assert_eq!(
+            execute(&mut storage, &["XREAD", "STREAMS", "a", "b", "0"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be \
+                 specified."
+            )))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XREAD", "BLOCK", "-1", "STREAMS", "a", "0"]),
+            Err(ServerError::InvalidArgument(String::from(
+                "timeout is negative"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_consumer_groups() {
+        let mut storage = Storage::new();
+
+        assert!(matches!(
+            execute(&mut storage, &["XGROUP", "CREATE", "s", "g", "$"]),
+            Err(ServerError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XGROUP", "CREATE", "s", "g", "$", "MKSTREAM"]
+            ),
+            Ok(RESP::SimpleString(String::from("OK")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XGROUP", "CREATE", "s", "g", "0"]),
+            Err(ServerError::BusyGroup)
+        );
+
+        for id in ["1-0", "2-0", "3-0"] {
+            execute(&mut storage, &["XADD", "s", id, "f", id]).unwrap();
+        }
+
+        assert_eq!(
+            execute(
+                &mut storage,
+                &[
+                    "XREADGROUP",
+                    "GROUP",
+                    "g",
+                    "alice",
+                    "COUNT",
+                    "2",
+                    "STREAMS",
+                    "s",
+                    ">"
+                ]
+            ),
+            Ok(RESP::Array(vec![RESP::Array(vec![
+                bulk("s"),
+                RESP::Array(vec![
+                    entry("1-0", &["f", "1-0"]),
+                    entry("2-0", &["f", "2-0"])
+                ]),
+            ])]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XREADGROUP", "GROUP", "g", "bob", "STREAMS", "s", ">"]
+            ),
+            Ok(RESP::Array(vec![RESP::Array(vec![
+                bulk("s"),
+                RESP::Array(vec![entry("3-0", &["f", "3-0"])]),
+            ])]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XREADGROUP", "GROUP", "g", "bob", "STREAMS", "s", ">"]
+            ),
+            Ok(RESP::NullArray)
+        );
+
+        // The history of a consumer gives its pending entries, without deleted ones' fields
+        execute(&mut storage, &["XDEL", "s", "1-0"]).unwrap();
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XREADGROUP", "GROUP", "g", "alice", "STREAMS", "s", "0"]
+            ),
+            Ok(RESP::Array(vec![RESP::Array(vec![
+                bulk("s"),
+                RESP::Array(vec![
+                    RESP::Array(vec![bulk("1-0"), RESP::NullArray]),
+                    entry("2-0", &["f", "2-0"]),
+                ]),
+            ])]))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["XPENDING", "s", "g"]),
+            Ok(RESP::Array(vec![
+                RESP::Integer(3),
+                bulk("1-0"),
+                bulk("3-0"),
+                RESP::Array(vec![
+                    RESP::Array(vec![bulk("alice"), bulk("2")]),
+                    RESP::Array(vec![bulk("bob"), bulk("1")]),
+                ]),
+            ]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XACK", "s", "g", "1-0", "2-0", "2-0"]),
+            Ok(RESP::Integer(2))
+        );
+
+        let pending = execute(&mut storage, &["XPENDING", "s", "g", "-", "+", "10", "bob"]);
+        match pending {
+            Ok(RESP::Array(entries)) => match entries.as_slice() {
+                [RESP::Array(entry)] => {
+                    assert_eq!(entry[0], bulk("3-0"));
+                    assert_eq!(entry[1], bulk("bob"));
+                    assert_eq!(entry[3], RESP::Integer(1));
+                }
+                other => panic!("unexpected entries {:?}", other),
+            },
+            other => panic!("unexpected reply {:?}", other),
+        }
+
+        assert_eq!(
+            execute(&mut storage, &["XPENDING", "s", "nope"]),
+            Err(ServerError::NoGroup(String::from(
+                "No such key 's' or consumer group 'nope'"
+            )))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XREADGROUP", "GROUP", "g", "bob", "STREAMS", "s", "$"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "The $ ID is meaningless in the context of XREADGROUP: you want to read the \
+                 history of this consumer by specifying a proper ID, or use the > ID to get new \
+                 messages. The $ ID would just return an empty result set."
+            )))
+        );
+    }
+
+    #[test]
+    fn test_xclaim_and_xautoclaim() {
+        let mut storage = Storage::new();
+        for id in ["1-0", "2-0", "3-0"] {
+            execute(&mut storage, &["XADD", "s", id, "f", id]).unwrap();
+        }
+        execute(&mut storage, &["XGROUP", "CREATE", "s", "g", "0"]).unwrap();
+        execute(
+            &mut storage,
+            &["XREADGROUP", "GROUP", "g", "alice", "STREAMS", "s", ">"],
+        )
+        .unwrap();
+
+        // Entries were delivered too recently to be claimed
+        assert_eq!(
+            execute(&mut storage, &["XCLAIM", "s", "g", "bob", "100000", "1-0"]),
+            Ok(RESP::Array(vec![]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XCLAIM", "s", "g", "bob", "0", "1-0", "JUSTID"]
+            ),
+            Ok(RESP::Array(vec![bulk("1-0")]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XCLAIM", "s", "g", "bob", "0", "1-0", "BOGUS"]
+            ),
+            Err(ServerError::InvalidArgument(String::from(
+                "Unrecognized XCLAIM option 'BOGUS'"
+            )))
+        );
+
+        execute(&mut storage, &["XDEL", "s", "3-0"]).unwrap();
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XAUTOCLAIM", "s", "g", "carol", "0", "-", "COUNT", "1"]
+            ),
+            Ok(RESP::Array(vec![
+                bulk("2-0"),
+                RESP::Array(vec![entry("1-0", &["f", "1-0"])]),
+                RESP::Array(vec![]),
+            ]))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["XAUTOCLAIM", "s", "g", "carol", "0", "2-0", "JUSTID"]
+            ),
+            Ok(RESP::Array(vec![
+                bulk("0-0"),
+                RESP::Array(vec![bulk("2-0")]),
+                RESP::Array(vec![bulk("3-0")]),
+            ]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XGROUP", "DELCONSUMER", "s", "g", "carol"]),
+            Ok(RESP::Integer(2))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XPENDING", "s", "g"]),
+            Ok(RESP::Array(vec![
+                RESP::Integer(0),
+                RESP::Null,
+                RESP::Null,
+                RESP::NullArray,
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_xinfo() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["XADD", "s", "1-0", "f", "v"]).unwrap();
+        execute(&mut storage, &["XADD", "s", "2-0", "f", "v"]).unwrap();
+        execute(&mut storage, &["XGROUP", "CREATE", "s", "g", "0"]).unwrap();
+        execute(
+            &mut storage,
+            &[
+                "XREADGROUP",
+                "GROUP",
+                "g",
+                "c",
+                "COUNT",
+                "1",
+                "STREAMS",
+                "s",
+                ">",
+            ],
+        )
+        .unwrap();
+
+        let info = match execute(&mut storage, &["XINFO", "STREAM", "s"]) {
+            Ok(RESP::Map(info)) => info,
+            other => panic!("unexpected reply {:?}", other),
+        };
+        assert_eq!(info[0], (bulk("length"), RESP::Integer(2)));
+        assert_eq!(info[1], (bulk("last-generated-id"), bulk("2-0")));
+        assert_eq!(info[5], (bulk("groups"), RESP::Integer(1)));
+
+        assert_eq!(
+            execute(&mut storage, &["XINFO", "GROUPS", "s"]),
+            Ok(RESP::Array(vec![RESP::Map(vec![
+                (bulk("name"), bulk("g")),
+                (bulk("consumers"), RESP::Integer(1)),
+                (bulk("pending"), RESP::Integer(1)),
+                (bulk("last-delivered-id"), bulk("1-0")),
+                (bulk("entries-read"), RESP::Integer(1)),
+                (bulk("lag"), RESP::Integer(1)),
+            ])]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["XINFO", "STREAM", "missing"]),
+            Err(ServerError::NoSuchKey)
+        );
+    }
+}
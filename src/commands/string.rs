@@ -0,0 +1,903 @@
+use crate::commands::{Context, parse_float, parse_integer};
+use crate::resp::RESP;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{MAX_STRING_LENGTH, Value, current_time_ms};
+
+/// How the expiration time of SET-like commands is expressed
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExpireOption {
+    Seconds,
+    Milliseconds,
+    UnixSeconds,
+    UnixMilliseconds,
+}
+
+impl ExpireOption {
+    pub fn parse(arg: &[u8]) -> Option<Self> {
+        match arg.to_ascii_lowercase().as_slice() {
+            b"ex" => Some(Self::Seconds),
+            b"px" => Some(Self::Milliseconds),
+            b"exat" => Some(Self::UnixSeconds),
+            b"pxat" => Some(Self::UnixMilliseconds),
+            _ => None,
+        }
+    }
+
+    /// Convert the given amount into an absolute time in milliseconds
+    pub fn to_absolute_ms(self, amount: &[u8], command: &str) -> ServerResult<u64> {
+        let amount = parse_integer(amount)?;
+        let invalid = || ServerError::InvalidExpireTime(String::from(command));
+
+        if amount <= 0 {
+            return Err(invalid());
+        }
+
+        let milliseconds = match self {
+            Self::Seconds | Self::UnixSeconds => amount.checked_mul(1000).ok_or_else(invalid)?,
+            Self::Milliseconds | Self::UnixMilliseconds => amount,
+        };
+
+        match self {
+            Self::Seconds | Self::Milliseconds => (milliseconds as u64)
+                .checked_add(current_time_ms())
+                .filter(|&time| time <= i64::MAX as u64)
+                .ok_or_else(invalid),
+            Self::UnixSeconds | Self::UnixMilliseconds => Ok(milliseconds as u64),
+        }
+    }
+}
+
+/// Resolve a possibly negative index against a string of the given length
+pub fn normalise_range(start: i64, end: i64, length: usize) -> Option<(usize, usize)> {
+    let length = length as i64;
+
+    if length == 0 || (start < 0 && end < 0 && start > end) {
+        return None;
+    }
+
+    let start = if start < 0 {
+        (length + start).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 { (length + end).max(0) } else { end };
+    let end = end.min(length - 1);
+
+    if start > end {
+        return None;
+    }
+
+    Some((start as usize, end as usize))
+}
+
+fn bulk_or_null(value: Option<&Vec<u8>>) -> RESP {
+    match value {
+        Some(data) => RESP::BulkString(data.clone()),
+        None => RESP::Null,
+    }
+}
+
+/// GET key
+pub fn get(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    Ok(bulk_or_null(ctx.db().get_string(args[0])?))
+}
+
+/// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds |
+///   EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
+pub fn set(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (key, value) = (args[0], args[1]);
+
+    let mut only_if_missing = false;
+    let mut only_if_exists = false;
+    let mut return_old = false;
+    let mut keep_ttl = false;
+    let mut expire: Option<(ExpireOption, &[u8])> = None;
+
+    let mut index = 2;
+    while index < args.len() {
+        let option = args[index].to_ascii_lowercase();
+
+        match option.as_slice() {
+            b"nx" if !only_if_exists => only_if_missing = true,
+            b"xx" if !only_if_missing => only_if_exists = true,
+            b"get" => return_old = true,
+            b"keepttl" if expire.is_none() => keep_ttl = true,
+            _ => match ExpireOption::parse(&option) {
+                Some(unit)
+                    if !keep_ttl
+                        && expire.is_none_or(|(previous, _)| previous == unit)
+                        && index + 1 < args.len() =>
+                {
+                    index += 1;
+                    expire = Some((unit, args[index]));
+                }
+                _ => return Err(ServerError::Syntax),
+            },
+        }
+
+        index += 1;
+    }
+
+    let expires_at = match expire {
+        Some((unit, amount)) => Some(unit.to_absolute_ms(amount, "set")?),
+        None => None,
+    };
+
+    let db = ctx.db();
+
+    let old_value = if return_old {
+        db.get_string(key)?.cloned()
+    } else {
+        None
+    };
+
+    let exists = db.contains_key(key);
+    let reply = if return_old {
+        bulk_or_null(old_value.as_ref())
+    } else {
+        RESP::SimpleString(String::from("OK"))
+    };
+
+    if (only_if_missing && exists) || (only_if_exists && !exists) {
+        return Ok(if return_old { reply } else { RESP::Null });
+    }
+
+    if keep_ttl {
+        db.insert_keep_ttl(key, Value::String(value.to_vec()));
+    } else {
+        db.insert(key, Value::String(value.to_vec()));
+        if expires_at.is_some() {
+            db.set_expiry(key, expires_at);
+        }
+    }
+
+    Ok(reply)
+}
+
+/// SETNX key value
+pub fn setnx(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+
+    if db.contains_key(args[0]) {
+        return Ok(RESP::Integer(0));
+    }
+
+    db.insert(args[0], Value::String(args[1].to_vec()));
+    Ok(RESP::Integer(1))
+}
+
+fn set_with_expire(
+    ctx: &mut Context,
+    args: &[&[u8]],
+    unit: ExpireOption,
+    command: &str,
+) -> ServerResult<RESP> {
+    let expires_at = unit.to_absolute_ms(args[1], command)?;
+
+    let db = ctx.db();
+    db.insert(args[0], Value::String(args[2].to_vec()));
+    db.set_expiry(args[0], Some(expires_at));
+
+    Ok(RESP::SimpleString(String::from("OK")))
+}
+
+/// SETEX key seconds value
+pub fn setex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    set_with_expire(ctx, args, ExpireOption::Seconds, "setex")
+}
+
+/// PSETEX key milliseconds value
+pub fn psetex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    set_with_expire(ctx, args, ExpireOption::Milliseconds, "psetex")
+}
+
+/// GETSET key value
+pub fn getset(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+    let old_value = db.get_string(args[0])?.cloned();
+
+    db.insert(args[0], Value::String(args[1].to_vec()));
+    Ok(bulk_or_null(old_value.as_ref()))
+}
+
+/// GETDEL key
+pub fn getdel(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+    let value = db.get_string(args[0])?.cloned();
+
+    if value.is_some() {
+        db.remove(args[0]);
+    }
+
+    Ok(bulk_or_null(value.as_ref()))
+}
+
+/// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+///   PXAT unix-time-milliseconds | PERSIST]
+pub fn getex(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+
+    // None keeps the current time to live, Some(None) removes it
+    let expires_at: Option<Option<u64>> = match &args[1..] {
+        [] => None,
+        [option] if option.eq_ignore_ascii_case(b"persist") => Some(None),
+        [option, amount] => match ExpireOption::parse(option) {
+            Some(unit) => Some(Some(unit.to_absolute_ms(amount, "getex")?)),
+            None => return Err(ServerError::Syntax),
+        },
+        _ => return Err(ServerError::Syntax),
+    };
+
+    let db = ctx.db();
+    let value = db.get_string(key)?.cloned();
+
+    if let (Some(_), Some(expires_at)) = (&value, expires_at) {
+        db.set_expiry(key, expires_at);
+    }
+
+    Ok(bulk_or_null(value.as_ref()))
+}
+
+/// MGET key [key ...]
+pub fn mget(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+
+    let values = args
+        .iter()
+        .map(|key| match db.get(key) {
+            Some(Value::String(data)) => RESP::BulkString(data.clone()),
+            // Keys holding other types are reported as missing rather than failing the command
+            Some(_) | None => RESP::Null,
+        })
+        .collect();
+
+    Ok(RESP::Array(values))
+}
+
+fn check_pairs(args: &[&[u8]], command: &str) -> ServerResult<()> {
+    if !args.len().is_multiple_of(2) {
+        return Err(ServerError::WrongArity(String::from(command)));
+    }
+
+    Ok(())
+}
+
+/// MSET key value [key value ...]
+pub fn mset(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    check_pairs(args, "mset")?;
+
+    let db = ctx.db();
+    for pair in args.chunks(2) {
+        db.insert(pair[0], Value::String(pair[1].to_vec()));
+    }
+
+    Ok(RESP::SimpleString(String::from("OK")))
+}
+
+/// MSETNX key value [key value ...]
+pub fn msetnx(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    check_pairs(args, "msetnx")?;
+
+    let db = ctx.db();
+    if args.chunks(2).any(|pair| db.contains_key(pair[0])) {
+        return Ok(RESP::Integer(0));
+    }
+
+    for pair in args.chunks(2) {
+        db.insert(pair[0], Value::String(pair[1].to_vec()));
+    }
+
+    Ok(RESP::Integer(1))
+}
+
+/// APPEND key value
+pub fn append(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let db = ctx.db();
+
+    match db.get_string_mut(args[0])? {
+        Some(data) => {
+            if data.len() + args[1].len() > MAX_STRING_LENGTH {
+                return Err(ServerError::StringTooLong);
+            }
+
+            data.extend_from_slice(args[1]);
+            Ok(RESP::Integer(data.len() as i64))
+        }
+        None => {
+            db.insert(args[0], Value::String(args[1].to_vec()));
+            Ok(RESP::Integer(args[1].len() as i64))
+        }
+    }
+}
+
+/// STRLEN key
+pub fn strlen(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let length = ctx.db().get_string(args[0])?.map_or(0, |data| data.len());
+    Ok(RESP::Integer(length as i64))
+}
+
+/// GETRANGE key start end
+pub fn getrange(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let start = parse_integer(args[1])?;
+    let end = parse_integer(args[2])?;
+
+    let data = match ctx.db().get_string(args[0])? {
+        Some(data) => data,
+        None => return Ok(RESP::BulkString(vec![])),
+    };
+
+    match normalise_range(start, end, data.len()) {
+        Some((start, end)) => Ok(RESP::BulkString(data[start..=end].to_vec())),
+        None => Ok(RESP::BulkString(vec![])),
+    }
+}
+
+/// SETRANGE key offset value
+pub fn setrange(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let (key, value) = (args[0], args[2]);
+
+    let offset = parse_integer(args[1])?;
+    if offset < 0 {
+        return Err(ServerError::OffsetOutOfRange);
+    }
+    let offset = offset as usize;
+
+    let db = ctx.db();
+    let current_length = db.get_string(key)?.map_or(0, |data| data.len());
+
+    // Nothing to write: the key is left untouched, and not created if missing
+    if value.is_empty() {
+        return Ok(RESP::Integer(current_length as i64));
+    }
+
+    if offset + value.len() > MAX_STRING_LENGTH {
+        return Err(ServerError::StringTooLong);
+    }
+
+    if db.get_string(key)?.is_none() {
+        db.insert(key, Value::String(vec![]));
+    }
+
+    let data = db
+        .get_string_mut(key)?
+        .expect("the key has just been created");
+
+    if data.len() < offset + value.len() {
+        data.resize(offset + value.len(), 0);
+    }
+    data[offset..offset + value.len()].copy_from_slice(value);
+
+    Ok(RESP::Integer(data.len() as i64))
+}
+
+/// Add `increment` to the integer stored at the key, a missing key counts as 0
+fn increment_by(ctx: &mut Context, key: &[u8], increment: i64) -> ServerResult<RESP> {
+    let db = ctx.db();
+
+    let current = match db.get_string(key)? {
+        Some(data) => parse_integer(data)?,
+        None => 0,
+    };
+    let value = current
+        .checked_add(increment)
+        .ok_or(ServerError::IncrementOverflow)?;
+
+    db.insert_keep_ttl(key, Value::String(value.to_string().into_bytes()));
+    Ok(RESP::Integer(value))
+}
+
+/// INCR key
+pub fn incr(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    increment_by(ctx, args[0], 1)
+}
+
+/// DECR key
+pub fn decr(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    increment_by(ctx, args[0], -1)
+}
+
+/// INCRBY key increment
+pub fn incrby(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let increment = parse_integer(args[1])?;
+    increment_by(ctx, args[0], increment)
+}
+
+/// DECRBY key decrement
+pub fn decrby(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let decrement = parse_integer(args[1])?;
+    let increment = decrement
+        .checked_neg()
+        .ok_or(ServerError::DecrementOverflow)?;
+    increment_by(ctx, args[0], increment)
+}
+
+/// INCRBYFLOAT key increment
+pub fn incrbyfloat(ctx: &mut Context, args: &[&[u8]]) -> ServerResult<RESP> {
+    let key = args[0];
+    let increment = parse_float(args[1])?;
+
+    let db = ctx.db();
+    let current = match db.get_string(key)? {
+        Some(data) => parse_float(data)?,
+        None => 0.0,
+    };
+
+    let value = current + increment;
+    if !value.is_finite() {
+        return Err(ServerError::IncrementNaN);
+    }
+
+    // Plain decimal notation, as stored by Redis, so the value can be read back by INCRBYFLOAT
+    let data = value.to_string().into_bytes();
+    db.insert_keep_ttl(key, Value::String(data.clone()));
+    Ok(RESP::BulkString(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::execute;
+    use crate::resp::RESP;
+    use crate::server_result::ServerError;
+    use crate::storage::{Storage, current_time_ms};
+
+    fn bulk(data: &str) -> RESP {
+        RESP::BulkString(data.as_bytes().to_vec())
+    }
+
+    fn ok() -> RESP {
+        RESP::SimpleString(String::from("OK"))
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        let mut storage = Storage::new();
+
+        assert_eq!(execute(&mut storage, &["GET", "key"]), Ok(RESP::Null));
+        assert_eq!(execute(&mut storage, &["SET", "key", "value"]), Ok(ok()));
+        assert_eq!(execute(&mut storage, &["GET", "key"]), Ok(bulk("value")));
+    }
+
+    #[test]
+    fn test_set_nx_xx() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "1", "XX"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(execute(&mut storage, &["SET", "key", "1", "NX"]), Ok(ok()));
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "2", "NX"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(execute(&mut storage, &["SET", "key", "3", "XX"]), Ok(ok()));
+        assert_eq!(execute(&mut storage, &["GET", "key"]), Ok(bulk("3")));
+
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "4", "NX", "XX"]),
+            Err(ServerError::Syntax)
+        );
+    }
+
+    #[test]
+    fn test_set_get_option() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "1", "GET"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "2", "GET"]),
+            Ok(bulk("1"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "3", "NX", "GET"]),
+            Ok(bulk("2"))
+        );
+        assert_eq!(execute(&mut storage, &["GET", "key"]), Ok(bulk("2")));
+    }
+
+    #[test]
+    fn test_set_expire_options() {
+        let mut storage = Storage::new();
+
+        execute(&mut storage, &["SET", "key", "value", "EX", "100"]).unwrap();
+        let expires_at = storage.dbs[0]
+            .get_entry(b"key")
+            .unwrap()
+            .expires_at
+            .unwrap();
+        assert!(expires_at > current_time_ms() + 99_000);
+
+        execute(&mut storage, &["SET", "key", "other", "KEEPTTL"]).unwrap();
+        assert_eq!(
+            storage.dbs[0].get_entry(b"key").unwrap().expires_at,
+            Some(expires_at)
+        );
+
+        execute(&mut storage, &["SET", "key", "other"]).unwrap();
+        assert_eq!(storage.dbs[0].get_entry(b"key").unwrap().expires_at, None);
+
+        execute(&mut storage, &["SET", "key", "value", "PXAT", "1"]).unwrap();
+        assert_eq!(execute(&mut storage, &["GET", "key"]), Ok(RESP::Null));
+    }
+
+    #[test]
+    fn test_set_invalid_expire() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "value", "EX", "0"]),
+            Err(ServerError::InvalidExpireTime(String::from("set")))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "value", "PX", "abc"]),
+            Err(ServerError::NotAnInteger)
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["SET", "key", "value", "EX", "9223372036854775807"]
+            ),
+            Err(ServerError::InvalidExpireTime(String::from("set")))
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["SET", "key", "value", "EX", "10", "KEEPTTL"]
+            ),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(
+                &mut storage,
+                &["SET", "key", "value", "EX", "10", "PX", "10"]
+            ),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SET", "key", "value", "EX"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(execute(&mut storage, &["GET", "key"]), Ok(RESP::Null));
+    }
+
+    #[test]
+    fn test_setnx_setex_psetex() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["SETNX", "key", "1"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["SETNX", "key", "2"]),
+            Ok(RESP::Integer(0))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["SETEX", "key", "10", "3"]),
+            Ok(ok())
+        );
+        assert!(
+            storage.dbs[0]
+                .get_entry(b"key")
+                .unwrap()
+                .expires_at
+                .is_some()
+        );
+        assert_eq!(execute(&mut storage, &["GET", "key"]), Ok(bulk("3")));
+
+        assert_eq!(
+            execute(&mut storage, &["PSETEX", "key", "-5", "4"]),
+            Err(ServerError::InvalidExpireTime(String::from("psetex")))
+        );
+    }
+
+    #[test]
+    fn test_getset_getdel() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["GETSET", "key", "1"]),
+            Ok(RESP::Null)
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETSET", "key", "2"]),
+            Ok(bulk("1"))
+        );
+        assert_eq!(execute(&mut storage, &["GETDEL", "key"]), Ok(bulk("2")));
+        assert_eq!(execute(&mut storage, &["GETDEL", "key"]), Ok(RESP::Null));
+    }
+
+    #[test]
+    fn test_getex() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "key", "value"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["GETEX", "key", "PX", "100000"]),
+            Ok(bulk("value"))
+        );
+        assert!(
+            storage.dbs[0]
+                .get_entry(b"key")
+                .unwrap()
+                .expires_at
+                .is_some()
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["GETEX", "key", "PERSIST"]),
+            Ok(bulk("value"))
+        );
+        assert_eq!(storage.dbs[0].get_entry(b"key").unwrap().expires_at, None);
+
+        assert_eq!(
+            execute(&mut storage, &["GETEX", "key", "PERSIST", "EX"]),
+            Err(ServerError::Syntax)
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETEX", "missing", "EX", "1"]),
+            Ok(RESP::Null)
+        );
+    }
+
+    #[test]
+    fn test_mset_mget_msetnx() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["MSET", "a", "1", "b", "2"]),
+            Ok(ok())
+        );
+        assert_eq!(
+            execute(&mut storage, &["MGET", "a", "missing", "b"]),
+            Ok(RESP::Array(vec![bulk("1"), RESP::Null, bulk("2")]))
+        );
+        assert_eq!(
+            execute(&mut storage, &["MSET", "a", "1", "b"]),
+            Err(ServerError::WrongArity(String::from("mset")))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["MSETNX", "c", "3", "a", "4"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(execute(&mut storage, &["GET", "c"]), Ok(RESP::Null));
+        assert_eq!(
+            execute(&mut storage, &["MSETNX", "c", "3", "d", "4"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(execute(&mut storage, &["GET", "d"]), Ok(bulk("4")));
+    }
+
+    #[test]
+    fn test_append_strlen() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["STRLEN", "key"]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(
+            execute(&mut storage, &["APPEND", "key", "Hello"]),
+            Ok(RESP::Integer(5))
+        );
+        assert_eq!(
+            execute(&mut storage, &["APPEND", "key", " World"]),
+            Ok(RESP::Integer(11))
+        );
+        assert_eq!(
+            execute(&mut storage, &["STRLEN", "key"]),
+            Ok(RESP::Integer(11))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GET", "key"]),
+            Ok(bulk("Hello World"))
+        );
+    }
+
+    #[test]
+    fn test_getrange() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "key", "This is a string"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["GETRANGE", "key", "0", "3"]),
+            Ok(bulk("This"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETRANGE", "key", "-3", "-1"]),
+            Ok(bulk("ing"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETRANGE", "key", "0", "-1"]),
+            Ok(bulk("This is a string"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETRANGE", "key", "10", "100"]),
+            Ok(bulk("string"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETRANGE", "key", "5", "3"]),
+            Ok(bulk(""))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETRANGE", "key", "-1", "-5"]),
+            Ok(bulk(""))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GETRANGE", "missing", "0", "-1"]),
+            Ok(bulk(""))
+        );
+    }
+
+    #[test]
+    fn test_setrange() {
+        let mut storage = Storage::new();
+        execute(&mut storage, &["SET", "key", "Hello World"]).unwrap();
+
+        assert_eq!(
+            execute(&mut storage, &["SETRANGE", "key", "6", "Redis"]),
+            Ok(RESP::Integer(11))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GET", "key"]),
+            Ok(bulk("Hello Redis"))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["SETRANGE", "padded", "3", "ab"]),
+            Ok(RESP::Integer(5))
+        );
+        assert_eq!(
+            execute(&mut storage, &["GET", "padded"]),
+            Ok(RESP::BulkString(b"\0\0\0ab".to_vec()))
+        );
+
+        assert_eq!(
+            execute(&mut storage, &["SETRANGE", "empty", "3", ""]),
+            Ok(RESP::Integer(0))
+        );
+        assert_eq!(execute(&mut storage, &["GET", "empty"]), Ok(RESP::Null));
+
+        assert_eq!(
+            execute(&mut storage, &["SETRANGE", "key", "-1", "x"]),
+            Err(ServerError::OffsetOutOfRange)
+        );
+        assert_eq!(
+            execute(&mut storage, &["SETRANGE", "key", "536870911", "xx"]),
+            Err(ServerError::StringTooLong)
+        );
+    }
+    #[test]
+    fn test_incr_and_decr() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["INCR", "counter"]),
+            Ok(RESP::Integer(1))
+        );
+        assert_eq!(
+            execute(&mut storage, &["INCRBY", "counter", "10"]),
+            Ok(RESP::Integer(11))
+        );
+        assert_eq!(
+            execute(&mut storage, &["DECR", "counter"]),
+            Ok(RESP::Integer(10))
+        );
+        assert_eq!(
+            execute(&mut storage, &["DECRBY", "counter", "-5"]),
+            Ok(RESP::Integer(15))
+        );
+        assert_eq!(execute(&mut storage, &["GET", "counter"]), Ok(bulk("15")));
+    }
+
+    #[test]
+    fn test_incr_not_an_integer() {
+        let mut storage = Storage::new();
+
+        for value in ["abc", "1.5", " 1", "01", "99999999999999999999"] {
+            execute(&mut storage, &["SET", "key", value]).unwrap();
+            assert_eq!(
+                execute(&mut storage, &["INCR", "key"]),
+                Err(ServerError::NotAnInteger)
+            );
+        }
+
+        assert_eq!(
+            execute(&mut storage, &["INCRBY", "other", "x"]),
+            Err(ServerError::NotAnInteger)
+        );
+    }
+
+    #[test]
+    fn test_incr_overflow() {
+        let mut storage = Storage::new();
+
+        execute(&mut storage, &["SET", "key", "9223372036854775807"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["INCR", "key"]),
+            Err(ServerError::IncrementOverflow)
+        );
+
+        execute(&mut storage, &["SET", "key", "-9223372036854775808"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["DECR", "key"]),
+            Err(ServerError::IncrementOverflow)
+        );
+        assert_eq!(
+            execute(&mut storage, &["DECRBY", "other", "-9223372036854775808"]),
+            Err(ServerError::DecrementOverflow)
+        );
+        assert_eq!(
+            execute(&mut storage, &["GET", "key"]),
+            Ok(bulk("-9223372036854775808"))
+        );
+    }
+
+    #[test]
+    fn test_incr_keeps_ttl() {
+        let mut storage = Storage::new();
+
+        execute(&mut storage, &["SET", "key", "1", "EX", "100"]).unwrap();
+        execute(&mut storage, &["INCR", "key"]).unwrap();
+
+        let entry = storage.dbs[0].get_entry(b"key").unwrap();
+        assert!(entry.expires_at.unwrap() > current_time_ms());
+    }
+
+    #[test]
+    fn test_incrbyfloat() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["INCRBYFLOAT", "key", "10.5"]),
+            Ok(bulk("10.5"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["INCRBYFLOAT", "key", "0.1"]),
+            Ok(bulk("10.6"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["INCRBYFLOAT", "key", "-5"]),
+            Ok(bulk("5.6"))
+        );
+
+        execute(&mut storage, &["SET", "key", "5.0e3"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["INCRBYFLOAT", "key", "2.0e2"]),
+            Ok(bulk("5200"))
+        );
+        assert_eq!(
+            execute(&mut storage, &["INCR", "key"]),
+            Ok(RESP::Integer(5201))
+        );
+    }
+
+    #[test]
+    fn test_incrbyfloat_errors() {
+        let mut storage = Storage::new();
+
+        assert_eq!(
+            execute(&mut storage, &["INCRBYFLOAT", "key", "abc"]),
+            Err(ServerError::NotAFloat)
+        );
+        assert_eq!(
+            execute(&mut storage, &["INCRBYFLOAT", "key", "nan"]),
+            Err(ServerError::NotAFloat)
+        );
+        assert_eq!(
+            execute(&mut storage, &["INCRBYFLOAT", "key", "inf"]),
+            Err(ServerError::IncrementNaN)
+        );
+
+        execute(&mut storage, &["SET", "key", "text"]).unwrap();
+        assert_eq!(
+            execute(&mut storage, &["INCRBYFLOAT", "key", "1"]),
+            Err(ServerError::NotAFloat)
+        );
+    }
+}
@@ -0,0 +1,523 @@
+//! A hash table in the manner of the Redis dict: chained buckets in a power of two sized table,
+//! which is resized incrementally. While resizing, entries are moved a bucket at a time from the
+//! old table to the new one, on each change to the dict, so that no single operation has to move
+//! them all.
+//!
+//! This layout is what makes [`Dict::scan`] possible: cursors are bucket indexes incremented from
+//! their most significant bit, so that the buckets already visited are known whatever size the
+//! table grows or shrinks to in between calls.
+
+use rand::RngExt;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+/// Size of the table of a new dict
+const INITIAL_SIZE: usize = 4;
+/// Most empty buckets skipped by a single rehashing step, to bound its cost
+const EMPTY_VISITS_PER_STEP: usize = 10;
+
+type Bucket<K, V> = Vec<(K, V)>;
+
+#[derive(Clone)]
+struct Table<K, V> {
+    buckets: Vec<Bucket<K, V>>,
+    used: usize,
+}
+
+impl<K, V> Table<K, V> {
+    fn empty() -> Self {
+        Self {
+            buckets: Vec::new(),
+            used: 0,
+        }
+    }
+
+    fn with_size(size: usize) -> Self {
+        Self {
+            buckets: (0..size).map(|_| Vec::new()).collect(),
+            used: 0,
+        }
+    }
+
+    fn mask(&self) -> u64 {
+        (self.buckets.len() as u64).wrapping_sub(1)
+    }
+
+    fn bucket_index(&self, hash: u64) -> usize {
+        (hash & self.mask()) as usize
+    }
+}
+
+#[derive(Clone)]
+pub struct Dict<K, V> {
+    /// The table in use, and the one entries are moved to while resizing
+    tables: [Table<K, V>; 2],
+    /// Next bucket of the first table to move to the second one, `None` unless resizing
+    rehash_index: Option<usize>,
+    hasher: RandomState,
+}
+
+impl<K, V> Default for Dict<K, V> {
+    fn default() -> Self {
+        Self {
+            tables: [Table::empty(), Table::empty()],
+            rehash_index: None,
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Dict<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables[0].used + self.tables[1].used
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        self.hasher.hash_one(key)
+    }
+
+    /// The tables holding entries: the second one only while resizing
+    fn tables(&self) -> &[Table<K, V>] {
+        match self.rehash_index {
+            Some(_) => &self.tables,
+            None => &self.tables[..1],
+        }
+    }
+
+    /// The table and bucket holding the key, if present
+    fn find<Q>(&self, key: &Q) -> Option<(usize, usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+
+        let hash = self.hash(key);
+        self.tables()
+            .iter()
+            .enumerate()
+            .find_map(|(table_index, table)| {
+                let bucket = table.bucket_index(hash);
+                table.buckets[bucket]
+                    .iter()
+                    .position(|(other, _)| other.borrow() == key)
+                    .map(|position| (table_index, bucket, position))
+            })
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (table, bucket, position) = self.find(key)?;
+        Some(&self.tables[table].buckets[bucket][position].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (table, bucket, position) = self.find(key)?;
+        Some(&mut self.tables[table].buckets[bucket][position].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Set the value of a key, returning its previous value if any
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.rehash_step();
+
+        if let Some((table, bucket, position)) = self.find(&key) {
+            let entry = &mut self.tables[table].buckets[bucket][position];
+            return Some(std::mem::replace(&mut entry.1, value));
+        }
+
+        self.expand_if_needed();
+
+        // New entries go to the new table while resizing
+        let hash = self.hash(&key);
+        let table = &mut self.tables[self.rehash_index.map_or(0, |_| 1)];
+        let bucket = table.bucket_index(hash);
+        table.buckets[bucket].push((key, value));
+        table.used += 1;
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.rehash_step();
+
+        let (table, bucket, position) = self.find(key)?;
+        let table = &mut self.tables[table];
+        let entry = table.buckets[bucket].swap_remove(position);
+        table.used -= 1;
+
+        self.shrink_if_needed();
+        Some(entry)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.tables()
+            .iter()
+            .flat_map(|table| table.buckets.iter().flatten())
+            .map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// An entry picked at random. Buckets are picked first, so entries sharing a bucket with
+    /// others are less likely to be picked, as with Redis.
+    pub fn random_entry(&self) -> Option<(&K, &V)> {
+        if self.is_empty() {
+            return None;
+        }
+
+        // The buckets of the first table before the rehashing index are known to be empty
+        let first_bucket = self.rehash_index.unwrap_or(0);
+        let buckets = self.tables[0].buckets.len() + self.tables[1].buckets.len() - first_bucket;
+        let mut rng = rand::rng();
+
+        loop {
+            let index = first_bucket + rng.random_range(0..buckets);
+            let bucket = match self.tables[0].buckets.get(index) {
+                Some(bucket) => bucket,
+                None => &self.tables[1].buckets[index - self.tables[0].buckets.len()],
+            };
+
+            if !bucket.is_empty() {
+                let (key, value) = &bucket[rng.random_range(0..bucket.len())];
+                return Some((key, value));
+            }
+        }
+    }
+
+    /// Visit the entries of the bucket at the cursor, returning the cursor of the next bucket or
+    /// 0 once all the buckets were visited. Every entry present from the first call to the last
+    /// one is visited at least once, entries may be visited several times when the table is
+    /// resized in between.
+    ///
+    /// Cursors are incremented from their most significant bit, so that a bucket and the buckets
+    /// it is split into or merged with by a resize are visited together. While resizing, the
+    /// bucket of the smaller table is visited along with all its counterparts in the larger one.
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&K, &V)) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+
+        let mut cursor = cursor;
+        let mut visit_bucket = |table: &Table<K, V>, cursor: u64| {
+            for (key, value) in &table.buckets[(cursor & table.mask()) as usize] {
+                visit(key, value);
+            }
+        };
+
+        match self.rehash_index {
+            None => {
+                let table = &self.tables[0];
+                visit_bucket(table, cursor);
+                cursor = next_cursor(cursor, table.mask());
+            }
+            Some(_) => {
+                let (small, large) =
+                    match self.tables[0].buckets.len() <= self.tables[1].buckets.len() {
+                        true => (&self.tables[0], &self.tables[1]),
+                        false => (&self.tables[1], &self.tables[0]),
+                    };
+                let (small_mask, large_mask) = (small.mask(), large.mask());
+
+                visit_bucket(small, cursor);
+                // The buckets of the larger table whose index ends with the smaller one's
+                loop {
+                    visit_bucket(large, cursor);
+                    cursor = next_cursor(cursor, large_mask);
+                    if cursor & (small_mask ^ large_mask) == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+
+        cursor
+    }
+
+    /// Start resizing once there are as many entries as buckets
+    fn expand_if_needed(&mut self) {
+        if self.rehash_index.is_some() {
+            return;
+        }
+
+        let size = self.tables[0].buckets.len();
+        if size == 0 {
+            self.tables[0] = Table::with_size(INITIAL_SIZE);
+        } else if self.tables[0].used >= size {
+            self.start_resize(self.tables[0].used + 1);
+        }
+    }
+
+    /// Start resizing once no more than an eighth of the buckets would be used
+    fn shrink_if_needed(&mut self) {
+        if self.rehash_index.is_some() {
+            return;
+        }
+
+        let table = &self.tables[0];
+        if table.buckets.len() > INITIAL_SIZE && table.used * 8 <= table.buckets.len() {
+            self.start_resize(table.used.max(INITIAL_SIZE));
+        }
+    }
+
+    /// Resize to the smallest power of two greater or equal to the number of entries given
+    fn start_resize(&mut self, entries: usize) {
+        let size = entries.next_power_of_two();
+        if size == self.tables[0].buckets.len() {
+            return;
+        }
+
+        self.tables[1] = Table::with_size(size);
+        self.rehash_index = Some(0);
+    }
+
+    /// Move the entries of a bucket of the old table to the new one when resizing
+    fn rehash_step(&mut self) {
+        let mut index = match self.rehash_index {
+            Some(index) => index,
+            None => return,
+        };
+
+        let [old, new] = &mut self.tables;
+        let mut empty_visits = 0;
+        while index < old.buckets.len() && old.buckets[index].is_empty() {
+            index += 1;
+            empty_visits += 1;
+            if empty_visits == EMPTY_VISITS_PER_STEP {
+                self.rehash_index = Some(index);
+                return;
+            }
+        }
+
+        if let Some(bucket) = old.buckets.get_mut(index) {
+            for (key, value) in bucket.drain(..) {
+                let target = new.bucket_index(self.hasher.hash_one(&key));
+                new.buckets[target].push((key, value));
+                old.used -= 1;
+                new.used += 1;
+            }
+            index += 1;
+        }
+
+        // Done once the old table is empty, the new table then takes its place
+        if old.used == 0 {
+            self.tables[0] = std::mem::replace(&mut self.tables[1], Table::empty());
+            self.rehash_index = None;
+        } else {
+            self.rehash_index = Some(index);
+        }
+    }
+}
+
+/// The cursor following one of a table with the mask: its bits under the mask are reversed,
+/// incremented and reversed back
+fn next_cursor(cursor: u64, mask: u64) -> u64 {
+    let cursor = cursor | !mask;
+    cursor.reverse_bits().wrapping_add(1).reverse_bits()
+}
+
+impl<K: Hash + Eq, V: PartialEq> PartialEq for Dict<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for Dict<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tables = match self.rehash_index {
+            Some(_) => &self.tables[..],
+            None => &self.tables[..1],
+        };
+        f.debug_map()
+            .entries(
+                tables
+                    .iter()
+                    .flat_map(|table| table.buckets.iter().flatten())
+                    .map(|(key, value)| (key, value)),
+            )
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for Dict<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        let mut dict = Self::new();
+        dict.extend(entries);
+        dict
+    }
+}
+
+impl<K: Hash + Eq, V> Extend<(K, V)> for Dict<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        for (key, value) in entries {
+            self.insert(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Scan the whole dict, applying the change after each call
+    fn full_scan(dict: &mut Dict<u64, ()>, mut change: impl FnMut(&mut Dict<u64, ()>)) -> Vec<u64> {
+        let mut visited = vec![];
+        let mut cursor = 0;
+
+        loop {
+            cursor = dict.scan(cursor, |key, _| visited.push(*key));
+            if cursor == 0 {
+                return visited;
+            }
+            change(dict);
+        }
+    }
+
+    #[test]
+    fn test_insert_get_and_remove() {
+        let mut dict = Dict::new();
+        for key in 0..1000u64 {
+            assert_eq!(dict.insert(key, key * 2), None);
+        }
+        assert_eq!(dict.insert(7, 0), Some(14));
+        assert_eq!(dict.len(), 1000);
+        assert_eq!(dict.get(&7), Some(&0));
+        assert_eq!(dict.get(&999), Some(&1998));
+        assert_eq!(dict.get(&1000), None);
+
+        for key in 0..990u64 {
+            assert!(dict.remove(&key).is_some());
+        }
+        assert_eq!(dict.remove(&0), None);
+        assert_eq!(dict.len(), 10);
+        assert_eq!(dict.keys().count(), 10);
+        assert!((990..1000).all(|key| dict.contains_key(&key)));
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let mut dict: Dict<Vec<u8>, u8> = Dict::new();
+        dict.insert(b"key".to_vec(), 1);
+
+        assert_eq!(dict.get(b"key".as_slice()), Some(&1));
+        assert_eq!(dict.remove(b"key".as_slice()), Some(1));
+        assert!(dict.is_empty());
+    }
+
+    #[test]
+    fn test_scan_visits_every_key_once_without_resize() {
+        let mut dict: Dict<u64, ()> = (0..100).map(|key| (key, ())).collect();
+        while dict.rehash_index.is_some() {
+            dict.rehash_step();
+        }
+
+        let mut visited = full_scan(&mut dict, |_| {});
+        visited.sort();
+        assert_eq!(visited, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_scan_while_growing_and_shrinking() {
+        let mut dict: Dict<u64, ()> = (0..100).map(|key| (key, ())).collect();
+
+        // Keys present from start to end are visited, whatever happens to the others
+        let mut next = 1000;
+        let visited: HashSet<u64> = full_scan(&mut dict, |dict| {
+            // Growing for good would keep the scan from ever completing
+            for _ in 0..20 {
+                if next < 2000 {
+                    dict.insert(next, ());
+                    next += 1;
+                }
+            }
+        })
+        .into_iter()
+        .collect();
+        assert!((0..100).all(|key| visited.contains(&key)));
+
+        let visited: HashSet<u64> = full_scan(&mut dict, |dict| {
+            let removed: Vec<u64> = dict
+                .keys()
+                .copied()
+                .filter(|key| *key >= 1000)
+                .take(50)
+                .collect();
+            for key in removed {
+                dict.remove(&key);
+            }
+        })
+        .into_iter()
+        .collect();
+        assert!((0..100).all(|key| visited.contains(&key)));
+    }
+
+    #[test]
+    fn test_random_entry() {
+        let mut dict = Dict::new();
+        assert_eq!(dict.random_entry(), None);
+
+        dict.insert(1, "one");
+        dict.insert(2, "two");
+        let mut seen = HashSet::new();
+        for _ in 0..100 {
+            seen.insert(*dict.random_entry().unwrap().0);
+        }
+        assert_eq!(seen, HashSet::from([1, 2]));
+    }
+
+    #[test]
+    fn test_equality_ignores_layout() {
+        let small: Dict<u64, u64> = (0..10).map(|key| (key, key)).collect();
+        let mut large: Dict<u64, u64> = (0..1000).map(|key| (key, key)).collect();
+        for key in 10..1000 {
+            large.remove(&key);
+        }
+
+        assert_eq!(small, large);
+        large.insert(3, 0);
+        assert_ne!(small, large);
+    }
+}
@@ -0,0 +1,458 @@
+//! Geohash encoding of coordinates into sorted set scores, and the geometry of geo searches,
+//! following Redis so that scores, distances and search results are the same.
+//!
+//! Coordinates are encoded as 52 bits interleaving 26 bits of longitude and 26 bits of latitude,
+//! the latitude range being limited to that of the Web Mercator projection.
+
+use std::f64::consts::PI;
+
+/// Precision of the geohash stored as score, in bits per coordinate
+pub const MAX_STEP: u8 = 26;
+
+pub const LONGITUDE_MIN: f64 = -180.0;
+pub const LONGITUDE_MAX: f64 = 180.0;
+pub const LATITUDE_MIN: f64 = -85.05112878;
+pub const LATITUDE_MAX: f64 = 85.05112878;
+
+/// Earth's quadratic mean radius for WGS-84, as used by Redis
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+/// Half of the circumference of the Earth in the Web Mercator projection
+const MERCATOR_MAX: f64 = 20037726.37;
+
+const GEOHASH_ALPHABET: &[u8] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// The range of a coordinate covered by a geohash
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Range {
+    min: f64,
+    max: f64,
+}
+
+const LONGITUDE_RANGE: Range = Range {
+    min: LONGITUDE_MIN,
+    max: LONGITUDE_MAX,
+};
+const LATITUDE_RANGE: Range = Range {
+    min: LATITUDE_MIN,
+    max: LATITUDE_MAX,
+};
+
+/// A geohash of `step` bits per coordinate, latitude bits at even positions and longitude bits at
+/// odd positions
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GeoHash {
+    bits: u64,
+    step: u8,
+}
+
+/// The area covered by a geohash
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Area {
+    longitude: Range,
+    latitude: Range,
+}
+
+/// Spread the 32 bits of the value to the even bits of the result
+fn spread(value: u32) -> u64 {
+    let mut value = value as u64;
+    value = (value | (value << 16)) & 0x0000ffff0000ffff;
+    value = (value | (value << 8)) & 0x00ff00ff00ff00ff;
+    value = (value | (value << 4)) & 0x0f0f0f0f0f0f0f0f;
+    value = (value | (value << 2)) & 0x3333333333333333;
+    (value | (value << 1)) & 0x5555555555555555
+}
+
+/// Gather the even bits of the value, the reverse of [`spread`]
+fn squash(value: u64) -> u32 {
+    let mut value = value & 0x5555555555555555;
+    value = (value | (value >> 1)) & 0x3333333333333333;
+    value = (value | (value >> 2)) & 0x0f0f0f0f0f0f0f0f;
+    value = (value | (value >> 4)) & 0x00ff00ff00ff00ff;
+    value = (value | (value >> 8)) & 0x0000ffff0000ffff;
+    ((value | (value >> 16)) & 0x00000000ffffffff) as u32
+}
+
+/// Whether the coordinates can be indexed
+pub fn is_valid(longitude: f64, latitude: f64) -> bool {
+    (LONGITUDE_MIN..=LONGITUDE_MAX).contains(&longitude)
+        && (LATITUDE_MIN..=LATITUDE_MAX).contains(&latitude)
+}
+
+fn encode_in(
+    longitude_range: Range,
+    latitude_range: Range,
+    longitude: f64,
+    latitude: f64,
+    step: u8,
+) -> Option<GeoHash> {
+    if !is_valid(longitude, latitude)
+        || !(longitude_range.min..=longitude_range.max).contains(&longitude)
+        || !(latitude_range.min..=latitude_range.max).contains(&latitude)
+    {
+        return None;
+    }
+
+    let scale = (1u64 << step) as f64;
+    let latitude_offset =
+        (latitude - latitude_range.min) / (latitude_range.max - latitude_range.min);
+    let longitude_offset =
+        (longitude - longitude_range.min) / (longitude_range.max - longitude_range.min);
+
+    // Offsets are truncated to fixed point, as C does converting them to integers
+    let latitude_bits = (latitude_offset * scale) as u32;
+    let longitude_bits = (longitude_offset * scale) as u32;
+
+    Some(GeoHash {
+        bits: spread(latitude_bits) | (spread(longitude_bits) << 1),
+        step,
+    })
+}
+
+fn encode(longitude: f64, latitude: f64, step: u8) -> Option<GeoHash> {
+    encode_in(LONGITUDE_RANGE, LATITUDE_RANGE, longitude, latitude, step)
+}
+
+impl GeoHash {
+    fn area(self) -> Area {
+        let scale = (1u64 << self.step) as f64;
+        let latitude_bits = squash(self.bits) as f64;
+        let longitude_bits = squash(self.bits >> 1) as f64;
+
+        let latitude_span = LATITUDE_RANGE.max - LATITUDE_RANGE.min;
+        let longitude_span = LONGITUDE_RANGE.max - LONGITUDE_RANGE.min;
+
+        Area {
+            latitude: Range {
+                min: LATITUDE_RANGE.min + (latitude_bits / scale) * latitude_span,
+                max: LATITUDE_RANGE.min + ((latitude_bits + 1.0) / scale) * latitude_span,
+            },
+            longitude: Range {
+                min: LONGITUDE_RANGE.min + (longitude_bits / scale) * longitude_span,
+                max: LONGITUDE_RANGE.min + ((longitude_bits + 1.0) / scale) * longitude_span,
+            },
+        }
+    }
+
+    /// The 52 bits score of the geohash, the area of coarser ones starting at their score
+    fn score(self) -> u64 {
+        self.bits << (52 - self.step as u32 * 2)
+    }
+
+    /// The adjacent geohash in a direction, by one step east or west and north or south
+    fn moved(self, east: i8, north: i8) -> Self {
+        let mut moved = self;
+        moved.move_coordinate(east, 0xaaaaaaaaaaaaaaaa);
+        moved.move_coordinate(north, 0x5555555555555555);
+        moved
+    }
+
+    /// Add or remove one to the coordinate of the bits in the mask, wrapping around
+    fn move_coordinate(&mut self, direction: i8, mask: u64) {
+        if direction == 0 {
+            return;
+        }
+
+        let width = 64 - self.step as u32 * 2;
+        let coordinate = self.bits & mask;
+        let other = self.bits & !mask;
+        // The bits of the other coordinate, set so that carries go through them
+        let filler = !mask >> width;
+
+        let coordinate = match direction > 0 {
+            true => coordinate.wrapping_add(filler + 1),
+            false => (coordinate | filler).wrapping_sub(filler + 1),
+        };
+        self.bits = (coordinate & (mask >> width)) | other;
+    }
+}
+
+/// Decode a score into the coordinates of the center of its area
+pub fn decode(score: f64) -> (f64, f64) {
+    let area = GeoHash {
+        bits: score as u64,
+        step: MAX_STEP,
+    }
+    .area();
+
+    let longitude =
+        ((area.longitude.min + area.longitude.max) / 2.0).clamp(LONGITUDE_MIN, LONGITUDE_MAX);
+    let latitude =
+        ((area.latitude.min + area.latitude.max) / 2.0).clamp(LATITUDE_MIN, LATITUDE_MAX);
+    (longitude, latitude)
+}
+
+/// The score of valid coordinates
+pub fn score(longitude: f64, latitude: f64) -> Option<u64> {
+    encode(longitude, latitude, MAX_STEP).map(GeoHash::score)
+}
+
+/// The standard 11 characters geohash of a score, whose latitude range is -90 to 90
+pub fn geohash_string(score: f64) -> String {
+    let (longitude, latitude) = decode(score);
+    let standard_latitude = Range {
+        min: -90.0,
+        max: 90.0,
+    };
+    let bits = encode_in(
+        LONGITUDE_RANGE,
+        standard_latitude,
+        longitude,
+        latitude,
+        MAX_STEP,
+    )
+    .map_or(0, |hash| hash.bits);
+
+    // 52 bits make 10 characters and a bit, the last character is padded as zero
+    (0..11)
+        .map(|index| {
+            let digit = match index {
+                10 => 0,
+                _ => (bits >> (52 - (index + 1) * 5)) & 0x1f,
+            };
+            GEOHASH_ALPHABET[digit as usize] as char
+        })
+        .collect()
+}
+
+fn to_radians(degrees: f64) -> f64 {
+    degrees * (PI / 180.0)
+}
+
+fn to_degrees(radians: f64) -> f64 {
+    radians / (PI / 180.0)
+}
+
+/// Distance between two latitudes on a meridian, in meters
+fn latitude_distance(latitude1: f64, latitude2: f64) -> f64 {
+    EARTH_RADIUS_IN_METERS * (to_radians(latitude2) - to_radians(latitude1)).abs()
+}
+
+/// Great circle distance between two points, in meters, with the haversine formula
+pub fn distance(longitude1: f64, latitude1: f64, longitude2: f64, latitude2: f64) -> f64 {
+    let v = ((to_radians(longitude2) - to_radians(longitude1)) / 2.0).sin();
+    if v == 0.0 {
+        return latitude_distance(latitude1, latitude2);
+    }
+
+    let (latitude1, latitude2) = (to_radians(latitude1), to_radians(latitude2));
+    let u = ((latitude2 - latitude1) / 2.0).sin();
+    let a = u * u + latitude1.cos() * latitude2.cos() * v * v;
+    2.0 * EARTH_RADIUS_IN_METERS * a.sqrt().asin()
+}
+
+/// The area searched, in meters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Radius(f64),
+    /// Width and height
+    Box(f64, f64),
+}
+
+/// A search around a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Search {
+    pub longitude: f64,
+    pub latitude: f64,
+    pub shape: Shape,
+}
+
+impl Search {
+    /// The distance of the point from the center, if it lies in the shape
+    pub fn distance_if_within(&self, longitude: f64, latitude: f64) -> Option<f64> {
+        match self.shape {
+            Shape::Radius(radius) => {
+                let distance = distance(self.longitude, self.latitude, longitude, latitude);
+                (distance <= radius).then_some(distance)
+            }
+            Shape::Box(width, height) => {
+                // The latitude distance is cheaper, so it is checked first
+                if latitude_distance(latitude, self.latitude) > height / 2.0 {
+                    return None;
+                }
+                if distance(longitude, latitude, self.longitude, latitude) > width / 2.0 {
+                    return None;
+                }
+                Some(distance(self.longitude, self.latitude, longitude, latitude))
+            }
+        }
+    }
+
+    /// The bounding box of the shape: minimum longitude, minimum latitude, maximum longitude and
+    /// maximum latitude
+    fn bounds(&self) -> (f64, f64, f64, f64) {
+        let (width, height) = match self.shape {
+            Shape::Radius(radius) => (radius, radius),
+            Shape::Box(width, height) => (width / 2.0, height / 2.0),
+        };
+
+        let latitude_delta = to_degrees(height / EARTH_RADIUS_IN_METERS);
+        let longitude_delta_top = to_degrees(
+            width / EARTH_RADIUS_IN_METERS / to_radians(self.latitude + latitude_delta).cos(),
+        );
+        let longitude_delta_bottom = to_degrees(
+            width / EARTH_RADIUS_IN_METERS / to_radians(self.latitude - latitude_delta).cos(),
+        );
+
+        // The widest side of the box is toward the equator
+        let longitude_delta = match self.latitude < 0.0 {
+            true => longitude_delta_bottom,
+            false => longitude_delta_top,
+        };
+        (
+            self.longitude - longitude_delta,
+            self.latitude - latitude_delta,
+            self.longitude + longitude_delta,
+            self.latitude + latitude_delta,
+        )
+    }
+
+    /// The number of bits per coordinate of geohashes whose area is larger than the search
+    fn estimate_step(&self) -> u8 {
+        let mut range = match self.shape {
+            Shape::Radius(radius) => radius,
+            Shape::Box(width, height) => ((width / 2.0).powi(2) + (height / 2.0).powi(2)).sqrt(),
+        };
+        if range == 0.0 {
+            return MAX_STEP;
+        }
+
+        let mut step: i32 = 1;
+        while range < MERCATOR_MAX {
+            range *= 2.0;
+            step += 1;
+        }
+        step -= 2;
+
+        // Meridians get closer toward the poles, making areas narrower
+        if self.latitude > 66.0 || self.latitude < -66.0 {
+            step -= 1;
+            if self.latitude > 80.0 || self.latitude < -80.0 {
+                step -= 1;
+            }
+        }
+
+        step.clamp(1, MAX_STEP as i32) as u8
+    }
+
+    /// The areas to look for members in: the area of the center and its neighbours, in the order
+    /// Redis scans them, leaving out the neighbours outside of the bounding box
+    fn areas(&self) -> Vec<GeoHash> {
+        let (min_longitude, min_latitude, max_longitude, max_latitude) = self.bounds();
+        let mut step = self.estimate_step();
+
+        let center = |step| encode(self.longitude, self.latitude, step).unwrap_or_default();
+        let mut hash = center(step);
+
+        // A smaller step is needed when the bounding box reaches past the neighbours
+        let too_small = hash.moved(0, 1).area().latitude.max < max_latitude
+            || hash.moved(0, -1).area().latitude.min > min_latitude
+            || hash.moved(1, 0).area().longitude.max < max_longitude
+            || hash.moved(-1, 0).area().longitude.min > min_longitude;
+        if step > 1 && too_small {
+            step -= 1;
+            hash = center(step);
+        }
+
+        // North, south, east, west, north east, north west, south east and south west
+        let directions = [
+            (0, 1),
+            (0, -1),
+            (1, 0),
+            (-1, 0),
+            (1, 1),
+            (-1, 1),
+            (1, -1),
+            (-1, -1),
+        ];
+        let area = hash.area();
+
+        let mut areas = vec![hash];
+        for (east, north) in directions {
+            let useless = step >= 2
+                && ((north < 0 && area.latitude.min < min_latitude)
+                    || (north > 0 && area.latitude.max > max_latitude)
+                    || (east < 0 && area.longitude.min < min_longitude)
+                    || (east > 0 && area.longitude.max > max_longitude));
+
+            areas.push(match useless {
+                true => GeoHash::default(),
+                false => hash.moved(east, north),
+            });
+        }
+        areas
+    }
+
+    /// The ranges of scores to look for members in, from the first included to the last
+    /// excluded. Huge searches may give the same area for adjacent neighbours, which are only
+    /// scanned once.
+    pub fn score_ranges(&self) -> Vec<(u64, u64)> {
+        let areas = self.areas();
+        let mut ranges = vec![];
+        let mut last_scanned: Option<GeoHash> = None;
+
+        for (index, area) in areas.iter().enumerate() {
+            if *area == GeoHash::default() {
+                continue;
+            }
+            // As in Redis, an area is compared with the last one scanned except the center
+            if index > 0
+                && let Some(last) = last_scanned
+                && last == *area
+            {
+                continue;
+            }
+
+            let next = GeoHash {
+                bits: area.bits + 1,
+                step: area.step,
+            };
+            ranges.push((area.score(), next.score()));
+            last_scanned = Some(*area).filter(|_| index > 0);
+        }
+
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_round_trip() {
+        let score = score(13.361389, 38.115556).unwrap();
+        assert_eq!(score, 3479099956230698);
+
+        let (longitude, latitude) = decode(score as f64);
+        assert!((longitude - 13.361389).abs() < 1e-5);
+        assert!((latitude - 38.115556).abs() < 1e-5);
+
+        assert_eq!(super::score(181.0, 0.0), None);
+        assert_eq!(super::score(0.0, 86.0), None);
+    }
+
+    #[test]
+    fn test_geohash_string() {
+        let palermo = score(13.361389, 38.115556).unwrap();
+        let catania = score(15.087269, 37.502669).unwrap();
+
+        assert_eq!(geohash_string(palermo as f64), "sqc8b49rny0");
+        assert_eq!(geohash_string(catania as f64), "sqdtr74hyu0");
+    }
+
+    #[test]
+    fn test_distance() {
+        let distance = distance(13.361389, 38.115556, 15.087269, 37.502669);
+        assert!((distance - 166274.1516).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_neighbours_wrap_around() {
+        let hash = encode(0.0, 0.0, 2).unwrap();
+        let east = hash.moved(1, 0);
+
+        assert_eq!(east.moved(-1, 0), hash);
+        assert_eq!(hash.moved(0, 1).moved(0, -1), hash);
+        assert!(east.area().longitude.min >= hash.area().longitude.max - 1e-9);
+    }
+}
@@ -0,0 +1,194 @@
+//! Glob-style pattern matching over binary strings, with the syntax of Redis: `*` matches any
+//! sequence, `?` any single byte, `[abc]`, `[a-z]` and `[^a]` sets of bytes, and a backslash
+//! escapes the next byte. It is shared by the commands taking a pattern: KEYS, SCAN MATCH,
+//! PSUBSCRIBE and CONFIG GET.
+
+/// Deepest nesting of stars tried, so that hostile patterns cannot stall the server
+const MAX_NESTING: usize = 1000;
+
+/// Whether the whole string matches the pattern
+pub fn matches(pattern: &[u8], string: &[u8]) -> bool {
+    let mut skip_longer_matches = false;
+
+    matches_from(pattern, string, &mut skip_longer_matches, 0)
+}
+
+/// Match the rest of the pattern against the rest of the string. Once a star fails to match
+/// after consuming the whole string, no star before it can match by consuming more, which
+/// `skip_longer_matches` records to cut the backtracking short.
+fn matches_from(
+    pattern: &[u8],
+    string: &[u8],
+    skip_longer_matches: &mut bool,
+    nesting: usize,
+) -> bool {
+    if nesting > MAX_NESTING {
+        return false;
+    }
+
+    let (mut p, mut s) = (0, 0);
+
+    while p < pattern.len() && s < string.len() {
+        match pattern[p] {
+            b'*' => {
+                while pattern.get(p + 1) == Some(&b'*') {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+
+                for start in s..string.len() {
+                    if matches_from(
+                        &pattern[p + 1..],
+                        &string[start..],
+                        skip_longer_matches,
+                        nesting + 1,
+                    ) {
+                        return true;
+                    }
+                    if *skip_longer_matches {
+                        return false;
+                    }
+                }
+
+                *skip_longer_matches = true;
+                return false;
+            }
+            b'?' => s += 1,
+            b'[' => {
+                p += 1;
+                let negated = pattern.get(p) == Some(&b'^');
+                if negated {
+                    p += 1;
+                }
+
+                let mut matched = false;
+                loop {
+                    match pattern.get(p) {
+                        Some(b'\\') if p + 1 < pattern.len() => {
+                            p += 1;
+                            matched |= pattern[p] == string[s];
+                        }
+                        Some(b']') => break,
+                        // An unterminated set ends with the pattern
+                        None => {
+                            p -= 1;
+                            break;
+                        }
+                        Some(&start) if p + 2 < pattern.len() && pattern[p + 1] == b'-' => {
+                            let end = pattern[p + 2];
+                            matched |= (start.min(end)..=start.max(end)).contains(&string[s]);
+                            p += 2;
+                        }
+                        Some(&byte) => matched |= byte == string[s],
+                    }
+                    p += 1;
+                }
+
+                if matched == negated {
+                    return false;
+                }
+                s += 1;
+            }
+            b'\\' if p + 1 < pattern.len() => {
+                p += 1;
+                if pattern[p] != string[s] {
+                    return false;
+                }
+                s += 1;
+            }
+            byte => {
+                if byte != string[s] {
+                    return false;
+                }
+                s += 1;
+            }
+        }
+        p += 1;
+    }
+
+    // Trailing stars match the end of the string
+    if s == string.len() {
+        while pattern.get(p) == Some(&b'*') {
+            p += 1;
+        }
+    }
+
+    p == pattern.len() && s == string.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn test_literal() {
+        assert!(matches(b"hello", b"hello"));
+        assert!(!matches(b"hello", b"hell"));
+        assert!(!matches(b"hell", b"hello"));
+        assert!(matches(b"", b""));
+        assert!(!matches(b"", b"a"));
+    }
+
+    #[test]
+    fn test_star() {
+        assert!(matches(b"*", b""));
+        assert!(matches(b"*", b"anything"));
+        assert!(matches(b"h*o", b"hello"));
+        assert!(matches(b"h*o", b"ho"));
+        assert!(matches(b"h**o", b"hello"));
+        assert!(matches(b"*llo*", b"hello"));
+        assert!(!matches(b"h*x", b"hello"));
+        assert!(matches(b"a*b*c", b"aXbYbZc"));
+    }
+
+    #[test]
+    fn test_question_mark() {
+        assert!(matches(b"h?llo", b"hello"));
+        assert!(matches(b"h?llo", b"hallo"));
+        assert!(!matches(b"h?llo", b"hllo"));
+        assert!(!matches(b"?", b""));
+    }
+
+    #[test]
+    fn test_sets() {
+        assert!(matches(b"h[ae]llo", b"hello"));
+        assert!(matches(b"h[ae]llo", b"hallo"));
+        assert!(!matches(b"h[ae]llo", b"hillo"));
+        assert!(matches(b"h[^e]llo", b"hallo"));
+        assert!(!matches(b"h[^e]llo", b"hello"));
+        assert!(matches(b"h[a-b]llo", b"hbllo"));
+        assert!(matches(b"h[b-a]llo", b"hallo"));
+        assert!(!matches(b"h[a-b]llo", b"hcllo"));
+        assert!(matches(b"[\\]]", b"]"));
+        assert!(matches(b"[a-", b"a"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert!(matches(b"\\*", b"*"));
+        assert!(!matches(b"\\*", b"a"));
+        assert!(matches(b"a\\?", b"a?"));
+        assert!(!matches(b"a\\?", b"ab"));
+        assert!(matches(b"a\\", b"a\\"));
+    }
+
+    #[test]
+    fn test_binary() {
+        assert!(matches(b"\x00*\xff", b"\x00abc\xff"));
+        assert!(matches(b"[\x80-\xff]", b"\x90"));
+    }
+
+    #[test]
+    fn test_hostile_patterns() {
+        let pattern = b"a*".repeat(50);
+        let string = b"a".repeat(100);
+        assert!(!matches(&[pattern.as_slice(), b"b"].concat(), &string));
+
+        // Stars nested beyond the limit never match
+        let pattern = b"*a".repeat(2000);
+        let string = b"a".repeat(2000);
+        assert!(!matches(&pattern, &string));
+    }
+}
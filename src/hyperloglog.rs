@@ -0,0 +1,667 @@
+//! HyperLogLog cardinality estimation, stored in string values with the same layout as Redis so
+//! that keys are interchangeable with it.
+//!
+//! A value starts with a 16 bytes header: the "HYLL" magic, the encoding, three unused bytes and
+//! the cached cardinality as a little-endian integer, whose most significant bit is set when the
+//! cache is stale. The 16384 registers of 6 bits follow, either packed (dense encoding) or
+//! run-length encoded (sparse encoding) with the opcodes of [`Opcode`].
+
+use std::iter;
+
+/// Number of bits of the hash used to select a register
+const P: u32 = 14;
+/// Number of bits of the hash in which the first set bit is looked for
+const Q: u32 = 64 - P;
+pub const REGISTERS: usize = 1 << P;
+const REGISTER_BITS: usize = 6;
+const REGISTER_MAX: u8 = (1 << REGISTER_BITS) - 1;
+
+pub const HEADER_SIZE: usize = 16;
+pub const DENSE_SIZE: usize = HEADER_SIZE + (REGISTERS * REGISTER_BITS).div_ceil(8);
+const MAGIC: &[u8] = b"HYLL";
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+/// Offset of the most significant byte of the cached cardinality
+const CACHE_MSB: usize = 15;
+
+/// Size past which a sparse value is converted to the dense encoding, as in Redis by default
+pub const SPARSE_MAX_BYTES: usize = 3000;
+const ZERO_MAX_LEN: usize = 64;
+const XZERO_MAX_LEN: usize = 16384;
+const VAL_MAX_VALUE: u8 = 32;
+const VAL_MAX_LEN: usize = 4;
+
+/// Seed of the MurmurHash64A hash of elements, as in Redis
+const HASH_SEED: u64 = 0xadc83b19;
+/// The alpha constant of the estimator for an infinite number of registers, 1 / (2 ln 2)
+const ALPHA_INF: f64 = 0.721_347_520_444_481_7;
+
+/// The value is not a well-formed HyperLogLog
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Corrupted;
+
+/// A run of registers in the sparse encoding
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opcode {
+    /// `00xxxxxx`: 1 to 64 registers set to 0
+    Zero(usize),
+    /// `01xxxxxx yyyyyyyy`: 1 to 16384 registers set to 0
+    XZero(usize),
+    /// `1vvvvvxx`: 1 to 4 registers set to a value from 1 to 32
+    Val(u8, usize),
+}
+
+impl Opcode {
+    /// Decode the opcode at the start of the bytes, returning it along with its size
+    fn decode(bytes: &[u8]) -> (Self, usize) {
+        let byte = bytes[0];
+
+        if byte & 0xc0 == 0 {
+            (Self::Zero((byte & 0x3f) as usize + 1), 1)
+        } else if byte & 0xc0 == 0x40 {
+            let low = bytes.get(1).copied().unwrap_or(0) as usize;
+            (Self::XZero((((byte & 0x3f) as usize) << 8 | low) + 1), 2)
+        } else {
+            (
+                Self::Val(((byte >> 2) & 0x1f) + 1, (byte & 0x03) as usize + 1),
+                1,
+            )
+        }
+    }
+
+    fn encode(self, output: &mut Vec<u8>) {
+        match self {
+            Self::Zero(len) => output.push((len - 1) as u8),
+            Self::XZero(len) => {
+                output.push(((len - 1) >> 8) as u8 | 0x40);
+                output.push(((len - 1) & 0xff) as u8);
+            }
+            Self::Val(value, len) => output.push(((value - 1) << 2 | (len - 1) as u8) | 0x80),
+        }
+    }
+
+    /// Number of registers covered
+    pub fn len(self) -> usize {
+        match self {
+            Self::Zero(len) | Self::XZero(len) | Self::Val(_, len) => len,
+        }
+    }
+
+    /// The shortest opcode for a run of zero registers
+    fn zeros(len: usize) -> Self {
+        match len > ZERO_MAX_LEN {
+            true => Self::XZero(len),
+            false => Self::Zero(len),
+        }
+    }
+}
+
+/// The opcodes of a sparse value
+pub fn opcodes(data: &[u8]) -> impl Iterator<Item = Opcode> + '_ {
+    let mut position = HEADER_SIZE;
+
+    iter::from_fn(move || {
+        if position >= data.len() {
+            return None;
+        }
+        let (opcode, size) = Opcode::decode(&data[position..]);
+        position += size;
+        Some(opcode)
+    })
+}
+
+/// MurmurHash64A, the hash function used by Redis for HyperLogLog elements
+pub fn murmur_hash64a(key: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4a7935bd1e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (key.len() as u64).wrapping_mul(M);
+
+    let mut chunks = key.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().expect("chunks have 8 bytes"));
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+
+    let remainder = chunks.remainder();
+    if !remainder.is_empty() {
+        for (index, byte) in remainder.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * index);
+        }
+        h = h.wrapping_mul(M);
+    }
+
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+/// The register of an element, and the position of the first set bit in the rest of its hash
+fn register_and_count(element: &[u8]) -> (usize, u8) {
+    let hash = murmur_hash64a(element, HASH_SEED);
+    let index = (hash & (REGISTERS as u64 - 1)) as usize;
+
+    // The bit past the Q bits makes the count at most Q + 1
+    let hash = (hash >> P) | (1 << Q);
+    (index, hash.trailing_zeros() as u8 + 1)
+}
+
+/// A new empty value, in the sparse encoding with a valid cached cardinality of 0
+pub fn new() -> Vec<u8> {
+    let mut data = vec![0; HEADER_SIZE];
+    data[..MAGIC.len()].copy_from_slice(MAGIC);
+    data[4] = SPARSE;
+
+    let mut covered = 0;
+    while covered < REGISTERS {
+        let len = (REGISTERS - covered).min(XZERO_MAX_LEN);
+        Opcode::XZero(len).encode(&mut data);
+        covered += len;
+    }
+    data
+}
+
+/// Whether a string holds a HyperLogLog, whose registers may still be corrupted
+pub fn is_hyperloglog(data: &[u8]) -> bool {
+    data.len() >= HEADER_SIZE
+        && data.starts_with(MAGIC)
+        && match data[4] {
+            DENSE => data.len() == DENSE_SIZE,
+            SPARSE => true,
+            _ => false,
+        }
+}
+
+pub fn is_sparse(data: &[u8]) -> bool {
+    data[4] == SPARSE
+}
+
+pub fn cached_count(data: &[u8]) -> Option<u64> {
+    let count = u64::from_le_bytes(data[8..HEADER_SIZE].try_into().expect("8 bytes"));
+    (data[CACHE_MSB] & 0x80 == 0).then_some(count)
+}
+
+pub fn set_cached_count(data: &mut [u8], count: u64) {
+    data[8..HEADER_SIZE].copy_from_slice(&count.to_le_bytes());
+}
+
+pub fn invalidate_cache(data: &mut [u8]) {
+    data[CACHE_MSB] |= 0x80;
+}
+
+/// Read a register of packed registers. The last register is followed by the spare bits of the
+/// last byte, which are always clear.
+pub fn dense_register(registers: &[u8], index: usize) -> u8 {
+    let byte = index * REGISTER_BITS / 8;
+    let shift = index * REGISTER_BITS % 8;
+
+    let low = registers[byte] as u16 >> shift;
+    let high = registers.get(byte + 1).copied().unwrap_or(0) as u16;
+    ((low | high << (8 - shift)) & REGISTER_MAX as u16) as u8
+}
+
+fn set_dense_register(registers: &mut [u8], index: usize, value: u8) {
+    let byte = index * REGISTER_BITS / 8;
+    let shift = index * REGISTER_BITS % 8;
+    let value = value as u16;
+
+    registers[byte] &= !((REGISTER_MAX as u16) << shift) as u8;
+    registers[byte] |= (value << shift) as u8;
+    if let Some(next) = registers.get_mut(byte + 1) {
+        *next &= !(REGISTER_MAX as u16 >> (8 - shift)) as u8;
+        *next |= (value >> (8 - shift)) as u8;
+    }
+}
+
+/// Raise a register of packed registers to the count, returning whether it changed
+fn raise_dense_register(registers: &mut [u8], index: usize, count: u8) -> bool {
+    if dense_register(registers, index) >= count {
+        return false;
+    }
+
+    set_dense_register(registers, index, count);
+    true
+}
+
+/// Convert a sparse value to the dense encoding, keeping its cached cardinality. Returns whether
+/// the value was sparse.
+pub fn to_dense(data: &mut Vec<u8>) -> Result<bool, Corrupted> {
+    if !is_sparse(data) {
+        return Ok(false);
+    }
+
+    let mut dense = vec![0; DENSE_SIZE];
+    dense[..HEADER_SIZE].copy_from_slice(&data[..HEADER_SIZE]);
+    dense[4] = DENSE;
+
+    let registers = &mut dense[HEADER_SIZE..];
+    let mut index = 0;
+    for opcode in opcodes(data) {
+        if let Opcode::Val(value, len) = opcode {
+            if index + len > REGISTERS {
+                return Err(Corrupted);
+            }
+            for register in index..index + len {
+                set_dense_register(registers, register, value);
+            }
+        }
+        index += opcode.len();
+    }
+
+    if index != REGISTERS {
+        return Err(Corrupted);
+    }
+
+    *data = dense;
+    Ok(true)
+}
+
+/// Raise a register of a sparse value to the count, splitting the opcode covering it. The value
+/// is converted to the dense encoding when the count is too large for the sparse encoding, or
+/// when the value would grow past [`SPARSE_MAX_BYTES`].
+fn raise_sparse_register(data: &mut Vec<u8>, index: usize, count: u8) -> Result<bool, Corrupted> {
+    if count > VAL_MAX_VALUE {
+        return promote(data, index, count);
+    }
+
+    // Find the opcode covering the register, and the one before it
+    let mut position = HEADER_SIZE;
+    let mut previous = None;
+    let mut first = 0;
+    let mut current = None;
+    while position < data.len() {
+        let (opcode, size) = Opcode::decode(&data[position..]);
+        if index < first + opcode.len() {
+            current = Some((opcode, size));
+            break;
+        }
+
+        previous = Some(position);
+        position += size;
+        first += opcode.len();
+    }
+    let (opcode, size) = current.ok_or(Corrupted)?;
+    let last = first + opcode.len() - 1;
+
+    match opcode {
+        Opcode::Val(value, _) if value >= count => return Ok(false),
+        // A run of a single register is just replaced
+        Opcode::Val(_, 1) | Opcode::Zero(1) => {
+            let mut replacement = Vec::with_capacity(1);
+            Opcode::Val(count, 1).encode(&mut replacement);
+            data.splice(position..position + 1, replacement);
+        }
+        // Otherwise the run is split around the register, in up to 3 opcodes
+        _ => {
+            let around = |len| match opcode {
+                Opcode::Val(value, _) => Opcode::Val(value, len),
+                _ => Opcode::zeros(len),
+            };
+
+            let mut sequence = Vec::with_capacity(5);
+            if index != first {
+                around(index - first).encode(&mut sequence);
+            }
+            Opcode::Val(count, 1).encode(&mut sequence);
+            if index != last {
+                around(last - index).encode(&mut sequence);
+            }
+
+            if sequence.len() > size && data.len() + sequence.len() - size > SPARSE_MAX_BYTES {
+                return promote(data, index, count);
+            }
+            data.splice(position..position + size, sequence);
+        }
+    }
+
+    merge_adjacent_values(data, previous.unwrap_or(HEADER_SIZE));
+    invalidate_cache(data);
+    Ok(true)
+}
+
+/// Merge the adjacent VAL opcodes of the same value among the few from the position, to keep
+/// the sparse encoding compact after an update
+fn merge_adjacent_values(data: &mut Vec<u8>, mut position: usize) {
+    let mut remaining = 5;
+
+    while position < data.len() && remaining > 0 {
+        remaining -= 1;
+
+        let (opcode, size) = Opcode::decode(&data[position..]);
+        let (value, len) = match opcode {
+            Opcode::Val(value, len) => (value, len),
+            _ => {
+                position += size;
+                continue;
+            }
+        };
+
+        if let Some(&next) = data.get(position + 1)
+            && let (Opcode::Val(next_value, next_len), _) = Opcode::decode(&[next])
+            && next_value == value
+            && len + next_len <= VAL_MAX_LEN
+        {
+            // Retry from the same position, to merge with the following opcode too
+            let mut merged = Vec::with_capacity(1);
+            Opcode::Val(value, len + next_len).encode(&mut merged);
+            data.splice(position..position + 2, merged);
+            continue;
+        }
+
+        position += 1;
+    }
+}
+
+/// Convert a sparse value to the dense encoding to raise one of its registers
+fn promote(data: &mut Vec<u8>, index: usize, count: u8) -> Result<bool, Corrupted> {
+    to_dense(data)?;
+    raise_dense_register(&mut data[HEADER_SIZE..], index, count);
+    invalidate_cache(data);
+    Ok(true)
+}
+
+/// Raise a register to the count, returning whether it changed
+pub fn raise_register(data: &mut Vec<u8>, index: usize, count: u8) -> Result<bool, Corrupted> {
+    if is_sparse(data) {
+        return raise_sparse_register(data, index, count);
+    }
+
+    let changed = raise_dense_register(&mut data[HEADER_SIZE..], index, count);
+    if changed {
+        invalidate_cache(data);
+    }
+    Ok(changed)
+}
+
+/// Add an element, returning whether a register changed
+pub fn add(data: &mut Vec<u8>, element: &[u8]) -> Result<bool, Corrupted> {
+    let (index, count) = register_and_count(element);
+    raise_register(data, index, count)
+}
+
+/// Raise the registers to those of a value, register by register
+pub fn merge_into(registers: &mut [u8; REGISTERS], data: &[u8]) -> Result<(), Corrupted> {
+    if !is_sparse(data) {
+        for (index, register) in registers.iter_mut().enumerate() {
+            *register = (*register).max(dense_register(&data[HEADER_SIZE..], index));
+        }
+        return Ok(());
+    }
+
+    let mut index = 0;
+    for opcode in opcodes(data) {
+        if let Opcode::Val(value, len) = opcode {
+            if index + len > REGISTERS {
+                return Err(Corrupted);
+            }
+            for register in &mut registers[index..index + len] {
+                *register = (*register).max(value);
+            }
+        }
+        index += opcode.len();
+    }
+
+    match index {
+        REGISTERS => Ok(()),
+        _ => Err(Corrupted),
+    }
+}
+
+/// Number of registers holding each value
+type Histogram = [u64; 64];
+
+fn histogram(data: &[u8]) -> Result<Histogram, Corrupted> {
+    let mut histogram = [0; 64];
+
+    if !is_sparse(data) {
+        for index in 0..REGISTERS {
+            histogram[dense_register(&data[HEADER_SIZE..], index) as usize] += 1;
+        }
+        return Ok(histogram);
+    }
+
+    let mut index = 0;
+    for opcode in opcodes(data) {
+        match opcode {
+            Opcode::Zero(len) | Opcode::XZero(len) => histogram[0] += len as u64,
+            Opcode::Val(value, len) => histogram[value as usize] += len as u64,
+        }
+        index += opcode.len();
+    }
+
+    match index {
+        REGISTERS => Ok(histogram),
+        _ => Err(Corrupted),
+    }
+}
+
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if previous == z {
+            return z;
+        }
+    }
+}
+
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if previous == z {
+            return z / 3.0;
+        }
+    }
+}
+
+/// Estimate the cardinality from the histogram of the registers, with the improved estimator of
+/// "New cardinality estimation algorithms for HyperLogLog sketches" (Otmar Ertl) used by Redis
+fn estimate(histogram: &Histogram) -> u64 {
+    let m = REGISTERS as f64;
+
+    let mut z = m * tau((m - histogram[Q as usize + 1] as f64) / m);
+    for count in histogram[1..=Q as usize].iter().rev() {
+        z += *count as f64;
+        z *= 0.5;
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+
+    (ALPHA_INF * m * m / z).round() as u64
+}
+
+/// Estimate the cardinality of a value, ignoring its cache
+pub fn count(data: &[u8]) -> Result<u64, Corrupted> {
+    histogram(data).map(|histogram| estimate(&histogram))
+}
+
+/// Estimate the cardinality of registers merged with [`merge_into`]
+pub fn count_registers(registers: &[u8; REGISTERS]) -> u64 {
+    let mut histogram = [0; 64];
+    for register in registers {
+        histogram[*register as usize] += 1;
+    }
+
+    estimate(&histogram)
+}
+
+/// Check that registers are packed correctly and that the estimates stay within a few standard
+/// errors, for both encodings, as PFSELFTEST does in Redis
+pub fn self_test() -> Result<(), String> {
+    let mut registers = vec![0; DENSE_SIZE - HEADER_SIZE];
+    let mut expected = [0u8; REGISTERS];
+
+    for _ in 0..1000 {
+        for (index, value) in expected.iter_mut().enumerate() {
+            *value = rand::random::<u8>() & REGISTER_MAX;
+            set_dense_register(&mut registers, index, *value);
+        }
+        for (index, value) in expected.iter().enumerate() {
+            let actual = dense_register(&registers, index);
+            if actual != *value {
+                return Err(format!(
+                    "Register {} should be {} but is {}",
+                    index, value, actual
+                ));
+            }
+        }
+    }
+
+    let mut dense = new();
+    to_dense(&mut dense).expect("a new value is well-formed");
+    let mut sparse = new();
+
+    let relative_error = 1.04 / (REGISTERS as f64).sqrt();
+    let seed: u64 = rand::random();
+    let mut checkpoint: u64 = 1;
+
+    for added in 1..=10_000_000u64 {
+        let element = (added ^ seed).to_le_bytes();
+        add(&mut dense, &element).expect("the value is well-formed");
+        add(&mut sparse, &element).expect("the value is well-formed");
+
+        if added == checkpoint {
+            if checkpoint < SPARSE_MAX_BYTES as u64 / 2 && !is_sparse(&sparse) {
+                return Err(String::from("sparse encoding not used"));
+            }
+
+            let estimate = count(&dense).expect("the value is well-formed");
+            if count(&sparse) != Ok(estimate) {
+                return Err(String::from("dense/sparse disagree"));
+            }
+
+            // Collisions make a larger error likely from time to time at 10 elements
+            let max_error = match checkpoint {
+                10 => 1,
+                _ => (relative_error * 6.0 * checkpoint as f64).ceil() as u64,
+            };
+            let error = checkpoint.abs_diff(estimate);
+            if error > max_error {
+                return Err(format!(
+                    "Too big error. card:{} abserr:{}",
+                    checkpoint, error
+                ));
+            }
+
+            checkpoint *= 10;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_murmur_hash64a() {
+        assert_eq!(murmur_hash64a(b"", 0), 0);
+
+        // Computed with the reference C implementation of MurmurHash64A
+        let known = [
+            (&b""[..], 0xd8dfea6585bc9732),
+            (b"a", 0x53d2470a9b43b1a7),
+            (b"foo", 0xe64609b8b0141cb4),
+            (b"hello", 0x0f656f01eecfe400),
+            (b"abcdefgh", 0xf3a65df559914567),
+            (b"abcdefghi", 0x834fba4d9152daf7),
+            (b"hello world!", 0x0fc444011f57220c),
+        ];
+        for (key, hash) in known {
+            assert_eq!(murmur_hash64a(key, HASH_SEED), hash, "{:?}", key);
+        }
+
+        // The hash depends on every byte, including those past the last whole word
+        assert_ne!(
+            murmur_hash64a(b"abcdefghi", HASH_SEED),
+            murmur_hash64a(b"abcdefghj", HASH_SEED)
+        );
+    }
+
+    #[test]
+    fn test_new_value() {
+        let data = new();
+
+        assert_eq!(data.len(), HEADER_SIZE + 2);
+        assert_eq!(&data[HEADER_SIZE..], &[0x7f, 0xff]);
+        assert!(is_hyperloglog(&data));
+        assert_eq!(cached_count(&data), Some(0));
+        assert_eq!(count(&data), Ok(0));
+    }
+
+    #[test]
+    fn test_sparse_split_and_merge() {
+        let mut data = new();
+
+        assert_eq!(raise_register(&mut data, 100, 3), Ok(true));
+        assert_eq!(
+            opcodes(&data).collect::<Vec<_>>(),
+            vec![
+                Opcode::XZero(100),
+                Opcode::Val(3, 1),
+                Opcode::XZero(REGISTERS - 101)
+            ]
+        );
+        assert_eq!(cached_count(&data), None);
+
+        // Adjacent registers of the same value share an opcode
+        assert_eq!(raise_register(&mut data, 101, 3), Ok(true));
+        assert_eq!(raise_register(&mut data, 101, 2), Ok(false));
+        assert_eq!(
+            opcodes(&data).collect::<Vec<_>>(),
+            vec![
+                Opcode::XZero(100),
+                Opcode::Val(3, 2),
+                Opcode::XZero(REGISTERS - 102)
+            ]
+        );
+
+        let mut dense = data.clone();
+        assert_eq!(to_dense(&mut dense), Ok(true));
+        assert_eq!(dense.len(), DENSE_SIZE);
+        assert_eq!(dense_register(&dense[HEADER_SIZE..], 101), 3);
+        assert_eq!(count(&dense), count(&data));
+    }
+
+    #[test]
+    fn test_large_counts_promote_to_dense() {
+        let mut data = new();
+
+        assert_eq!(raise_register(&mut data, REGISTERS - 1, 33), Ok(true));
+        assert!(!is_sparse(&data));
+        assert_eq!(dense_register(&data[HEADER_SIZE..], REGISTERS - 1), 33);
+    }
+
+    #[test]
+    fn test_corrupted_sparse_value() {
+        let mut data = new();
+        data.truncate(HEADER_SIZE);
+        Opcode::XZero(100).encode(&mut data);
+
+        assert_eq!(count(&data), Err(Corrupted));
+        assert_eq!(to_dense(&mut data), Err(Corrupted));
+    }
+}
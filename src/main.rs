@@ -1,20 +1,58 @@
+mod blocking;
+mod client;
+mod commands;
+mod dict;
+mod geohash;
+mod glob;
+mod hyperloglog;
+mod pubsub;
 mod resp;
 mod resp_result;
+mod resp_writer;
+mod server;
+mod server_result;
+mod sorted_set;
+mod storage;
+mod stream;
 
+use crate::blocking::{Blocked, unblock_client};
+use crate::client::Client;
 use crate::resp::RESP;
+use crate::server::process_buffer;
+use crate::server_result::ServerResult;
+use crate::storage::{DATABASES, Storage, current_time_ms, lock};
+use bytes::BytesMut;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream},
+    sync::mpsc,
 };
 
+/// Address and port the server listens on
+pub const BIND_ADDRESS: &str = "127.0.0.1";
+pub const PORT: u16 = 6379;
+/// How many times per second background tasks, like reclaiming expired keys, run
+pub const HZ: u64 = 10;
+
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+/// How often keys whose time to live has elapsed are reclaimed in the background
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(1000 / HZ);
+/// Most keys reclaimed while holding the storage lock, so that clients are not stalled
+const ACTIVE_EXPIRE_BATCH_SIZE: usize = 1000;
+
 #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    let listener = TcpListener::bind("127.0.0.1:6379").await?; // defines the function's return type
+    let listener = TcpListener::bind((BIND_ADDRESS, PORT)).await?; // defines the function's return type
+    let storage = Arc::new(Mutex::new(Storage::new()));
+
+    tokio::spawn(active_expire(storage.clone()));
 
     loop {
         match listener.accept().await {
             Ok((stream, _)) => {
-                tokio::spawn(handle_connection(stream)); // spawn a new task for each new connection 
+                tokio::spawn(handle_connection(stream, storage.clone())); // spawn a new task for each new connection 
             }
             Err(e) => {
                 println!("Error: {}", e);
@@ -24,28 +62,141 @@ async fn main() -> std::io::Result<()> {
     }
 }
 
-async fn handle_connection(mut stream: TcpStream) {
+/// Reclaim expired keys that are never accessed again, which lazy expiry alone would keep forever
+async fn active_expire(storage: Arc<Mutex<Storage>>) {
+    let mut interval = tokio::time::interval(ACTIVE_EXPIRE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        for db in 0..DATABASES {
+            loop {
+                let removed = lock(&storage).dbs[db]
+                    .remove_expired(current_time_ms(), ACTIVE_EXPIRE_BATCH_SIZE);
+
+                // Release the lock between batches to let clients run
+                if removed < ACTIVE_EXPIRE_BATCH_SIZE {
+                    break;
+                }
+                tokio::task::yield_now().await;
+            }
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, storage: Arc<Mutex<Storage>>) {
     println!("Incoming connection from: {}", stream.peer_addr().unwrap());
 
-    let mut buffer: [u8; 512] = [0; 512]; // u8 used to represent one Byte
+    let (sender, mut messages) = mpsc::unbounded_channel();
+    let mut client = Client::new();
+    client.messages = Some(sender);
+
+    serve_connection(&mut stream, &storage, &mut client, &mut messages).await;
+
+    // Messages published from now on have nowhere to go
+    lock(&storage).pubsub.remove_client(client.id);
+}
+
+/// Execute the requests of a client and send it the messages published to its subscriptions,
+/// until the connection is closed
+async fn serve_connection(
+    stream: &mut TcpStream,
+    storage: &Mutex<Storage>,
+    client: &mut Client,
+    messages: &mut mpsc::UnboundedReceiver<RESP>,
+) {
+    let mut buffer = BytesMut::with_capacity(READ_BUFFER_SIZE);
+    let mut output: Vec<u8> = Vec::with_capacity(READ_BUFFER_SIZE);
 
     loop {
-        match stream.read(&mut buffer).await {
-            Ok(size) if size != 0 => {
-                let response = RESP::SimpleString(String::from("PONG"));
+        buffer.reserve(READ_BUFFER_SIZE);
 
-                if let Err(e) = stream.write_all(response.to_string().as_bytes()).await {
+        tokio::select! {
+            read = stream.read_buf(&mut buffer) => match read {
+                Ok(size) if size != 0 => loop {
+                    // Replies to all the requests received so far are sent back with a single write
+                    let result = process_buffer(client, storage, &mut buffer, &mut output);
+
+                    if let Err(e) = stream.write_all(&output).await {
+                        eprintln!("Error writing to socket: {}", e);
+                        return;
+                    }
+                    output.clear();
+
+                    match result {
+                        Ok(Some(blocked)) => {
+                            // The requests pipelined after a blocking one wait for its reply
+                            match wait_until_served(stream, &mut buffer, storage, blocked).await {
+                                Some(response) => {
+                                    let response = response
+                                        .unwrap_or_else(|error| RESP::SimpleError(error.to_string()));
+                                    response.encode_into(&mut output, client.protocol);
+                                }
+                                None => return,
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(_) => return,
+                    }
+                },
+                Ok(_) => {
+                    println!("Connection closed: {}", stream.peer_addr().unwrap());
+                    return;
+                }
+                Err(error) => {
+                    println!("Error when reading from stream: {}", error);
+                    return;
+                }
+            },
+            Some(message) = messages.recv() => {
+                // Messages published meanwhile are sent along with a single write
+                message.encode_into(&mut output, client.protocol);
+                while let Ok(message) = messages.try_recv() {
+                    message.encode_into(&mut output, client.protocol);
+                }
+
+                if let Err(e) = stream.write_all(&output).await {
                     eprintln!("Error writing to socket: {}", e);
+                    return;
                 }
+                output.clear();
             }
-            Ok(_) => {
-                println!("Connection closed: {}", stream.peer_addr().unwrap());
-                break;
+        }
+    }
+}
+
+/// Wait until a blocked client is served or its timeout elapses. Requests received meanwhile are
+/// kept in the buffer. `None` is returned if the connection is closed while waiting.
+async fn wait_until_served(
+    stream: &mut TcpStream,
+    buffer: &mut BytesMut,
+    storage: &Mutex<Storage>,
+    mut blocked: Blocked,
+) -> Option<ServerResult<RESP>> {
+    let timeout = tokio::time::sleep(blocked.timeout.unwrap_or(Duration::MAX));
+    tokio::pin!(timeout);
+
+    loop {
+        buffer.reserve(READ_BUFFER_SIZE);
+
+        tokio::select! {
+            response = &mut blocked.receiver => {
+                return Some(response.unwrap_or(Ok(RESP::NullArray)));
             }
-            Err(error) => {
-                println!("Error when reading from stream: {}", error);
-                break;
+            _ = &mut timeout, if blocked.timeout.is_some() => break,
+            read = stream.read_buf(buffer) => {
+                if !matches!(read, Ok(size) if size != 0) {
+                    unblock_client(&mut lock(storage), blocked.client_id);
+                    return None;
+                }
             }
         }
     }
+
+    if unblock_client(&mut lock(storage), blocked.client_id) {
+        return Some(Ok(RESP::NullArray));
+    }
+
+    // Served between the timeout and the lock being acquired
+    Some(blocked.receiver.try_recv().unwrap_or(Ok(RESP::NullArray)))
 }
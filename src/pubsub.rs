@@ -0,0 +1,172 @@
+use crate::client::Client;
+use crate::glob;
+use crate::resp::RESP;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+
+/// Where the messages published to a client are sent, for its connection to write them out
+pub type MessageSender = mpsc::UnboundedSender<RESP>;
+
+/// Whether a subscription is to a channel by name or to the channels matching a pattern
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subscription {
+    Channel,
+    Pattern,
+}
+
+/// The channels and patterns a client is subscribed to
+#[derive(Debug)]
+struct Subscriber {
+    /// `None` for clients without a connection, which count as receivers all the same
+    sender: Option<MessageSender>,
+    channels: HashSet<Vec<u8>>,
+    patterns: HashSet<Vec<u8>>,
+}
+
+impl Subscriber {
+    fn names(&mut self, subscription: Subscription) -> &mut HashSet<Vec<u8>> {
+        match subscription {
+            Subscription::Channel => &mut self.channels,
+            Subscription::Pattern => &mut self.patterns,
+        }
+    }
+}
+
+/// The subscriptions of all the clients, by client and by channel or pattern
+#[derive(Debug, Default)]
+pub struct PubSub {
+    subscribers: HashMap<u64, Subscriber>,
+    /// Identifiers of the clients subscribed to each channel, in the order they subscribed
+    channels: HashMap<Vec<u8>, Vec<u64>>,
+    /// Identifiers of the clients subscribed to each pattern, in the order they subscribed
+    patterns: HashMap<Vec<u8>, Vec<u64>>,
+}
+
+impl PubSub {
+    fn clients(&mut self, subscription: Subscription) -> &mut HashMap<Vec<u8>, Vec<u64>> {
+        match subscription {
+            Subscription::Channel => &mut self.channels,
+            Subscription::Pattern => &mut self.patterns,
+        }
+    }
+
+    /// Number of channels and patterns the client is subscribed to
+    pub fn subscription_count(&self, client_id: u64) -> usize {
+        self.subscribers.get(&client_id).map_or(0, |subscriber| {
+            subscriber.channels.len() + subscriber.patterns.len()
+        })
+    }
+
+    /// The channels or the patterns the client is subscribed to
+    pub fn subscriptions(&self, client_id: u64, subscription: Subscription) -> Vec<Vec<u8>> {
+        let subscriber = match self.subscribers.get(&client_id) {
+            Some(subscriber) => subscriber,
+            None => return vec![],
+        };
+
+        let names = match subscription {
+            Subscription::Channel => &subscriber.channels,
+            Subscription::Pattern => &subscriber.patterns,
+        };
+        names.iter().cloned().collect()
+    }
+
+    /// Subscribe a client to a channel or a pattern, returning false if it already is
+    pub fn subscribe(&mut self, client: &Client, subscription: Subscription, name: &[u8]) -> bool {
+        let subscriber = self
+            .subscribers
+            .entry(client.id)
+            .or_insert_with(|| Subscriber {
+                sender: client.messages.clone(),
+                channels: HashSet::new(),
+                patterns: HashSet::new(),
+            });
+        if !subscriber.names(subscription).insert(name.to_vec()) {
+            return false;
+        }
+
+        self.clients(subscription)
+            .entry(name.to_vec())
+            .or_default()
+            .push(client.id);
+        true
+    }
+
+    /// Unsubscribe a client from a channel or a pattern, returning false if it was not subscribed
+    pub fn unsubscribe(&mut self, client_id: u64, subscription: Subscription, name: &[u8]) -> bool {
+        let subscriber = match self.subscribers.get_mut(&client_id) {
+            Some(subscriber) => subscriber,
+            None => return false,
+        };
+        if !subscriber.names(subscription).remove(name) {
+            return false;
+        }
+        if subscriber.channels.is_empty() && subscriber.patterns.is_empty() {
+            self.subscribers.remove(&client_id);
+        }
+
+        let clients = self.clients(subscription);
+        if let Some(ids) = clients.get_mut(name) {
+            ids.retain(|&id| id != client_id);
+            if ids.is_empty() {
+                clients.remove(name);
+            }
+        }
+        true
+    }
+
+    /// Drop every subscription of a client whose connection is closed
+    pub fn remove_client(&mut self, client_id: u64) {
+        for subscription in [Subscription::Channel, Subscription::Pattern] {
+            for name in self.subscriptions(client_id, subscription) {
+                self.unsubscribe(client_id, subscription, &name);
+            }
+        }
+    }
+
+    fn send(&self, client_id: u64, message: RESP) {
+        // Clients whose connection went away are removed once it is closed
+        if let Some(sender) = self
+            .subscribers
+            .get(&client_id)
+            .and_then(|subscriber| subscriber.sender.as_ref())
+        {
+            let _ = sender.send(message);
+        }
+    }
+
+    /// Send a message to the clients subscribed to the channel and to the patterns matching it,
+    /// returning how many received it. Clients matching several times receive it several times.
+    pub fn publish(&self, channel: &[u8], message: &[u8]) -> usize {
+        let bulk = |data: &[u8]| RESP::BulkString(data.to_vec());
+        let mut receivers = 0;
+
+        for &client_id in self.channels.get(channel).into_iter().flatten() {
+            self.send(
+                client_id,
+                RESP::Push(vec![bulk(b"message"), bulk(channel), bulk(message)]),
+            );
+            receivers += 1;
+        }
+
+        for (pattern, clients) in &self.patterns {
+            if !glob::matches(pattern, channel) {
+                continue;
+            }
+            for &client_id in clients {
+                self.send(
+                    client_id,
+                    RESP::Push(vec![
+                        bulk(b"pmessage"),
+                        bulk(pattern),
+                        bulk(channel),
+                        bulk(message),
+                    ]),
+                );
+                receivers += 1;
+            }
+        }
+
+        receivers
+    }
+}
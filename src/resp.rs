@@ -1,53 +1,94 @@
-use crate::resp_result::RESPError::OutOfBounds;
 use crate::resp_result::{RESPError, RESPLength, RESPResult};
+use crate::resp_writer::RESPWriter;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
+/// Protocol version negotiated by a client through HELLO
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ProtocolVersion {
+    #[default]
+    RESP2,
+    RESP3,
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq)]
 pub enum RESP {
     Null,
+    /// Absent array, which RESP2 encodes differently from an absent bulk string
+    NullArray,
     SimpleString(String),
-    BulkString(String),
+    SimpleError(String),
+    Integer(i64),
+    BulkString(Vec<u8>),
     Array(Vec<RESP>),
+    Map(Vec<(RESP, RESP)>),
+    Set(Vec<RESP>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    VerbatimString(String, String),
+    BlobError(String),
+    Push(Vec<RESP>),
+    /// Attributes together with the value they annotate
+    Attribute(Vec<(RESP, RESP)>, Box<RESP>),
+}
+
+impl RESP {
+    /// Serialise the value at the end of `output` for a client speaking the given protocol
+    /// version. RESP3 types are downgraded to their closest RESP2 equivalent when needed.
+    pub fn encode_into(&self, output: &mut Vec<u8>, protocol: ProtocolVersion) {
+        RESPWriter::new(output, protocol).value(self);
+    }
+
+    /// Serialise the value into a new buffer, see `encode_into`
+    pub fn encode(&self, protocol: ProtocolVersion) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.encode_into(&mut output, protocol);
+        output
+    }
 }
 
 impl Display for RESP {
-    // Display is preferred (compared to Into<String>) when the output string is to be viewed on a Screen
+    // Display is preferred (compared to Into<String>) when the output string is to be viewed on a Screen.
+    // It is meant for debugging, replies are sent to clients through `encode_into`.
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let data = match self {
-            Self::Null => String::from("$-1\r\n"),
-            Self::SimpleString(data) => format!("+{}\r\n", data),
-            Self::BulkString(data) => format!("${}\r\n{}\r\n", data.len(), data),
-            Self::Array(data) => {
-                let mut output = String::from("*");
-                output.push_str(format!("{}\r\n", data.len()).as_str());
-
-                for element in data.iter() {
-                    output.push_str(element.to_string().as_str());
-                }
-
-                output
-            }
-        };
-        write!(f, "{}", data)
+        let data = self.encode(ProtocolVersion::default());
+        write!(f, "{}", String::from_utf8_lossy(&data))
     }
 }
 
+type ParseFunction = fn(&[u8], &mut usize) -> RESPResult<RESP>;
+
+/// Largest payload accepted for a single bulk string, as in Redis (512MB)
+pub const MAX_BULK_LENGTH: RESPLength = 512 * 1024 * 1024;
+
 /// Return one of the parsing functions according to the initial character of the buffer
-fn parse_router(
-    buffer: &[u8],
-    index: &mut usize,
-) -> Option<fn(&[u8], &mut usize) -> RESPResult<RESP>> {
+fn parse_router(buffer: &[u8], index: &mut usize) -> Option<ParseFunction> {
     match buffer[*index] {
         b'+' => Some(parse_simple_string),
+        b'-' => Some(parse_simple_error),
+        b':' => Some(parse_integer),
         b'$' => Some(parse_bulk_string),
         b'*' => Some(parse_array),
+        b'%' => Some(parse_map),
+        b'~' => Some(parse_set),
+        b',' => Some(parse_double),
+        b'#' => Some(parse_boolean),
+        b'(' => Some(parse_big_number),
+        b'=' => Some(parse_verbatim_string),
+        b'_' => Some(parse_null),
+        b'!' => Some(parse_blob_error),
+        b'>' => Some(parse_push),
+        b'|' => Some(parse_attribute),
         _ => None,
     }
 }
 
-fn parse_array(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
-    resp_remove_type('*', buffer, index)?;
+/// Parse the length header of an aggregate type and then as many elements as it announces
+fn parse_aggregate(prefix: char, buffer: &[u8], index: &mut usize) -> RESPResult<Vec<RESP>> {
+    resp_remove_type(prefix, buffer, index)?;
 
     let length = resp_extract_length(buffer, index)?;
 
@@ -58,28 +99,327 @@ fn parse_array(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
     let mut data = Vec::new();
 
     for _ in 0..length {
-        match parse_router(buffer, index) {
-            Some(parse_function) => {
-                let array_element = parse_function(buffer, index)?;
-                data.push(array_element);
-            }
-            None => return Err(RESPError::Unknown),
-        }
+        data.push(parse_value(buffer, index)?);
     }
 
-    Ok(RESP::Array(data))
+    Ok(data)
+}
+
+/// Parse the length header of a map-like type and then as many key-value pairs as it announces
+fn parse_pairs(prefix: char, buffer: &[u8], index: &mut usize) -> RESPResult<Vec<(RESP, RESP)>> {
+    resp_remove_type(prefix, buffer, index)?;
+
+    let length = resp_extract_length(buffer, index)?;
+
+    if length < 0 {
+        return Err(RESPError::IncorrectLength(length));
+    }
+
+    let mut data = Vec::new();
+
+    for _ in 0..length {
+        let key = parse_value(buffer, index)?;
+        let value = parse_value(buffer, index)?;
+        data.push((key, value));
+    }
+
+    Ok(data)
+}
+
+fn parse_array(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    Ok(RESP::Array(parse_aggregate('*', buffer, index)?))
+}
+
+fn parse_set(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    Ok(RESP::Set(parse_aggregate('~', buffer, index)?))
+}
+
+fn parse_push(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    Ok(RESP::Push(parse_aggregate('>', buffer, index)?))
+}
+
+fn parse_map(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    Ok(RESP::Map(parse_pairs('%', buffer, index)?))
+}
+
+fn parse_attribute(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    let attributes = parse_pairs('|', buffer, index)?;
+    let data = parse_value(buffer, index)?;
+    Ok(RESP::Attribute(attributes, Box::new(data)))
 }
 
+/// Parse one complete value starting at `index`.
+/// Returns `RESPError::Incomplete` if the buffer ends before the value does, in which case
+/// the caller should read more data and try again from the same starting index.
+/// Data that does not start with a RESP type prefix is parsed as an inline command,
+/// i.e. a line of space-separated arguments as typed in telnet.
 pub fn bytes_to_resp(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    if *index >= buffer.len() {
+        return Err(RESPError::Incomplete);
+    }
+
     match parse_router(buffer, index) {
         Some(parse_function) => {
             let result = parse_function(buffer, index)?;
             Ok(result)
         }
+        None => parse_inline(buffer, index),
+    }
+}
+
+/// Parse a value nested into another one, where inline commands are not allowed
+fn parse_value(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    if *index >= buffer.len() {
+        return Err(RESPError::Incomplete);
+    }
+
+    match parse_router(buffer, index) {
+        Some(parse_function) => parse_function(buffer, index),
         None => Err(RESPError::Unknown),
     }
 }
 
+/// Longest inline command accepted while waiting for its newline, as in Redis (64KB)
+const MAX_INLINE_LENGTH: usize = 64 * 1024;
+
+/// Parse an inline command terminated by \n or \r\n into an array of bulk strings
+fn parse_inline(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    let line_end = match buffer[*index..].iter().position(|&c| c == b'\n') {
+        Some(position) => *index + position,
+        None if buffer.len() - *index > MAX_INLINE_LENGTH => {
+            return Err(RESPError::InlineTooBig);
+        }
+        None => return Err(RESPError::Incomplete),
+    };
+
+    let mut line = &buffer[*index..line_end];
+    if let Some(stripped) = line.strip_suffix(b"\r") {
+        line = stripped;
+    }
+
+    let args = split_inline_args(line)?;
+    *index = line_end + 1;
+
+    Ok(RESP::Array(
+        args.into_iter().map(RESP::BulkString).collect(),
+    ))
+}
+
+/// Split a line into arguments separated by whitespace, the way `redis-cli` does.
+/// Arguments can be wrapped in double quotes, which support escapes like `\n` or `\x41`,
+/// or in single quotes, which only support `\'`.
+fn split_inline_args(line: &[u8]) -> RESPResult<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut position = 0;
+
+    loop {
+        while position < line.len() && line[position].is_ascii_whitespace() {
+            position += 1;
+        }
+
+        if position == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = Vec::new();
+        let mut in_double_quotes = false;
+        let mut in_single_quotes = false;
+
+        loop {
+            let current = line.get(position).copied();
+
+            if in_double_quotes {
+                match (current, line.get(position + 1).copied()) {
+                    (None, _) => return Err(RESPError::UnbalancedQuotes),
+                    (Some(b'\\'), Some(b'x'))
+                        if position + 3 < line.len()
+                            && line[position + 2].is_ascii_hexdigit()
+                            && line[position + 3].is_ascii_hexdigit() =>
+                    {
+                        let hex = std::str::from_utf8(&line[position + 2..position + 4])?;
+                        arg.push(u8::from_str_radix(hex, 16)?);
+                        position += 3;
+                    }
+                    (Some(b'\\'), Some(escaped)) => {
+                        arg.push(match escaped {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                        position += 1;
+                    }
+                    (Some(b'"'), next) => {
+                        // The closing quote must be followed by a space or by the end of the line
+                        if next.is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return Err(RESPError::UnbalancedQuotes);
+                        }
+                        position += 1;
+                        break;
+                    }
+                    (Some(c), _) => arg.push(c),
+                }
+            } else if in_single_quotes {
+                match (current, line.get(position + 1).copied()) {
+                    (None, _) => return Err(RESPError::UnbalancedQuotes),
+                    (Some(b'\\'), Some(b'\'')) => {
+                        arg.push(b'\'');
+                        position += 1;
+                    }
+                    (Some(b'\''), next) => {
+                        if next.is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return Err(RESPError::UnbalancedQuotes);
+                        }
+                        position += 1;
+                        break;
+                    }
+                    (Some(c), _) => arg.push(c),
+                }
+            } else {
+                match current {
+                    None => break,
+                    Some(c) if c.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double_quotes = true,
+                    Some(b'\'') => in_single_quotes = true,
+                    Some(c) => arg.push(c),
+                }
+            }
+
+            position += 1;
+        }
+
+        args.push(arg);
+    }
+}
+
+/// A request frame whose bulk strings borrow their payload from the buffer they were parsed
+/// from, so that values are not copied before a command decides what to do with them
+#[derive(Debug, PartialEq)]
+pub enum RESPFrame<'a> {
+    BulkString(&'a [u8]),
+    Array(Vec<RESPFrame<'a>>),
+    /// Any other type, which is parsed into an owned value as it carries no large payload
+    Value(RESP),
+}
+
+/// Most arguments accepted in a request, as in Redis
+const MAX_MULTIBULK_LENGTH: i64 = i32::MAX as i64;
+
+/// Progress made parsing a request array that is not complete yet. It is kept between reads so
+/// that parsing resumes where it stopped, rather than starting over with every read of a large
+/// pipelined request.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PartialRequest {
+    /// Number of arguments announced by the header, which is parsed once `index` is not zero
+    length: usize,
+    /// Position and length in the buffer of the arguments parsed so far
+    arguments: Vec<(usize, usize)>,
+    /// Where parsing resumes
+    index: usize,
+}
+
+/// Parse one complete value starting at `index` like `bytes_to_resp`, without copying bulk strings
+#[cfg(test)]
+pub fn bytes_to_frame<'a>(buffer: &'a [u8], index: &mut usize) -> RESPResult<RESPFrame<'a>> {
+    bytes_to_frame_resuming(buffer, index, &mut PartialRequest::default())
+}
+
+/// Like `bytes_to_frame`, resuming the parsing of a request array from the progress made by the
+/// previous calls on the same buffer. The progress is reset once the request is complete.
+pub fn bytes_to_frame_resuming<'a>(
+    buffer: &'a [u8],
+    index: &mut usize,
+    partial: &mut PartialRequest,
+) -> RESPResult<RESPFrame<'a>> {
+    if *index >= buffer.len() {
+        return Err(RESPError::Incomplete);
+    }
+
+    match buffer[*index] {
+        b'$' => parse_bulk_string_frame(buffer, index),
+        b'*' => {
+            let result = parse_array_frame(buffer, index, partial);
+            if !matches!(result, Err(RESPError::Incomplete)) {
+                *partial = PartialRequest::default();
+            }
+            result
+        }
+        // Requests are never nested, which also spares parsing hostile payloads recursively
+        prefix @ (b'%' | b'~' | b'>' | b'|') => Err(RESPError::UnexpectedType(b'*', prefix)),
+        _ => Ok(RESPFrame::Value(bytes_to_resp(buffer, index)?)),
+    }
+}
+
+fn parse_bulk_string_frame<'a>(buffer: &'a [u8], index: &mut usize) -> RESPResult<RESPFrame<'a>> {
+    resp_remove_type('$', buffer, index)?;
+    let length = resp_extract_length(buffer, index)?;
+
+    if length == -1 {
+        return Ok(RESPFrame::Value(RESP::Null));
+    }
+
+    if !(-1..=MAX_BULK_LENGTH).contains(&length) {
+        return Err(RESPError::IncorrectLength(length));
+    }
+
+    let data = binary_extract_bytes(buffer, index, length as usize)?;
+    binary_remove_terminator(buffer, index)?;
+
+    Ok(RESPFrame::BulkString(data))
+}
+
+fn parse_array_frame<'a>(
+    buffer: &'a [u8],
+    index: &mut usize,
+    partial: &mut PartialRequest,
+) -> RESPResult<RESPFrame<'a>> {
+    if partial.index == 0 {
+        resp_remove_type('*', buffer, index)?;
+        let line = resp_extract_header(buffer, index)?;
+
+        partial.length = std::str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.parse::<i64>().ok())
+            .filter(|length| (0..=MAX_MULTIBULK_LENGTH).contains(length))
+            .ok_or(RESPError::InvalidMultibulkLength)? as usize;
+        partial.index = *index;
+    }
+    *index = partial.index;
+
+    // Like Redis, the arguments of a request can only be bulk strings
+    while partial.arguments.len() < partial.length {
+        match buffer.get(*index) {
+            None => return Err(RESPError::Incomplete),
+            Some(b'$') => {}
+            Some(&prefix) => return Err(RESPError::UnexpectedType(b'$', prefix)),
+        }
+
+        resp_remove_type('$', buffer, index)?;
+        let line = resp_extract_header(buffer, index)?;
+        let length = std::str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.parse::<i64>().ok())
+            .filter(|length| (0..=MAX_BULK_LENGTH as i64).contains(length))
+            .ok_or(RESPError::InvalidBulkLength)? as usize;
+
+        binary_extract_bytes(buffer, index, length)?;
+        binary_remove_terminator(buffer, index)?;
+
+        partial.arguments.push((*index - length - 2, length));
+        partial.index = *index;
+    }
+
+    Ok(RESPFrame::Array(
+        partial
+            .arguments
+            .iter()
+            .map(|&(start, length)| RESPFrame::BulkString(&buffer[start..start + length]))
+            .collect(),
+    ))
+}
+
 fn parse_simple_string(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
     resp_remove_type('+', buffer, index)?;
 
@@ -87,14 +427,107 @@ fn parse_simple_string(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
     Ok(RESP::SimpleString(line))
 }
 
+fn parse_double(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    resp_remove_type(',', buffer, index)?;
+
+    let line = binary_extract_line_as_string(buffer, index)?;
+    let value = line.parse::<f64>()?;
+    Ok(RESP::Double(value))
+}
+
+fn parse_boolean(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    resp_remove_type('#', buffer, index)?;
+
+    let line = binary_extract_line(buffer, index)?;
+    match line {
+        b"t" => Ok(RESP::Boolean(true)),
+        b"f" => Ok(RESP::Boolean(false)),
+        _ => Err(RESPError::ParseBool),
+    }
+}
+
+fn parse_big_number(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    resp_remove_type('(', buffer, index)?;
+
+    let line = binary_extract_line_as_string(buffer, index)?;
+    let digits = line.strip_prefix('-').unwrap_or(&line);
+
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(RESPError::ParseInt);
+    }
+
+    Ok(RESP::BigNumber(line))
+}
+
+fn parse_null(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    resp_remove_type('_', buffer, index)?;
+
+    let line = binary_extract_line(buffer, index)?;
+    if !line.is_empty() {
+        return Err(RESPError::Unknown);
+    }
+
+    Ok(RESP::Null)
+}
+
+/// Extract the payload of a length-prefixed type such as bulk strings or blob errors
+fn resp_extract_blob(buffer: &[u8], index: &mut usize) -> RESPResult<String> {
+    let length = resp_extract_length(buffer, index)?;
+
+    if !(0..=MAX_BULK_LENGTH).contains(&length) {
+        return Err(RESPError::IncorrectLength(length));
+    }
+
+    let bytes = binary_extract_bytes(buffer, index, length as usize)?;
+    binary_remove_terminator(buffer, index)?;
+
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+fn parse_blob_error(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    resp_remove_type('!', buffer, index)?;
+    Ok(RESP::BlobError(resp_extract_blob(buffer, index)?))
+}
+
+fn parse_verbatim_string(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    resp_remove_type('=', buffer, index)?;
+    let data = resp_extract_blob(buffer, index)?;
+
+    // The payload starts with a three characters encoding followed by a colon, e.g. "txt:"
+    match data.split_at_checked(3) {
+        Some((encoding, rest)) if rest.starts_with(':') => Ok(RESP::VerbatimString(
+            String::from(encoding),
+            String::from(&rest[1..]),
+        )),
+        _ => Err(RESPError::IncorrectLength(data.len() as RESPLength)),
+    }
+}
+
+fn parse_simple_error(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    resp_remove_type('-', buffer, index)?;
+
+    let line = binary_extract_line_as_string(buffer, index)?;
+    Ok(RESP::SimpleError(line))
+}
+
+fn parse_integer(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
+    resp_remove_type(':', buffer, index)?;
+
+    let line = binary_extract_line_as_string(buffer, index)?;
+    let value = line.parse::<i64>()?;
+    Ok(RESP::Integer(value))
+}
+
 pub fn binary_extract_line_as_string(buffer: &[u8], index: &mut usize) -> RESPResult<String> {
     let line = binary_extract_line(buffer, index)?;
-    Ok(String::from_utf8(line)?)
+    Ok(String::from_utf8(line.to_vec())?)
 }
 
 /// Check first character of buffer is the expected one and remove it
 pub fn resp_remove_type(value: char, buffer: &[u8], index: &mut usize) -> RESPResult<()> {
-    if buffer[*index] != value as u8 {
+    if *index >= buffer.len() {
+        Err(RESPError::Incomplete)
+    } else if buffer[*index] != value as u8 {
         Err(RESPError::WrongType)
     } else {
         *index += 1;
@@ -110,48 +543,73 @@ fn parse_bulk_string(buffer: &[u8], index: &mut usize) -> RESPResult<RESP> {
         return Ok(RESP::Null);
     }
 
-    if length < -1 {
+    if !(-1..=MAX_BULK_LENGTH).contains(&length) {
         return Err(RESPError::IncorrectLength(length));
     }
 
-    let bytes = binary_extract_bytes(buffer, index, length as usize)?;
-    let data: String = String::from_utf8(bytes)?;
+    let data = binary_extract_bytes(buffer, index, length as usize)?;
+    binary_remove_terminator(buffer, index)?;
+
+    Ok(RESP::BulkString(data.to_vec()))
+}
+
+/// Check that the buffer continues with \r\n and skip it
+fn binary_remove_terminator(buffer: &[u8], index: &mut usize) -> RESPResult<()> {
+    if *index + 2 > buffer.len() {
+        return Err(RESPError::Incomplete);
+    }
+
+    if &buffer[*index..*index + 2] != b"\r\n" {
+        return Err(RESPError::MissingTerminator(*index));
+    }
 
-    *index += 2; // Increment the index to skip the \r\n
-    Ok(RESP::BulkString(data))
+    *index += 2;
+    Ok(())
 }
 
-fn binary_extract_bytes(buffer: &[u8], index: &mut usize, length: usize) -> RESPResult<Vec<u8>> {
+fn binary_extract_bytes<'a>(
+    buffer: &'a [u8],
+    index: &mut usize,
+    length: usize,
+) -> RESPResult<&'a [u8]> {
     if *index + length > buffer.len() {
-        return Err(RESPError::OutOfBounds(*index + buffer.len()));
+        return Err(RESPError::Incomplete);
     }
 
-    let extraction = (&buffer[*index..(*index + length)]).to_vec();
+    let extraction = &buffer[*index..(*index + length)];
 
     *index += length; // update the index
     Ok(extraction)
 }
 
 pub fn resp_extract_length(buffer: &[u8], index: &mut usize) -> RESPResult<RESPLength> {
-    let line = binary_extract_line_as_string(buffer, index)?;
-    let length = line.parse::<i32>()?;
+    let line = resp_extract_header(buffer, index)?;
+    let length = std::str::from_utf8(line)?.parse::<i32>()?;
     Ok(length)
 }
 
-fn binary_extract_line(buffer: &[u8], index: &mut usize) -> RESPResult<Vec<u8>> {
-    // pretty low level buffer handling
+/// Extract the line holding the length of an aggregate or a bulk string. Like inline commands,
+/// it cannot grow past 64KB while waiting for its terminator.
+fn resp_extract_header<'a>(buffer: &'a [u8], index: &mut usize) -> RESPResult<&'a [u8]> {
+    let end = buffer.len().min(*index + MAX_INLINE_LENGTH + 2);
 
-    if *index >= buffer.len() {
-        return Err(OutOfBounds(*index));
+    match binary_extract_line(&buffer[..end], index) {
+        Err(RESPError::Incomplete) if end - *index > MAX_INLINE_LENGTH => {
+            Err(RESPError::HeaderTooBig)
+        }
+        result => result,
     }
+}
+
+fn binary_extract_line<'a>(buffer: &'a [u8], index: &mut usize) -> RESPResult<&'a [u8]> {
+    // pretty low level buffer handling
 
-    // If there is not enough space for 2 byte characters (i.e.  \r\n) the buffer is definitely invalid
-    if buffer.len() - *index - 1 < 2 {
-        *index = buffer.len();
-        return Err(OutOfBounds(*index));
+    // If there is not enough space for 2 byte characters (i.e.  \r\n) the line cannot be complete yet
+    if *index >= buffer.len() || buffer.len() - *index < 2 {
+        return Err(RESPError::Incomplete);
     }
 
-    let mut previous_elem = buffer[*index].clone();
+    let mut previous_elem = buffer[*index];
     let mut separator_found = false;
     let mut final_index = *index;
 
@@ -162,15 +620,14 @@ fn binary_extract_line(buffer: &[u8], index: &mut usize) -> RESPResult<Vec<u8>>
             separator_found = true;
             break;
         }
-        previous_elem = element.clone();
+        previous_elem = element;
     }
 
     if !separator_found {
-        *index = final_index;
-        return Err(OutOfBounds(*index));
+        return Err(RESPError::Incomplete);
     }
 
-    let extraction = (&buffer[*index..final_index - 2]).to_vec();
+    let extraction = &buffer[*index..final_index - 2];
     *index = final_index; // Make sure the index is updated with the latest position
     Ok(extraction)
 }
@@ -185,78 +642,60 @@ mod tests {
     fn test_binary_extract_line_empty_buffer() {
         let buffer = "".as_bytes();
         let mut index: usize = 0;
+        let error = binary_extract_line(buffer, &mut index).unwrap_err();
 
-        match binary_extract_line(buffer, &mut index) {
-            Err(OutOfBounds(index)) => {
-                assert_eq!(index, 0);
-            }
-            _ => panic!(),
-        }
+        assert_eq!(error, RESPError::Incomplete);
+        assert_eq!(index, 0);
     }
 
     #[test]
     fn test_binary_extract_line_single_character() {
         let buffer = "O".as_bytes();
         let mut index: usize = 0;
+        let error = binary_extract_line(buffer, &mut index).unwrap_err();
 
-        match binary_extract_line(buffer, &mut index) {
-            Err(OutOfBounds(index)) => {
-                assert_eq!(index, 1);
-            }
-            _ => panic!(),
-        }
+        assert_eq!(error, RESPError::Incomplete);
+        assert_eq!(index, 0);
     }
 
     #[test]
     fn test_binary_extract_line_index_too_advanced() {
         let buffer = "OK".as_bytes();
         let mut index: usize = 1;
+        let error = binary_extract_line(buffer, &mut index).unwrap_err();
 
-        match binary_extract_line(buffer, &mut index) {
-            Err(OutOfBounds(index)) => {
-                assert_eq!(index, 2);
-            }
-            _ => panic!(),
-        }
+        assert_eq!(error, RESPError::Incomplete);
+        assert_eq!(index, 1);
     }
 
     #[test]
     fn test_binary_extract_line_no_separator() {
         let buffer = "OK".as_bytes();
         let mut index: usize = 0;
+        let error = binary_extract_line(buffer, &mut index).unwrap_err();
 
-        match binary_extract_line(buffer, &mut index) {
-            Err(OutOfBounds(index)) => {
-                assert_eq!(index, 2);
-            }
-            _ => panic!(),
-        }
+        assert_eq!(error, RESPError::Incomplete);
+        assert_eq!(index, 0);
     }
 
     #[test]
     fn test_binary_extract_line_half_separator() {
         let buffer = "OK\r".as_bytes();
         let mut index: usize = 0;
+        let error = binary_extract_line(buffer, &mut index).unwrap_err();
 
-        match binary_extract_line(buffer, &mut index) {
-            Err(OutOfBounds(index)) => {
-                assert_eq!(index, 3);
-            }
-            _ => panic!(),
-        }
+        assert_eq!(error, RESPError::Incomplete);
+        assert_eq!(index, 0);
     }
 
     #[test]
     fn test_binary_extract_line_incorrect_separator() {
         let buffer = "OK\n".as_bytes();
         let mut index: usize = 0;
+        let error = binary_extract_line(buffer, &mut index).unwrap_err();
 
-        match binary_extract_line(buffer, &mut index) {
-            Err(OutOfBounds(index)) => {
-                assert_eq!(index, 3);
-            }
-            _ => panic!(),
-        }
+        assert_eq!(error, RESPError::Incomplete);
+        assert_eq!(index, 0);
     }
 
     #[test]
@@ -269,6 +708,16 @@ mod tests {
         assert_eq!(index, 4);
     }
 
+    #[test]
+    fn test_binary_extract_line_empty_line() {
+        let buffer = "\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = binary_extract_line(buffer, &mut index).unwrap();
+
+        assert_eq!(output, "".as_bytes());
+        assert_eq!(index, 2);
+    }
+
     #[test]
     fn test_binary_extract_line_as_string() {
         let buffer = "OK\r\n".as_bytes();
@@ -308,6 +757,70 @@ mod tests {
         assert_eq!(index, 5);
     }
 
+    #[test]
+    fn test_parse_simple_error() {
+        let buffer = "-ERR something went wrong\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = parse_simple_error(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESP::SimpleError(String::from("ERR something went wrong"))
+        );
+        assert_eq!(index, 27);
+    }
+
+    #[test]
+    fn test_parse_integer() {
+        let buffer = ":1000\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = parse_integer(buffer, &mut index).unwrap();
+
+        assert_eq!(output, RESP::Integer(1000));
+        assert_eq!(index, 7);
+    }
+
+    #[test]
+    fn test_parse_integer_negative() {
+        let buffer = ":-42\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = parse_integer(buffer, &mut index).unwrap();
+
+        assert_eq!(output, RESP::Integer(-42));
+        assert_eq!(index, 6);
+    }
+
+    #[test]
+    fn test_parse_integer_unparsable() {
+        let buffer = ":12a\r\n".as_bytes();
+        let mut index: usize = 0;
+        let error = parse_integer(buffer, &mut index).unwrap_err();
+
+        assert_eq!(error, RESPError::ParseInt);
+        assert_eq!(index, 6);
+    }
+
+    #[test]
+    fn test_bytes_to_resp_integer() {
+        let buffer = ":7\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(output, RESP::Integer(7));
+        assert_eq!(index, 4);
+    }
+
+    #[test]
+    fn test_display_simple_error() {
+        let value = RESP::SimpleError(String::from("ERR unknown command"));
+        assert_eq!(value.to_string(), "-ERR unknown command\r\n");
+    }
+
+    #[test]
+    fn test_display_integer() {
+        assert_eq!(RESP::Integer(-3).to_string(), ":-3\r\n");
+    }
+
     #[test]
     fn test_bytes_to_resp_simple_string() {
         let buffer = "+OK\r\n".as_bytes();
@@ -318,22 +831,103 @@ mod tests {
         assert_eq!(index, 5);
     }
     #[test]
-    fn test_bytes_to_resp_unknown() {
-        let buffer = "?OK\r\n".as_bytes();
+    fn test_bytes_to_resp_unknown_nested_type() {
+        let buffer = "*1\r\n?OK\r\n".as_bytes();
         let mut index: usize = 0;
         let error = bytes_to_resp(buffer, &mut index).unwrap_err();
 
         assert_eq!(error, RESPError::Unknown);
+        assert_eq!(index, 4);
+    }
+
+    #[test]
+    fn test_bytes_to_resp_inline() {
+        let buffer = "SET foo  bar\r\nPING\n".as_bytes();
+        let mut index: usize = 0;
+
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+        assert_eq!(
+            output,
+            RESP::Array(vec![
+                RESP::BulkString(b"SET".to_vec()),
+                RESP::BulkString(b"foo".to_vec()),
+                RESP::BulkString(b"bar".to_vec()),
+            ])
+        );
+        assert_eq!(index, 14);
+
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+        assert_eq!(
+            output,
+            RESP::Array(vec![RESP::BulkString(b"PING".to_vec())])
+        );
+        assert_eq!(index, buffer.len());
+    }
+
+    #[test]
+    fn test_bytes_to_resp_inline_incomplete() {
+        let buffer = "SET foo bar".as_bytes();
+        let mut index: usize = 0;
+        let error = bytes_to_resp(buffer, &mut index).unwrap_err();
+
+        assert_eq!(error, RESPError::Incomplete);
         assert_eq!(index, 0);
     }
 
+    #[test]
+    fn test_bytes_to_resp_inline_too_big() {
+        let buffer = vec![b'a'; MAX_INLINE_LENGTH + 1];
+        let mut index: usize = 0;
+        let error = bytes_to_resp(&buffer, &mut index).unwrap_err();
+
+        assert_eq!(error, RESPError::InlineTooBig);
+    }
+
+    #[test]
+    fn test_bytes_to_resp_inline_empty_line() {
+        let buffer = "  \r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(output, RESP::Array(vec![]));
+        assert_eq!(index, 4);
+    }
+
+    #[test]
+    fn test_split_inline_args_quotes() {
+        let output = split_inline_args(br#"set "hello world" 'it\'s' "a\x41\n\"" """#).unwrap();
+
+        assert_eq!(
+            output,
+            vec![
+                b"set".to_vec(),
+                b"hello world".to_vec(),
+                b"it's".to_vec(),
+                b"aA\n\"".to_vec(),
+                b"".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_inline_args_unbalanced_quotes() {
+        let error = split_inline_args(br#"set "hello"#).unwrap_err();
+        assert_eq!(error, RESPError::UnbalancedQuotes);
+
+        let error = split_inline_args(br#"set "hello"world"#).unwrap_err();
+        assert_eq!(error, RESPError::UnbalancedQuotes);
+
+        let error = split_inline_args(br#"set 'hello"#).unwrap_err();
+        assert_eq!(error, RESPError::UnbalancedQuotes);
+    }
+
     #[test]
     fn test_binary_extract_bytes() {
         let buffer = "SOMEBYTES".as_bytes();
         let mut index: usize = 0;
         let output = binary_extract_bytes(buffer, &mut index, 6).unwrap();
 
-        assert_eq!(output, "SOMEBY".as_bytes().to_vec());
+        assert_eq!(output, "SOMEBY".as_bytes());
         assert_eq!(index, 6);
     }
 
@@ -343,7 +937,7 @@ mod tests {
         let mut index: usize = 0;
         let error = binary_extract_bytes(buffer, &mut index, 10).unwrap_err();
 
-        assert_eq!(error, RESPError::OutOfBounds(9));
+        assert_eq!(error, RESPError::Incomplete);
         assert_eq!(index, 0);
     }
 
@@ -352,10 +946,49 @@ mod tests {
         let buffer = "$2\r\nOK\r\n".as_bytes();
         let mut index: usize = 0;
         let output = parse_bulk_string(buffer, &mut index).unwrap();
-        assert_eq!(output, RESP::BulkString(String::from("OK")));
+        assert_eq!(output, RESP::BulkString(b"OK".to_vec()));
         assert_eq!(index, 8);
     }
 
+    #[test]
+    fn test_parse_bulk_string_binary() {
+        let buffer = b"$4\r\n\x00\xff\r\n\r\n";
+        let mut index: usize = 0;
+        let output = parse_bulk_string(buffer, &mut index).unwrap();
+        assert_eq!(output, RESP::BulkString(vec![0x00, 0xff, b'\r', b'\n']));
+        assert_eq!(index, 10);
+    }
+
+    #[test]
+    fn test_encode_bulk_string_binary() {
+        let value = RESP::BulkString(vec![0x00, 0xff, b'\r', b'\n']);
+        assert_eq!(
+            value.encode(ProtocolVersion::RESP2),
+            b"$4\r\n\x00\xff\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_bulk_string_missing_terminator() {
+        let buffer = "$2\r\nOK\r".as_bytes();
+        let mut index: usize = 0;
+        let error = parse_bulk_string(buffer, &mut index).unwrap_err();
+        assert_eq!(error, RESPError::Incomplete);
+
+        let buffer = "$2\r\nOKAY\r\n".as_bytes();
+        let mut index: usize = 0;
+        let error = parse_bulk_string(buffer, &mut index).unwrap_err();
+        assert_eq!(error, RESPError::MissingTerminator(6));
+    }
+
+    #[test]
+    fn test_parse_bulk_string_too_long() {
+        let buffer = "$536870913\r\n".as_bytes();
+        let mut index: usize = 0;
+        let error = parse_bulk_string(buffer, &mut index).unwrap_err();
+        assert_eq!(error, RESPError::IncorrectLength(536870913));
+    }
+
     #[test]
     fn test_parse_bulk_string_empty() {
         let buffer = "$-1\r\n".as_bytes();
@@ -397,7 +1030,7 @@ mod tests {
         let buffer = "$7\r\nOK\r\n".as_bytes();
         let mut index: usize = 0;
         let error = parse_bulk_string(buffer, &mut index).unwrap_err();
-        assert_eq!(error, RESPError::OutOfBounds(12));
+        assert_eq!(error, RESPError::Incomplete);
         assert_eq!(index, 4);
     }
 
@@ -406,7 +1039,7 @@ mod tests {
         let buffer = "$2\r\nOK\r\n".as_bytes();
         let mut index: usize = 0;
         let output = bytes_to_resp(buffer, &mut index).unwrap();
-        assert_eq!(output, RESP::BulkString(String::from("OK")));
+        assert_eq!(output, RESP::BulkString(b"OK".to_vec()));
         assert_eq!(index, 8);
     }
 
@@ -420,7 +1053,7 @@ mod tests {
             output,
             RESP::Array(vec![
                 RESP::SimpleString(String::from("OK")),
-                RESP::BulkString(String::from("VALUE"))
+                RESP::BulkString(b"VALUE".to_vec())
             ])
         );
         assert_eq!(index, 20);
@@ -435,6 +1068,34 @@ mod tests {
         assert_eq!(index, 5);
     }
 
+    #[test]
+    fn test_bytes_to_resp_incomplete_array() {
+        let buffer = "*2\r\n$3\r\nGET\r\n$3\r\nke".as_bytes();
+
+        for end in 0..buffer.len() {
+            let mut index: usize = 0;
+            let error = bytes_to_resp(&buffer[..end], &mut index).unwrap_err();
+            assert_eq!(error, RESPError::Incomplete);
+        }
+    }
+
+    #[test]
+    fn test_bytes_to_resp_consecutive_frames() {
+        let buffer = "+FIRST\r\n:2\r\n+THI".as_bytes();
+        let mut index: usize = 0;
+
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+        assert_eq!(output, RESP::SimpleString(String::from("FIRST")));
+        assert_eq!(index, 8);
+
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+        assert_eq!(output, RESP::Integer(2));
+        assert_eq!(index, 12);
+
+        let error = bytes_to_resp(buffer, &mut index).unwrap_err();
+        assert_eq!(error, RESPError::Incomplete);
+    }
+
     #[test]
     fn test_bytes_to_resp_array() {
         let buffer = "*2\r\n+OK\r\n$5\r\nVALUE\r\n".as_bytes();
@@ -445,9 +1106,336 @@ mod tests {
             output,
             RESP::Array(vec![
                 RESP::SimpleString(String::from("OK")),
-                RESP::BulkString(String::from("VALUE"))
+                RESP::BulkString(b"VALUE".to_vec())
             ])
         );
         assert_eq!(index, 20);
     }
+
+    #[test]
+    fn test_bytes_to_resp_map() {
+        let buffer = "%2\r\n+first\r\n:1\r\n+second\r\n:2\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESP::Map(vec![
+                (RESP::SimpleString(String::from("first")), RESP::Integer(1)),
+                (RESP::SimpleString(String::from("second")), RESP::Integer(2)),
+            ])
+        );
+        assert_eq!(index, buffer.len());
+    }
+
+    #[test]
+    fn test_bytes_to_resp_set() {
+        let buffer = "~2\r\n+a\r\n+b\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESP::Set(vec![
+                RESP::SimpleString(String::from("a")),
+                RESP::SimpleString(String::from("b"))
+            ])
+        );
+        assert_eq!(index, buffer.len());
+    }
+
+    #[test]
+    fn test_bytes_to_resp_double() {
+        let buffer = ",1.23\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+        assert_eq!(output, RESP::Double(1.23));
+
+        let buffer = ",-inf\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+        assert_eq!(output, RESP::Double(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn test_bytes_to_resp_double_unparsable() {
+        let buffer = ",1.2.3\r\n".as_bytes();
+        let mut index: usize = 0;
+        let error = bytes_to_resp(buffer, &mut index).unwrap_err();
+        assert_eq!(error, RESPError::ParseFloat);
+    }
+
+    #[test]
+    fn test_bytes_to_resp_boolean() {
+        let mut index: usize = 0;
+        let output = bytes_to_resp("#t\r\n".as_bytes(), &mut index).unwrap();
+        assert_eq!(output, RESP::Boolean(true));
+
+        let mut index: usize = 0;
+        let output = bytes_to_resp("#f\r\n".as_bytes(), &mut index).unwrap();
+        assert_eq!(output, RESP::Boolean(false));
+
+        let mut index: usize = 0;
+        let error = bytes_to_resp("#x\r\n".as_bytes(), &mut index).unwrap_err();
+        assert_eq!(error, RESPError::ParseBool);
+    }
+
+    #[test]
+    fn test_bytes_to_resp_big_number() {
+        let buffer = "(-3492890328409238509324850943850943825024385\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESP::BigNumber(String::from("-3492890328409238509324850943850943825024385"))
+        );
+
+        let mut index: usize = 0;
+        let error = bytes_to_resp("(12a\r\n".as_bytes(), &mut index).unwrap_err();
+        assert_eq!(error, RESPError::ParseInt);
+    }
+
+    #[test]
+    fn test_bytes_to_resp_verbatim_string() {
+        let buffer = "=15\r\ntxt:Some string\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESP::VerbatimString(String::from("txt"), String::from("Some string"))
+        );
+        assert_eq!(index, buffer.len());
+    }
+
+    #[test]
+    fn test_bytes_to_resp_verbatim_string_without_encoding() {
+        let buffer = "=2\r\nab\r\n".as_bytes();
+        let mut index: usize = 0;
+        let error = bytes_to_resp(buffer, &mut index).unwrap_err();
+        assert_eq!(error, RESPError::IncorrectLength(2));
+    }
+
+    #[test]
+    fn test_bytes_to_resp_null() {
+        let buffer = "_\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(output, RESP::Null);
+        assert_eq!(index, 3);
+    }
+
+    #[test]
+    fn test_bytes_to_resp_blob_error() {
+        let buffer = "!21\r\nSYNTAX invalid syntax\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESP::BlobError(String::from("SYNTAX invalid syntax"))
+        );
+        assert_eq!(index, buffer.len());
+    }
+
+    #[test]
+    fn test_bytes_to_resp_push() {
+        let buffer = ">2\r\n+message\r\n:1\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESP::Push(vec![
+                RESP::SimpleString(String::from("message")),
+                RESP::Integer(1)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_bytes_to_resp_attribute() {
+        let buffer = "|1\r\n+ttl\r\n:3600\r\n:42\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_resp(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESP::Attribute(
+                vec![(RESP::SimpleString(String::from("ttl")), RESP::Integer(3600))],
+                Box::new(RESP::Integer(42))
+            )
+        );
+        assert_eq!(index, buffer.len());
+    }
+
+    #[test]
+    fn test_encode_resp3_types() {
+        let protocol = ProtocolVersion::RESP3;
+
+        assert_eq!(RESP::Null.encode(protocol), b"_\r\n");
+        assert_eq!(RESP::Double(1.5).encode(protocol), b",1.5\r\n");
+        assert_eq!(RESP::Double(f64::INFINITY).encode(protocol), b",inf\r\n");
+        assert_eq!(RESP::Boolean(true).encode(protocol), b"#t\r\n");
+        assert_eq!(
+            RESP::BigNumber(String::from("12345678901234567890")).encode(protocol),
+            b"(12345678901234567890\r\n"
+        );
+        assert_eq!(
+            RESP::VerbatimString(String::from("txt"), String::from("hi")).encode(protocol),
+            b"=6\r\ntxt:hi\r\n"
+        );
+        assert_eq!(
+            RESP::Map(vec![(RESP::BulkString(b"a".to_vec()), RESP::Integer(1))]).encode(protocol),
+            b"%1\r\n$1\r\na\r\n:1\r\n"
+        );
+        assert_eq!(
+            RESP::Set(vec![RESP::Integer(1)]).encode(protocol),
+            b"~1\r\n:1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_encode_resp2_downgrades_resp3_types() {
+        let protocol = ProtocolVersion::RESP2;
+
+        assert_eq!(RESP::Null.encode(protocol), b"$-1\r\n");
+        assert_eq!(RESP::Double(1.5).encode(protocol), b"$3\r\n1.5\r\n");
+        assert_eq!(RESP::Boolean(true).encode(protocol), b":1\r\n");
+        assert_eq!(
+            RESP::Map(vec![(RESP::BulkString(b"a".to_vec()), RESP::Integer(1))]).encode(protocol),
+            b"*2\r\n$1\r\na\r\n:1\r\n"
+        );
+        assert_eq!(
+            RESP::Set(vec![RESP::Integer(1)]).encode(protocol),
+            b"*1\r\n:1\r\n"
+        );
+        assert_eq!(
+            RESP::Attribute(vec![], Box::new(RESP::Integer(1))).encode(protocol),
+            b":1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_bytes_to_frame_borrows_bulk_strings() {
+        let buffer = "*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n".as_bytes();
+        let mut index: usize = 0;
+        let output = bytes_to_frame(buffer, &mut index).unwrap();
+
+        assert_eq!(
+            output,
+            RESPFrame::Array(vec![
+                RESPFrame::BulkString(b"SET"),
+                RESPFrame::BulkString(b"key"),
+                RESPFrame::BulkString(b"value"),
+            ])
+        );
+        assert_eq!(index, buffer.len());
+
+        match output {
+            RESPFrame::Array(elements) => match elements[2] {
+                RESPFrame::BulkString(data) => {
+                    assert_eq!(data.as_ptr(), buffer[index - 7..].as_ptr())
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_bytes_to_frame_other_types() {
+        let buffer = "*2\r\n$1\r\na\r\n:1\r\n".as_bytes();
+        let mut index: usize = 0;
+        let error = bytes_to_frame(buffer, &mut index).unwrap_err();
+        assert_eq!(error, RESPError::UnexpectedType(b'$', b':'));
+
+        let buffer = "~1\r\n$4\r\nPING\r\n".as_bytes();
+        let mut index: usize = 0;
+        let error = bytes_to_frame(buffer, &mut index).unwrap_err();
+        assert_eq!(error, RESPError::UnexpectedType(b'*', b'~'));
+    }
+
+    #[test]
+    fn test_bytes_to_frame_deeply_nested() {
+        let buffer = "*1\r\n".repeat(200_000);
+        let mut index: usize = 0;
+        let error = bytes_to_frame(buffer.as_bytes(), &mut index).unwrap_err();
+
+        assert_eq!(error, RESPError::UnexpectedType(b'$', b'*'));
+        assert_eq!(error.to_string(), "expected '$', got '*'");
+    }
+
+    #[test]
+    fn test_bytes_to_frame_incomplete() {
+        let buffer = "*2\r\n$3\r\nGET\r\n$3\r\nke".as_bytes();
+
+        for end in 0..buffer.len() {
+            let mut index: usize = 0;
+            let error = bytes_to_frame(&buffer[..end], &mut index).unwrap_err();
+            assert_eq!(error, RESPError::Incomplete);
+        }
+    }
+
+    #[test]
+    fn test_bytes_to_frame_resumes_partial_requests() {
+        let buffer = "*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n".as_bytes();
+        let mut partial = PartialRequest::default();
+
+        // Stop in the middle of the last argument
+        let mut index: usize = 0;
+        let error = bytes_to_frame_resuming(&buffer[..24], &mut index, &mut partial).unwrap_err();
+        assert_eq!(error, RESPError::Incomplete);
+        assert_eq!(partial.arguments, vec![(8, 3), (17, 3)]);
+        assert_eq!(partial.index, 22);
+
+        let mut index: usize = 0;
+        let output = bytes_to_frame_resuming(buffer, &mut index, &mut partial).unwrap();
+        assert_eq!(
+            output,
+            RESPFrame::Array(vec![
+                RESPFrame::BulkString(b"SET"),
+                RESPFrame::BulkString(b"key"),
+                RESPFrame::BulkString(b"value"),
+            ])
+        );
+        assert_eq!(index, buffer.len());
+        assert_eq!(partial, PartialRequest::default());
+    }
+
+    #[test]
+    fn test_bytes_to_frame_invalid_lengths() {
+        for (buffer, expected) in [
+            ("*x\r\n", RESPError::InvalidMultibulkLength),
+            ("*-1\r\n", RESPError::InvalidMultibulkLength),
+            ("*2147483648\r\n", RESPError::InvalidMultibulkLength),
+            ("*1\r\n$-1\r\n", RESPError::InvalidBulkLength),
+            ("*1\r\n$536870913\r\n", RESPError::InvalidBulkLength),
+        ] {
+            let mut index: usize = 0;
+            let error = bytes_to_frame(buffer.as_bytes(), &mut index).unwrap_err();
+            assert_eq!(error, expected, "{buffer:?}");
+        }
+    }
+
+    #[test]
+    fn test_bytes_to_frame_header_too_big() {
+        let mut index: usize = 0;
+        let buffer = format!("*{}", "1".repeat(MAX_INLINE_LENGTH + 1));
+        let error = bytes_to_frame(buffer.as_bytes(), &mut index).unwrap_err();
+        assert_eq!(error, RESPError::HeaderTooBig);
+
+        let mut index: usize = 0;
+        let buffer = format!("*1\r\n${}", "1".repeat(MAX_INLINE_LENGTH + 1));
+        let error = bytes_to_frame(buffer.as_bytes(), &mut index).unwrap_err();
+        assert_eq!(error, RESPError::HeaderTooBig);
+
+        // Shorter headers are waited for
+        let mut index: usize = 0;
+        let buffer = format!("*{}", "1".repeat(MAX_INLINE_LENGTH));
+        let error = bytes_to_frame(buffer.as_bytes(), &mut index).unwrap_err();
+        assert_eq!(error, RESPError::Incomplete);
+    }
 }
@@ -1,14 +1,25 @@
 use std::fmt;
 use std::num;
+use std::str::Utf8Error;
 use std::string::FromUtf8Error;
 
 #[derive(Debug, PartialEq)]
 pub enum RESPError {
     FromUtf8,
-    OutOfBounds(usize),
+    Incomplete,
+    InlineTooBig,
+    HeaderTooBig,
+    InvalidBulkLength,
+    InvalidMultibulkLength,
+    MissingTerminator(usize),
     WrongType,
     IncorrectLength(RESPLength),
     ParseInt,
+    ParseFloat,
+    ParseBool,
+    UnbalancedQuotes,
+    /// The prefix expected and the one found
+    UnexpectedType(u8, u8),
     Unknown,
 }
 
@@ -18,20 +29,47 @@ impl From<FromUtf8Error> for RESPError {
     }
 }
 
+impl From<Utf8Error> for RESPError {
+    fn from(_: Utf8Error) -> Self {
+        Self::FromUtf8
+    }
+}
+
 impl From<num::ParseIntError> for RESPError {
     fn from(_err: num::ParseIntError) -> Self {
         Self::ParseInt
     }
 }
 
+impl From<num::ParseFloatError> for RESPError {
+    fn from(_err: num::ParseFloatError) -> Self {
+        Self::ParseFloat
+    }
+}
+
 impl fmt::Display for RESPError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            RESPError::OutOfBounds(index) => write!(f, "Out of bounds at index {}", index),
+            RESPError::Incomplete => write!(f, "Incomplete data, more bytes are needed"),
+            RESPError::InlineTooBig => write!(f, "too big inline request"),
+            RESPError::HeaderTooBig => write!(f, "too big count string"),
+            RESPError::InvalidBulkLength => write!(f, "invalid bulk length"),
+            RESPError::InvalidMultibulkLength => write!(f, "invalid multibulk length"),
+            RESPError::MissingTerminator(index) => {
+                write!(f, "Expected \\r\\n at index {}", index)
+            }
             RESPError::FromUtf8 => write!(f, "Cannot convert from UTF-8"),
             RESPError::WrongType => write!(f, "Wrong prefix for RESP type"),
             RESPError::IncorrectLength(length) => write!(f, "Incorrect legth {}", length),
             RESPError::ParseInt => write!(f, "Cannot parse string into integer"),
+            RESPError::ParseFloat => write!(f, "Cannot parse string into float"),
+            RESPError::ParseBool => write!(f, "Cannot parse string into boolean"),
+            RESPError::UnbalancedQuotes => write!(f, "unbalanced quotes in request"),
+            RESPError::UnexpectedType(expected, found) => write!(
+                f,
+                "expected '{}', got '{}'",
+                *expected as char, *found as char
+            ),
             RESPError::Unknown => write!(f, "Unknown format for RESP string"),
         }
     }
@@ -0,0 +1,364 @@
+use crate::resp::{ProtocolVersion, RESP};
+use std::io::{Cursor, Write};
+
+/// Enough room for the longest text produced by `write_double`
+const DOUBLE_MAX_LENGTH: usize = 32;
+
+/// Write a double the way Redis does: `inf`, `-inf`, `nan` or the shortest representation that
+/// round-trips, switching to exponent notation for very large or very small values like `%.17g`
+pub fn write_double<W: Write>(output: &mut W, value: f64) {
+    let result = if value.is_nan() {
+        output.write_all(b"nan")
+    } else if value.is_infinite() {
+        output.write_all(if value > 0.0 { b"inf" } else { b"-inf" })
+    } else if value != 0.0 && !(1e-4..1e17).contains(&value.abs()) {
+        let mut buffer = [0u8; DOUBLE_MAX_LENGTH];
+        let mut cursor = Cursor::new(&mut buffer[..]);
+        write!(cursor, "{:e}", value).expect("the buffer is large enough for any double");
+        let length = cursor.position() as usize;
+        let text = &buffer[..length];
+
+        // Rust omits the sign of positive exponents, C does not
+        match text.iter().position(|&c| c == b'e') {
+            Some(split) if text.get(split + 1) != Some(&b'-') => output
+                .write_all(&text[..=split])
+                .and_then(|_| output.write_all(b"+"))
+                .and_then(|_| output.write_all(&text[split + 1..])),
+            _ => output.write_all(text),
+        }
+    } else {
+        write!(output, "{}", value)
+    };
+
+    result.expect("the output buffer is large enough for any double");
+}
+
+/// Serialises RESP values straight into a reusable byte buffer, without intermediate strings
+pub struct RESPWriter<'a> {
+    output: &'a mut Vec<u8>,
+    protocol: ProtocolVersion,
+}
+
+impl<'a> RESPWriter<'a> {
+    pub fn new(output: &'a mut Vec<u8>, protocol: ProtocolVersion) -> Self {
+        Self { output, protocol }
+    }
+
+    /// Number of bytes in the output, including those written before this writer was created
+    pub fn position(&self) -> usize {
+        self.output.len()
+    }
+
+    fn resp3(&self) -> bool {
+        self.protocol == ProtocolVersion::RESP3
+    }
+
+    fn push_number(&mut self, value: u64) {
+        // u64::MAX has 20 digits
+        let mut digits = [0u8; 20];
+        let mut position = digits.len();
+        let mut value = value;
+
+        loop {
+            position -= 1;
+            digits[position] = b'0' + (value % 10) as u8;
+            value /= 10;
+
+            if value == 0 {
+                break;
+            }
+        }
+
+        self.output.extend_from_slice(&digits[position..]);
+    }
+
+    fn push_header(&mut self, prefix: u8, length: usize) {
+        self.output.push(prefix);
+        self.push_number(length as u64);
+        self.output.extend_from_slice(b"\r\n");
+    }
+
+    fn push_line(&mut self, prefix: u8, data: &[u8]) {
+        self.output.push(prefix);
+        self.output.extend_from_slice(data);
+        self.output.extend_from_slice(b"\r\n");
+    }
+
+    fn push_blob(&mut self, prefix: u8, data: &[u8]) {
+        self.push_header(prefix, data.len());
+        self.output.extend_from_slice(data);
+        self.output.extend_from_slice(b"\r\n");
+    }
+
+    pub fn null(&mut self) {
+        if self.resp3() {
+            self.output.extend_from_slice(b"_\r\n");
+        } else {
+            self.output.extend_from_slice(b"$-1\r\n");
+        }
+    }
+
+    pub fn null_array(&mut self) {
+        if self.resp3() {
+            self.output.extend_from_slice(b"_\r\n");
+        } else {
+            self.output.extend_from_slice(b"*-1\r\n");
+        }
+    }
+
+    pub fn simple_string(&mut self, data: &str) {
+        self.push_line(b'+', data.as_bytes());
+    }
+
+    pub fn simple_error(&mut self, data: &str) {
+        // A line break would end the error early and be read as the start of another reply
+        self.output.push(b'-');
+        self.output.extend(
+            data.bytes()
+                .map(|c| if c == b'\r' || c == b'\n' { b' ' } else { c }),
+        );
+        self.output.extend_from_slice(b"\r\n");
+    }
+
+    pub fn integer(&mut self, value: i64) {
+        self.output.push(b':');
+        if value < 0 {
+            self.output.push(b'-');
+        }
+        self.push_number(value.unsigned_abs());
+        self.output.extend_from_slice(b"\r\n");
+    }
+
+    pub fn bulk_string(&mut self, data: &[u8]) {
+        self.push_blob(b'$', data);
+    }
+
+    pub fn double(&mut self, value: f64) {
+        let mut buffer = [0u8; DOUBLE_MAX_LENGTH];
+        let mut cursor = Cursor::new(&mut buffer[..]);
+        write_double(&mut cursor, value);
+        let length = cursor.position() as usize;
+
+        if self.resp3() {
+            self.push_line(b',', &buffer[..length]);
+        } else {
+            self.push_blob(b'$', &buffer[..length]);
+        }
+    }
+
+    pub fn boolean(&mut self, value: bool) {
+        if self.resp3() {
+            self.output
+                .extend_from_slice(if value { b"#t\r\n" } else { b"#f\r\n" });
+        } else {
+            self.integer(value as i64);
+        }
+    }
+
+    pub fn big_number(&mut self, data: &str) {
+        if self.resp3() {
+            self.push_line(b'(', data.as_bytes());
+        } else {
+            self.bulk_string(data.as_bytes());
+        }
+    }
+
+    pub fn verbatim_string(&mut self, encoding: &str, data: &str) {
+        if self.resp3() {
+            self.push_header(b'=', encoding.len() + 1 + data.len());
+            self.output.extend_from_slice(encoding.as_bytes());
+            self.output.push(b':');
+            self.output.extend_from_slice(data.as_bytes());
+            self.output.extend_from_slice(b"\r\n");
+        } else {
+            self.bulk_string(data.as_bytes());
+        }
+    }
+
+    pub fn blob_error(&mut self, data: &str) {
+        if self.resp3() {
+            self.push_blob(b'!', data.as_bytes());
+        } else {
+            // RESP2 errors are single lines
+            self.simple_error(data);
+        }
+    }
+
+    /// Announce an array of `length` elements, which must be written right after
+    pub fn array_header(&mut self, length: usize) {
+        self.push_header(b'*', length);
+    }
+
+    /// Write an array whose length is only known once its elements are written, `write`
+    /// returning how many it wrote. The header is inserted before them afterwards.
+    pub fn deferred_array(&mut self, write: impl FnOnce(&mut Self) -> usize) {
+        let position = self.output.len();
+        let length = write(self);
+
+        let mut header = Vec::new();
+        RESPWriter::new(&mut header, self.protocol).array_header(length);
+        self.output.splice(position..position, header);
+    }
+
+    /// Announce a map of `length` key-value pairs, which must be written right after.
+    /// RESP2 has no map type, so maps are flattened into arrays of alternating keys and values.
+    pub fn map_header(&mut self, length: usize) {
+        if self.resp3() {
+            self.push_header(b'%', length);
+        } else {
+            self.push_header(b'*', length * 2);
+        }
+    }
+
+    /// Announce a set of `length` elements, which must be written right after
+    pub fn set_header(&mut self, length: usize) {
+        let prefix = if self.resp3() { b'~' } else { b'*' };
+        self.push_header(prefix, length);
+    }
+
+    /// Announce a push message of `length` elements, which must be written right after
+    pub fn push_message_header(&mut self, length: usize) {
+        let prefix = if self.resp3() { b'>' } else { b'*' };
+        self.push_header(prefix, length);
+    }
+
+    /// Write a whole value, downgrading RESP3 types when the client speaks RESP2
+    pub fn value(&mut self, value: &RESP) {
+        match value {
+            RESP::Null => self.null(),
+            RESP::NullArray => self.null_array(),
+            RESP::SimpleString(data) => self.simple_string(data),
+            RESP::SimpleError(data) => self.simple_error(data),
+            RESP::Integer(data) => self.integer(*data),
+            RESP::BulkString(data) => self.bulk_string(data),
+            RESP::Array(data) => {
+                self.array_header(data.len());
+                self.values(data);
+            }
+            RESP::Map(data) => {
+                self.map_header(data.len());
+                self.pairs(data);
+            }
+            RESP::Set(data) => {
+                self.set_header(data.len());
+                self.values(data);
+            }
+            RESP::Double(data) => self.double(*data),
+            RESP::Boolean(data) => self.boolean(*data),
+            RESP::BigNumber(data) => self.big_number(data),
+            RESP::VerbatimString(encoding, data) => self.verbatim_string(encoding, data),
+            RESP::BlobError(data) => self.blob_error(data),
+            RESP::Push(data) => {
+                self.push_message_header(data.len());
+                self.values(data);
+            }
+            RESP::Attribute(attributes, data) => {
+                // Attributes are out-of-band information that RESP2 clients cannot receive
+                if self.resp3() {
+                    self.push_header(b'|', attributes.len());
+                    self.pairs(attributes);
+                }
+                self.value(data);
+            }
+        }
+    }
+
+    fn values(&mut self, values: &[RESP]) {
+        for value in values {
+            self.value(value);
+        }
+    }
+
+    fn pairs(&mut self, pairs: &[(RESP, RESP)]) {
+        for (key, value) in pairs {
+            self.value(key);
+            self.value(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(protocol: ProtocolVersion, function: impl FnOnce(&mut RESPWriter)) -> Vec<u8> {
+        let mut output = Vec::new();
+        function(&mut RESPWriter::new(&mut output, protocol));
+        output
+    }
+
+    #[test]
+    fn test_integer() {
+        let output = write(ProtocolVersion::RESP2, |writer| {
+            writer.integer(0);
+            writer.integer(-42);
+            writer.integer(i64::MIN);
+        });
+
+        assert_eq!(output, b":0\r\n:-42\r\n:-9223372036854775808\r\n");
+    }
+
+    #[test]
+    fn test_double() {
+        let output = write(ProtocolVersion::RESP2, |writer| writer.double(3.25));
+        assert_eq!(output, b"$4\r\n3.25\r\n");
+
+        let output = write(ProtocolVersion::RESP3, |writer| writer.double(f64::NAN));
+        assert_eq!(output, b",nan\r\n");
+    }
+
+    #[test]
+    fn test_write_double() {
+        let format = |value: f64| {
+            let mut output = Vec::new();
+            write_double(&mut output, value);
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(format(0.0), "0");
+        assert_eq!(format(-1.5), "-1.5");
+        assert_eq!(format(0.1), "0.1");
+        assert_eq!(format(1e300), "1e+300");
+        assert_eq!(format(-2.5e-10), "-2.5e-10");
+        assert_eq!(format(f64::MAX), "1.7976931348623157e+308");
+        assert_eq!(format(f64::NEG_INFINITY), "-inf");
+    }
+
+    #[test]
+    fn test_map_header() {
+        let output = write(ProtocolVersion::RESP2, |writer| writer.map_header(2));
+        assert_eq!(output, b"*4\r\n");
+
+        let output = write(ProtocolVersion::RESP3, |writer| writer.map_header(2));
+        assert_eq!(output, b"%2\r\n");
+    }
+
+    #[test]
+    fn test_deferred_array() {
+        let mut output = b"+OK\r\n".to_vec();
+        RESPWriter::new(&mut output, ProtocolVersion::RESP2).deferred_array(|writer| {
+            writer.bulk_string(b"a");
+            writer.integer(1);
+            2
+        });
+
+        assert_eq!(output, b"+OK\r\n*2\r\n$1\r\na\r\n:1\r\n");
+    }
+
+    #[test]
+    fn test_appends_to_existing_buffer() {
+        let mut output = b"+OK\r\n".to_vec();
+        RESPWriter::new(&mut output, ProtocolVersion::RESP3).value(&RESP::Null);
+
+        assert_eq!(output, b"+OK\r\n_\r\n");
+    }
+
+    #[test]
+    fn test_null_array() {
+        let output = write(ProtocolVersion::RESP2, |writer| writer.null_array());
+        assert_eq!(output, b"*-1\r\n");
+
+        let output = write(ProtocolVersion::RESP3, |writer| writer.null_array());
+        assert_eq!(output, b"_\r\n");
+    }
+}
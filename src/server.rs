@@ -0,0 +1,819 @@
+use crate::blocking::{Blocked, block_client, serve_blocked_clients};
+use crate::client::Client;
+use crate::commands::{Context, lookup_command};
+use crate::resp::{ProtocolVersion, RESP, RESPFrame, bytes_to_frame_resuming};
+use crate::resp_result::RESPError;
+use crate::server_result::{ServerError, ServerResult};
+use crate::storage::{Storage, lock};
+use bytes::{Buf, BytesMut};
+use std::sync::Mutex;
+
+/// Largest buffer of requests not complete yet, as in Redis (1GB). It holds at least one bulk
+/// string of the largest size accepted.
+pub const MAX_QUERY_BUFFER_LENGTH: usize = 1024 * 1024 * 1024;
+
+/// Longest part of the command name, and of the quoted arguments altogether, echoed back by
+/// unknown command errors, as in Redis
+const MAX_ECHOED_LENGTH: usize = 128;
+
+/// Commands a RESP2 client subscribed to channels or patterns can send, its connection being
+/// otherwise reserved for the messages published
+const ALLOWED_WHEN_SUBSCRIBED: &[&str] = &[
+    "ping",
+    "psubscribe",
+    "punsubscribe",
+    "subscribe",
+    "unsubscribe",
+];
+
+/// Split a request into the command name and its arguments
+fn extract_command<'a>(request: &'a RESPFrame) -> ServerResult<(&'a [u8], Vec<&'a [u8]>)> {
+    let mut parts = Vec::new();
+
+    match request {
+        RESPFrame::Array(elements) => {
+            for element in elements {
+                match element {
+                    RESPFrame::BulkString(data) => parts.push(*data),
+                    _ => return Err(ServerError::IncorrectData),
+                }
+            }
+        }
+        // Inline commands are parsed into owned arrays
+        RESPFrame::Value(RESP::Array(elements)) => {
+            for element in elements {
+                match element {
+                    RESP::BulkString(data) => parts.push(data.as_slice()),
+                    _ => return Err(ServerError::IncorrectData),
+                }
+            }
+        }
+        _ => return Err(ServerError::IncorrectData),
+    }
+
+    if parts.is_empty() {
+        return Err(ServerError::IncorrectData);
+    }
+
+    let name = parts.remove(0);
+    Ok((name, parts))
+}
+
+/// Result of a request that was executed successfully
+#[derive(Debug)]
+pub enum Outcome {
+    Reply(RESP),
+    /// The client waits for data, the reply is delivered later
+    Blocked(Blocked),
+    /// The reply was written to the output as it was produced
+    Streamed,
+}
+
+/// Execute a request. Commands producing large replies write them to `output` if given, see
+/// [`Context::write_reply`].
+pub fn process_request(
+    client: &mut Client,
+    storage: &mut Storage,
+    request: &RESPFrame,
+    output: Option<&mut Vec<u8>>,
+) -> ServerResult<Outcome> {
+    let (name, args) = extract_command(request)?;
+
+    let command = match lookup_command(name) {
+        Some(command) => command,
+        None => {
+            let echo = |data: &[u8], limit: usize| {
+                String::from_utf8_lossy(&data[..data.len().min(limit)]).into_owned()
+            };
+
+            // Each argument is echoed between quotes and followed by a space
+            let mut echoed = Vec::new();
+            let mut length = 0;
+            for arg in args.iter() {
+                if length >= MAX_ECHOED_LENGTH {
+                    break;
+                }
+                let arg = echo(arg, MAX_ECHOED_LENGTH - length);
+                length += arg.len() + 3;
+                echoed.push(arg);
+            }
+
+            return Err(ServerError::UnknownCommand(
+                echo(name, MAX_ECHOED_LENGTH),
+                echoed,
+            ));
+        }
+    };
+
+    if !command.check_arity(args.len() + 1) {
+        return Err(ServerError::WrongArity(String::from(command.name)));
+    }
+
+    if client.protocol == ProtocolVersion::RESP2
+        && storage.pubsub.subscription_count(client.id) > 0
+        && !ALLOWED_WHEN_SUBSCRIBED.contains(&command.name)
+    {
+        return Err(ServerError::InvalidArgument(format!(
+            "Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET \
+             are allowed in this context",
+            command.name
+        )));
+    }
+
+    let mut ctx = Context::new(client, storage);
+    ctx.output = output;
+    let reply = (command.handler)(&mut ctx, &args)?;
+    if ctx.streamed {
+        return Ok(Outcome::Streamed);
+    }
+
+    match ctx.block.take() {
+        Some(block) => Ok(Outcome::Blocked(block_client(
+            storage, client, command, &args, block,
+        ))),
+        None => Ok(Outcome::Reply(reply)),
+    }
+}
+
+fn is_empty_request(request: &RESPFrame) -> bool {
+    match request {
+        RESPFrame::Array(elements) => elements.is_empty(),
+        RESPFrame::Value(RESP::Array(elements)) => elements.is_empty(),
+        _ => false,
+    }
+}
+
+/// Execute every complete request in the buffer, in order, appending the replies to `output`.
+/// Partial requests are left in the buffer until more data arrives. Processing stops at a
+/// request that blocks the client, which is returned so that the caller can wait for its reply.
+/// An error is returned when the buffer contains malformed data, in which case the connection
+/// should be closed.
+pub fn process_buffer(
+    client: &mut Client,
+    storage: &Mutex<Storage>,
+    buffer: &mut BytesMut,
+    output: &mut Vec<u8>,
+) -> ServerResult<Option<Blocked>> {
+    loop {
+        let mut index: usize = 0;
+
+        let result = match bytes_to_frame_resuming(buffer, &mut index, &mut client.partial_request)
+        {
+            Ok(request) if is_empty_request(&request) => {
+                // Empty requests, like blank lines sent through telnet, get no reply
+                buffer.advance(index);
+                continue;
+            }
+            Ok(request) => {
+                let mut storage = lock(storage);
+                let result = process_request(client, &mut storage, &request, Some(output));
+                serve_blocked_clients(&mut storage);
+                result
+            }
+            Err(RESPError::Incomplete) if buffer.len() > MAX_QUERY_BUFFER_LENGTH => {
+                let error = ServerError::Protocol(String::from("query buffer limit reached"));
+                RESP::SimpleError(error.to_string()).encode_into(output, client.protocol);
+                return Err(error);
+            }
+            Err(RESPError::Incomplete) => return Ok(None),
+            Err(error) => {
+                // The stream cannot be resynchronised after a malformed request
+                let error = ServerError::from(error);
+                RESP::SimpleError(error.to_string()).encode_into(output, client.protocol);
+                return Err(error);
+            }
+        };
+
+        buffer.advance(index);
+
+        let response = match result {
+            Ok(Outcome::Reply(response)) => response,
+            Ok(Outcome::Blocked(blocked)) => return Ok(Some(blocked)),
+            Ok(Outcome::Streamed) => continue,
+            Err(error) => RESP::SimpleError(error.to_string()),
+        };
+
+        response.encode_into(output, client.protocol);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocking::unblock_client;
+
+    fn request<'a>(parts: &[&'a str]) -> RESPFrame<'a> {
+        RESPFrame::Array(
+            parts
+                .iter()
+                .map(|part| RESPFrame::BulkString(part.as_bytes()))
+                .collect(),
+        )
+    }
+
+    fn reply(outcome: Outcome) -> RESP {
+        match outcome {
+            Outcome::Reply(reply) => reply,
+            Outcome::Blocked(_) => panic!("the client is blocked"),
+            Outcome::Streamed => panic!("the reply was streamed"),
+        }
+    }
+
+    /// Feed the input to process_buffer as sent by the client, returning the bytes written back
+    fn run(
+        client: &mut Client,
+        storage: &Mutex<Storage>,
+        input: &str,
+    ) -> (Vec<u8>, Option<Blocked>) {
+        let mut buffer = BytesMut::from(input);
+        let mut output = Vec::new();
+        let blocked = process_buffer(client, storage, &mut buffer, &mut output).unwrap();
+
+        (output, blocked)
+    }
+
+    #[test]
+    fn test_process_request_ping() {
+        let output = process_request(
+            &mut Client::new(),
+            &mut Storage::new(),
+            &request(&["PING"]),
+            None,
+        )
+        .unwrap();
+        assert_eq!(reply(output), RESP::SimpleString(String::from("PONG")));
+    }
+
+    #[test]
+    fn test_process_request_echo() {
+        let output = process_request(
+            &mut Client::new(),
+            &mut Storage::new(),
+            &request(&["echo", "hello"]),
+            None,
+        )
+        .unwrap();
+        assert_eq!(reply(output), RESP::BulkString(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn test_process_request_unknown_command() {
+        let error = process_request(
+            &mut Client::new(),
+            &mut Storage::new(),
+            &request(&["foo", "bar"]),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            ServerError::UnknownCommand(String::from("foo"), vec![String::from("bar")])
+        );
+        assert_eq!(
+            error.to_string(),
+            "ERR unknown command 'foo', with args beginning with: 'bar'"
+        );
+    }
+
+    #[test]
+    fn test_unknown_command_error_stays_on_one_line() {
+        let mut client = Client::new();
+        let storage = Mutex::new(Storage::new());
+        let long = "x".repeat(200);
+        let mut buffer = BytesMut::from(
+            format!("*4\r\n$3\r\nfoo\r\n$4\r\na\r\nb\r\n$200\r\n{long}\r\n$1\r\nc\r\n").as_str(),
+        );
+        let mut output = Vec::new();
+
+        process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap();
+
+        let expected = format!(
+            "-ERR unknown command 'foo', with args beginning with: 'a  b' '{}'\r\n",
+            "x".repeat(121)
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_process_request_wrong_arity() {
+        let error = process_request(
+            &mut Client::new(),
+            &mut Storage::new(),
+            &request(&["echo"]),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(error, ServerError::WrongArity(String::from("echo")));
+    }
+
+    #[test]
+    fn test_process_request_not_an_array() {
+        let error = process_request(
+            &mut Client::new(),
+            &mut Storage::new(),
+            &RESPFrame::Value(RESP::SimpleString(String::from("PING"))),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(error, ServerError::IncorrectData);
+    }
+
+    #[test]
+    fn test_process_request_empty_array() {
+        let error = process_request(
+            &mut Client::new(),
+            &mut Storage::new(),
+            &RESPFrame::Array(vec![]),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(error, ServerError::IncorrectData);
+    }
+
+    #[test]
+    fn test_process_buffer_pipeline() {
+        let mut client = Client::new();
+        let storage = Mutex::new(Storage::new());
+        let mut buffer =
+            BytesMut::from("*1\r\n$4\r\nPING\r\n*2\r\n$4\r\nECHO\r\n$2\r\nhi\r\n*1\r\n$4\r\nPI");
+        let mut output = Vec::new();
+
+        process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap();
+
+        assert_eq!(output, b"+PONG\r\n$2\r\nhi\r\n");
+        assert_eq!(&buffer[..], b"*1\r\n$4\r\nPI");
+
+        buffer.extend_from_slice(b"NG\r\n");
+        output.clear();
+        process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap();
+
+        assert_eq!(output, b"+PONG\r\n");
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_large_replies_are_written_as_produced() {
+        let storage = Mutex::new(Storage::new());
+        let mut client = Client::new();
+
+        let (output, _) = run(
+            &mut client,
+            &storage,
+            "RPUSH list a b c\r\nSET string x\r\nLRANGE list 1 -1\r\nKEYS l*\r\n\
+             LRANGE string 0 -1\r\nLRANGE list 5 6\r\nPING\r\n",
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ":3\r\n+OK\r\n*2\r\n$1\r\nb\r\n$1\r\nc\r\n*1\r\n$4\r\nlist\r\n\
+             -WRONGTYPE Operation against a key holding the wrong kind of value\r\n*0\r\n+PONG\r\n"
+        );
+
+        run(&mut client, &storage, "HELLO 3\r\n");
+        let (output, _) = run(
+            &mut client,
+            &storage,
+            "SADD set m\r\nSMEMBERS set\r\nSMEMBERS none\r\n",
+        );
+        assert_eq!(output, b":1\r\n~1\r\n$1\r\nm\r\n~0\r\n");
+    }
+
+    #[test]
+    fn test_random_picks_beyond_a_million() {
+        let storage = Mutex::new(Storage::new());
+        let mut client = Client::new();
+
+        let (output, _) = run(
+            &mut client,
+            &storage,
+            "HSET h a 1\r\nHRANDFIELD h -1000001\r\n",
+        );
+        let header = b":1\r\n*1000001\r\n";
+        assert_eq!(&output[..header.len()], header);
+        assert_eq!(
+            output.len(),
+            header.len() + 1_000_001 * b"$1\r\na\r\n".len()
+        );
+    }
+
+    #[test]
+    fn test_process_buffer_command_error_keeps_going() {
+        let mut client = Client::new();
+        let storage = Mutex::new(Storage::new());
+        let mut buffer = BytesMut::from("*1\r\n$4\r\nECHO\r\n*1\r\n$4\r\nPING\r\n");
+        let mut output = Vec::new();
+
+        process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap();
+
+        assert_eq!(
+            output,
+            b"-ERR wrong number of arguments for 'echo' command\r\n+PONG\r\n"
+        );
+    }
+
+    #[test]
+    fn test_process_buffer_protocol_error() {
+        let mut client = Client::new();
+        let storage = Mutex::new(Storage::new());
+        let mut buffer = BytesMut::from("*1\r\n$4\r\nPING\r\n*x\r\n");
+        let mut output = Vec::new();
+
+        let error = process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap_err();
+
+        assert_eq!(
+            error,
+            ServerError::Protocol(String::from("invalid multibulk length"))
+        );
+        assert_eq!(
+            output,
+            b"+PONG\r\n-ERR Protocol error: invalid multibulk length\r\n"
+        );
+    }
+
+    #[test]
+    fn test_process_buffer_deeply_nested_request() {
+        let mut client = Client::new();
+        let storage = Mutex::new(Storage::new());
+        let mut buffer = BytesMut::from("*1\r\n".repeat(200_000).as_str());
+        let mut output = Vec::new();
+
+        let error = process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap_err();
+
+        assert_eq!(
+            error,
+            ServerError::Protocol(String::from("expected '$', got '*'"))
+        );
+        assert_eq!(output, b"-ERR Protocol error: expected '$', got '*'\r\n");
+    }
+
+    #[test]
+    fn test_process_buffer_inline_commands() {
+        let mut client = Client::new();
+        let storage = Mutex::new(Storage::new());
+        let mut buffer = BytesMut::from("PING\r\n\r\necho \"hello world\"\nPI");
+        let mut output = Vec::new();
+
+        process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap();
+
+        assert_eq!(output, b"+PONG\r\n$11\r\nhello world\r\n");
+        assert_eq!(&buffer[..], b"PI");
+    }
+
+    #[test]
+    fn test_process_buffer_inline_unbalanced_quotes() {
+        let mut client = Client::new();
+        let storage = Mutex::new(Storage::new());
+        let mut buffer = BytesMut::from("echo \"hello\r\n");
+        let mut output = Vec::new();
+
+        let error = process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap_err();
+
+        assert_eq!(
+            error,
+            ServerError::Protocol(String::from("unbalanced quotes in request"))
+        );
+        assert_eq!(
+            output,
+            b"-ERR Protocol error: unbalanced quotes in request\r\n"
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let storage = Mutex::new(Storage::new());
+        let (mut first, mut second) = (Client::new(), Client::new());
+
+        let (output, _) = run(&mut first, &storage, "SELECT 1\r\nSET key value\r\n");
+        assert_eq!(output, b"+OK\r\n+OK\r\n");
+
+        let (output, _) = run(&mut second, &storage, "GET key\r\nSELECT 1\r\nGET key\r\n");
+        assert_eq!(output, b"$-1\r\n+OK\r\n$5\r\nvalue\r\n");
+
+        let (output, _) = run(&mut second, &storage, "SELECT 16\r\n");
+        assert_eq!(output, b"-ERR DB index is out of range\r\n");
+    }
+
+    #[test]
+    fn test_blocked_clients_are_served_in_their_database() {
+        let storage = Mutex::new(Storage::new());
+        let (mut waiter, mut pusher) = (Client::new(), Client::new());
+
+        let (_, blocked) = run(&mut waiter, &storage, "SELECT 2\r\nBLPOP list 0\r\n");
+        let mut blocked = blocked.unwrap();
+
+        run(&mut pusher, &storage, "RPUSH list a\r\n");
+        assert!(blocked.receiver.try_recv().is_err());
+
+        let (output, _) = run(&mut pusher, &storage, "MOVE list 2\r\n");
+        assert_eq!(output, b":1\r\n");
+        assert_eq!(
+            blocked.receiver.try_recv().unwrap(),
+            Ok(RESP::Array(vec![
+                RESP::BulkString(b"list".to_vec()),
+                RESP::BulkString(b"a".to_vec())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_blocking_pop_served_by_push() {
+        let storage = Mutex::new(Storage::new());
+        let (mut waiter, mut pusher) = (Client::new(), Client::new());
+
+        let (output, blocked) = run(&mut waiter, &storage, "BLPOP list 0\r\n");
+        assert!(output.is_empty());
+        let mut blocked = blocked.unwrap();
+        assert_eq!(blocked.timeout, None);
+
+        let (output, _) = run(&mut pusher, &storage, "RPUSH list a b\r\nLLEN list\r\n");
+        assert_eq!(output, b":2\r\n:1\r\n");
+
+        assert_eq!(
+            blocked.receiver.try_recv().unwrap(),
+            Ok(RESP::Array(vec![
+                RESP::BulkString(b"list".to_vec()),
+                RESP::BulkString(b"a".to_vec())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_blocked_sorted_set_pop_served_by_zadd() {
+        let storage = Mutex::new(Storage::new());
+        let (mut waiter, mut writer) = (Client::new(), Client::new());
+
+        let mut blocked = run(&mut waiter, &storage, "BZPOPMIN zset 0\r\n").1.unwrap();
+
+        let (output, _) = run(&mut writer, &storage, "ZADD zset 2 b 1 a\r\nZCARD zset\r\n");
+        assert_eq!(output, b":2\r\n:1\r\n");
+
+        assert_eq!(
+            blocked.receiver.try_recv().unwrap(),
+            Ok(RESP::Array(vec![
+                RESP::BulkString(b"zset".to_vec()),
+                RESP::BulkString(b"a".to_vec()),
+                RESP::Double(1.0)
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_blocked_client_waits_while_its_key_holds_another_type() {
+        let storage = Mutex::new(Storage::new());
+        let (mut waiter, mut writer) = (Client::new(), Client::new());
+
+        let mut blocked = run(&mut waiter, &storage, "BLPOP key 0\r\n").1.unwrap();
+
+        let (output, _) = run(&mut writer, &storage, "ZADD key 1 m\r\n");
+        assert_eq!(output, b":1\r\n");
+        assert!(blocked.receiver.try_recv().is_err());
+
+        run(&mut writer, &storage, "DEL key\r\nRPUSH key a\r\n");
+        assert_eq!(
+            blocked.receiver.try_recv().unwrap(),
+            Ok(RESP::Array(vec![
+                RESP::BulkString(b"key".to_vec()),
+                RESP::BulkString(b"a".to_vec())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_blocked_stream_reads_served_by_xadd() {
+        let storage = Mutex::new(Storage::new());
+        let (mut reader, mut consumer, mut writer) = (Client::new(), Client::new(), Client::new());
+
+        run(
+            &mut writer,
+            &storage,
+            "XADD s 1-0 f old\r\nXGROUP CREATE s g $\r\n",
+        );
+
+        // `$` is resolved when blocking, so only entries added afterwards are read
+        let mut read = run(&mut reader, &storage, "XREAD BLOCK 0 STREAMS s $\r\n")
+            .1
+            .unwrap();
+        let mut group_read = run(
+            &mut consumer,
+            &storage,
+            "XREADGROUP GROUP g c BLOCK 0 STREAMS s >\r\n",
+        )
+        .1
+        .unwrap();
+
+        let (output, _) = run(&mut writer, &storage, "XADD s 2-0 f new\r\n");
+        assert_eq!(output, b"$3\r\n2-0\r\n");
+
+        let expected = || {
+            RESP::Array(vec![RESP::Array(vec![
+                RESP::BulkString(b"s".to_vec()),
+                RESP::Array(vec![RESP::Array(vec![
+                    RESP::BulkString(b"2-0".to_vec()),
+                    RESP::Array(vec![
+                        RESP::BulkString(b"f".to_vec()),
+                        RESP::BulkString(b"new".to_vec()),
+                    ]),
+                ])]),
+            ])])
+        };
+        assert_eq!(read.receiver.try_recv().unwrap(), Ok(expected()));
+        assert_eq!(group_read.receiver.try_recv().unwrap(), Ok(expected()));
+
+        let (output, _) = run(&mut writer, &storage, "XPENDING s g - + 10 c\r\n");
+        assert!(output.starts_with(b"*1\r\n*4\r\n$3\r\n2-0\r\n$1\r\nc\r\n"));
+    }
+
+    #[test]
+    fn test_blocking_pop_immediate() {
+        let storage = Mutex::new(Storage::new());
+        let mut client = Client::new();
+
+        let (output, blocked) = run(&mut client, &storage, "RPUSH b x\r\nBRPOP a b 1\r\n");
+        assert!(blocked.is_none());
+        assert_eq!(output, b":1\r\n*2\r\n$1\r\nb\r\n$1\r\nx\r\n");
+    }
+
+    #[test]
+    fn test_blocked_clients_are_served_in_order() {
+        let storage = Mutex::new(Storage::new());
+        let (mut first, mut second, mut pusher) = (Client::new(), Client::new(), Client::new());
+
+        let mut first_blocked = run(&mut first, &storage, "BLPOP list 0\r\n").1.unwrap();
+        let mut second_blocked = run(&mut second, &storage, "BRPOP other list 0\r\n")
+            .1
+            .unwrap();
+
+        run(&mut pusher, &storage, "LPUSH list x\r\n");
+        assert!(first_blocked.receiver.try_recv().is_ok());
+        assert!(second_blocked.receiver.try_recv().is_err());
+
+        run(&mut pusher, &storage, "LPUSH list y\r\n");
+        assert_eq!(
+            second_blocked.receiver.try_recv().unwrap(),
+            Ok(RESP::Array(vec![
+                RESP::BulkString(b"list".to_vec()),
+                RESP::BulkString(b"y".to_vec())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_unblocked_client_is_not_served() {
+        let storage = Mutex::new(Storage::new());
+        let (mut waiter, mut pusher) = (Client::new(), Client::new());
+
+        let blocked = run(&mut waiter, &storage, "BLMPOP 0.5 1 list LEFT\r\n")
+            .1
+            .unwrap();
+        assert_eq!(blocked.timeout, Some(std::time::Duration::from_millis(500)));
+
+        assert!(unblock_client(
+            &mut storage.lock().unwrap(),
+            blocked.client_id
+        ));
+        assert!(!unblock_client(
+            &mut storage.lock().unwrap(),
+            blocked.client_id
+        ));
+
+        let (output, _) = run(&mut pusher, &storage, "RPUSH list a\r\nLLEN list\r\n");
+        assert_eq!(output, b":1\r\n:1\r\n");
+    }
+
+    #[test]
+    fn test_blocking_move_chain() {
+        let storage = Mutex::new(Storage::new());
+        let (mut mover, mut waiter, mut pusher) = (Client::new(), Client::new(), Client::new());
+
+        let mut moved = run(
+            &mut mover,
+            &storage,
+            "BLMOVE source target LEFT RIGHT 0\r\n",
+        )
+        .1
+        .unwrap();
+        let mut popped = run(&mut waiter, &storage, "BLPOP target 0\r\n").1.unwrap();
+
+        run(&mut pusher, &storage, "RPUSH source a\r\n");
+
+        assert_eq!(
+            moved.receiver.try_recv().unwrap(),
+            Ok(RESP::BulkString(b"a".to_vec()))
+        );
+        assert_eq!(
+            popped.receiver.try_recv().unwrap(),
+            Ok(RESP::Array(vec![
+                RESP::BulkString(b"target".to_vec()),
+                RESP::BulkString(b"a".to_vec())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_requests_after_a_blocking_one_wait() {
+        let storage = Mutex::new(Storage::new());
+        let mut client = Client::new();
+        let mut buffer = BytesMut::from("BLPOP list 0\r\nPING\r\n");
+        let mut output = Vec::new();
+
+        let blocked = process_buffer(&mut client, &storage, &mut buffer, &mut output).unwrap();
+
+        assert!(blocked.is_some());
+        assert!(output.is_empty());
+        assert_eq!(&buffer[..], b"PING\r\n");
+    }
+
+    #[test]
+    fn test_blocking_timeout_errors() {
+        let storage = Mutex::new(Storage::new());
+        let mut client = Client::new();
+
+        let (output, blocked) = run(&mut client, &storage, "BLPOP list -1\r\nBLPOP list x\r\n");
+        assert!(blocked.is_none());
+        assert_eq!(
+            output,
+            b"-ERR timeout is negative\r\n-ERR timeout is not a float or out of range\r\n"
+        );
+    }
+
+    #[test]
+    fn test_published_messages_reach_subscribers() {
+        let storage = Mutex::new(Storage::new());
+        let (sender, mut messages) = tokio::sync::mpsc::unbounded_channel();
+        let mut subscriber = Client::new();
+        subscriber.messages = Some(sender);
+        let mut publisher = Client::new();
+
+        let (output, _) = run(
+            &mut subscriber,
+            &storage,
+            "SUBSCRIBE news sport\r\nPSUBSCRIBE n*\r\n",
+        );
+        assert_eq!(
+            output,
+            b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n\
+              *3\r\n$9\r\nsubscribe\r\n$5\r\nsport\r\n:2\r\n\
+              *3\r\n$10\r\npsubscribe\r\n$2\r\nn*\r\n:3\r\n"
+        );
+
+        let (output, _) = run(&mut publisher, &storage, "PUBLISH news hello\r\n");
+        assert_eq!(output, b":2\r\n");
+
+        let mut encoded = Vec::new();
+        while let Ok(message) = messages.try_recv() {
+            message.encode_into(&mut encoded, subscriber.protocol);
+        }
+        assert_eq!(
+            encoded,
+            b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n\
+              *4\r\n$8\r\npmessage\r\n$2\r\nn*\r\n$4\r\nnews\r\n$5\r\nhello\r\n"
+        );
+
+        let (output, _) = run(&mut publisher, &storage, "PUBLISH weather rain\r\n");
+        assert_eq!(output, b":0\r\n");
+
+        let (output, _) = run(
+            &mut subscriber,
+            &storage,
+            "UNSUBSCRIBE\r\nPUNSUBSCRIBE n*\r\n",
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("*3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n"));
+        assert!(output.contains("*3\r\n$11\r\nunsubscribe\r\n$5\r\nsport\r\n"));
+        assert!(output.ends_with("*3\r\n$12\r\npunsubscribe\r\n$2\r\nn*\r\n:0\r\n"));
+
+        let (output, _) = run(&mut subscriber, &storage, "UNSUBSCRIBE\r\n");
+        assert_eq!(output, b"*3\r\n$11\r\nunsubscribe\r\n$-1\r\n:0\r\n");
+    }
+
+    #[test]
+    fn test_subscribed_resp2_clients_are_restricted() {
+        let storage = Mutex::new(Storage::new());
+        let mut client = Client::new();
+
+        let (output, _) = run(
+            &mut client,
+            &storage,
+            "SUBSCRIBE news\r\nGET key\r\nPING\r\nPING hi\r\n",
+        );
+        assert_eq!(
+            output,
+            b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n\
+              -ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / \
+              RESET are allowed in this context\r\n\
+              *2\r\n$4\r\npong\r\n$0\r\n\r\n\
+              *2\r\n$4\r\npong\r\n$2\r\nhi\r\n"
+        );
+
+        // RESP3 replies and messages have distinct types, so any command is allowed
+        let mut client = Client::new();
+        let (output, _) = run(
+            &mut client,
+            &storage,
+            "HELLO 3\r\nSUBSCRIBE sport\r\nGET key\r\nPING\r\n",
+        );
+        assert!(output.ends_with(b">3\r\n$9\r\nsubscribe\r\n$5\r\nsport\r\n:1\r\n_\r\n+PONG\r\n"));
+
+        lock(&storage).pubsub.remove_client(client.id);
+        assert_eq!(lock(&storage).pubsub.publish(b"sport", b"goal"), 0);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::hyperloglog::Corrupted;
+use crate::resp_result::RESPError;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum ServerError {
+    BusyGroup,
+    DecrementOverflow,
+    HashValueNotAFloat,
+    HashValueNotAnInteger,
+    IncompatibleOptions(String),
+    IncorrectData,
+    IncrementNaN,
+    IncrementOverflow,
+    IndexOutOfRange,
+    InvalidArgument(String),
+    InvalidBitOffset,
+    InvalidExpireTime(String),
+    InvalidHyperLogLog,
+    InvalidStreamId,
+    NoGroup(String),
+    NoProto,
+    NoSuchKey,
+    NotHyperLogLog,
+    NotAFloat,
+    NotAnInteger,
+    NotPositive,
+    OffsetOutOfRange,
+    Protocol(String),
+    StringTooLong,
+    Syntax,
+    TestFailed(String),
+    UnknownCommand(String, Vec<String>),
+    UnsupportedOption(String),
+    WrongArity(String),
+    WrongPass,
+    WrongType,
+}
+
+impl From<RESPError> for ServerError {
+    fn from(err: RESPError) -> Self {
+        Self::Protocol(err.to_string())
+    }
+}
+
+impl From<Corrupted> for ServerError {
+    fn from(_: Corrupted) -> Self {
+        Self::InvalidHyperLogLog
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::BusyGroup => write!(f, "BUSYGROUP Consumer Group name already exists"),
+            ServerError::DecrementOverflow => write!(f, "ERR decrement would overflow"),
+            ServerError::HashValueNotAFloat => write!(f, "ERR hash value is not a float"),
+            ServerError::HashValueNotAnInteger => write!(f, "ERR hash value is not an integer"),
+            ServerError::IncompatibleOptions(options) => {
+                write!(
+                    f,
+                    "ERR {} options at the same time are not compatible",
+                    options
+                )
+            }
+            ServerError::IncorrectData => write!(f, "ERR protocol error: invalid request format"),
+            ServerError::IncrementNaN => {
+                write!(f, "ERR increment would produce NaN or Infinity")
+            }
+            ServerError::IncrementOverflow => {
+                write!(f, "ERR increment or decrement would overflow")
+            }
+            ServerError::IndexOutOfRange => write!(f, "ERR index out of range"),
+            ServerError::InvalidArgument(message) => write!(f, "ERR {}", message),
+            ServerError::InvalidBitOffset => {
+                write!(f, "ERR bit offset is not an integer or out of range")
+            }
+            ServerError::InvalidExpireTime(command) => {
+                write!(f, "ERR invalid expire time in '{}' command", command)
+            }
+            ServerError::InvalidHyperLogLog => {
+                write!(f, "INVALIDOBJ Corrupted HLL object detected")
+            }
+            ServerError::InvalidStreamId => write!(
+                f,
+                "ERR Invalid stream ID specified as stream command argument"
+            ),
+            ServerError::NoGroup(message) => write!(f, "NOGROUP {}", message),
+            ServerError::NoSuchKey => write!(f, "ERR no such key"),
+            ServerError::NotHyperLogLog => {
+                write!(f, "WRONGTYPE Key is not a valid HyperLogLog string value.")
+            }
+            ServerError::NoProto => write!(f, "NOPROTO unsupported protocol version"),
+            ServerError::NotAFloat => write!(f, "ERR value is not a valid float"),
+            ServerError::NotAnInteger => write!(f, "ERR value is not an integer or out of range"),
+            ServerError::NotPositive => write!(f, "ERR value is out of range, must be positive"),
+            ServerError::OffsetOutOfRange => write!(f, "ERR offset is out of range"),
+            ServerError::Protocol(message) => write!(f, "ERR Protocol error: {}", message),
+            ServerError::StringTooLong => write!(
+                f,
+                "ERR string exceeds maximum allowed size (proto-max-bulk-len)"
+            ),
+            ServerError::Syntax => write!(f, "ERR syntax error"),
+            ServerError::TestFailed(message) => write!(f, "TESTFAILED {}", message),
+            ServerError::UnknownCommand(name, args) => {
+                write!(
+                    f,
+                    "ERR unknown command '{}', with args beginning with:",
+                    name
+                )?;
+                for arg in args {
+                    write!(f, " '{}'", arg)?;
+                }
+                Ok(())
+            }
+            ServerError::UnsupportedOption(option) => {
+                write!(f, "ERR Unsupported option {}", option)
+            }
+            ServerError::WrongArity(name) => {
+                write!(f, "ERR wrong number of arguments for '{}' command", name)
+            }
+            ServerError::WrongPass => write!(
+                f,
+                "WRONGPASS invalid username-password pair or user is disabled."
+            ),
+            ServerError::WrongType => write!(
+                f,
+                "WRONGTYPE Operation against a key holding the wrong kind of value"
+            ),
+        }
+    }
+}
+
+pub type ServerResult<T> = Result<T, ServerError>;
@@ -0,0 +1,549 @@
+use crate::dict::Dict;
+use rand::RngExt;
+use std::sync::Arc;
+
+/// Most levels a skiplist node can have, enough for 2^64 elements with p = 1/4
+const MAX_LEVEL: usize = 32;
+/// Index of the node holding the first link of every level, which is not an element
+const HEAD: usize = 0;
+/// Fewest free slots for which the arena is compacted, see [`SkipList::compact`]
+const MIN_COMPACTED_SLOTS: usize = 64;
+
+/// A link to the next node of a level, with the number of elements it skips over
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Level {
+    forward: Option<usize>,
+    /// Only meaningful when `forward` is set. The arithmetic on the spans of the last links wraps,
+    /// as in Redis, as their values are never read.
+    span: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    /// Shared with the member table of the sorted set
+    member: Arc<[u8]>,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Level>,
+}
+
+impl Node {
+    /// Whether the node is ordered before the given score and member
+    fn precedes(&self, score: f64, member: &[u8]) -> bool {
+        self.score < score || (self.score == score && &*self.member < member)
+    }
+}
+
+/// The elements of a sorted set ordered by score then member. The links of each level know how
+/// many elements they skip, which gives ranks in O(log n). Nodes live in an arena and refer to
+/// each other by index.
+#[derive(Debug, Clone, PartialEq)]
+struct SkipList {
+    nodes: Vec<Node>,
+    /// Indexes of the nodes removed from the list, reused by the next insertions
+    free: Vec<usize>,
+    tail: Option<usize>,
+    /// Number of levels in use
+    level: usize,
+    len: usize,
+}
+
+impl Default for SkipList {
+    fn default() -> Self {
+        let head = Node {
+            member: Arc::default(),
+            score: 0.0,
+            backward: None,
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0
+                };
+                MAX_LEVEL
+            ],
+        };
+
+        Self {
+            nodes: vec![head],
+            free: vec![],
+            tail: None,
+            level: 1,
+            len: 0,
+        }
+    }
+}
+
+impl SkipList {
+    /// Each node is given one more level with probability 1/4
+    fn random_level() -> usize {
+        let mut rng = rand::rng();
+        let mut level = 1;
+        while level < MAX_LEVEL && rng.random_range(0..4) == 0 {
+            level += 1;
+        }
+        level
+    }
+
+    fn allocate(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Insert an element that is not in the list yet
+    fn insert(&mut self, score: f64, member: Arc<[u8]>) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.nodes[x].levels[i].forward
+                && self.nodes[next].precedes(score, &member)
+            {
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let level = Self::random_level();
+        if level > self.level {
+            for i in self.level..level {
+                self.nodes[HEAD].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let id = self.allocate(Node {
+            member,
+            score,
+            backward: (update[0] != HEAD).then_some(update[0]),
+            levels: vec![
+                Level {
+                    forward: None,
+                    span: 0
+                };
+                level
+            ],
+        });
+
+        for i in 0..level {
+            let previous = self.nodes[update[i]].levels[i];
+            self.nodes[id].levels[i] = Level {
+                forward: previous.forward,
+                span: previous.span.wrapping_sub(rank[0] - rank[i]),
+            };
+            self.nodes[update[i]].levels[i] = Level {
+                forward: Some(id),
+                span: rank[0] - rank[i] + 1,
+            };
+        }
+        for (i, previous) in update.iter().enumerate().take(self.level).skip(level) {
+            let span = &mut self.nodes[*previous].levels[i].span;
+            *span = span.wrapping_add(1);
+        }
+
+        match self.nodes[id].levels[0].forward {
+            Some(next) => self.nodes[next].backward = Some(id),
+            None => self.tail = Some(id),
+        }
+        self.len += 1;
+    }
+
+    /// Remove an element, returning its member if it was in the list
+    fn remove(&mut self, score: f64, member: &[u8]) -> Option<Arc<[u8]>> {
+        let mut update = [HEAD; MAX_LEVEL];
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward
+                && self.nodes[next].precedes(score, member)
+            {
+                x = next;
+            }
+            update[i] = x;
+        }
+
+        let id = match self.nodes[x].levels[0].forward {
+            Some(next)
+                if self.nodes[next].score == score && &*self.nodes[next].member == member =>
+            {
+                next
+            }
+            _ => return None,
+        };
+
+        for (i, previous) in update.iter().enumerate().take(self.level) {
+            let removed = self.nodes[id].levels.get(i).copied();
+            let previous = &mut self.nodes[*previous].levels[i];
+
+            match removed {
+                Some(removed) if previous.forward == Some(id) => {
+                    previous.span = previous.span.wrapping_add(removed.span).wrapping_sub(1);
+                    previous.forward = removed.forward;
+                }
+                _ => previous.span = previous.span.wrapping_sub(1),
+            }
+        }
+
+        match self.nodes[id].levels[0].forward {
+            Some(next) => self.nodes[next].backward = self.nodes[id].backward,
+            None => self.tail = self.nodes[id].backward,
+        }
+        while self.level > 1 && self.nodes[HEAD].levels[self.level - 1].forward.is_none() {
+            self.level -= 1;
+        }
+
+        // The node is reused by a later insertion, or dropped when the arena is compacted
+        let member = std::mem::take(&mut self.nodes[id].member);
+        self.nodes[id].levels = vec![];
+        self.free.push(id);
+        self.len -= 1;
+
+        if self.free.len() >= MIN_COMPACTED_SLOTS && self.free.len() > 3 * self.len {
+            self.compact();
+        }
+        Some(member)
+    }
+
+    /// Move the nodes to an arena without free slots, in list order, so that a list which
+    /// shrank gives its memory back
+    fn compact(&mut self) {
+        let mut order = Vec::with_capacity(self.len + 1);
+        let mut node = Some(HEAD);
+        while let Some(id) = node {
+            order.push(id);
+            node = self.nodes[id].levels[0].forward;
+        }
+
+        let mut new_ids = vec![HEAD; self.nodes.len()];
+        for (new_id, &id) in order.iter().enumerate() {
+            new_ids[id] = new_id;
+        }
+
+        let mut nodes = Vec::with_capacity(order.len());
+        for id in order {
+            let mut node = std::mem::replace(
+                &mut self.nodes[id],
+                Node {
+                    member: Arc::default(),
+                    score: 0.0,
+                    backward: None,
+                    levels: vec![],
+                },
+            );
+            node.backward = node.backward.map(|id| new_ids[id]);
+            for level in &mut node.levels {
+                level.forward = level.forward.map(|id| new_ids[id]);
+            }
+            nodes.push(node);
+        }
+
+        self.tail = self.tail.map(|id| new_ids[id]);
+        self.nodes = nodes;
+        self.free = vec![];
+    }
+
+    /// Number of elements at the start of the list for which the predicate holds. The predicate
+    /// must hold for a prefix of the list only, as for "is below a bound".
+    fn count_while(&self, predicate: impl Fn(f64, &[u8]) -> bool) -> usize {
+        let mut count = 0;
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward
+                && predicate(self.nodes[next].score, &self.nodes[next].member)
+            {
+                count += self.nodes[x].levels[i].span;
+                x = next;
+            }
+        }
+
+        count
+    }
+
+    /// The node of the element at the 0-based rank
+    fn node_at_rank(&self, rank: usize) -> Option<usize> {
+        // Ranks counted from the head are 1-based
+        let target = rank + 1;
+        let mut traversed = 0;
+
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.nodes[x].levels[i].forward
+                && traversed + self.nodes[x].levels[i].span <= target
+            {
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+
+        None
+    }
+
+    /// The elements with ranks from `start` to `end` excluded, backwards if `reverse`
+    fn range(&self, start: usize, end: usize, reverse: bool) -> impl Iterator<Item = (&[u8], f64)> {
+        let end = end.min(self.len);
+        let count = end.saturating_sub(start);
+
+        let mut node = match count {
+            0 => None,
+            _ if reverse => self.node_at_rank(end - 1),
+            _ => self.node_at_rank(start),
+        };
+
+        std::iter::from_fn(move || {
+            let current = &self.nodes[node?];
+            node = if reverse {
+                current.backward
+            } else {
+                current.levels[0].forward
+            };
+            Some((&*current.member, current.score))
+        })
+        .take(count)
+    }
+}
+
+/// A set of members ordered by score. Scores are looked up by member in O(1), while ranks and
+/// ranges are found through a skiplist in O(log n).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortedSet {
+    /// The skiplist nodes share the members of this table rather than copying them
+    scores: Dict<Arc<[u8]>, f64>,
+    list: SkipList,
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Set the score of a member, returning true if the member is new
+    pub fn insert(&mut self, member: &[u8], score: f64) -> bool {
+        match self.scores.get_mut(member) {
+            Some(current) => {
+                if *current != score {
+                    let member = self
+                        .list
+                        .remove(*current, member)
+                        .expect("members of the table are in the list");
+                    self.list.insert(score, member);
+                    *current = score;
+                }
+                false
+            }
+            None => {
+                let member: Arc<[u8]> = Arc::from(member);
+                self.scores.insert(member.clone(), score);
+                self.list.insert(score, member);
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.list.remove(score, member).is_some(),
+            None => false,
+        }
+    }
+
+    /// 0-based position of the member in ascending order
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        let score = self.score(member)?;
+        Some(self.list.count_while(|other_score, other_member| {
+            other_score < score || (other_score == score && other_member < member)
+        }))
+    }
+
+    /// Number of the lowest elements for which the predicate holds, see [`SkipList::count_while`]
+    pub fn count_while(&self, predicate: impl Fn(f64, &[u8]) -> bool) -> usize {
+        self.list.count_while(predicate)
+    }
+
+    /// The elements with ranks from `start` to `end` excluded, in descending order if `reverse`
+    pub fn range(
+        &self,
+        start: usize,
+        end: usize,
+        reverse: bool,
+    ) -> impl Iterator<Item = (&[u8], f64)> {
+        self.list.range(start, end, reverse)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], f64)> {
+        self.range(0, self.len(), false)
+    }
+
+    /// Visit the members of a bucket of the member table, see [`Dict::scan`]
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&[u8], f64)) -> u64 {
+        self.scores
+            .scan(cursor, |member, score| visit(member, *score))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(zset: &SortedSet) -> Vec<(String, f64)> {
+        zset.iter()
+            .map(|(member, score)| (String::from_utf8(member.to_vec()).unwrap(), score))
+            .collect()
+    }
+
+    #[test]
+    fn test_order_by_score_then_member() {
+        let mut zset = SortedSet::default();
+        assert!(zset.insert(b"c", 1.0));
+        assert!(zset.insert(b"a", 2.0));
+        assert!(zset.insert(b"b", 1.0));
+        assert!(!zset.insert(b"c", 3.0));
+
+        assert_eq!(
+            members(&zset),
+            vec![
+                (String::from("b"), 1.0),
+                (String::from("a"), 2.0),
+                (String::from("c"), 3.0)
+            ]
+        );
+        assert_eq!(zset.rank(b"b"), Some(0));
+        assert_eq!(zset.rank(b"c"), Some(2));
+        assert_eq!(zset.rank(b"x"), None);
+    }
+
+    #[test]
+    fn test_ranks_and_ranges_on_many_elements() {
+        let mut zset = SortedSet::default();
+        for i in (0..1000).rev() {
+            zset.insert(format!("{:04}", i).as_bytes(), i as f64);
+        }
+        for i in (0..1000).step_by(3) {
+            assert!(zset.remove(format!("{:04}", i).as_bytes()));
+        }
+
+        let expected: Vec<usize> = (0..1000).filter(|i| i % 3 != 0).collect();
+        assert_eq!(zset.len(), expected.len());
+
+        for (rank, i) in expected.iter().enumerate() {
+            assert_eq!(zset.rank(format!("{:04}", i).as_bytes()), Some(rank));
+        }
+
+        let range: Vec<f64> = zset.range(10, 15, false).map(|(_, score)| score).collect();
+        assert_eq!(range, vec![16.0, 17.0, 19.0, 20.0, 22.0]);
+        let range: Vec<f64> = zset.range(10, 13, true).map(|(_, score)| score).collect();
+        assert_eq!(range, vec![19.0, 17.0, 16.0]);
+
+        assert_eq!(zset.count_while(|score, _| score < 100.0), 66);
+        assert_eq!(zset.range(660, 700, false).count(), 6);
+    }
+
+    #[test]
+    fn test_remove_everything() {
+        let mut zset = SortedSet::default();
+        for i in 0..100 {
+            zset.insert(format!("{}", i).as_bytes(), (i % 7) as f64);
+        }
+        for i in 0..100 {
+            assert!(zset.remove(format!("{}", i).as_bytes()));
+        }
+
+        assert!(zset.is_empty());
+        assert_eq!(zset.iter().count(), 0);
+        assert!(!zset.remove(b"0"));
+
+        zset.insert(b"a", 1.0);
+        assert_eq!(members(&zset), vec![(String::from("a"), 1.0)]);
+    }
+
+    #[test]
+    fn test_removals_shrink_the_arena() {
+        let mut zset = SortedSet::default();
+        for i in 0..1000 {
+            zset.insert(format!("{:04}", i).as_bytes(), i as f64);
+        }
+        for i in 10..1000 {
+            assert!(zset.remove(format!("{:04}", i).as_bytes()));
+        }
+
+        assert!(zset.list.nodes.len() < 100);
+        assert_eq!(zset.rank(b"0009"), Some(9));
+        assert_eq!(zset.range(8, 10, true).next(), Some((&b"0009"[..], 9.0)));
+
+        zset.insert(b"0005", 100.0);
+        assert_eq!(zset.rank(b"0005"), Some(9));
+    }
+
+    #[test]
+    fn test_members_are_shared_with_the_list() {
+        let mut zset = SortedSet::default();
+        zset.insert(b"member", 1.0);
+        zset.insert(b"member", 2.0);
+
+        let (member, _) = zset.scores.iter().next().unwrap();
+        assert_eq!(Arc::strong_count(member), 2);
+    }
+
+    #[test]
+    fn test_matches_a_sorted_vector() {
+        let mut rng = rand::rng();
+        let mut zset = SortedSet::default();
+        let mut expected: Vec<(f64, Vec<u8>)> = vec![];
+
+        for _ in 0..5000 {
+            let member = format!("{}", rng.random_range(0..300)).into_bytes();
+            let score = rng.random_range(0..50) as f64;
+
+            expected.retain(|(_, other)| *other != member);
+            if rng.random_range(0..3) == 0 {
+                zset.remove(&member);
+            } else {
+                zset.insert(&member, score);
+                expected.push((score, member));
+            }
+        }
+
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let actual: Vec<(f64, Vec<u8>)> = zset
+            .iter()
+            .map(|(member, score)| (score, member.to_vec()))
+            .collect();
+        assert_eq!(actual, expected);
+
+        for (rank, (_, member)) in expected.iter().enumerate() {
+            assert_eq!(zset.rank(member), Some(rank));
+            assert_eq!(zset.range(rank, rank + 1, false).next().unwrap().0, member);
+        }
+        let reversed: Vec<&[u8]> = zset
+            .range(0, zset.len(), true)
+            .map(|(member, _)| member)
+            .collect();
+        let mut members: Vec<&[u8]> = expected
+            .iter()
+            .map(|(_, member)| member.as_slice())
+            .collect();
+        members.reverse();
+        assert_eq!(reversed, members);
+    }
+}
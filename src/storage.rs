@@ -0,0 +1,847 @@
+use crate::blocking::BlockedClients;
+use crate::dict::Dict;
+use crate::pubsub::PubSub;
+use crate::server_result::{ServerError, ServerResult};
+use crate::sorted_set::SortedSet;
+use crate::stream::Stream;
+use rand::RngExt;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub type Key = Vec<u8>;
+
+/// Number of databases, which clients switch between with SELECT
+pub const DATABASES: usize = 16;
+
+/// Largest string value that can be stored, as in Redis (512MB)
+pub const MAX_STRING_LENGTH: usize = 512 * 1024 * 1024;
+
+/// Field of a hash
+pub type Field = Vec<u8>;
+
+/// Members of a set, which have no value attached
+pub type Set = Dict<Vec<u8>, ()>;
+
+/// Milliseconds since the Unix epoch, the unit used for all expiration times
+pub fn current_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    String(Vec<u8>),
+    List(VecDeque<Vec<u8>>),
+    Hash(Hash),
+    Set(Set),
+    SortedSet(SortedSet),
+    Stream(Stream),
+}
+
+/// The type of a value, without the data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueType {
+    String,
+    List,
+    Hash,
+    Set,
+    SortedSet,
+    Stream,
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::String(_) => ValueType::String,
+            Value::List(_) => ValueType::List,
+            Value::Hash(_) => ValueType::Hash,
+            Value::Set(_) => ValueType::Set,
+            Value::SortedSet(_) => ValueType::SortedSet,
+            Value::Stream(_) => ValueType::Stream,
+        }
+    }
+
+    /// Whether the value is an empty collection, which must not be kept in the keyspace
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Value::String(_) => false,
+            Value::List(list) => list.is_empty(),
+            Value::Hash(hash) => hash.is_empty(),
+            Value::Set(set) => set.is_empty(),
+            Value::SortedSet(zset) => zset.is_empty(),
+            // Empty streams are kept, with their consumer groups and last identifier
+            Value::Stream(_) => false,
+        }
+    }
+}
+
+/// A hash whose fields may expire independently. The accessors only check the time to live of
+/// the fields they touch, expired fields are hidden until they are removed, either when written
+/// or by the active expiry of the keyspace, see [`Db::remove_expired`].
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Hash {
+    fields: Dict<Field, Vec<u8>>,
+    /// Expiration times in milliseconds of the fields that have one
+    expires: HashMap<Field, u64>,
+}
+
+/// Picks of an expired field tolerated by [`Hash::random_entry`] before it walks the fields
+const RANDOM_ENTRY_ATTEMPTS: usize = 16;
+
+impl Hash {
+    /// Number of fields, including the expired ones not removed yet, as HLEN counts them in Redis
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Whether the time to live of the field has elapsed
+    fn is_expired(&self, field: &[u8], now: u64) -> bool {
+        self.expires
+            .get(field)
+            .is_some_and(|&expires_at| expires_at <= now)
+    }
+
+    /// Number of fields whose time to live has not elapsed, which takes a walk over the fields
+    /// when some of them have one
+    pub fn live_len(&self) -> usize {
+        match self.expires.is_empty() {
+            true => self.fields.len(),
+            false => self.iter().count(),
+        }
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&Vec<u8>> {
+        self.fields
+            .get(field)
+            .filter(|_| !self.is_expired(field, current_time_ms()))
+    }
+
+    pub fn contains_key(&self, field: &[u8]) -> bool {
+        self.get(field).is_some()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Field> {
+        self.iter().map(|(field, _)| field)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Field, &Vec<u8>)> {
+        let now = current_time_ms();
+        self.fields
+            .iter()
+            .filter(move |(field, _)| !self.is_expired(field, now))
+    }
+
+    /// Set a field, discarding its time to live. Returns true if the field is new.
+    pub fn insert(&mut self, field: &[u8], value: Vec<u8>) -> bool {
+        let expired = self.is_expired(field, current_time_ms());
+        self.expires.remove(field);
+        self.fields.insert(field.to_vec(), value).is_none() || expired
+    }
+
+    /// Set a field, retaining its time to live unless it elapsed. Returns true if the field is
+    /// new.
+    pub fn insert_keep_ttl(&mut self, field: &[u8], value: Vec<u8>) -> bool {
+        if self.is_expired(field, current_time_ms()) {
+            return self.insert(field, value);
+        }
+        self.fields.insert(field.to_vec(), value).is_none()
+    }
+
+    /// Remove a field, returning its value unless it expired
+    pub fn remove(&mut self, field: &[u8]) -> Option<Vec<u8>> {
+        let expired = self.is_expired(field, current_time_ms());
+        self.expires.remove(field);
+        self.fields.remove(field).filter(|_| !expired)
+    }
+
+    /// A field picked at random with its value, see [`Dict::random_entry`]. Expired fields are
+    /// picked again, a few times before falling back to a walk over the live ones.
+    pub fn random_entry(&self) -> Option<(&Field, &Vec<u8>)> {
+        if self.expires.is_empty() {
+            return self.fields.random_entry();
+        }
+
+        let now = current_time_ms();
+        for _ in 0..RANDOM_ENTRY_ATTEMPTS {
+            let (field, value) = self.fields.random_entry()?;
+            if !self.is_expired(field, now) {
+                return Some((field, value));
+            }
+        }
+
+        match self.live_len() {
+            0 => None,
+            live => self.iter().nth(rand::rng().random_range(0..live)),
+        }
+    }
+
+    /// Visit the fields of a bucket, see [`Dict::scan`]
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&Field, &Vec<u8>)) -> u64 {
+        let now = current_time_ms();
+        self.fields.scan(cursor, |field, value| {
+            if !self.is_expired(field, now) {
+                visit(field, value);
+            }
+        })
+    }
+
+    /// Absolute expiration time of the field in milliseconds, `None` for persistent fields
+    pub fn expires_at(&self, field: &[u8]) -> Option<u64> {
+        self.expires.get(field).copied()
+    }
+}
+
+/// Access frequency counter of new keys, so that they get a chance to be accessed again
+const LFU_INIT_VAL: u8 = 5;
+/// How hard it gets to increment the access frequency counter as it grows
+const LFU_LOG_FACTOR: f64 = 10.0;
+/// Idle time after which the access frequency counter is decremented, in milliseconds
+const LFU_DECAY_TIME: u64 = 60 * 1000;
+
+#[derive(Debug)]
+pub struct Entry {
+    pub value: Value,
+    /// Absolute expiration time in milliseconds, `None` for persistent keys
+    pub expires_at: Option<u64>,
+    /// Time of the last access in milliseconds
+    pub accessed_at: u64,
+    /// Logarithmic access frequency counter, as maintained by Redis for LFU eviction
+    frequency: u8,
+}
+
+impl Entry {
+    fn new(value: Value) -> Self {
+        Self {
+            value,
+            expires_at: None,
+            accessed_at: current_time_ms(),
+            frequency: LFU_INIT_VAL,
+        }
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// The access frequency counter, decremented for every period the key was left idle
+    pub fn frequency(&self, now: u64) -> u8 {
+        let periods = now.saturating_sub(self.accessed_at) / LFU_DECAY_TIME;
+        self.frequency
+            .saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+
+    /// Record an access. The counter is incremented with a probability decreasing as it grows,
+    /// so that it can tell apart keys accessed from a few times to millions of times.
+    fn touch(&mut self, now: u64) {
+        let mut frequency = self.frequency(now);
+        if frequency < u8::MAX {
+            let base = frequency.saturating_sub(LFU_INIT_VAL) as f64;
+            if rand::random::<f64>() < 1.0 / (base * LFU_LOG_FACTOR + 1.0) {
+                frequency += 1;
+            }
+        }
+
+        self.frequency = frequency;
+        self.accessed_at = now;
+    }
+}
+
+/// A keyspace. Expired keys are removed lazily, when they are accessed, and actively by
+/// [`Db::remove_expired`], which walks the keys in order of expiration time.
+#[derive(Debug, Default)]
+pub struct Db {
+    entries: Dict<Key, Entry>,
+    /// Keys with a time to live, ordered by expiration time
+    expires: BTreeSet<(u64, Key)>,
+    /// Hash fields with a time to live, ordered by expiration time. Entries may be stale when
+    /// a field was overwritten or deleted, they are checked against the hash when they are due.
+    field_expires: BTreeSet<(u64, Key, Field)>,
+    /// Identifiers of the clients blocked on each key, in the order they blocked
+    blocking_keys: HashMap<Key, VecDeque<u64>>,
+    /// Keys with blocked clients that received data since blocked clients were last served
+    ready_keys: Vec<Key>,
+}
+
+impl Db {
+    /// Remove an entry along with its expiration time
+    fn remove_entry(&mut self, key: &[u8]) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        if let Some(expires_at) = entry.expires_at {
+            self.expires.remove(&(expires_at, key.to_vec()));
+        }
+        if let Value::Hash(hash) = &entry.value {
+            for (field, expires_at) in &hash.expires {
+                self.field_expires
+                    .remove(&(*expires_at, key.to_vec(), field.clone()));
+            }
+        }
+        Some(entry)
+    }
+
+    /// Remove the key if its time to live has elapsed
+    fn expire_if_needed(&mut self, key: &[u8]) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.is_expired(current_time_ms()))
+        {
+            self.remove_entry(key);
+        }
+    }
+
+    fn get_entry_mut(&mut self, key: &[u8]) -> Option<&mut Entry> {
+        self.expire_if_needed(key);
+
+        let entry = self.entries.get_mut(key)?;
+        entry.touch(current_time_ms());
+        Some(entry)
+    }
+
+    /// Look an entry up without counting it as an access, for introspection commands
+    pub fn peek_entry(&mut self, key: &[u8]) -> Option<&Entry> {
+        self.expire_if_needed(key);
+        self.entries.get(key)
+    }
+
+    /// Expiration times can only be changed through [`Db::set_expiry`], to keep them indexed
+    pub fn get_entry(&mut self, key: &[u8]) -> Option<&Entry> {
+        self.get_entry_mut(key).map(|entry| &*entry)
+    }
+
+    pub fn get(&mut self, key: &[u8]) -> Option<&Value> {
+        self.get_entry(key).map(|entry| &entry.value)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Value> {
+        self.get_entry_mut(key).map(|entry| &mut entry.value)
+    }
+
+    pub fn contains_key(&mut self, key: &[u8]) -> bool {
+        self.get_entry(key).is_some()
+    }
+
+    /// Store a value, discarding any previous value and time to live
+    pub fn insert(&mut self, key: &[u8], value: Value) {
+        self.remove_entry(key);
+
+        // Clients may be blocked waiting for a collection to appear under this key
+        if matches!(
+            value,
+            Value::List(_) | Value::SortedSet(_) | Value::Stream(_)
+        ) {
+            self.signal_ready(key);
+        }
+        // Hashes copied or moved from another key keep the time to live of their fields
+        if let Value::Hash(hash) = &value {
+            for (field, expires_at) in &hash.expires {
+                self.field_expires
+                    .insert((*expires_at, key.to_vec(), field.clone()));
+            }
+        }
+        self.entries.insert(key.to_vec(), Entry::new(value));
+    }
+
+    /// Store a value, retaining the time to live of the previous value if any
+    pub fn insert_keep_ttl(&mut self, key: &[u8], value: Value) {
+        let entry = match self.get_entry_mut(key) {
+            Some(entry) => entry,
+            None => return self.insert(key, value),
+        };
+
+        // The time to live of hash fields is indexed like in `insert` and `remove_entry`
+        if let Value::Hash(hash) = std::mem::replace(&mut entry.value, value) {
+            for (field, expires_at) in hash.expires {
+                self.field_expires
+                    .remove(&(expires_at, key.to_vec(), field));
+            }
+        }
+        if let Some(Entry {
+            value: Value::Hash(hash),
+            ..
+        }) = self.entries.get(key)
+        {
+            for (field, expires_at) in &hash.expires {
+                self.field_expires
+                    .insert((*expires_at, key.to_vec(), field.clone()));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Value> {
+        self.take(key).map(|(value, _)| value)
+    }
+
+    /// Remove a key, returning its value and expiration time
+    pub fn take(&mut self, key: &[u8]) -> Option<(Value, Option<u64>)> {
+        self.expire_if_needed(key);
+        self.remove_entry(key)
+            .map(|entry| (entry.value, entry.expires_at))
+    }
+
+    /// Number of keys, including those expired but not reclaimed yet
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The keys whose time to live has not elapsed, expired keys are left to the active expiry
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        let now = current_time_ms();
+        self.entries
+            .iter()
+            .filter(move |(_, entry)| !entry.is_expired(now))
+            .map(|(key, _)| key)
+    }
+
+    /// Visit the keys of a bucket, see [`Dict::scan`]. Expired keys are visited as well.
+    pub fn scan(&self, cursor: u64, mut visit: impl FnMut(&Key)) -> u64 {
+        self.entries.scan(cursor, |key, _| visit(key))
+    }
+
+    /// A key picked at random. Expired keys met along the way are removed.
+    pub fn random_key(&mut self) -> Option<Key> {
+        let now = current_time_ms();
+
+        loop {
+            let (key, entry) = self.entries.random_entry()?;
+            let key = key.clone();
+            if !entry.is_expired(now) {
+                return Some(key);
+            }
+            self.remove_entry(&key);
+        }
+    }
+
+    /// Set or clear the expiration time of an existing key, deleting it if the time is in the past
+    pub fn set_expiry(&mut self, key: &[u8], expires_at: Option<u64>) {
+        let now = current_time_ms();
+
+        let previous = match self.get_entry_mut(key) {
+            Some(entry) => std::mem::replace(&mut entry.expires_at, expires_at),
+            None => return,
+        };
+
+        if let Some(previous) = previous {
+            self.expires.remove(&(previous, key.to_vec()));
+        }
+
+        match expires_at {
+            Some(expires_at) if expires_at <= now => {
+                self.remove_entry(key);
+            }
+            Some(expires_at) => {
+                self.expires.insert((expires_at, key.to_vec()));
+            }
+            None => {}
+        }
+    }
+
+    /// Remove up to `limit` keys and hash fields whose time to live has elapsed, returning how
+    /// many were removed
+    pub fn remove_expired(&mut self, now: u64, limit: usize) -> usize {
+        let mut removed = 0;
+
+        while removed < limit {
+            match self.expires.first() {
+                Some((expires_at, _)) if *expires_at <= now => {
+                    let (_, key) = self.expires.pop_first().expect("the set is not empty");
+                    self.remove_entry(&key);
+                    removed += 1;
+                }
+                _ => break,
+            }
+        }
+
+        while removed < limit {
+            match self.field_expires.first() {
+                Some((expires_at, _, _)) if *expires_at <= now => {
+                    let (expires_at, key, field) = self
+                        .field_expires
+                        .pop_first()
+                        .expect("the set is not empty");
+                    self.remove_expired_field(&key, &field, expires_at);
+                    removed += 1;
+                }
+                _ => break,
+            }
+        }
+
+        removed
+    }
+
+    /// Remove a hash field if it still expires at the given time, and the hash if it is left empty
+    fn remove_expired_field(&mut self, key: &[u8], field: &[u8], expires_at: u64) {
+        if let Some(Entry {
+            value: Value::Hash(hash),
+            ..
+        }) = self.entries.get_mut(key)
+            && hash.expires_at(field) == Some(expires_at)
+        {
+            hash.remove(field);
+            self.remove_if_empty(key);
+        }
+    }
+
+    /// Set or clear the expiration time of an existing hash field, deleting it if the time is in
+    /// the past. The hash is deleted as well if it is left empty.
+    pub fn set_field_expiry(&mut self, key: &[u8], field: &[u8], expires_at: Option<u64>) {
+        let now = current_time_ms();
+
+        let hash = match self.get_hash_mut(key) {
+            Ok(Some(hash)) if hash.contains_key(field) => hash,
+            _ => return,
+        };
+
+        let previous = match expires_at {
+            Some(expires_at) if expires_at <= now => {
+                let previous = hash.expires_at(field);
+                hash.remove(field);
+                previous
+            }
+            Some(expires_at) => hash.expires.insert(field.to_vec(), expires_at),
+            None => hash.expires.remove(field),
+        };
+
+        if let Some(previous) = previous {
+            self.field_expires
+                .remove(&(previous, key.to_vec(), field.to_vec()));
+        }
+        if let Some(expires_at) = expires_at.filter(|&expires_at| expires_at > now) {
+            self.field_expires
+                .insert((expires_at, key.to_vec(), field.to_vec()));
+        }
+
+        self.remove_if_empty(key);
+    }
+
+    /// Remove the key if it holds an empty collection, after elements were removed from it
+    pub fn remove_if_empty(&mut self, key: &[u8]) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.value.is_empty())
+        {
+            self.remove_entry(key);
+        }
+    }
+
+    /// Register a blocked client as waiting for data on the key
+    pub fn block_on(&mut self, key: &[u8], client_id: u64) {
+        self.blocking_keys
+            .entry(key.to_vec())
+            .or_default()
+            .push_back(client_id);
+    }
+
+    pub fn unblock_from(&mut self, key: &[u8], client_id: u64) {
+        if let Some(clients) = self.blocking_keys.get_mut(key) {
+            clients.retain(|&id| id != client_id);
+
+            if clients.is_empty() {
+                self.blocking_keys.remove(key);
+            }
+        }
+    }
+
+    /// Clients blocked on the key, in the order they blocked
+    pub fn blocked_on(&self, key: &[u8]) -> Vec<u64> {
+        self.blocking_keys
+            .get(key)
+            .map_or_else(Vec::new, |clients| clients.iter().copied().collect())
+    }
+
+    /// Record that data was added to the key, so that the clients blocked on it can be served
+    pub fn signal_ready(&mut self, key: &[u8]) {
+        if self.blocking_keys.contains_key(key) && !self.ready_keys.iter().any(|ready| ready == key)
+        {
+            self.ready_keys.push(key.to_vec());
+        }
+    }
+
+    pub fn take_ready_keys(&mut self) -> Vec<Key> {
+        std::mem::take(&mut self.ready_keys)
+    }
+
+    pub fn get_string(&mut self, key: &[u8]) -> ServerResult<Option<&Vec<u8>>> {
+        match self.get(key) {
+            Some(Value::String(data)) => Ok(Some(data)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_string_mut(&mut self, key: &[u8]) -> ServerResult<Option<&mut Vec<u8>>> {
+        match self.get_mut(key) {
+            Some(Value::String(data)) => Ok(Some(data)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_list(&mut self, key: &[u8]) -> ServerResult<Option<&VecDeque<Vec<u8>>>> {
+        match self.get(key) {
+            Some(Value::List(list)) => Ok(Some(list)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_list_mut(&mut self, key: &[u8]) -> ServerResult<Option<&mut VecDeque<Vec<u8>>>> {
+        match self.get_mut(key) {
+            Some(Value::List(list)) => Ok(Some(list)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_set(&mut self, key: &[u8]) -> ServerResult<Option<&Set>> {
+        match self.get(key) {
+            Some(Value::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_set_mut(&mut self, key: &[u8]) -> ServerResult<Option<&mut Set>> {
+        match self.get_mut(key) {
+            Some(Value::Set(set)) => Ok(Some(set)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// The values stored at several keys at once, borrowed rather than copied
+    pub fn get_values(&mut self, keys: &[&[u8]]) -> Vec<Option<&Value>> {
+        for key in keys {
+            self.expire_if_needed(key);
+        }
+
+        keys.iter()
+            .map(|key| self.entries.get(*key).map(|entry| &entry.value))
+            .collect()
+    }
+
+    /// The sets stored at several keys at once, borrowed rather than copied
+    pub fn get_sets(&mut self, keys: &[&[u8]]) -> ServerResult<Vec<Option<&Set>>> {
+        self.get_values(keys)
+            .into_iter()
+            .map(|value| match value {
+                Some(Value::Set(set)) => Ok(Some(set)),
+                Some(_) => Err(ServerError::WrongType),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    pub fn get_sorted_set(&mut self, key: &[u8]) -> ServerResult<Option<&SortedSet>> {
+        match self.get(key) {
+            Some(Value::SortedSet(zset)) => Ok(Some(zset)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_sorted_set_mut(&mut self, key: &[u8]) -> ServerResult<Option<&mut SortedSet>> {
+        match self.get_mut(key) {
+            Some(Value::SortedSet(zset)) => Ok(Some(zset)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_stream(&mut self, key: &[u8]) -> ServerResult<Option<&Stream>> {
+        match self.get(key) {
+            Some(Value::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_stream_mut(&mut self, key: &[u8]) -> ServerResult<Option<&mut Stream>> {
+        match self.get_mut(key) {
+            Some(Value::Stream(stream)) => Ok(Some(stream)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_hash(&mut self, key: &[u8]) -> ServerResult<Option<&Hash>> {
+        match self.get(key) {
+            Some(Value::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_hash_mut(&mut self, key: &[u8]) -> ServerResult<Option<&mut Hash>> {
+        match self.get_mut(key) {
+            Some(Value::Hash(hash)) => Ok(Some(hash)),
+            Some(_) => Err(ServerError::WrongType),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The data owned by the server and shared by all connections
+#[derive(Debug)]
+pub struct Storage {
+    /// The databases, by index
+    pub dbs: Vec<Db>,
+    pub blocked_clients: BlockedClients,
+    pub pubsub: PubSub,
+}
+
+impl Storage {
+    pub fn new() -> Self {
+        Self {
+            dbs: (0..DATABASES).map(|_| Db::default()).collect(),
+            blocked_clients: BlockedClients::default(),
+            pubsub: PubSub::default(),
+        }
+    }
+}
+
+/// Lock the storage shared by all connections. A command that panicked while holding the lock
+/// may have left the storage half updated, for instance with the expiry indexes out of sync with
+/// the keys. Serving it could corrupt the data further, so the whole server stops instead.
+pub fn lock(storage: &Mutex<Storage>) -> MutexGuard<'_, Storage> {
+    storage.lock().unwrap_or_else(|_| {
+        eprintln!("A command panicked while updating the storage, exiting");
+        std::process::exit(1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_get() {
+        let mut db = Db::default();
+        db.insert(b"key", Value::String(b"value".to_vec()));
+
+        assert_eq!(db.get(b"key"), Some(&Value::String(b"value".to_vec())));
+        assert_eq!(db.get(b"missing"), None);
+    }
+
+    #[test]
+    fn test_expired_keys_are_removed_on_access() {
+        let mut db = Db::default();
+        db.insert(b"key", Value::String(b"value".to_vec()));
+        db.set_expiry(b"key", Some(current_time_ms() + 10_000));
+
+        // Pretend the time has elapsed
+        let expires_at = current_time_ms() - 1;
+        db.entries.get_mut(b"key".as_slice()).unwrap().expires_at = Some(expires_at);
+
+        assert!(!db.contains_key(b"key"));
+        assert!(db.entries.is_empty());
+    }
+
+    #[test]
+    fn test_set_expiry_in_the_past_deletes_the_key() {
+        let mut db = Db::default();
+        db.insert(b"key", Value::String(b"value".to_vec()));
+        db.set_expiry(b"key", Some(1));
+
+        assert!(db.entries.is_empty());
+        assert!(db.expires.is_empty());
+    }
+
+    #[test]
+    fn test_set_expiry_in_the_past_deletes_the_field_expiries() {
+        let mut db = Db::default();
+        let mut hash = Hash::default();
+        hash.insert(b"field", b"value".to_vec());
+        db.insert(b"hash", Value::Hash(hash));
+        db.set_field_expiry(b"hash", b"field", Some(current_time_ms() + 10_000));
+        assert_eq!(db.field_expires.len(), 1);
+
+        db.set_expiry(b"hash", Some(1));
+
+        assert!(db.entries.is_empty());
+        assert!(db.field_expires.is_empty());
+    }
+
+    #[test]
+    fn test_insert_keep_ttl() {
+        let mut db = Db::default();
+        let expires_at = current_time_ms() + 10_000;
+        db.insert(b"key", Value::String(b"old".to_vec()));
+        db.set_expiry(b"key", Some(expires_at));
+
+        db.insert_keep_ttl(b"key", Value::String(b"new".to_vec()));
+        assert_eq!(db.get_entry(b"key").unwrap().expires_at, Some(expires_at));
+
+        db.insert(b"key", Value::String(b"newer".to_vec()));
+        assert_eq!(db.get_entry(b"key").unwrap().expires_at, None);
+    }
+
+    #[test]
+    fn test_insert_keep_ttl_indexes_field_expiries() {
+        let mut db = Db::default();
+        let expires_at = current_time_ms() + 10_000;
+        let mut hash = Hash::default();
+        hash.insert(b"field", b"value".to_vec());
+        hash.expires.insert(b"field".to_vec(), expires_at);
+
+        db.insert(b"key", Value::String(b"old".to_vec()));
+        db.insert_keep_ttl(b"key", Value::Hash(hash.clone()));
+        assert_eq!(
+            db.field_expires.first(),
+            Some(&(expires_at, b"key".to_vec(), b"field".to_vec()))
+        );
+
+        db.insert_keep_ttl(b"key", Value::String(b"new".to_vec()));
+        assert!(db.field_expires.is_empty());
+
+        db.insert_keep_ttl(b"other", Value::Hash(hash));
+        assert_eq!(db.field_expires.len(), 1);
+    }
+
+    #[test]
+    fn test_expiry_index_follows_the_keys() {
+        let mut db = Db::default();
+        let expires_at = current_time_ms() + 10_000;
+        db.insert(b"key", Value::String(b"value".to_vec()));
+
+        db.set_expiry(b"key", Some(expires_at));
+        assert_eq!(db.expires.len(), 1);
+
+        db.set_expiry(b"key", Some(expires_at + 1));
+        assert_eq!(db.expires.first(), Some(&(expires_at + 1, b"key".to_vec())));
+
+        db.insert(b"key", Value::String(b"other".to_vec()));
+        assert!(db.expires.is_empty());
+
+        db.set_expiry(b"key", Some(expires_at));
+        db.remove(b"key");
+        assert!(db.expires.is_empty());
+    }
+
+    #[test]
+    fn test_remove_expired() {
+        let mut db = Db::default();
+        let now = current_time_ms();
+        for (key, ttl) in [("a", 100), ("b", 200), ("c", 300), ("d", 10_000)] {
+            db.insert(key.as_bytes(), Value::String(vec![]));
+            db.set_expiry(key.as_bytes(), Some(now + ttl));
+        }
+
+        assert_eq!(db.remove_expired(now + 1000, 2), 2);
+        assert_eq!(db.entries.len(), 2);
+        assert_eq!(db.remove_expired(now + 1000, 10), 1);
+        assert_eq!(db.remove_expired(now + 1000, 10), 0);
+        assert!(db.entries.contains_key(b"d".as_slice()));
+    }
+}
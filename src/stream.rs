@@ -0,0 +1,362 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// The identifier of a stream entry: a time in milliseconds and a sequence number within it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl StreamId {
+    pub const MIN: Self = Self { ms: 0, seq: 0 };
+    pub const MAX: Self = Self {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub fn new(ms: u64, seq: u64) -> Self {
+        Self { ms, seq }
+    }
+
+    /// Parse `ms-seq`, or `ms` alone with the given sequence number
+    pub fn parse(arg: &[u8], default_seq: u64) -> Option<Self> {
+        let arg = std::str::from_utf8(arg).ok()?;
+        let number = |part: &str| {
+            part.bytes()
+                .all(|byte| byte.is_ascii_digit())
+                .then(|| part.parse::<u64>().ok())
+                .flatten()
+        };
+
+        match arg.split_once('-') {
+            Some((ms, seq)) => Some(Self::new(number(ms)?, number(seq)?)),
+            None => Some(Self::new(number(arg)?, default_seq)),
+        }
+    }
+
+    /// The smallest identifier greater than this one
+    pub fn next(self) -> Option<Self> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(Self::new(self.ms, seq)),
+            None => Some(Self::new(self.ms.checked_add(1)?, 0)),
+        }
+    }
+
+    /// The greatest identifier smaller than this one
+    pub fn previous(self) -> Option<Self> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(Self::new(self.ms, seq)),
+            None => Some(Self::new(self.ms.checked_sub(1)?, u64::MAX)),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+/// The field-value pairs of an entry
+pub type Fields = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// An entry delivered to a consumer of a group but not acknowledged yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingEntry {
+    pub consumer: Vec<u8>,
+    /// When the entry was last delivered, in milliseconds
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Consumer {
+    /// When the consumer last interacted with the group, in milliseconds
+    pub seen_time: u64,
+    /// When the consumer last read or claimed entries, in milliseconds
+    pub active_time: Option<u64>,
+    /// Identifiers of the entries pending for this consumer, also in the group's list
+    pub pending: BTreeSet<StreamId>,
+}
+
+impl Consumer {
+    pub fn new(now: u64) -> Self {
+        Self {
+            seen_time: now,
+            active_time: None,
+            pending: BTreeSet::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumerGroup {
+    /// The last entry delivered to any consumer of the group
+    pub last_delivered_id: StreamId,
+    /// Number of entries of the stream read by the group, `None` when it cannot be known
+    pub entries_read: Option<u64>,
+    /// The pending entries list of the group, for all its consumers
+    pub pending: BTreeMap<StreamId, PendingEntry>,
+    pub consumers: BTreeMap<Vec<u8>, Consumer>,
+}
+
+impl ConsumerGroup {
+    pub fn new(last_delivered_id: StreamId, entries_read: Option<u64>) -> Self {
+        Self {
+            last_delivered_id,
+            entries_read,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
+
+    /// Return the consumer, creating it if needed, and record that it was seen
+    pub fn consumer(&mut self, name: &[u8], now: u64) -> &mut Consumer {
+        let consumer = self
+            .consumers
+            .entry(name.to_vec())
+            .or_insert_with(|| Consumer::new(now));
+        consumer.seen_time = now;
+        consumer
+    }
+
+    /// Remove an entry from the pending entries list of the group and of its consumer
+    pub fn acknowledge(&mut self, id: StreamId) -> bool {
+        match self.pending.remove(&id) {
+            Some(entry) => {
+                if let Some(consumer) = self.consumers.get_mut(&entry.consumer) {
+                    consumer.pending.remove(&id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Make an entry pending for a consumer, taking it from its previous owner if any
+    pub fn assign(
+        &mut self,
+        id: StreamId,
+        consumer: &[u8],
+        delivery_time: u64,
+        delivery_count: u64,
+    ) {
+        self.acknowledge(id);
+        self.pending.insert(
+            id,
+            PendingEntry {
+                consumer: consumer.to_vec(),
+                delivery_time,
+                delivery_count,
+            },
+        );
+        if let Some(consumer) = self.consumers.get_mut(consumer) {
+            consumer.pending.insert(id);
+        }
+    }
+}
+
+/// How a stream is trimmed, by count of entries or by smallest identifier kept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimStrategy {
+    MaxLen(u64),
+    MinId(StreamId),
+}
+
+/// An append-only log of entries ordered by identifier, with the consumer groups reading it.
+/// Unlike other collections, a stream stays in the keyspace when it is empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stream {
+    pub entries: BTreeMap<StreamId, Fields>,
+    /// The greatest identifier ever added, which new entries must exceed
+    pub last_id: StreamId,
+    /// The greatest identifier deleted by XDEL, trimming does not count
+    pub max_deleted_id: StreamId,
+    /// Number of entries ever added
+    pub entries_added: u64,
+    pub groups: BTreeMap<Vec<u8>, ConsumerGroup>,
+}
+
+impl Stream {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn first_id(&self) -> Option<StreamId> {
+        self.entries.keys().next().copied()
+    }
+
+    /// Append an entry, whose identifier must be greater than all the previous ones
+    pub fn append(&mut self, id: StreamId, fields: Fields) {
+        self.entries.insert(id, fields);
+        self.last_id = id;
+        self.entries_added += 1;
+    }
+
+    /// Delete an entry, returning false if it does not exist
+    pub fn delete(&mut self, id: StreamId) -> bool {
+        if self.entries.remove(&id).is_none() {
+            return false;
+        }
+
+        self.max_deleted_id = self.max_deleted_id.max(id);
+        true
+    }
+
+    /// Remove the oldest entries until the strategy is satisfied or `limit` entries are removed,
+    /// returning how many were removed
+    pub fn trim(&mut self, strategy: TrimStrategy, limit: Option<usize>) -> usize {
+        let mut removed = 0;
+
+        while limit.is_none_or(|limit| removed < limit) {
+            let first = match self.first_id() {
+                Some(first) => first,
+                None => break,
+            };
+
+            let exceeds = match strategy {
+                TrimStrategy::MaxLen(max_len) => self.entries.len() as u64 > max_len,
+                TrimStrategy::MinId(min_id) => first < min_id,
+            };
+            if !exceeds {
+                break;
+            }
+
+            self.entries.remove(&first);
+            removed += 1;
+        }
+
+        removed
+    }
+
+    /// Whether entries were deleted by XDEL from the given identifier on
+    pub fn has_tombstones_from(&self, start: StreamId) -> bool {
+        self.max_deleted_id != StreamId::MIN && self.max_deleted_id >= start
+    }
+
+    /// Number of entries added up to the identifier included, when it can be known
+    pub fn entries_read_until(&self, id: StreamId) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+        if self.is_empty() && id <= self.max_deleted_id {
+            return Some(self.entries_added);
+        }
+        if id == self.last_id {
+            return Some(self.entries_added);
+        }
+        if id > self.last_id {
+            return None;
+        }
+
+        // Without deletions in the middle, entries before the first one were all trimmed
+        let first = self.first_id()?;
+        if !self.has_tombstones_from(first) && id < first {
+            return Some(self.entries_added - self.len() as u64);
+        }
+
+        None
+    }
+
+    /// Number of entries the group has yet to read, when it can be known
+    pub fn lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if self.entries_added == 0 {
+            return Some(0);
+        }
+
+        let entries_read = match group.entries_read {
+            Some(entries_read) if !self.has_tombstones_from(group.last_delivered_id) => {
+                Some(entries_read)
+            }
+            _ => self.entries_read_until(group.last_delivered_id),
+        };
+
+        entries_read.map(|entries_read| self.entries_added.saturating_sub(entries_read))
+    }
+
+    /// Record that the group was delivered the entry, which follows the last one it was delivered
+    pub fn advance_group(&mut self, name: &[u8], id: StreamId) {
+        let tombstones = self.has_tombstones_from(id);
+        let estimate = self.entries_read_until(id);
+
+        if let Some(group) = self.groups.get_mut(name) {
+            group.last_delivered_id = id;
+            group.entries_read = match group.entries_read {
+                Some(entries_read) if !tombstones => Some(entries_read + 1),
+                _ => estimate,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream_id() {
+        assert_eq!(StreamId::parse(b"5-3", 0), Some(StreamId::new(5, 3)));
+        assert_eq!(StreamId::parse(b"5", 7), Some(StreamId::new(5, 7)));
+        assert_eq!(
+            StreamId::parse(b"18446744073709551615-18446744073709551615", 0),
+            Some(StreamId::MAX)
+        );
+        assert_eq!(StreamId::parse(b"5-", 0), None);
+        assert_eq!(StreamId::parse(b"+5-1", 0), None);
+        assert_eq!(StreamId::parse(b"5-1-1", 0), None);
+        assert_eq!(StreamId::parse(b"18446744073709551616", 0), None);
+    }
+
+    #[test]
+    fn test_next_and_previous() {
+        assert_eq!(StreamId::new(1, 2).next(), Some(StreamId::new(1, 3)));
+        assert_eq!(StreamId::new(1, u64::MAX).next(), Some(StreamId::new(2, 0)));
+        assert_eq!(StreamId::MAX.next(), None);
+        assert_eq!(
+            StreamId::new(2, 0).previous(),
+            Some(StreamId::new(1, u64::MAX))
+        );
+        assert_eq!(StreamId::MIN.previous(), None);
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut stream = Stream::default();
+        for ms in 1..=10 {
+            stream.append(StreamId::new(ms, 0), vec![]);
+        }
+
+        assert_eq!(stream.trim(TrimStrategy::MaxLen(8), None), 2);
+        assert_eq!(
+            stream.trim(TrimStrategy::MinId(StreamId::new(7, 0)), Some(2)),
+            2
+        );
+        assert_eq!(stream.first_id(), Some(StreamId::new(5, 0)));
+        assert_eq!(stream.entries_added, 10);
+        assert_eq!(stream.entries_read_until(StreamId::new(4, 0)), Some(4));
+    }
+
+    #[test]
+    fn test_lag() {
+        let mut stream = Stream::default();
+        for ms in 1..=5 {
+            stream.append(StreamId::new(ms, 0), vec![]);
+        }
+        stream.groups.insert(
+            b"group".to_vec(),
+            ConsumerGroup::new(StreamId::MIN, Some(0)),
+        );
+
+        stream.advance_group(b"group", StreamId::new(1, 0));
+        assert_eq!(stream.lag(&stream.groups[b"group".as_slice()]), Some(4));
+
+        // A deletion after the last delivered entry makes the lag unknown
+        stream.delete(StreamId::new(3, 0));
+        assert_eq!(stream.lag(&stream.groups[b"group".as_slice()]), None);
+    }
+}